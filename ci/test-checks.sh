@@ -78,6 +78,13 @@ _ ci/order-crates-for-publishing.py
 
 _ scripts/cargo-clippy.sh
 
+# The workspace build unifies the features of tpu-client-next, check the ones
+# enabling its test helpers on their own.
+for features in test-utils bench; do
+  _ $cargoNightly clippy --locked -p solana-tpu-client-next --all-targets \
+    --features "$features" -- --deny=warnings
+done
+
 if [[ -n $CI ]]; then
   # exclude from printing "Checking xxx ..."
   _ scripts/cargo-for-all-lock-files.sh -- "+${rust_nightly}" sort --workspace --check > /dev/null
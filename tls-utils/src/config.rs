@@ -1,16 +1,37 @@
 use {
     rustls::{
-        client::WantsClientCert, server::WantsServerCert, ClientConfig, ConfigBuilder, ServerConfig,
+        client::{danger::ServerCertVerifier, WantsClientCert},
+        crypto::CryptoProvider,
+        server::WantsServerCert,
+        ClientConfig, ConfigBuilder, ServerConfig,
     },
     std::sync::Arc,
 };
 
 pub fn tls_client_config_builder() -> ConfigBuilder<ClientConfig, WantsClientCert> {
-    ClientConfig::builder_with_provider(Arc::new(crate::crypto_provider()))
+    tls_client_config_builder_with_provider(Arc::new(crate::crypto_provider()))
+}
+
+/// Same as [`tls_client_config_builder`], but using `provider` for the
+/// handshake and for checking the signatures of the server.
+pub fn tls_client_config_builder_with_provider(
+    provider: Arc<CryptoProvider>,
+) -> ConfigBuilder<ClientConfig, WantsClientCert> {
+    let verifier = crate::SkipServerVerification::with_provider(provider.clone());
+    tls_client_config_builder_with_verifier(provider, verifier)
+}
+
+/// Same as [`tls_client_config_builder_with_provider`], but verifying the
+/// certificate of the server with `verifier` instead of accepting any.
+pub fn tls_client_config_builder_with_verifier(
+    provider: Arc<CryptoProvider>,
+    verifier: Arc<dyn ServerCertVerifier>,
+) -> ConfigBuilder<ClientConfig, WantsClientCert> {
+    ClientConfig::builder_with_provider(provider)
         .with_safe_default_protocol_versions()
         .unwrap()
         .dangerous()
-        .with_custom_certificate_verifier(crate::SkipServerVerification::new())
+        .with_custom_certificate_verifier(verifier)
 }
 
 pub fn tls_server_config_builder() -> ConfigBuilder<ServerConfig, WantsServerCert> {
@@ -20,7 +20,12 @@ pub struct SkipServerVerification(Arc<CryptoProvider>);
 
 impl SkipServerVerification {
     pub fn new() -> Arc<Self> {
-        Arc::new(Self(Arc::new(crypto_provider())))
+        Self::with_provider(Arc::new(crypto_provider()))
+    }
+
+    /// Checks the TLS signatures with the algorithms of `provider`.
+    pub fn with_provider(provider: Arc<CryptoProvider>) -> Arc<Self> {
+        Arc::new(Self(provider))
     }
 }
 
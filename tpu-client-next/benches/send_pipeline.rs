@@ -0,0 +1,179 @@
+use {
+    criterion::{
+        black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+    },
+    solana_tpu_client_next::{
+        connection_workers_scheduler::{BindTarget, ConnectionWorkersSchedulerConfig, Fanout},
+        test_utils::{MockLeaderUpdater, TestTpuServer},
+        transaction_batch::TransactionBatch,
+        workers_cache::{WorkerInfo, WorkersCache},
+        ConnectionWorkersScheduler,
+    },
+    std::{
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        time::{Duration, Instant},
+    },
+    tokio::{
+        runtime::Runtime,
+        sync::{mpsc, watch},
+    },
+    tokio_util::sync::CancellationToken,
+};
+
+const TRANSACTION_SIZE: usize = 256;
+const BATCH_SIZES: [usize; 3] = [1, 16, 64];
+const NUM_LEADERS: u16 = 8;
+
+fn transactions(batch_size: usize) -> Vec<Vec<u8>> {
+    (0..batch_size)
+        .map(|i| vec![i as u8; TRANSACTION_SIZE])
+        .collect()
+}
+
+fn bench_batch_construction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("batch_construction");
+    for batch_size in BATCH_SIZES {
+        group.throughput(Throughput::Elements(batch_size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("new", batch_size),
+            &batch_size,
+            |b, &batch_size| {
+                b.iter_batched(
+                    || transactions(batch_size),
+                    |transactions| black_box(TransactionBatch::new(transactions)),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("split_by_size", batch_size),
+            &batch_size,
+            |b, &batch_size| {
+                b.iter_batched(
+                    || TransactionBatch::new(transactions(batch_size)),
+                    |batch| black_box(batch.split_by_size(8 * TRANSACTION_SIZE, 8)),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+// Dispatches a batch to the workers of `NUM_LEADERS` leaders, whose channels
+// are drained by tasks standing in for the connection workers.
+fn bench_scheduler_dispatch(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let _guard = runtime.enter();
+    let cancel = CancellationToken::new();
+    let mut workers = WorkersCache::new(NUM_LEADERS as usize, cancel.clone());
+    let leaders: Vec<SocketAddr> = (0..NUM_LEADERS)
+        .map(|port| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8000 + port))
+        .collect();
+    for leader in &leaders {
+        let (sender, mut receiver) = mpsc::channel(1024);
+        let handle = tokio::spawn(async move { while receiver.recv().await.is_some() {} });
+        let _ = workers.push(*leader, WorkerInfo::new(sender, handle, cancel.clone()));
+    }
+
+    let mut group = c.benchmark_group("scheduler_dispatch");
+    for batch_size in BATCH_SIZES {
+        let batch = TransactionBatch::new(transactions(batch_size));
+        group.throughput(Throughput::Elements(leaders.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("leaders", batch_size),
+            &batch,
+            |b, batch| {
+                b.iter(|| {
+                    for leader in &leaders {
+                        let _ = workers.try_send_transactions_to_address(leader, batch.clone());
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+    cancel.cancel();
+}
+
+async fn send_batch(
+    transaction_sender: &mpsc::Sender<TransactionBatch>,
+    server: &TestTpuServer,
+    batch_size: usize,
+) {
+    transaction_sender
+        .send(TransactionBatch::new(transactions(batch_size)))
+        .await
+        .unwrap();
+    assert!(
+        server
+            .wait_for_transactions(batch_size, Duration::from_secs(10))
+            .await
+    );
+    server.take_transactions();
+}
+
+// Sends each batch through the scheduler and a connection worker to a
+// `TestTpuServer`, and waits for the server to receive it.
+fn bench_send_path(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let server = runtime.block_on(async { TestTpuServer::start().unwrap() });
+    let (leader_updater, _schedule) = MockLeaderUpdater::new(vec![server.address()]);
+    let (transaction_sender, transaction_receiver) = mpsc::channel(1);
+    let (_identity_sender, identity_receiver) = watch::channel(None);
+    let cancel = CancellationToken::new();
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(leader_updater),
+        transaction_receiver,
+        identity_receiver,
+        cancel.clone(),
+    );
+    let config = ConnectionWorkersSchedulerConfig {
+        bind: BindTarget::Address(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)),
+        stake_identity: None,
+        num_connections: 1,
+        skip_check_transaction_age: true,
+        worker_channel_size: 16,
+        max_reconnect_attempts: 4,
+        leaders_fanout: Fanout {
+            send: 1,
+            connect: 1,
+        },
+    };
+    let scheduler_handle = runtime.spawn(scheduler.run(config));
+
+    // Establishes the connection before measuring.
+    runtime.block_on(send_batch(&transaction_sender, &server, 1));
+
+    let mut group = c.benchmark_group("send_path");
+    for batch_size in BATCH_SIZES {
+        group.throughput(Throughput::Elements(batch_size as u64));
+        group.bench_with_input(
+            BenchmarkId::new("mock_transport", batch_size),
+            &batch_size,
+            |b, &batch_size| {
+                b.iter_custom(|iters| {
+                    runtime.block_on(async {
+                        let start = Instant::now();
+                        for _ in 0..iters {
+                            send_batch(&transaction_sender, &server, batch_size).await;
+                        }
+                        start.elapsed()
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+
+    cancel.cancel();
+    let _ = runtime.block_on(scheduler_handle);
+}
+
+criterion_group!(
+    benches,
+    bench_batch_construction,
+    bench_scheduler_dispatch,
+    bench_send_path
+);
+criterion_main!(benches);
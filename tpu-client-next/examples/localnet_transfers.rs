@@ -0,0 +1,143 @@
+//! Sends transfers to a local cluster through the TPU and waits for their
+//! confirmation, as a reference of the wiring of the
+//! [`ConnectionWorkersScheduler`].
+//!
+//! Start a validator with `solana-test-validator` and run:
+//!
+//! ```text
+//! cargo run --example localnet_transfers --features transaction -- [RPC_URL] [NUM_TRANSFERS]
+//! ```
+//!
+//! The RPC URL defaults to `http://127.0.0.1:8899` and the number of
+//! transfers to 10.
+
+use {
+    solana_commitment_config::CommitmentConfig,
+    solana_keypair::Keypair,
+    solana_rpc_client::nonblocking::rpc_client::RpcClient,
+    solana_signer::Signer,
+    solana_system_transaction::transfer,
+    solana_tpu_client_next::{
+        connection_workers_scheduler::{BindTarget, ConnectionWorkersSchedulerConfig, Fanout},
+        leader_updater::create_local_leader_updater,
+        transaction_batch::TransactionBatch,
+        ConnectionWorkersScheduler,
+    },
+    solana_transaction::versioned::VersionedTransaction,
+    std::{
+        error::Error,
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        time::Duration,
+    },
+    tokio::{
+        sync::{mpsc, watch},
+        time::{sleep, Instant},
+    },
+    tokio_util::sync::CancellationToken,
+};
+
+const DEFAULT_RPC_URL: &str = "http://127.0.0.1:8899";
+const DEFAULT_NUM_TRANSFERS: u64 = 10;
+const AIRDROP_LAMPORTS: u64 = 1_000_000_000;
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+// Transactions can be dropped on the way to the leader, so the unconfirmed
+// ones are sent again at this interval.
+const RESEND_INTERVAL: Duration = Duration::from_secs(2);
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1);
+    let rpc_url = args.next().unwrap_or_else(|| DEFAULT_RPC_URL.to_string());
+    let num_transfers = match args.next() {
+        Some(num_transfers) => num_transfers.parse()?,
+        None => DEFAULT_NUM_TRANSFERS,
+    };
+    let rpc_client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
+
+    // Fund a new payer.
+    let payer = Keypair::new();
+    let airdrop = rpc_client
+        .request_airdrop(&payer.pubkey(), AIRDROP_LAMPORTS)
+        .await?;
+    let start = Instant::now();
+    while !rpc_client.confirm_transaction(&airdrop).await? {
+        if start.elapsed() > CONFIRMATION_TIMEOUT {
+            return Err("the airdrop was not confirmed".into());
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    println!("Funded payer {}", payer.pubkey());
+
+    // The local node is the only leader of the cluster.
+    let leader_updater = create_local_leader_updater(&rpc_client).await?;
+    let (transaction_sender, transaction_receiver) = mpsc::channel(16);
+    // Sending with the identity of a staked node would get a larger share of
+    // the TPU bandwidth, the payer is not staked.
+    let (_identity_sender, identity_receiver) = watch::channel(None);
+    let cancel = CancellationToken::new();
+    let scheduler = ConnectionWorkersScheduler::new(
+        leader_updater,
+        transaction_receiver,
+        identity_receiver,
+        cancel.clone(),
+    );
+    let config = ConnectionWorkersSchedulerConfig {
+        bind: BindTarget::Address(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)),
+        stake_identity: None,
+        num_connections: 1,
+        skip_check_transaction_age: false,
+        worker_channel_size: 16,
+        max_reconnect_attempts: 4,
+        leaders_fanout: Fanout {
+            send: 1,
+            connect: 1,
+        },
+    };
+    let scheduler_handle = tokio::spawn(scheduler.run(config));
+
+    // Transfer distinct amounts so that the transactions differ.
+    let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+    let mut unconfirmed: Vec<VersionedTransaction> = (1..=num_transfers)
+        .map(|lamports| {
+            let recipient = Keypair::new().pubkey();
+            transfer(&payer, &recipient, lamports, recent_blockhash).into()
+        })
+        .collect();
+
+    let start = Instant::now();
+    while !unconfirmed.is_empty() {
+        if start.elapsed() > CONFIRMATION_TIMEOUT {
+            break;
+        }
+        transaction_sender
+            .send(TransactionBatch::try_from_transactions(&unconfirmed)?)
+            .await?;
+        sleep(RESEND_INTERVAL).await;
+
+        let signatures: Vec<_> = unconfirmed
+            .iter()
+            .map(|transaction| transaction.signatures[0])
+            .collect();
+        let statuses = rpc_client.get_signature_statuses(&signatures).await?.value;
+        let mut statuses = statuses.into_iter();
+        unconfirmed.retain(|_| {
+            !statuses.next().flatten().is_some_and(|status| {
+                status.err.is_none() && status.satisfies_commitment(CommitmentConfig::confirmed())
+            })
+        });
+        println!(
+            "Confirmed {} of {num_transfers} transfers",
+            num_transfers - unconfirmed.len() as u64
+        );
+    }
+
+    cancel.cancel();
+    let stats = scheduler_handle.await??;
+    println!("Scheduler stats: {stats}");
+
+    if unconfirmed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} transfers were not confirmed", unconfirmed.len()).into())
+    }
+}
@@ -0,0 +1,27 @@
+//! Deserializes batches from arbitrary bytes, as received from the upstream
+//! services, and checks that the accepted ones can be serialized back.
+//!
+//! Run with `cargo +nightly fuzz run batch_deserialize` from `tpu-client-next`.
+
+#![no_main]
+
+use {libfuzzer_sys::fuzz_target, solana_tpu_client_next::transaction_batch::TransactionBatch};
+
+fn check(batch: TransactionBatch) {
+    assert!(batch.metadata().is_empty() || batch.metadata().len() == batch.len());
+    let serialized = serde_json::to_vec(&batch).unwrap();
+    let deserialized: TransactionBatch = serde_json::from_slice(&serialized).unwrap();
+    assert_eq!(deserialized.len(), batch.len());
+    assert_eq!(deserialized.metadata(), batch.metadata());
+    assert_eq!(deserialized.leaders_fanout(), batch.leaders_fanout());
+    let _ = batch.split_by_size(usize::MAX, usize::MAX);
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(batch) = serde_json::from_slice::<TransactionBatch>(data) {
+        check(batch);
+    }
+    if let Ok(batch) = bincode::deserialize::<TransactionBatch>(data) {
+        check(batch);
+    }
+});
@@ -0,0 +1,81 @@
+//! Classifies the arbitrary errors which the peers can close the connections
+//! with, and counts them as the workers do.
+//!
+//! Run with `cargo +nightly fuzz run quic_error_code` from `tpu-client-next`.
+
+#![no_main]
+
+use {
+    arbitrary::Arbitrary,
+    bytes::Bytes,
+    libfuzzer_sys::fuzz_target,
+    quinn::{ApplicationClose, ConnectionClose, ConnectionError, TransportErrorCode, VarInt},
+    solana_tpu_client_next::send_transaction_stats::quic_error_codes::{
+        QuicErrorCode, QuicErrorCodeStats, MAX_TRACKED_ERROR_CODES,
+    },
+};
+
+const TRANSPORT_ERROR_CODES: [TransportErrorCode; 4] = [
+    TransportErrorCode::NO_ERROR,
+    TransportErrorCode::INTERNAL_ERROR,
+    TransportErrorCode::CONNECTION_REFUSED,
+    TransportErrorCode::PROTOCOL_VIOLATION,
+];
+
+#[derive(Debug, Arbitrary)]
+enum Error {
+    ApplicationClosed { code: u64, reason: Vec<u8> },
+    ConnectionClosed { code: u8, reason: Vec<u8> },
+    TlsAlert { alert: u8, reason: Vec<u8> },
+    VersionMismatch,
+    Reset,
+    TimedOut,
+    LocallyClosed,
+    CidsExhausted,
+}
+
+impl From<Error> for ConnectionError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::ApplicationClosed { code, reason } => {
+                ConnectionError::ApplicationClosed(ApplicationClose {
+                    error_code: VarInt::from_u64(code).unwrap_or(VarInt::MAX),
+                    reason: Bytes::from(reason),
+                })
+            }
+            Error::ConnectionClosed { code, reason } => {
+                ConnectionError::ConnectionClosed(ConnectionClose {
+                    error_code: TRANSPORT_ERROR_CODES[code as usize % TRANSPORT_ERROR_CODES.len()],
+                    frame_type: None,
+                    reason: Bytes::from(reason),
+                })
+            }
+            Error::TlsAlert { alert, reason } => {
+                ConnectionError::ConnectionClosed(ConnectionClose {
+                    error_code: TransportErrorCode::crypto(alert),
+                    frame_type: None,
+                    reason: Bytes::from(reason),
+                })
+            }
+            Error::VersionMismatch => ConnectionError::VersionMismatch,
+            Error::Reset => ConnectionError::Reset,
+            Error::TimedOut => ConnectionError::TimedOut,
+            Error::LocallyClosed => ConnectionError::LocallyClosed,
+            Error::CidsExhausted => ConnectionError::CidsExhausted,
+        }
+    }
+}
+
+fuzz_target!(|errors: Vec<Error>| {
+    let stats = QuicErrorCodeStats::default();
+    for err in errors {
+        let is_tls_alert = matches!(err, Error::TlsAlert { .. });
+        let Some(code) = QuicErrorCode::from_connection_error(&ConnectionError::from(err)) else {
+            continue;
+        };
+        assert_eq!(is_tls_alert, matches!(code, QuicErrorCode::TlsAlert(_)));
+        assert!(!code.to_string().is_empty());
+        stats.record(code);
+    }
+    assert!(stats.to_non_atomic().len() <= MAX_TRACKED_ERROR_CODES);
+});
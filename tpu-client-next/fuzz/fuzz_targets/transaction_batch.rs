@@ -0,0 +1,70 @@
+//! Builds, reorders and splits batches of arbitrary wire transactions, as the
+//! scheduler and the workers do with the batches handed by their users.
+//!
+//! Run with `cargo +nightly fuzz run transaction_batch` from `tpu-client-next`.
+
+#![no_main]
+
+use {
+    arbitrary::Arbitrary,
+    libfuzzer_sys::fuzz_target,
+    solana_tpu_client_next::transaction_batch::{TransactionBatch, TransactionMetadata},
+};
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    wired_transactions: Vec<Vec<u8>>,
+    metadata: Vec<(Option<u64>, Option<u64>)>,
+    max_bytes: usize,
+    max_count: usize,
+}
+
+fuzz_target!(|input: Input| {
+    let Input {
+        wired_transactions,
+        metadata,
+        max_bytes,
+        max_count,
+    } = input;
+    // Oversized transactions are rejected by `try_new` but still accepted by
+    // `new`, in which case the workers drop them.
+    let batch = match TransactionBatch::try_new(wired_transactions.clone()) {
+        Ok(batch) => batch,
+        Err(_) => TransactionBatch::new(wired_transactions),
+    };
+    let metadata = metadata
+        .into_iter()
+        .map(|(id, priority)| TransactionMetadata { id, priority })
+        .collect();
+    let mut batch = batch.with_metadata(metadata);
+    assert_eq!(batch.metadata().len(), batch.len());
+
+    batch.sort_by_compute_unit_price();
+    batch.dedup_by_signature();
+    assert_eq!(batch.signatures().count(), batch.len());
+
+    let num_transactions = batch.len();
+    let num_bytes = batch.num_bytes();
+    let transactions: Vec<Vec<u8>> = batch.clone().into();
+    let batches = batch.split_by_size(max_bytes, max_count);
+    assert_eq!(
+        batches.iter().map(TransactionBatch::len).sum::<usize>(),
+        num_transactions
+    );
+    assert_eq!(
+        batches
+            .iter()
+            .map(TransactionBatch::num_bytes)
+            .sum::<usize>(),
+        num_bytes
+    );
+    for split in &batches {
+        assert!(!split.is_empty());
+        assert!(split.len() <= max_count.max(1));
+        assert!(split.len() == 1 || split.num_bytes() <= max_bytes);
+        assert_eq!(split.metadata().len(), split.len());
+    }
+    let split_transactions: Vec<Vec<u8>> =
+        batches.into_iter().flat_map(Vec::<Vec<u8>>::from).collect();
+    assert_eq!(split_transactions, transactions);
+});
@@ -0,0 +1,276 @@
+//! Sends transfers at a fixed rate to the leaders of a cluster through the
+//! [`ConnectionWorkersScheduler`] and prints the statistics of the scheduler
+//! periodically, to benchmark the leaders and to validate the tuning of the
+//! scheduler.
+//!
+//! ```text
+//! cargo run --features load-gen --bin solana-tpu-client-next-load-gen -- \
+//!     --url http://127.0.0.1:8899 --tps 1000 --duration 30
+//! ```
+//!
+//! The transfers are sent by the payer to itself, each with a distinct amount,
+//! so that the payer only spends the fees.
+
+use {
+    clap::{crate_version, value_t_or_exit, App, Arg},
+    solana_commitment_config::CommitmentConfig,
+    solana_keypair::{read_keypair_file, Keypair},
+    solana_rpc_client::nonblocking::rpc_client::RpcClient,
+    solana_signer::Signer,
+    solana_system_transaction::transfer,
+    solana_tpu_client_next::{
+        connection_workers_scheduler::{
+            ConnectionWorkersSchedulerConfig, RpcScheduler, StakeIdentity,
+        },
+        send_transaction_stats::SendTransactionStatsSnapshot,
+        transaction_batch::TransactionBatch,
+        ConnectionWorkersScheduler,
+    },
+    solana_transaction::versioned::VersionedTransaction,
+    std::{error::Error, fs, sync::Arc, time::Duration},
+    tokio::{
+        sync::watch,
+        time::{interval, sleep, Instant, MissedTickBehavior},
+    },
+};
+
+const DEFAULT_RPC_URL: &str = "http://127.0.0.1:8899";
+const AIRDROP_LAMPORTS: u64 = 10_000_000_000;
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+// The batches are spread over the second, so that the load is smooth.
+const BATCHES_PER_SECOND: u64 = 100;
+// The blockhash is valid for about a minute, it is refreshed well before.
+const BLOCKHASH_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+fn is_parsable<T: std::str::FromStr>(string: String) -> Result<(), String> {
+    string
+        .parse::<T>()
+        .map(|_| ())
+        .map_err(|_| format!("error parsing '{string}'"))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let matches = App::new("solana-tpu-client-next-load-gen")
+        .about("Sends transfers at a fixed rate through the scheduler and prints its statistics")
+        .version(crate_version!())
+        .arg(
+            Arg::with_name("url")
+                .long("url")
+                .short("u")
+                .value_name("URL")
+                .takes_value(true)
+                .default_value(DEFAULT_RPC_URL)
+                .help("RPC URL of the cluster, the leaders are tracked from it"),
+        )
+        .arg(
+            Arg::with_name("keypair")
+                .long("keypair")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("Payer of the transfers [default: a new payer funded with an airdrop]"),
+        )
+        .arg(
+            Arg::with_name("identity")
+                .long("identity")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("Identity to connect to the leaders with [default: unstaked]"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("TOML configuration of the scheduler [default: the default configuration]"),
+        )
+        .arg(
+            Arg::with_name("tps")
+                .long("tps")
+                .value_name("NUM")
+                .takes_value(true)
+                .default_value("1000")
+                .validator(is_parsable::<u64>)
+                .help("Number of transfers sent per second"),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .long("duration")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .default_value("30")
+                .validator(is_parsable::<u64>)
+                .help("How long to send for"),
+        )
+        .arg(
+            Arg::with_name("stats_interval")
+                .long("stats-interval")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .default_value("1")
+                .validator(is_parsable::<u64>)
+                .help("How often the statistics are printed"),
+        )
+        .get_matches();
+
+    let url = value_t_or_exit!(matches, "url", String);
+    let tps = value_t_or_exit!(matches, "tps", u64).max(1);
+    let duration = Duration::from_secs(value_t_or_exit!(matches, "duration", u64));
+    let stats_interval =
+        Duration::from_secs(value_t_or_exit!(matches, "stats_interval", u64).max(1));
+    let mut config = match matches.value_of("config") {
+        Some(path) => toml::from_str(&fs::read_to_string(path)?)?,
+        None => ConnectionWorkersSchedulerConfig::default(),
+    };
+    if let Some(path) = matches.value_of("identity") {
+        config.stake_identity = Some(StakeIdentity::new(&read_keypair_file(path)?));
+    }
+
+    let rpc_client = Arc::new(RpcClient::new_with_commitment(
+        url,
+        CommitmentConfig::confirmed(),
+    ));
+    let payer = match matches.value_of("keypair") {
+        Some(path) => read_keypair_file(path)?,
+        None => fund_new_payer(&rpc_client).await?,
+    };
+
+    let RpcScheduler {
+        scheduler,
+        config,
+        transaction_sender,
+        cancel,
+        ..
+    } = ConnectionWorkersScheduler::from_rpc(rpc_client.clone(), config).await?;
+    let stats = scheduler.get_stats();
+    let scheduler_handle = tokio::spawn(scheduler.run(config));
+
+    let (blockhash_sender, blockhash_receiver) =
+        watch::channel(rpc_client.get_latest_blockhash().await?);
+    let blockhash_updater = tokio::spawn({
+        let rpc_client = rpc_client.clone();
+        async move {
+            loop {
+                sleep(BLOCKHASH_REFRESH_INTERVAL).await;
+                match rpc_client.get_latest_blockhash().await {
+                    Ok(blockhash) => {
+                        blockhash_sender.send_replace(blockhash);
+                    }
+                    Err(err) => eprintln!("Failed to refresh the blockhash: {err}"),
+                }
+            }
+        }
+    });
+
+    let batch_size = tps.div_ceil(BATCHES_PER_SECOND);
+    let mut send_ticker = interval(Duration::from_secs_f64(batch_size as f64 / tps as f64));
+    send_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut stats_ticker = interval(stats_interval);
+    stats_ticker.tick().await;
+    let deadline = sleep(duration);
+    tokio::pin!(deadline);
+
+    println!(
+        "Sending {tps} transfers per second from {} for {duration:?}",
+        payer.pubkey()
+    );
+    let start = Instant::now();
+    let mut num_generated = 0u64;
+    let mut last_num_generated = 0u64;
+    let mut last_snapshot = stats.snapshot();
+    loop {
+        tokio::select! {
+            () = &mut deadline => break,
+            _ = send_ticker.tick() => {
+                let blockhash = *blockhash_receiver.borrow();
+                let transactions: Vec<VersionedTransaction> = (0..batch_size)
+                    .map(|i| {
+                        let lamports = num_generated.wrapping_add(i).wrapping_add(1);
+                        transfer(&payer, &payer.pubkey(), lamports, blockhash).into()
+                    })
+                    .collect();
+                transaction_sender
+                    .send(TransactionBatch::try_from_transactions(&transactions)?)
+                    .await?;
+                num_generated = num_generated.saturating_add(batch_size);
+            }
+            _ = stats_ticker.tick() => {
+                let snapshot = stats.snapshot();
+                print_stats(
+                    start.elapsed(),
+                    num_generated.saturating_sub(last_num_generated),
+                    &snapshot.diff(&last_snapshot),
+                    stats_interval,
+                );
+                last_num_generated = num_generated;
+                last_snapshot = snapshot;
+            }
+        }
+    }
+
+    blockhash_updater.abort();
+    cancel.cancel();
+    let stats = scheduler_handle.await??;
+    println!("Generated {num_generated} transfers, scheduler stats:\n{stats}");
+    Ok(())
+}
+
+async fn fund_new_payer(rpc_client: &RpcClient) -> Result<Keypair, Box<dyn Error>> {
+    let payer = Keypair::new();
+    let airdrop = rpc_client
+        .request_airdrop(&payer.pubkey(), AIRDROP_LAMPORTS)
+        .await?;
+    let start = Instant::now();
+    while !rpc_client.confirm_transaction(&airdrop).await? {
+        if start.elapsed() > CONFIRMATION_TIMEOUT {
+            return Err("the airdrop was not confirmed".into());
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    Ok(payer)
+}
+
+/// Prints the rates over the last `interval` from the `diff` of the stats.
+fn print_stats(
+    elapsed: Duration,
+    num_generated: u64,
+    diff: &SendTransactionStatsSnapshot,
+    interval: Duration,
+) {
+    let per_second = |count: u64| count as f64 / interval.as_secs_f64();
+    let num_errors: u64 = diff
+        .counters()
+        .fields()
+        .into_iter()
+        .filter(|(name, _)| {
+            name.starts_with("connect_error")
+                || name.starts_with("connection_error")
+                || name.starts_with("write_error")
+        })
+        .map(|(_, value)| value)
+        .sum();
+    let num_dropped: u64 = diff
+        .dropped()
+        .fields()
+        .into_iter()
+        .map(|(_, value)| value)
+        .sum();
+    let num_leaders = diff
+        .leaders()
+        .values()
+        .filter(|leader| leader.successfully_sent > 0)
+        .count();
+    let format_latency = |latency: Option<Duration>| {
+        latency.map_or("-".to_string(), |latency| format!("{latency:?}"))
+    };
+    let end_to_end = diff.latency().end_to_end.percentiles();
+    println!(
+        "[{:>5}s] generated {:.0} tx/s, sent {:.0} tx/s, dropped {num_dropped}, errors \
+         {num_errors}, to {num_leaders} leaders, end-to-end p50 {} p99 {}",
+        elapsed.as_secs(),
+        per_second(num_generated),
+        per_second(diff.counters().successfully_sent),
+        format_latency(end_to_end.p50),
+        format_latency(end_to_end.p99),
+    );
+}
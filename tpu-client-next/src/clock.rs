@@ -0,0 +1,23 @@
+//! This module provides [`timestamp`], the clock the age of the batches is
+//! measured with.
+//!
+//! It is the wall clock, except in the tests of this crate, where it follows
+//! the clock of tokio, so that the batches age along with the timers of the
+//! scheduler when the time is driven by
+//! [`MockClock`](crate::test_utils::MockClock).
+
+#[cfg(not(test))]
+pub(crate) use solana_time_utils::timestamp;
+#[cfg(test)]
+use {std::sync::LazyLock, tokio::time::Instant};
+
+/// Returns the milliseconds since the epoch, as seen by tokio: the wall clock
+/// unless the time of the runtime is paused.
+#[cfg(test)]
+pub(crate) fn timestamp() -> u64 {
+    static START: LazyLock<(u64, Instant)> =
+        LazyLock::new(|| (solana_time_utils::timestamp(), Instant::now()));
+    let (start_timestamp, start) = *START;
+    let elapsed = Instant::now().saturating_duration_since(start);
+    start_timestamp.saturating_add(elapsed.as_millis() as u64)
+}
@@ -0,0 +1,142 @@
+//! This module eases the migration from `solana-tpu-client`: it provides
+//! aliases with the names of its clients and conversions from its types to
+//! the ones of this crate, so that most of the code written against
+//! `solana-tpu-client` compiles against this crate.
+//!
+//! The aliases are deprecated, the warnings point to the types to adopt.
+
+pub use solana_tpu_client::{
+    nonblocking::tpu_client::TpuSenderError,
+    tpu_client::{TpuClientConfig, DEFAULT_FANOUT_SLOTS, MAX_FANOUT_SLOTS},
+};
+use {
+    crate::{
+        connection_workers_scheduler::Fanout,
+        nonblocking::tpu_client::TpuClientNextError,
+        transaction_batch::{TransactionBatch, TransactionBatchError},
+        SendTransactionStats,
+    },
+    solana_clock::NUM_CONSECUTIVE_LEADER_SLOTS,
+    solana_connection_cache::client_connection::ClientStats,
+    solana_transaction::Transaction,
+    std::sync::atomic::AtomicU64,
+};
+
+/// Name of the blocking client in `solana-tpu-client`.
+#[deprecated(
+    since = "3.1.0",
+    note = "Use `solana_tpu_client_next::tpu_client::TpuClientNext`, which sends batches and \
+            waits for their confirmation instead of sending transactions one by one"
+)]
+pub type TpuClient = crate::tpu_client::TpuClientNext;
+
+/// Name of the async client in `solana-tpu-client`.
+#[deprecated(
+    since = "3.1.0",
+    note = "Use `solana_tpu_client_next::TpuClientNext`, which sends batches and waits for \
+            their confirmation instead of sending transactions one by one"
+)]
+pub type NonblockingTpuClient = crate::nonblocking::tpu_client::TpuClientNext;
+
+/// The leaders of the `fanout_slots` upcoming slots are targeted, like with
+/// the legacy `TpuClient`, which connects to the same leaders it sends to.
+impl From<&TpuClientConfig> for Fanout {
+    fn from(config: &TpuClientConfig) -> Self {
+        let fanout_slots = config.fanout_slots.clamp(1, MAX_FANOUT_SLOTS);
+        let num_leaders = fanout_slots.div_ceil(NUM_CONSECUTIVE_LEADER_SLOTS) as usize;
+        Self {
+            send: num_leaders,
+            connect: num_leaders,
+        }
+    }
+}
+
+/// Serializes legacy transactions, like
+/// [`TransactionBatch::try_from_transactions`] does for versioned ones.
+impl TryFrom<&[Transaction]> for TransactionBatch {
+    type Error = TransactionBatchError;
+
+    fn try_from(transactions: &[Transaction]) -> Result<Self, Self::Error> {
+        let wired_transactions = transactions
+            .iter()
+            .map(bincode::serialize)
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::try_new(wired_transactions)
+    }
+}
+
+impl From<TpuClientNextError> for TpuSenderError {
+    fn from(err: TpuClientNextError) -> Self {
+        match err {
+            TpuClientNextError::RpcError(err) => Self::RpcError(*err),
+            TpuClientNextError::RuntimeError(err) => Self::IoError(err),
+            err => Self::Custom(err.to_string()),
+        }
+    }
+}
+
+/// Fills the counters of the connection cache stats which have an equivalent
+/// in [`SendTransactionStats`], the others are left to zero.
+impl From<&SendTransactionStats> for ClientStats {
+    fn from(stats: &SendTransactionStats) -> Self {
+        let counters = stats.to_non_atomic();
+        let leaders = stats.leaders.aggregate();
+        let connection_errors = [
+            counters.connect_error_cids_exhausted,
+            counters.connect_error_invalid_remote_address,
+            counters.connect_error_other,
+            counters.connection_error_application_closed,
+            counters.connection_error_cids_exhausted,
+            counters.connection_error_connection_closed,
+            counters.connection_error_locally_closed,
+            counters.connection_error_reset,
+            counters.connection_error_timed_out,
+            counters.connection_error_transport_error,
+            counters.connection_error_version_mismatch,
+        ]
+        .into_iter()
+        .fold(0u64, u64::saturating_add);
+        ClientStats {
+            total_connections: AtomicU64::new(leaders.connections_established),
+            connection_reuse: AtomicU64::new(leaders.cache_hits),
+            connection_errors: AtomicU64::new(connection_errors),
+            zero_rtt_rejects: AtomicU64::new(counters.write_error_zero_rtt_rejected),
+            successful_packets: AtomicU64::new(counters.successfully_sent),
+            ..ClientStats::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::sync::atomic::Ordering};
+
+    #[test]
+    fn test_compat_conversions() {
+        let config = TpuClientConfig::default();
+        assert_eq!(
+            Fanout::from(&config),
+            Fanout {
+                send: 3,
+                connect: 3
+            }
+        );
+
+        let transactions = vec![Transaction::default(); 2];
+        let batch = TransactionBatch::try_from(transactions.as_slice()).unwrap();
+        assert_eq!(batch.len(), 2);
+        let wired: Vec<Vec<u8>> = batch.into();
+        let transaction: Transaction = bincode::deserialize(&wired[0]).unwrap();
+        assert_eq!(transaction, transactions[0]);
+
+        let err = TpuSenderError::from(TpuClientNextError::SchedulerStopped);
+        assert!(matches!(err, TpuSenderError::Custom(_)));
+
+        let stats = SendTransactionStats::default();
+        stats.successfully_sent.store(5, Ordering::Relaxed);
+        stats.connection_error_reset.store(2, Ordering::Relaxed);
+        let client_stats = ClientStats::from(&stats);
+        assert_eq!(client_stats.successful_packets.load(Ordering::Relaxed), 5);
+        assert_eq!(client_stats.connection_errors.load(Ordering::Relaxed), 2);
+    }
+}
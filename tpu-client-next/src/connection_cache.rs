@@ -0,0 +1,383 @@
+//! This module provides an implementation of the connection pool traits of
+//! `solana-connection-cache` on top of the connection workers of this crate,
+//! so that the components using a [`ConnectionCache`], like the ones built on
+//! `solana-quic-client`, can switch to this crate without changing their code.
+//!
+//! Each entry of a [`TpuClientNextPool`] is a connection worker sending to the
+//! address of the pool. The data passed to the connections is queued to the
+//! worker, which sends it on its own task, so the methods of the connections
+//! return as soon as the data is queued. The workers run on the runtime of
+//! the [`TpuClientNextConfig`].
+
+use {
+    crate::{
+        connection_worker::DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
+        connection_workers_scheduler::BindTarget,
+        quic_networking::{create_client_config_with_provider, create_client_endpoint},
+        transaction_batch::TransactionBatch,
+        workers_cache::{spawn_worker, PeerCapabilities, WorkerInfo, WorkersCacheError},
+        QuicError, SendTransactionStats,
+    },
+    async_trait::async_trait,
+    quinn::Endpoint,
+    rustls::{client::danger::ServerCertVerifier, crypto::CryptoProvider},
+    solana_connection_cache::{
+        client_connection::ClientConnection as BlockingClientConnection,
+        connection_cache::{
+            BaseClientConnection, ClientError, ConnectionCache, ConnectionManager, ConnectionPool,
+            ConnectionPoolError, NewConnectionConfig, Protocol,
+        },
+        connection_cache_stats::ConnectionCacheStats,
+        nonblocking::client_connection::ClientConnection as NonblockingClientConnection,
+    },
+    solana_keypair::Keypair,
+    solana_tls_utils::QuicClientCertificate,
+    solana_transaction_error::{TransportError, TransportResult},
+    std::{
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        sync::{Arc, LazyLock, RwLock},
+    },
+    tokio::{
+        runtime::{Builder, Handle, Runtime},
+        sync::mpsc::error::TrySendError,
+    },
+};
+
+/// Runtime of the workers of the [`TpuClientNextConfig`]s which are not given
+/// one with [`TpuClientNextConfig::with_runtime`].
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    Builder::new_multi_thread()
+        .thread_name("solTpuNextCache")
+        .enable_all()
+        .build()
+        .expect("Failed to build the runtime of the connection cache")
+});
+
+/// Configuration of the connections of a [`TpuClientNextConnectionCache`].
+pub struct TpuClientNextConfig {
+    runtime: Handle,
+    bind: SocketAddr,
+    // Arc to prevent having to copy the certificate.
+    client_certificate: RwLock<Arc<QuicClientCertificate>>,
+    crypto_provider: Arc<CryptoProvider>,
+    server_cert_verifier: Option<Arc<dyn ServerCertVerifier>>,
+    worker_channel_size: usize,
+    max_reconnect_attempts: usize,
+    stats: Arc<SendTransactionStats>,
+}
+
+impl Clone for TpuClientNextConfig {
+    fn clone(&self) -> Self {
+        let client_certificate = self.client_certificate.read().unwrap().clone();
+        Self {
+            runtime: self.runtime.clone(),
+            bind: self.bind,
+            client_certificate: RwLock::new(client_certificate),
+            crypto_provider: self.crypto_provider.clone(),
+            server_cert_verifier: self.server_cert_verifier.clone(),
+            worker_channel_size: self.worker_channel_size,
+            max_reconnect_attempts: self.max_reconnect_attempts,
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl NewConnectionConfig for TpuClientNextConfig {
+    fn new() -> Result<Self, ClientError> {
+        Ok(Self {
+            runtime: RUNTIME.handle().clone(),
+            bind: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            client_certificate: RwLock::new(Arc::new(QuicClientCertificate::new(None))),
+            crypto_provider: Arc::new(solana_tls_utils::crypto_provider()),
+            server_cert_verifier: None,
+            worker_channel_size: 64,
+            max_reconnect_attempts: 4,
+            stats: Arc::default(),
+        })
+    }
+}
+
+impl TpuClientNextConfig {
+    /// Runs the workers on `runtime` instead of the runtime shared by the
+    /// configurations.
+    pub fn with_runtime(mut self, runtime: Handle) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Sets the address the endpoints of the pools are bound to.
+    pub fn with_bind_address(mut self, bind: SocketAddr) -> Self {
+        self.bind = bind;
+        self
+    }
+
+    /// Sets the rustls crypto provider used for the TLS handshakes, see
+    /// [`ConnectionWorkersScheduler::with_crypto_provider`].
+    ///
+    /// [`ConnectionWorkersScheduler::with_crypto_provider`]: crate::ConnectionWorkersScheduler::with_crypto_provider
+    pub fn with_crypto_provider(mut self, crypto_provider: Arc<CryptoProvider>) -> Self {
+        self.crypto_provider = crypto_provider;
+        self
+    }
+
+    /// Verifies the certificates of the peers with `server_cert_verifier`, see
+    /// [`ConnectionWorkersScheduler::with_server_cert_verifier`].
+    ///
+    /// [`ConnectionWorkersScheduler::with_server_cert_verifier`]: crate::ConnectionWorkersScheduler::with_server_cert_verifier
+    pub fn with_server_cert_verifier(
+        mut self,
+        server_cert_verifier: Arc<dyn ServerCertVerifier>,
+    ) -> Self {
+        self.server_cert_verifier = Some(server_cert_verifier);
+        self
+    }
+
+    /// Sets the size of the channel of each worker and how many times it
+    /// attempts to reconnect, see [`ConnectionWorkersSchedulerConfig`].
+    ///
+    /// [`ConnectionWorkersSchedulerConfig`]: crate::connection_workers_scheduler::ConnectionWorkersSchedulerConfig
+    pub fn with_worker_settings(
+        mut self,
+        worker_channel_size: usize,
+        max_reconnect_attempts: usize,
+    ) -> Self {
+        self.worker_channel_size = worker_channel_size;
+        self.max_reconnect_attempts = max_reconnect_attempts;
+        self
+    }
+
+    /// Returns the statistics of the workers of all the pools.
+    pub fn stats(&self) -> &Arc<SendTransactionStats> {
+        &self.stats
+    }
+
+    /// Replaces the identity used to connect to the peers. Only the pools
+    /// created afterwards use it.
+    pub fn update_keypair(&self, keypair: &Keypair) {
+        *self.client_certificate.write().unwrap() =
+            Arc::new(QuicClientCertificate::new(Some(keypair)));
+    }
+
+    fn create_endpoint(&self) -> Result<Endpoint, QuicError> {
+        let client_config = create_client_config_with_provider(
+            &self.client_certificate.read().unwrap(),
+            self.crypto_provider.clone(),
+            self.server_cert_verifier.clone(),
+        );
+        let _guard = self.runtime.enter();
+        create_client_endpoint(BindTarget::Address(self.bind), client_config)
+    }
+}
+
+/// Pool of the connections to one address, see [`ConnectionPool`].
+pub struct TpuClientNextPool {
+    connections: Vec<Arc<TpuClientNext>>,
+    endpoint: Endpoint,
+}
+
+impl ConnectionPool for TpuClientNextPool {
+    type BaseClientConnection = TpuClientNext;
+    type NewConnectionConfig = TpuClientNextConfig;
+
+    fn add_connection(&mut self, config: &Self::NewConnectionConfig, addr: &SocketAddr) -> usize {
+        let connection = self.create_pool_entry(config, addr);
+        let idx = self.connections.len();
+        self.connections.push(connection);
+        idx
+    }
+
+    fn num_connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    fn get(&self, index: usize) -> Result<Arc<Self::BaseClientConnection>, ConnectionPoolError> {
+        self.connections
+            .get(index)
+            .cloned()
+            .ok_or(ConnectionPoolError::IndexOutOfRange)
+    }
+
+    fn create_pool_entry(
+        &self,
+        config: &Self::NewConnectionConfig,
+        addr: &SocketAddr,
+    ) -> Arc<Self::BaseClientConnection> {
+        let _guard = config.runtime.enter();
+        let worker = spawn_worker(
+            &self.endpoint,
+            addr,
+            config.worker_channel_size,
+            // The batches are created when the data is sent, they are never
+            // older than the data.
+            true,
+            config.max_reconnect_attempts,
+            DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
+            config.stats.clone(),
+            config.stats.leaders.get_or_insert(*addr),
+            None,
+            None,
+            None,
+        );
+        Arc::new(TpuClientNext(Arc::new(TpuClientNextConnection {
+            server_addr: *addr,
+            worker,
+        })))
+    }
+}
+
+/// Entry of a [`TpuClientNextPool`], it creates the connections sharing its
+/// worker, see [`BaseClientConnection`].
+pub struct TpuClientNext(Arc<TpuClientNextConnection>);
+
+impl BaseClientConnection for TpuClientNext {
+    type BlockingClientConnection = TpuClientNextConnection;
+    type NonblockingClientConnection = TpuClientNextConnection;
+
+    fn new_blocking_connection(
+        &self,
+        _addr: SocketAddr,
+        _stats: Arc<ConnectionCacheStats>,
+    ) -> Arc<Self::BlockingClientConnection> {
+        self.0.clone()
+    }
+
+    fn new_nonblocking_connection(
+        &self,
+        _addr: SocketAddr,
+        _stats: Arc<ConnectionCacheStats>,
+    ) -> Arc<Self::NonblockingClientConnection> {
+        self.0.clone()
+    }
+}
+
+/// Connection queueing the data to the worker of a pool entry. The worker
+/// stops once the connection is dropped by the pool and by its users.
+pub struct TpuClientNextConnection {
+    server_addr: SocketAddr,
+    worker: WorkerInfo,
+}
+
+impl TpuClientNextConnection {
+    /// Returns what the peer supports, `None` until the worker has connected.
+    pub fn capabilities(&self) -> Option<PeerCapabilities> {
+        self.worker.capabilities()
+    }
+
+    fn try_send(&self, batch: TransactionBatch) -> TransportResult<()> {
+        self.worker
+            .transactions_sender()
+            .try_send(batch)
+            .map_err(|err| {
+                let err = match err {
+                    TrySendError::Full(_) => WorkersCacheError::FullChannel,
+                    TrySendError::Closed(_) => WorkersCacheError::ReceiverDropped,
+                };
+                TransportError::Custom(err.to_string())
+            })
+    }
+
+    async fn send(&self, batch: TransactionBatch) -> TransportResult<()> {
+        self.worker
+            .transactions_sender()
+            .send(batch)
+            .await
+            .map_err(|_| TransportError::Custom(WorkersCacheError::ReceiverDropped.to_string()))
+    }
+}
+
+impl BlockingClientConnection for TpuClientNextConnection {
+    fn server_addr(&self) -> &SocketAddr {
+        &self.server_addr
+    }
+
+    fn send_data(&self, buffer: &[u8]) -> TransportResult<()> {
+        self.try_send(TransactionBatch::new(vec![buffer.to_vec()]))
+    }
+
+    fn send_data_async(&self, buffer: Arc<Vec<u8>>) -> TransportResult<()> {
+        self.try_send(TransactionBatch::new(vec![Arc::unwrap_or_clone(buffer)]))
+    }
+
+    fn send_data_batch(&self, buffers: &[Vec<u8>]) -> TransportResult<()> {
+        self.send_data_batch_async(buffers.to_vec())
+    }
+
+    fn send_data_batch_async(&self, buffers: Vec<Vec<u8>>) -> TransportResult<()> {
+        if buffers.is_empty() {
+            return Ok(());
+        }
+        self.try_send(TransactionBatch::new(buffers))
+    }
+}
+
+#[async_trait]
+impl NonblockingClientConnection for TpuClientNextConnection {
+    fn server_addr(&self) -> &SocketAddr {
+        &self.server_addr
+    }
+
+    async fn send_data(&self, buffer: &[u8]) -> TransportResult<()> {
+        self.send(TransactionBatch::new(vec![buffer.to_vec()]))
+            .await
+    }
+
+    async fn send_data_batch(&self, buffers: &[Vec<u8>]) -> TransportResult<()> {
+        if buffers.is_empty() {
+            return Ok(());
+        }
+        self.send(TransactionBatch::new(buffers.to_vec())).await
+    }
+}
+
+/// [`ConnectionManager`] creating the [`TpuClientNextPool`]s.
+pub struct TpuClientNextConnectionManager {
+    connection_config: TpuClientNextConfig,
+}
+
+impl ConnectionManager for TpuClientNextConnectionManager {
+    type ConnectionPool = TpuClientNextPool;
+    type NewConnectionConfig = TpuClientNextConfig;
+
+    const PROTOCOL: Protocol = Protocol::QUIC;
+
+    fn new_connection_pool(&self) -> Self::ConnectionPool {
+        TpuClientNextPool {
+            connections: Vec::default(),
+            endpoint: self
+                .connection_config
+                .create_endpoint()
+                .expect("Failed to create the endpoint of the connection pool"),
+        }
+    }
+
+    fn new_connection_config(&self) -> TpuClientNextConfig {
+        self.connection_config.clone()
+    }
+
+    fn update_key(&self, key: &Keypair) -> Result<(), Box<dyn std::error::Error>> {
+        self.connection_config.update_keypair(key);
+        Ok(())
+    }
+}
+
+impl TpuClientNextConnectionManager {
+    pub fn new_with_connection_config(connection_config: TpuClientNextConfig) -> Self {
+        Self { connection_config }
+    }
+}
+
+pub type TpuClientNextConnectionCache =
+    ConnectionCache<TpuClientNextPool, TpuClientNextConnectionManager, TpuClientNextConfig>;
+
+/// Creates a [`TpuClientNextConnectionCache`] connecting with the identity of
+/// `keypair`, the counterpart of `solana_quic_client::new_quic_connection_cache`.
+pub fn new_tpu_client_next_connection_cache(
+    name: &'static str,
+    keypair: &Keypair,
+    connection_pool_size: usize,
+) -> Result<TpuClientNextConnectionCache, ClientError> {
+    let config = TpuClientNextConfig::new()?;
+    config.update_keypair(keypair);
+    let connection_manager = TpuClientNextConnectionManager::new_with_connection_config(config);
+    ConnectionCache::new(name, connection_manager, connection_pool_size)
+}
@@ -1,26 +1,31 @@
 //! This module defines [`ConnectionWorker`] which encapsulates the functionality
 //! needed to handle one connection within the scope of task.
 
+#[cfg(feature = "otel")]
+use crate::otel;
 use {
     super::SendTransactionStats,
     crate::{
-        logging::{debug, error, trace, warn},
+        clock::timestamp,
+        logging::{log_event, DebugValue},
         quic_networking::send_data_over_stream,
-        send_transaction_stats::record_error,
-        transaction_batch::TransactionBatch,
+        send_transaction_stats::{record_error, DropReason, IdentitySendStats, LeaderSendStats},
+        transaction_batch::{TransactionBatch, TransactionMetadata},
+        workers_cache::{PeerCapabilities, WorkersCacheEvent},
         QuicError,
     },
     quinn::{ConnectError, Connection, ConnectionError, Endpoint},
     solana_clock::{DEFAULT_MS_PER_SLOT, MAX_PROCESSING_AGE, NUM_CONSECUTIVE_LEADER_SLOTS},
     solana_measure::measure::Measure,
-    solana_time_utils::timestamp,
+    solana_streamer::packet::PACKET_DATA_SIZE,
     solana_tls_utils::socket_addr_to_quic_server_name,
     std::{
         net::SocketAddr,
         sync::{atomic::Ordering, Arc},
+        time::Instant,
     },
     tokio::{
-        sync::mpsc,
+        sync::{broadcast, mpsc, watch, Semaphore},
         time::{sleep, timeout, Duration},
     },
     tokio_util::sync::CancellationToken,
@@ -58,10 +63,11 @@ impl Drop for ConnectionState {
     /// finish.
     fn drop(&mut self) {
         if let Self::Active(connection) = self {
-            debug!(
-                "Close connection with {:?}, stats: {:?}. All pending streams will be dropped.",
-                connection.remote_address(),
-                connection.stats()
+            log_event!(
+                debug,
+                "Close connection, all pending streams will be dropped",
+                peer = connection.remote_address(),
+                stats = DebugValue(connection.stats()),
             );
             connection.close(0u32.into(), b"done");
         }
@@ -85,8 +91,46 @@ pub(crate) struct ConnectionWorker {
     skip_check_transaction_age: bool,
     max_reconnect_attempts: usize,
     send_txs_stats: Arc<SendTransactionStats>,
+    leader_stats: Arc<LeaderSendStats>,
+    identity_stats: Arc<IdentitySendStats>,
     cancel: CancellationToken,
     handshake_timeout: Duration,
+    connect_permits: Option<Arc<Semaphore>>,
+    events_sender: Option<broadcast::Sender<WorkersCacheEvent>>,
+    connection_uptime: Option<ConnectionUptime>,
+    capabilities_sender: watch::Sender<Option<PeerCapabilities>>,
+}
+
+/// [`ConnectionUptime`] accounts the lifetime of a connection in
+/// [`LeaderSendStats`] once the connection is gone, that is when the worker
+/// moves to retrying or exits.
+struct ConnectionUptime {
+    leader_stats: Arc<LeaderSendStats>,
+    established_at: Instant,
+}
+
+impl ConnectionUptime {
+    fn new(leader_stats: Arc<LeaderSendStats>) -> Self {
+        leader_stats
+            .connections_established
+            .fetch_add(1, Ordering::Relaxed);
+        Self {
+            leader_stats,
+            established_at: Instant::now(),
+        }
+    }
+}
+
+impl Drop for ConnectionUptime {
+    fn drop(&mut self) {
+        self.leader_stats
+            .connections_closed
+            .fetch_add(1, Ordering::Relaxed);
+        self.leader_stats.connection_uptime_ms.fetch_add(
+            self.established_at.elapsed().as_millis() as u64,
+            Ordering::Relaxed,
+        );
+    }
 }
 
 impl ConnectionWorker {
@@ -97,9 +141,11 @@ impl ConnectionWorker {
     /// `skip_check_transaction_age` is set to `true`, the worker skips checking
     /// for transaction blockhash expiration. The `max_reconnect_attempts`
     /// parameter controls how many times the worker will attempt to reconnect
-    /// in case of connection failure. Returns the created `ConnectionWorker`
-    /// along with a cancellation token that can be used by the caller to stop
-    /// the worker.
+    /// in case of connection failure. Outcomes of sending to the `peer` are
+    /// additionally accounted in `leader_stats`, and in the stats of the
+    /// current identity of `send_txs_stats`. Returns the created
+    /// `ConnectionWorker` along with a cancellation token that can be used by
+    /// the caller to stop the worker.
     pub fn new(
         endpoint: Endpoint,
         peer: SocketAddr,
@@ -107,9 +153,11 @@ impl ConnectionWorker {
         skip_check_transaction_age: bool,
         max_reconnect_attempts: usize,
         send_txs_stats: Arc<SendTransactionStats>,
+        leader_stats: Arc<LeaderSendStats>,
         handshake_timeout: Duration,
     ) -> (Self, CancellationToken) {
         let cancel = CancellationToken::new();
+        let identity_stats = send_txs_stats.identities.current();
         let this = Self {
             endpoint,
             peer,
@@ -118,13 +166,43 @@ impl ConnectionWorker {
             skip_check_transaction_age,
             max_reconnect_attempts,
             send_txs_stats,
+            leader_stats,
+            identity_stats,
             cancel: cancel.clone(),
             handshake_timeout,
+            connect_permits: None,
+            events_sender: None,
+            connection_uptime: None,
+            capabilities_sender: watch::Sender::new(None),
         };
 
         (this, cancel)
     }
 
+    /// Requires a permit from `connect_permits` to establish a connection, so
+    /// that the number of concurrent handshakes of the workers sharing the
+    /// semaphore is bounded.
+    pub fn with_connect_permits(mut self, connect_permits: Arc<Semaphore>) -> Self {
+        self.connect_permits = Some(connect_permits);
+        self
+    }
+
+    /// Sends a [`WorkersCacheEvent::Failed`] over `events_sender` for each
+    /// failure, in addition to accounting it in the stats.
+    pub fn with_events_sender(
+        mut self,
+        events_sender: broadcast::Sender<WorkersCacheEvent>,
+    ) -> Self {
+        self.events_sender = Some(events_sender);
+        self
+    }
+
+    /// Returns a receiver of the [`PeerCapabilities`] detected on the
+    /// connections of the worker, `None` until the first one is established.
+    pub fn subscribe_capabilities(&self) -> watch::Receiver<Option<PeerCapabilities>> {
+        self.capabilities_sender.subscribe()
+    }
+
     /// Starts the main loop of the [`ConnectionWorker`].
     ///
     /// This method manages the connection to the peer and handles state
@@ -134,7 +212,7 @@ impl ConnectionWorker {
     pub async fn run(&mut self) {
         let cancel = self.cancel.clone();
 
-        let main_loop = async move {
+        let main_loop = async {
             loop {
                 match &self.connection {
                     ConnectionState::Closing => {
@@ -149,12 +227,22 @@ impl ConnectionWorker {
                             transactions = self.transactions_receiver.recv() => {
                                 match transactions {
                                     Some(batch) => {
-                                        self.send_transactions(connection.clone(), batch).await;
+                                        #[cfg(feature = "tracing")]
+                                        let span = tracing::info_span!(
+                                            "transaction_batch",
+                                            batch_id = batch.id(),
+                                            num_transactions = batch.len(),
+                                        );
+                                        let send = self.send_transactions(connection.clone(), batch);
+                                        #[cfg(feature = "tracing")]
+                                        let send = tracing::Instrument::instrument(send, span);
+                                        send.await;
                                     }
                                     None => {
-                                        debug!(
-                                            "Transactions sender has been dropped for peer: {}",
-                                            self.peer
+                                        log_event!(
+                                            debug,
+                                            "Transactions sender has been dropped",
+                                            peer = self.peer,
                                         );
                                         self.connection = ConnectionState::Closing;
                                     }
@@ -169,8 +257,17 @@ impl ConnectionWorker {
                         }
                     }
                     ConnectionState::Retry(num_reconnects) => {
+                        self.connection_uptime = None;
                         if *num_reconnects > self.max_reconnect_attempts {
-                            error!("Failed to establish connection to {}: reached max reconnect attempts", self.peer);
+                            log_event!(
+                                error,
+                                "Failed to establish connection: reached max reconnect attempts",
+                                peer = self.peer,
+                            );
+                            self.send_txs_stats
+                                .retries
+                                .gave_up
+                                .fetch_add(1, Ordering::Relaxed);
                             self.connection = ConnectionState::Closing;
                             continue;
                         }
@@ -185,6 +282,13 @@ impl ConnectionWorker {
             () = main_loop => (),
             () = cancel.cancelled() => (),
         }
+
+        let reason = if cancel.is_cancelled() {
+            DropReason::Cancelled
+        } else {
+            DropReason::ConnectionLost
+        };
+        self.record_queued_transactions_dropped(reason);
     }
 
     /// Handles connection closure events detected by the connection monitor.
@@ -195,48 +299,57 @@ impl ConnectionWorker {
     async fn handle_connection_closed(&mut self, close_reason: ConnectionError) {
         match &close_reason {
             ConnectionError::ConnectionClosed(close) => {
-                debug!(
-                    "Connection to {} closed by peer: code={} reason={:?}",
-                    self.peer,
-                    close.error_code,
-                    String::from_utf8_lossy(&close.reason)
+                log_event!(
+                    debug,
+                    "Connection closed by peer",
+                    peer = self.peer,
+                    code = close.error_code,
+                    reason = DebugValue(String::from_utf8_lossy(&close.reason)),
                 );
             }
             ConnectionError::ApplicationClosed(close) => {
-                debug!(
-                    "Connection to {} closed by application: code={} reason={:?}",
-                    self.peer,
-                    close.error_code,
-                    String::from_utf8_lossy(&close.reason)
+                log_event!(
+                    debug,
+                    "Connection closed by application",
+                    peer = self.peer,
+                    code = close.error_code,
+                    reason = DebugValue(String::from_utf8_lossy(&close.reason)),
                 );
             }
             ConnectionError::LocallyClosed => {
-                debug!("Connection to {} closed locally", self.peer);
+                log_event!(debug, "Connection closed locally", peer = self.peer);
             }
             ConnectionError::TimedOut => {
-                warn!("Connection to {} timed out", self.peer);
+                log_event!(warn, "Connection timed out", peer = self.peer);
             }
             ConnectionError::Reset => {
-                warn!("Connection to {} reset", self.peer);
+                log_event!(warn, "Connection reset", peer = self.peer);
             }
             ConnectionError::TransportError(e) => {
-                warn!(
-                    "Connection to {} encountered transport error: {}",
-                    self.peer, e
+                log_event!(
+                    warn,
+                    "Connection encountered transport error",
+                    peer = self.peer,
+                    error = e,
                 );
             }
             ConnectionError::VersionMismatch => {
-                error!("Connection to {} failed: version mismatch", self.peer);
+                log_event!(
+                    error,
+                    "Connection failed: version mismatch",
+                    peer = self.peer
+                );
             }
             ConnectionError::CidsExhausted => {
-                warn!(
-                    "Connection to {} closed: connection IDs exhausted",
-                    self.peer
+                log_event!(
+                    warn,
+                    "Connection closed: connection IDs exhausted",
+                    peer = self.peer,
                 );
             }
         }
 
-        record_error(close_reason.clone().into(), &self.send_txs_stats);
+        self.record_error(close_reason.clone().into());
 
         // Determine next state based on close reason
         // Fatal errors transition to Closing, recoverable errors transition to Retry
@@ -264,41 +377,153 @@ impl ConnectionWorker {
         if !self.skip_check_transaction_age
             && now.saturating_sub(transactions.timestamp()) > MAX_PROCESSING_AGE_MS
         {
-            debug!("Drop outdated transaction batch for peer: {}", self.peer);
+            log_event!(
+                debug,
+                "Drop outdated transaction batch",
+                peer = self.peer,
+                batch_size = transactions.len(),
+            );
+            self.send_txs_stats.record_batch_dropped(
+                Some(self.peer),
+                DropReason::Deadline,
+                &transactions,
+            );
             return;
         }
 
+        let latency_stats = &self.send_txs_stats.latency;
+        let created_at = transactions.created_at();
+        let dispatched_at = Instant::now();
+        latency_stats
+            .queueing
+            .record(dispatched_at.saturating_duration_since(created_at));
+
+        #[cfg(feature = "otel")]
+        let span = otel::send_batch_span(transactions.span(), &self.peer, transactions.id());
+        let (transactions, metadata) = transactions.into_parts();
+        let num_transactions = transactions.len();
+        let batch_sizes = &self.send_txs_stats.batch_sizes;
+        batch_sizes.transactions.record(num_transactions as u64);
+        batch_sizes
+            .bytes
+            .record(transactions.iter().map(|data| data.len() as u64).sum());
+        // Only fetched if there are transactions to report.
+        let observer = if metadata.is_empty() {
+            None
+        } else {
+            self.send_txs_stats.observer()
+        };
+        let mut num_sent = 0usize;
         let mut measure_send = Measure::start("send transaction batch");
-        for data in transactions.into_iter() {
+        // The transactions are shared with the workers of the other leaders,
+        // only the handles to their payloads are cloned.
+        for (index, data) in transactions.iter().cloned().enumerate() {
             // Check connection health before each send
             if connection.close_reason().is_some() {
-                debug!("Connection closed during transaction batch sending");
+                log_event!(
+                    debug,
+                    "Connection closed during transaction batch sending",
+                    peer = self.peer,
+                    num_unsent = num_transactions - index,
+                );
+                self.leader_stats
+                    .failed
+                    .fetch_add((num_transactions - index) as u64, Ordering::Relaxed);
                 self.connection = ConnectionState::Retry(0);
+                self.record_transactions_not_retried(
+                    num_transactions - index,
+                    metadata.get(index..).unwrap_or_default(),
+                );
                 break;
             }
+            if data.len() > PACKET_DATA_SIZE {
+                log_event!(
+                    debug,
+                    "Drop oversized transaction",
+                    peer = self.peer,
+                    num_bytes = data.len(),
+                );
+                self.send_txs_stats
+                    .record_dropped(Some(self.peer), DropReason::Oversize, 1);
+                self.send_txs_stats.notify_transactions_dropped(
+                    self.peer,
+                    DropReason::Oversize,
+                    metadata.get(index..=index).unwrap_or_default(),
+                );
+                continue;
+            }
 
-            let result = send_data_over_stream(&connection, &data).await;
+            let size = data.len() as u64;
+            let result = send_data_over_stream(&connection, data).await;
 
             if let Err(error) = result {
-                trace!(
-                    "Failed to send transaction to {} over stream with error: {error}",
-                    self.peer
+                log_event!(
+                    trace,
+                    "Failed to send transaction over stream",
+                    peer = self.peer,
+                    batch_size = num_transactions,
+                    error = error,
                 );
-                record_error(error, &self.send_txs_stats);
+                self.record_error(error);
+                self.leader_stats
+                    .failed
+                    .fetch_add((num_transactions - index) as u64, Ordering::Relaxed);
                 self.connection = ConnectionState::Retry(0);
+                self.record_transactions_not_retried(
+                    num_transactions - index,
+                    metadata.get(index..).unwrap_or_default(),
+                );
                 // Exit early since connection is likely broken
                 break;
             } else {
                 self.send_txs_stats
                     .successfully_sent
                     .fetch_add(1, Ordering::Relaxed);
+                self.send_txs_stats
+                    .bytes_sent
+                    .fetch_add(size, Ordering::Relaxed);
+                self.send_txs_stats.rolling.record_sent();
+                if let (Some(observer), Some(metadata)) = (&observer, metadata.get(index)) {
+                    observer.on_transaction_sent(self.peer, *metadata);
+                }
+                self.leader_stats
+                    .successfully_sent
+                    .fetch_add(1, Ordering::Relaxed);
+                self.leader_stats
+                    .bytes_sent
+                    .fetch_add(size, Ordering::Relaxed);
+                self.identity_stats
+                    .successfully_sent
+                    .fetch_add(1, Ordering::Relaxed);
+                self.identity_stats
+                    .bytes_sent
+                    .fetch_add(size, Ordering::Relaxed);
+                let sending_latency = dispatched_at.elapsed();
+                let end_to_end_latency = created_at.elapsed();
+                latency_stats.sending.record(sending_latency);
+                latency_stats.end_to_end.record(end_to_end_latency);
+                latency_stats.recent_sending.record(sending_latency);
+                latency_stats.recent_end_to_end.record(end_to_end_latency);
+                num_sent = num_sent.saturating_add(1);
             }
         }
         measure_send.stop();
-        debug!(
-            "Time to send transactions batch to {}: {} us",
-            self.peer,
-            measure_send.as_us()
+        self.record_capabilities(&connection, false);
+        self.send_txs_stats.throughput.record_batch(num_sent);
+        #[cfg(feature = "otel")]
+        {
+            otel::record_batch_sent(&span, num_transactions, num_sent);
+            if num_sent > 0 {
+                otel::record_batch_latency(dispatched_at.elapsed(), created_at.elapsed());
+            }
+        }
+        log_event!(
+            debug,
+            "Sent transaction batch",
+            peer = self.peer,
+            batch_size = num_transactions,
+            num_sent = num_sent,
+            duration_us = measure_send.as_us(),
         );
     }
 
@@ -309,6 +534,11 @@ impl ConnectionWorker {
     /// If an error occurs, the state may transition to `Retry` or `Closing`,
     /// depending on the nature of the error.
     async fn create_connection(&mut self, retries_attempt: usize) {
+        // The semaphore is never closed, so the permit is always acquired.
+        let _permit = match &self.connect_permits {
+            Some(connect_permits) => connect_permits.acquire().await.ok(),
+            None => None,
+        };
         let server_name = socket_addr_to_quic_server_name(self.peer);
         let connecting = self.endpoint.connect(self.peer, &server_name);
         match connecting {
@@ -316,46 +546,66 @@ impl ConnectionWorker {
                 let mut measure_connection = Measure::start("establish connection");
                 let res = timeout(self.handshake_timeout, connecting).await;
                 measure_connection.stop();
-                debug!(
-                    "Establishing connection with {} took: {} us",
-                    self.peer,
-                    measure_connection.as_us()
+                log_event!(
+                    debug,
+                    "Establishing connection finished",
+                    peer = self.peer,
+                    duration_us = measure_connection.as_us(),
                 );
                 match res {
                     Ok(Ok(connection)) => {
+                        self.leader_stats
+                            .record_handshake(measure_connection.as_duration());
+                        self.record_capabilities(&connection, true);
                         self.connection = ConnectionState::Active(connection);
+                        self.connection_uptime =
+                            Some(ConnectionUptime::new(self.leader_stats.clone()));
                     }
                     Ok(Err(err)) => {
-                        warn!("Connection error {}: {}", self.peer, err);
-                        record_error(err.into(), &self.send_txs_stats);
+                        log_event!(warn, "Connection error", peer = self.peer, error = err);
+                        self.record_error(err.into());
+                        self.leader_stats
+                            .handshakes_failed
+                            .fetch_add(1, Ordering::Relaxed);
                         self.connection = ConnectionState::Retry(retries_attempt.saturating_add(1));
                     }
                     Err(_) => {
-                        debug!(
-                            "Connection to {} timed out after {:?}",
-                            self.peer, self.handshake_timeout
+                        log_event!(
+                            debug,
+                            "Connection handshake timed out",
+                            peer = self.peer,
+                            timeout = DebugValue(self.handshake_timeout),
                         );
-                        record_error(QuicError::HandshakeTimeout, &self.send_txs_stats);
+                        self.record_error(QuicError::HandshakeTimeout);
+                        self.leader_stats
+                            .handshakes_failed
+                            .fetch_add(1, Ordering::Relaxed);
                         self.connection = ConnectionState::Retry(retries_attempt.saturating_add(1));
                     }
                 }
             }
             Err(connecting_error) => {
-                record_error(connecting_error.clone().into(), &self.send_txs_stats);
+                self.record_error(connecting_error.clone().into());
                 match connecting_error {
                     ConnectError::EndpointStopping => {
-                        debug!(
-                            "Endpoint stopping, exit connection worker for peer: {}",
-                            self.peer
+                        log_event!(
+                            debug,
+                            "Endpoint stopping, exit connection worker",
+                            peer = self.peer,
                         );
                         self.connection = ConnectionState::Closing;
                     }
                     ConnectError::InvalidRemoteAddress(_) => {
-                        warn!("Invalid remote address for peer: {}", self.peer);
+                        log_event!(warn, "Invalid remote address", peer = self.peer);
                         self.connection = ConnectionState::Closing;
                     }
                     e => {
-                        error!("Unexpected error has happened while trying to create connection to {}: {e}", self.peer);
+                        log_event!(
+                            error,
+                            "Unexpected error has happened while trying to create connection",
+                            peer = self.peer,
+                            error = e,
+                        );
                         self.connection = ConnectionState::Closing;
                     }
                 }
@@ -363,15 +613,106 @@ impl ConnectionWorker {
         }
     }
 
+    /// Detects the [`PeerCapabilities`] on `connection` and publishes them if
+    /// they have changed. The congestion window is only recorded on a new
+    /// connection, so that it stays the initial one.
+    fn record_capabilities(&self, connection: &Connection, is_new_connection: bool) {
+        let mut capabilities = PeerCapabilities::detect(connection);
+        self.capabilities_sender.send_if_modified(|current| {
+            if let (false, Some(current)) = (is_new_connection, current.as_ref()) {
+                capabilities.initial_congestion_window = current.initial_congestion_window;
+            }
+            if current.as_ref() == Some(&capabilities) {
+                return false;
+            }
+            *current = Some(capabilities);
+            true
+        });
+    }
+
     /// Attempts to reconnect to the peer after a connection failure.
     async fn reconnect(&mut self, num_reconnects: usize) {
-        debug!(
-            "Trying to reconnect to {}. Reopen connection, 0rtt is not implemented yet.",
-            self.peer
+        log_event!(
+            debug,
+            "Trying to reconnect, 0rtt is not implemented yet",
+            peer = self.peer,
         );
         // We can reconnect using 0rtt, but not a priority for now. Check if we
         // need to call config.enable_0rtt() on the client side and where
         // session tickets are stored.
+        self.send_txs_stats
+            .retries
+            .reconnect_attempts
+            .fetch_add(1, Ordering::Relaxed);
         self.create_connection(num_reconnects).await;
+        if let ConnectionState::Active(_) = self.connection {
+            let retry_stats = &self.send_txs_stats.retries;
+            let reconnected = if num_reconnects == 0 {
+                &retry_stats.reconnected_at_first_attempt
+            } else {
+                &retry_stats.reconnected_after_retry
+            };
+            reconnected.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Accounts `err` in the stats and notifies the observer and the events
+    /// subscribers, if any.
+    fn record_error(&self, err: QuicError) {
+        if let Some(observer) = self.send_txs_stats.observer() {
+            observer.on_connection_error(self.peer, &err);
+        }
+        self.identity_stats.record_error(&err);
+        let error_records = &self.send_txs_stats.error_records;
+        match &self.events_sender {
+            Some(events_sender) => {
+                let record = error_records.new_record(self.peer, &err);
+                error_records.push(record.clone());
+                // There may be no subscriber.
+                let _ = events_sender.send(WorkersCacheEvent::Failed { record });
+            }
+            None => error_records.record(self.peer, &err),
+        }
+        record_error(err, &self.send_txs_stats);
+    }
+
+    /// Counts the transactions of the batch left unsent after a failure, see
+    /// [`RetryStats`].
+    ///
+    /// [`RetryStats`]: crate::send_transaction_stats::RetryStats
+    fn record_transactions_not_retried(
+        &self,
+        num_transactions: usize,
+        metadata: &[TransactionMetadata],
+    ) {
+        self.send_txs_stats
+            .retries
+            .transactions_not_retried
+            .fetch_add(num_transactions as u64, Ordering::Relaxed);
+        self.send_txs_stats.record_dropped(
+            Some(self.peer),
+            DropReason::ConnectionLost,
+            num_transactions,
+        );
+        self.send_txs_stats.notify_transactions_dropped(
+            self.peer,
+            DropReason::ConnectionLost,
+            metadata,
+        );
+    }
+
+    /// Accounts the batches left in the channel once the worker has stopped,
+    /// they are dropped with the worker.
+    fn record_queued_transactions_dropped(&mut self, reason: DropReason) {
+        let mut num_transactions = 0usize;
+        let mut metadata = Vec::new();
+        while let Ok(batch) = self.transactions_receiver.try_recv() {
+            num_transactions = num_transactions.saturating_add(batch.len());
+            metadata.extend_from_slice(batch.metadata());
+        }
+        self.send_txs_stats
+            .record_dropped(Some(self.peer), reason, num_transactions);
+        self.send_txs_stats
+            .notify_transactions_dropped(self.peer, reason, &metadata);
     }
 }
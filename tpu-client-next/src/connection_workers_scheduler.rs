@@ -4,30 +4,67 @@
 #[cfg(feature = "agave-unstable-api")]
 use qualifier_attr::qualifiers;
 use {
-    super::leader_updater::LeaderUpdater,
+    super::leader_updater::{
+        create_leader_updater, websocket_url_from_rpc_url, LeaderUpdater, LeaderUpdaterError,
+    },
     crate::{
         connection_worker::DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
-        logging::{debug, warn},
+        health::HealthProbe,
+        leader_prober::LeaderProber,
+        logging::{debug, log_event, set_verbosity, DebugValue, Verbosity},
+        negotiated_parameters::NegotiatedParameters,
         quic_networking::{
-            create_client_config, create_client_endpoint, QuicClientCertificate, QuicError,
+            create_client_config_with_provider, create_client_endpoint, QuicClientCertificate,
+            QuicError,
         },
+        send_transaction_stats::{DropReason, LeadersSendStats, SendingIdentity},
         transaction_batch::TransactionBatch,
-        workers_cache::{shutdown_worker, spawn_worker, WorkersCache, WorkersCacheError},
+        workers_cache::{
+            spawn_worker, EvictionPolicy, WorkerSnapshot, WorkerTaskPool, WorkersCache,
+            WorkersCacheError, WorkersCacheEvent, DEFAULT_MAX_CONCURRENT_SHUTDOWNS,
+            DEFAULT_SHUTDOWN_DEADLINE,
+        },
         SendTransactionStats,
     },
     async_trait::async_trait,
     quinn::{ClientConfig, Endpoint},
-    solana_keypair::Keypair,
+    rustls::{client::danger::ServerCertVerifier, crypto::CryptoProvider},
+    serde::{de, Deserializer},
+    serde_derive::{Deserialize, Serialize},
+    solana_clock::{DEFAULT_MS_PER_SLOT, NUM_CONSECUTIVE_LEADER_SLOTS},
+    solana_keypair::{Keypair, Signer},
+    solana_pubkey::Pubkey,
+    solana_rpc_client::nonblocking::rpc_client::RpcClient,
+    solana_tls_utils::crypto_provider,
     std::{
-        net::{SocketAddr, UdpSocket},
-        sync::Arc,
+        cmp::Reverse,
+        net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+        panic,
+        sync::{atomic::Ordering, Arc},
     },
     thiserror::Error,
-    tokio::sync::{mpsc, watch},
-    tokio_util::sync::CancellationToken,
+    tokio::{
+        runtime::Handle,
+        sync::{broadcast, mpsc, watch, Semaphore},
+        time::{interval, interval_at, Duration, Instant, MissedTickBehavior},
+    },
+    tokio_util::{sync::CancellationToken, task::AbortOnDropHandle},
 };
 pub type TransactionReceiver = mpsc::Receiver<TransactionBatch>;
 
+/// Default interval between the checks removing the workers which have
+/// stopped, see [`ConnectionWorkersScheduler::with_health_check_interval`].
+pub const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval between the checks of the fanout set when the fanout
+/// pre-creation is enabled, see
+/// [`ConnectionWorkersScheduler::with_fanout_precreation`].
+const FANOUT_PRECREATION_INTERVAL: Duration = Duration::from_millis(DEFAULT_MS_PER_SLOT);
+
+/// Size of the transaction channel created by
+/// [`ConnectionWorkersScheduler::from_rpc`].
+const RPC_SCHEDULER_CHANNEL_SIZE: usize = 1024;
+
 /// The [`ConnectionWorkersScheduler`] sends transactions from the provided
 /// receiver channel to upcoming leaders. It obtains information about future
 /// leaders from the implementation of the [`LeaderUpdater`] trait.
@@ -38,6 +75,29 @@ pub struct ConnectionWorkersScheduler {
     leader_updater: Box<dyn LeaderUpdater>,
     transaction_receiver: TransactionReceiver,
     update_identity_receiver: watch::Receiver<Option<StakeIdentity>>,
+    leaders_fanout_receiver: Option<watch::Receiver<Fanout>>,
+    block_height_receiver: Option<watch::Receiver<u64>>,
+    verbosity_receiver: Option<watch::Receiver<Verbosity>>,
+    prefer_successful_leaders: bool,
+    eviction_policy: Option<Box<dyn EvictionPolicy>>,
+    health_check_interval: Duration,
+    cache_warming: Option<CacheWarmingConfig>,
+    leader_probing: Option<LeaderProbingConfig>,
+    pinned_peers: Vec<SocketAddr>,
+    workers_snapshot_sender: Option<watch::Sender<Vec<WorkerSnapshot>>>,
+    max_concurrent_shutdowns: usize,
+    shutdown_deadline: Duration,
+    soft_num_connections: Option<usize>,
+    max_idle_worker_tasks: Option<usize>,
+    max_worker_lifetime: Option<Duration>,
+    workers_cache_events_sender: Option<broadcast::Sender<WorkersCacheEvent>>,
+    initial_peers: Vec<SocketAddr>,
+    max_concurrent_handshakes: Option<usize>,
+    crypto_provider: Arc<CryptoProvider>,
+    server_cert_verifier: Option<Arc<dyn ServerCertVerifier>>,
+    runtime: Option<Handle>,
+    leader_update_sender: watch::Sender<Option<Instant>>,
+    negotiated_parameters_sender: watch::Sender<Option<NegotiatedParameters>>,
     cancel: CancellationToken,
     stats: Arc<SendTransactionStats>,
 }
@@ -51,6 +111,8 @@ pub enum ConnectionWorkersSchedulerError {
     WorkersCacheError(#[from] WorkersCacheError),
     #[error("Leader receiver unexpectedly dropped.")]
     LeaderReceiverDropped,
+    #[error("The runtime of the scheduler has shut down.")]
+    RuntimeShutdown,
 }
 
 /// [`Fanout`] is a configuration struct that specifies how many leaders should
@@ -64,7 +126,12 @@ pub enum ConnectionWorkersSchedulerError {
 /// The idea of having a separate `connect` parameter is to create a set of
 /// nodes to connect to in advance in order to hide the latency of opening new
 /// connection. Hence, `connect` must be greater or equal to `send`
-#[derive(Debug, Clone)]
+///
+/// The fanout can be changed at runtime, see
+/// [`ConnectionWorkersScheduler::with_leaders_fanout_receiver`], and a deeper
+/// fanout can be requested for individual batches with
+/// [`TransactionBatch::with_leaders_fanout`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Fanout {
     /// The number of leaders to target for sending transactions.
     pub send: usize,
@@ -78,6 +145,9 @@ pub struct Fanout {
 /// This struct holds the necessary settings to initialize and manage connection
 /// workers, including network binding, identity, connection limits, and
 /// behavior related to transaction handling.
+///
+/// It can be deserialized from a configuration file, in which all the fields
+/// are optional except for the stake identity, which is not part of it.
 pub struct ConnectionWorkersSchedulerConfig {
     /// The local address to bind the scheduler to.
     pub bind: BindTarget,
@@ -86,7 +156,9 @@ pub struct ConnectionWorkersSchedulerConfig {
     /// identifying the sender.
     pub stake_identity: Option<StakeIdentity>,
 
-    /// The number of connections to be maintained by the scheduler.
+    /// The number of connections to be maintained by the scheduler, which is
+    /// the capacity of the workers cache. Evictions caused by this limit are
+    /// accounted in [`SendTransactionStats`].
     pub num_connections: usize,
 
     /// Whether to skip checking the transaction blockhash expiration.
@@ -104,6 +176,269 @@ pub struct ConnectionWorkersSchedulerConfig {
     pub leaders_fanout: Fanout,
 }
 
+/// Binds to an ephemeral port on all the interfaces and sends unstaked to the
+/// next 2 leaders, while connecting to one more leader in advance.
+impl Default for ConnectionWorkersSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            bind: BindTarget::Address(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)),
+            stake_identity: None,
+            num_connections: 16,
+            skip_check_transaction_age: false,
+            worker_channel_size: 64,
+            max_reconnect_attempts: 4,
+            leaders_fanout: Fanout {
+                send: 2,
+                connect: 3,
+            },
+        }
+    }
+}
+
+/// Errors of [`ConnectionWorkersSchedulerConfig::validate`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConnectionWorkersSchedulerConfigError {
+    #[error("num_connections must be positive.")]
+    NoConnections,
+    #[error("worker_channel_size must be positive.")]
+    EmptyWorkerChannel,
+    #[error("leaders_fanout.send must be positive.")]
+    NoSendFanout,
+    #[error("leaders_fanout.connect ({connect}) must be at least leaders_fanout.send ({send}).")]
+    ConnectFanoutSmallerThanSend { send: usize, connect: usize },
+    #[error(
+        "num_connections ({num_connections}) must be at least leaders_fanout.connect ({connect})."
+    )]
+    ConnectFanoutExceedsConnections {
+        num_connections: usize,
+        connect: usize,
+    },
+}
+
+impl ConnectionWorkersSchedulerConfig {
+    /// Checks that the scheduler can work with this configuration, which is
+    /// done when it is deserialized.
+    pub fn validate(&self) -> Result<(), ConnectionWorkersSchedulerConfigError> {
+        let Fanout { send, connect } = self.leaders_fanout;
+        if self.num_connections == 0 {
+            return Err(ConnectionWorkersSchedulerConfigError::NoConnections);
+        }
+        if self.worker_channel_size == 0 {
+            return Err(ConnectionWorkersSchedulerConfigError::EmptyWorkerChannel);
+        }
+        if send == 0 {
+            return Err(ConnectionWorkersSchedulerConfigError::NoSendFanout);
+        }
+        if connect < send {
+            return Err(
+                ConnectionWorkersSchedulerConfigError::ConnectFanoutSmallerThanSend {
+                    send,
+                    connect,
+                },
+            );
+        }
+        if self.num_connections < connect {
+            return Err(
+                ConnectionWorkersSchedulerConfigError::ConnectFanoutExceedsConnections {
+                    num_connections: self.num_connections,
+                    connect,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Deserialized form of [`ConnectionWorkersSchedulerConfig`], the missing
+/// fields take their default value.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DeserializedConfig {
+    bind: Option<SocketAddr>,
+    num_connections: Option<usize>,
+    skip_check_transaction_age: Option<bool>,
+    worker_channel_size: Option<usize>,
+    max_reconnect_attempts: Option<usize>,
+    leaders_fanout: Option<Fanout>,
+}
+
+// Deserialized from any serde format, e.g. TOML or JSON, so that the scheduler
+// can be tuned from the configuration files of the deployment. The stake
+// identity is not part of the files, it is set by the application, and the
+// scheduler binds to an address.
+impl<'de> serde::Deserialize<'de> for ConnectionWorkersSchedulerConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let DeserializedConfig {
+            bind,
+            num_connections,
+            skip_check_transaction_age,
+            worker_channel_size,
+            max_reconnect_attempts,
+            leaders_fanout,
+        } = DeserializedConfig::deserialize(deserializer)?;
+        let default = Self::default();
+        let config = Self {
+            bind: bind.map_or(default.bind, BindTarget::Address),
+            stake_identity: None,
+            num_connections: num_connections.unwrap_or(default.num_connections),
+            skip_check_transaction_age: skip_check_transaction_age
+                .unwrap_or(default.skip_check_transaction_age),
+            worker_channel_size: worker_channel_size.unwrap_or(default.worker_channel_size),
+            max_reconnect_attempts: max_reconnect_attempts
+                .unwrap_or(default.max_reconnect_attempts),
+            leaders_fanout: leaders_fanout.unwrap_or(default.leaders_fanout),
+        };
+        config.validate().map_err(de::Error::custom)?;
+        Ok(config)
+    }
+}
+
+/// The RPC node which [`ConnectionWorkersScheduler::from_rpc`] gets the
+/// leaders from. The websocket URL is derived from the RPC URL like the CLI
+/// does, i.e. `ws` or `wss` on the next port if the port is explicit.
+pub enum RpcSource {
+    Url(String),
+    Client(Arc<RpcClient>),
+}
+
+impl From<String> for RpcSource {
+    fn from(url: String) -> Self {
+        Self::Url(url)
+    }
+}
+
+impl From<&str> for RpcSource {
+    fn from(url: &str) -> Self {
+        Self::Url(url.to_string())
+    }
+}
+
+impl From<Arc<RpcClient>> for RpcSource {
+    fn from(rpc_client: Arc<RpcClient>) -> Self {
+        Self::Client(rpc_client)
+    }
+}
+
+/// A [`ConnectionWorkersScheduler`] created by
+/// [`ConnectionWorkersScheduler::from_rpc`] along with its configuration and
+/// the channels to drive it.
+///
+/// The scheduler can be configured further before [`RpcScheduler::run`] is
+/// called, e.g. `rpc_scheduler.scheduler =
+/// rpc_scheduler.scheduler.with_cache_warming(..)`.
+pub struct RpcScheduler {
+    pub scheduler: ConnectionWorkersScheduler,
+    pub config: ConnectionWorkersSchedulerConfig,
+    /// The sender of the transaction batches to send to the leaders.
+    pub transaction_sender: mpsc::Sender<TransactionBatch>,
+    /// The sender of the identity updates, see [`StakeIdentity`].
+    pub update_identity_sender: watch::Sender<Option<StakeIdentity>>,
+    /// Stops the scheduler once cancelled.
+    pub cancel: CancellationToken,
+}
+
+impl RpcScheduler {
+    /// Runs the scheduler with its configuration, see
+    /// [`ConnectionWorkersScheduler::run`].
+    pub async fn run(self) -> Result<Arc<SendTransactionStats>, ConnectionWorkersSchedulerError> {
+        self.scheduler.run(self.config).await
+    }
+}
+
+/// Configuration of the cache warming, which creates workers for the leaders
+/// of the upcoming slots before any transaction batch targets them, see
+/// [`ConnectionWorkersScheduler::with_cache_warming`].
+///
+/// Unlike [`Fanout::connect`], which is applied only when a batch is
+/// received, warming runs periodically, so the connection to the next leader
+/// is established even if there were no transactions to send for a while.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheWarmingConfig {
+    /// The number of upcoming slots whose leaders get a worker in advance.
+    pub lookahead_slots: u64,
+
+    /// How often the upcoming leaders are checked.
+    pub interval: Duration,
+}
+
+impl Default for CacheWarmingConfig {
+    fn default() -> Self {
+        Self {
+            lookahead_slots: 2 * NUM_CONSECUTIVE_LEADER_SLOTS,
+            interval: Duration::from_millis(DEFAULT_MS_PER_SLOT),
+        }
+    }
+}
+
+impl CacheWarmingConfig {
+    /// Returns the number of leaders covering `lookahead_slots`, in the
+    /// units of [`LeaderUpdater::next_leaders`].
+    fn num_leaders(&self) -> usize {
+        num_leaders_for_slots(self.lookahead_slots)
+    }
+}
+
+/// Configuration of the leader probing, see
+/// [`ConnectionWorkersScheduler::with_leader_probing`].
+///
+/// The probes look further ahead than the cache warming, so that whether a
+/// leader is reachable is known before its worker is created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaderProbingConfig {
+    /// The number of upcoming slots whose leaders are probed.
+    pub lookahead_slots: u64,
+
+    /// How often the upcoming leaders are checked.
+    pub interval: Duration,
+
+    /// How long a probe waits for the handshake to complete before the leader
+    /// is considered unreachable.
+    pub handshake_timeout: Duration,
+}
+
+impl Default for LeaderProbingConfig {
+    fn default() -> Self {
+        Self {
+            lookahead_slots: 8 * NUM_CONSECUTIVE_LEADER_SLOTS,
+            interval: Duration::from_millis(NUM_CONSECUTIVE_LEADER_SLOTS * DEFAULT_MS_PER_SLOT),
+            handshake_timeout: DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
+        }
+    }
+}
+
+impl LeaderProbingConfig {
+    /// Returns the number of leaders covering `lookahead_slots`, in the
+    /// units of [`LeaderUpdater::next_leaders`].
+    fn num_leaders(&self) -> usize {
+        num_leaders_for_slots(self.lookahead_slots)
+    }
+}
+
+fn num_leaders_for_slots(lookahead_slots: u64) -> usize {
+    lookahead_slots
+        .div_ceil(NUM_CONSECUTIVE_LEADER_SLOTS)
+        .max(1) as usize
+}
+
+/// Settings used to spawn the connection workers.
+struct WorkerSettings {
+    worker_channel_size: usize,
+    skip_check_transaction_age: bool,
+    max_reconnect_attempts: usize,
+    task_pool: Option<WorkerTaskPool>,
+    connect_permits: Option<Arc<Semaphore>>,
+}
+
+impl Fanout {
+    /// Returns the fanout which covers both `self` and `other`.
+    fn max(&self, other: &Fanout) -> Fanout {
+        Fanout {
+            send: self.send.max(other.send),
+            connect: self.connect.max(other.connect),
+        }
+    }
+}
+
 /// The [`BindTarget`] enum defines how the UDP socket should be bound:
 /// either by providing a [`SocketAddr`] or an existing [`UdpSocket`].
 pub enum BindTarget {
@@ -117,16 +452,22 @@ pub enum BindTarget {
 /// needs to be transferred. This wrapper structure allows the use of either a `Keypair`
 /// or a `&Keypair` to create a certificate, which is stored internally and later
 /// consumed by [`ConnectionWorkersScheduler`] to create an endpoint.
-pub struct StakeIdentity(QuicClientCertificate);
+pub struct StakeIdentity(QuicClientCertificate, Pubkey);
 
 impl StakeIdentity {
     pub fn new(keypair: &Keypair) -> Self {
-        Self(QuicClientCertificate::new(Some(keypair)))
+        Self(QuicClientCertificate::new(Some(keypair)), keypair.pubkey())
     }
 
     pub fn as_certificate(&self) -> &QuicClientCertificate {
         &self.0
     }
+
+    /// Returns the pubkey of the keypair, under which the stats are segmented,
+    /// see [`SendingIdentity`].
+    pub fn pubkey(&self) -> Pubkey {
+        self.1
+    }
 }
 
 impl From<StakeIdentity> for QuicClientCertificate {
@@ -169,16 +510,331 @@ impl ConnectionWorkersScheduler {
             leader_updater,
             transaction_receiver,
             update_identity_receiver,
+            leaders_fanout_receiver: None,
+            block_height_receiver: None,
+            verbosity_receiver: None,
+            prefer_successful_leaders: false,
+            eviction_policy: None,
+            health_check_interval: DEFAULT_HEALTH_CHECK_INTERVAL,
+            cache_warming: None,
+            leader_probing: None,
+            pinned_peers: Vec::new(),
+            workers_snapshot_sender: None,
+            max_concurrent_shutdowns: DEFAULT_MAX_CONCURRENT_SHUTDOWNS,
+            shutdown_deadline: DEFAULT_SHUTDOWN_DEADLINE,
+            soft_num_connections: None,
+            max_idle_worker_tasks: None,
+            max_worker_lifetime: None,
+            workers_cache_events_sender: None,
+            initial_peers: Vec::new(),
+            max_concurrent_handshakes: None,
+            crypto_provider: Arc::new(crypto_provider()),
+            server_cert_verifier: None,
+            runtime: None,
+            leader_update_sender: watch::Sender::new(None),
+            negotiated_parameters_sender: watch::Sender::new(None),
             cancel,
             stats,
         }
     }
 
+    /// Creates a scheduler sending to the leaders tracked with the RPC and
+    /// websocket of `rpc`, see [`create_leader_updater`], along with the
+    /// channels to drive it. [`ConnectionWorkersSchedulerConfig::default`]
+    /// provides a configuration suitable for most clients.
+    ///
+    /// It saves assembling the leader updater, the channels and the
+    /// cancellation token by hand, which remains possible with
+    /// [`ConnectionWorkersScheduler::new`] for the clients needing more
+    /// control.
+    pub async fn from_rpc(
+        rpc: impl Into<RpcSource>,
+        config: ConnectionWorkersSchedulerConfig,
+    ) -> Result<RpcScheduler, LeaderUpdaterError> {
+        let rpc_client = match rpc.into() {
+            RpcSource::Url(url) => Arc::new(RpcClient::new(url)),
+            RpcSource::Client(rpc_client) => rpc_client,
+        };
+        let websocket_url = websocket_url_from_rpc_url(&rpc_client.url())?;
+        let leader_updater = create_leader_updater(rpc_client, websocket_url, None).await?;
+        let (transaction_sender, transaction_receiver) = mpsc::channel(RPC_SCHEDULER_CHANNEL_SIZE);
+        let (update_identity_sender, update_identity_receiver) = watch::channel(None);
+        let cancel = CancellationToken::new();
+        let scheduler = Self::new(
+            leader_updater,
+            transaction_receiver,
+            update_identity_receiver,
+            cancel.clone(),
+        );
+        Ok(RpcScheduler {
+            scheduler,
+            config,
+            transaction_sender,
+            update_identity_sender,
+            cancel,
+        })
+    }
+
+    /// Allows to change the number of leaders to connect and send to while the
+    /// scheduler is running. Every value sent over `leaders_fanout_receiver`
+    /// replaces [`ConnectionWorkersSchedulerConfig::leaders_fanout`] starting
+    /// from the next transaction batch.
+    pub fn with_leaders_fanout_receiver(
+        mut self,
+        leaders_fanout_receiver: watch::Receiver<Fanout>,
+    ) -> Self {
+        self.leaders_fanout_receiver = Some(leaders_fanout_receiver);
+        self
+    }
+
+    /// Makes the scheduler drop the batches whose blockhash has expired, see
+    /// [`TransactionBatch::with_last_valid_block_height`]. The current block
+    /// height, e.g. tracked from the slot updates, is read from
+    /// `block_height_receiver` whenever a batch is received, and the dropped
+    /// transactions are counted as [`DropReason::BlockhashExpired`].
+    pub fn with_block_height_receiver(
+        mut self,
+        block_height_receiver: watch::Receiver<u64>,
+    ) -> Self {
+        self.block_height_receiver = Some(block_height_receiver);
+        self
+    }
+
+    /// Allows to change the verbosity of this crate while the scheduler is
+    /// running, e.g. to turn up the detail during an incident. Every value
+    /// sent over `verbosity_receiver` is applied with [`set_verbosity`], the
+    /// current one as soon as the scheduler starts.
+    ///
+    /// The verbosity is global to the process, not to this scheduler: the
+    /// values received apply to every scheduler and every other user of this
+    /// crate, and they outlive the scheduler. With several schedulers, drive
+    /// the verbosity from one of them only, or call [`set_verbosity`]
+    /// directly.
+    pub fn with_verbosity_receiver(
+        mut self,
+        verbosity_receiver: watch::Receiver<Verbosity>,
+    ) -> Self {
+        self.verbosity_receiver = Some(verbosity_receiver);
+        self
+    }
+
+    /// Makes the scheduler prefer the upcoming leaders which have historically
+    /// accepted more of the transactions of this client, see
+    /// [`LeaderSendStats::success_rate`].
+    ///
+    /// The current leader is always targeted. The other leaders returned by
+    /// the [`LeaderUpdater`] are ordered by their success rate, so when the
+    /// send fanout is smaller than the connect one, traffic and connection
+    /// warming go to the most successful leaders first. Leaders which haven't
+    /// been sent to yet are ordered by the share of their successful probes
+    /// if the leader probing is enabled, see
+    /// [`ConnectionWorkersScheduler::with_leader_probing`].
+    ///
+    /// [`LeaderSendStats::success_rate`]: crate::send_transaction_stats::LeaderSendStats::success_rate
+    pub fn with_success_rate_preference(mut self) -> Self {
+        self.prefer_successful_leaders = true;
+        self
+    }
+
+    /// Sets the policy used to select which worker to evict when the workers
+    /// cache is full, see [`EvictionPolicy`]. By default, the least recently
+    /// used worker is evicted.
+    pub fn with_eviction_policy(mut self, eviction_policy: Box<dyn EvictionPolicy>) -> Self {
+        self.eviction_policy = Some(eviction_policy);
+        self
+    }
+
+    /// Sets how often the workers are checked for being stopped, which
+    /// happens when their connection dies and they fail to reconnect. Such
+    /// workers are removed from the cache and their leader addresses are
+    /// invalidated, see [`LeaderUpdater::invalidate_leader_address`]. By
+    /// default, [`DEFAULT_HEALTH_CHECK_INTERVAL`] is used.
+    pub fn with_health_check_interval(mut self, health_check_interval: Duration) -> Self {
+        self.health_check_interval = health_check_interval;
+        self
+    }
+
+    /// Enables the cache warming: the scheduler periodically creates workers,
+    /// and so QUIC connections, for the leaders of the next
+    /// [`CacheWarmingConfig::lookahead_slots`] slots, so that the first
+    /// transactions sent to a leader do not wait for the handshake.
+    pub fn with_cache_warming(mut self, cache_warming: CacheWarmingConfig) -> Self {
+        self.cache_warming = Some(cache_warming);
+        self
+    }
+
+    /// Enables the leader probing: the scheduler periodically performs a
+    /// handshake with the leaders of the next
+    /// [`LeaderProbingConfig::lookahead_slots`] slots which are not connected
+    /// yet and closes the connection right away. The reachability and the RTT
+    /// of the leaders are recorded in [`SendTransactionStats::leaders`] and
+    /// feed [`ConnectionWorkersScheduler::with_success_rate_preference`].
+    pub fn with_leader_probing(mut self, leader_probing: LeaderProbingConfig) -> Self {
+        self.leader_probing = Some(leader_probing);
+        self
+    }
+
+    /// Pins the workers for `pinned_peers`, e.g. own validator or a relayer,
+    /// so that they are never evicted from the workers cache, see
+    /// [`WorkersCache::pin`].
+    pub fn with_pinned_peers(mut self, pinned_peers: Vec<SocketAddr>) -> Self {
+        self.pinned_peers = pinned_peers;
+        self
+    }
+
+    /// Publishes the state of the cached workers, see [`WorkerSnapshot`], over
+    /// `workers_snapshot_sender`. The snapshot is updated on every health
+    /// check, see [`ConnectionWorkersScheduler::with_health_check_interval`].
+    pub fn with_workers_snapshot_sender(
+        mut self,
+        workers_snapshot_sender: watch::Sender<Vec<WorkerSnapshot>>,
+    ) -> Self {
+        self.workers_snapshot_sender = Some(workers_snapshot_sender);
+        self
+    }
+
+    /// Sets how many of the workers removed from the cache are shut down
+    /// concurrently and the time each of them is given before its task is
+    /// aborted, see [`WorkersCache::spawn_shutdown`]. By default,
+    /// [`DEFAULT_MAX_CONCURRENT_SHUTDOWNS`] and [`DEFAULT_SHUTDOWN_DEADLINE`]
+    /// are used.
+    pub fn with_worker_shutdown_limits(
+        mut self,
+        max_concurrent_shutdowns: usize,
+        shutdown_deadline: Duration,
+    ) -> Self {
+        self.max_concurrent_shutdowns = max_concurrent_shutdowns;
+        self.shutdown_deadline = shutdown_deadline;
+        self
+    }
+
+    /// Allows the workers cache to temporarily hold up to
+    /// `soft_num_connections` workers, above
+    /// [`ConnectionWorkersSchedulerConfig::num_connections`]. The excess
+    /// workers are evicted after the transaction batch is sent, so that
+    /// connecting to a new leader never waits for an eviction.
+    pub fn with_soft_num_connections(mut self, soft_num_connections: usize) -> Self {
+        self.soft_num_connections = Some(soft_num_connections);
+        self
+    }
+
+    /// Runs the workers on recycled tasks, keeping up to `max_idle_tasks`
+    /// tasks of the finished workers for the next ones, see
+    /// [`WorkerTaskPool`]. By default, each worker runs on a new task.
+    pub fn with_worker_task_pool(mut self, max_idle_tasks: usize) -> Self {
+        self.max_idle_worker_tasks = Some(max_idle_tasks);
+        self
+    }
+
+    /// Limits the lifetime of the workers. When an upcoming leader's worker is
+    /// older than `max_worker_lifetime`, it is replaced with a new worker, so
+    /// that a degraded connection which never fails is not kept forever. The
+    /// replacements are accounted in [`SendTransactionStats::workers_recycled`].
+    pub fn with_max_worker_lifetime(mut self, max_worker_lifetime: Duration) -> Self {
+        self.max_worker_lifetime = Some(max_worker_lifetime);
+        self
+    }
+
+    /// Notifies about the workers added to and removed from the workers cache,
+    /// and about their connection and send failures, over
+    /// `workers_cache_events_sender`, see [`WorkersCacheEvent`]. Unlike the
+    /// logs, the failures carry their full context as an [`ErrorRecord`].
+    ///
+    /// [`ErrorRecord`]: crate::send_transaction_stats::ErrorRecord
+    pub fn with_workers_cache_events_sender(
+        mut self,
+        workers_cache_events_sender: broadcast::Sender<WorkersCacheEvent>,
+    ) -> Self {
+        self.workers_cache_events_sender = Some(workers_cache_events_sender);
+        self
+    }
+
+    /// Creates workers for `initial_peers` as soon as the scheduler starts,
+    /// before any transaction batch is received. Typically, these are the
+    /// sockets of [`LeaderUpdater::known_peers`] saved before restart, so that
+    /// connections are warm when the first transactions are sent. Only up to
+    /// [`ConnectionWorkersSchedulerConfig::num_connections`] of them are kept.
+    pub fn with_initial_peers(mut self, initial_peers: Vec<SocketAddr>) -> Self {
+        self.initial_peers = initial_peers;
+        self
+    }
+
+    /// Creates the workers for the whole [`Fanout::connect`] set as soon as
+    /// the upcoming leaders change, rather than when the next transaction
+    /// batch is received. The workers connect concurrently, with at most
+    /// `max_concurrent_handshakes` handshakes in flight, so that a leader
+    /// rotation does not stall the endpoint with a burst of handshakes.
+    ///
+    /// The limit applies to all the workers spawned by the scheduler.
+    pub fn with_fanout_precreation(mut self, max_concurrent_handshakes: usize) -> Self {
+        self.max_concurrent_handshakes = Some(max_concurrent_handshakes.max(1));
+        self
+    }
+
+    /// Sets the rustls [`CryptoProvider`] used for the TLS handshakes with the
+    /// leaders, instead of the `ring` based one of `solana-tls-utils`, e.g. to
+    /// use the FIPS provider of `aws-lc-rs`. The `rustls` features selecting
+    /// the provider are enabled by the application.
+    ///
+    /// The validators only accept the X25519 key exchange, so `crypto_provider`
+    /// has to support it.
+    pub fn with_crypto_provider(mut self, crypto_provider: Arc<CryptoProvider>) -> Self {
+        self.crypto_provider = crypto_provider;
+        self
+    }
+
+    /// Verifies the certificates of the leaders with `server_cert_verifier`,
+    /// e.g. against pinned certificates or a private CA in a permissioned
+    /// cluster. By default, any certificate is accepted since the validators
+    /// use self-signed ones, and only the TLS signatures are checked.
+    ///
+    /// The server name given to the verifier is built from the address of the
+    /// leader by `socket_addr_to_quic_server_name`, e.g. `127.0.0.1.8009.sol`.
+    pub fn with_server_cert_verifier(
+        mut self,
+        server_cert_verifier: Arc<dyn ServerCertVerifier>,
+    ) -> Self {
+        self.server_cert_verifier = Some(server_cert_verifier);
+        self
+    }
+
+    /// Runs the scheduler on `runtime` instead of the runtime polling
+    /// [`ConnectionWorkersScheduler::run`], e.g. on a runtime dedicated to
+    /// the networking of the application. The workers, the QUIC endpoint and
+    /// the other tasks of the scheduler are spawned on `runtime` as well.
+    ///
+    /// The scheduler is aborted if the future returned by `run` is dropped
+    /// before completion, like when it runs on the current runtime.
+    ///
+    /// Spawning the scheduler requires a `'static` broadcaster, so the
+    /// runtime is used by [`ConnectionWorkersScheduler::run`] and
+    /// [`ConnectionWorkersScheduler::run_with_broadcaster_on_runtime`], but not
+    /// by [`ConnectionWorkersScheduler::run_with_broadcaster`].
+    pub fn with_runtime(mut self, runtime: Handle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
     /// Retrieves a reference to the statistics of the scheduler
     pub fn get_stats(&self) -> Arc<SendTransactionStats> {
         self.stats.clone()
     }
 
+    /// Returns a [`HealthProbe`] reporting the health of the scheduler, which
+    /// is updated on every health check, see
+    /// [`ConnectionWorkersScheduler::with_health_check_interval`].
+    pub fn health_probe(&self) -> HealthProbe {
+        HealthProbe::new(self.leader_update_sender.subscribe(), self.stats.clone())
+    }
+
+    /// Returns a receiver of the [`NegotiatedParameters`] of the connections,
+    /// which are published on the first health check after the first
+    /// connections are established, see
+    /// [`ConnectionWorkersScheduler::with_health_check_interval`].
+    pub fn negotiated_parameters(&self) -> watch::Receiver<Option<NegotiatedParameters>> {
+        self.negotiated_parameters_sender.subscribe()
+    }
+
     /// Starts the scheduler.
     ///
     /// This method is a shorthand for
@@ -192,7 +848,7 @@ impl ConnectionWorkersScheduler {
         self,
         config: ConnectionWorkersSchedulerConfig,
     ) -> Result<Arc<SendTransactionStats>, ConnectionWorkersSchedulerError> {
-        self.run_with_broadcaster::<NonblockingBroadcaster>(config)
+        self.run_with_broadcaster_on_runtime::<NonblockingBroadcaster>(config)
             .await
     }
 
@@ -205,7 +861,48 @@ impl ConnectionWorkersScheduler {
     ///
     /// Importantly, if some transactions were not delivered due to network
     /// problems, they will not be retried when the problem is resolved.
+    ///
+    /// The scheduler runs on the current runtime, see
+    /// [`ConnectionWorkersScheduler::run_with_broadcaster_on_runtime`] to run
+    /// it on the runtime set with [`ConnectionWorkersScheduler::with_runtime`].
     pub async fn run_with_broadcaster<Broadcaster: WorkersBroadcaster>(
+        mut self,
+        config: ConnectionWorkersSchedulerConfig,
+    ) -> Result<Arc<SendTransactionStats>, ConnectionWorkersSchedulerError> {
+        if self.runtime.take().is_some() {
+            log_event!(
+                warn,
+                "The runtime of the scheduler is ignored, the scheduler runs on the current one",
+            );
+        }
+        self.run_main_loop::<Broadcaster>(config).await
+    }
+
+    /// Same as [`ConnectionWorkersScheduler::run_with_broadcaster`], but runs
+    /// the scheduler on the runtime set with
+    /// [`ConnectionWorkersScheduler::with_runtime`], if any.
+    pub async fn run_with_broadcaster_on_runtime<Broadcaster: WorkersBroadcaster + 'static>(
+        mut self,
+        config: ConnectionWorkersSchedulerConfig,
+    ) -> Result<Arc<SendTransactionStats>, ConnectionWorkersSchedulerError> {
+        let Some(runtime) = self.runtime.take() else {
+            return self.run_main_loop::<Broadcaster>(config).await;
+        };
+        // The tasks spawned by the main loop, including the ones of quinn,
+        // end up on the runtime it runs on.
+        let main_loop = runtime.spawn(self.run_main_loop::<Broadcaster>(config));
+        match AbortOnDropHandle::new(main_loop).await {
+            Ok(result) => result,
+            Err(err) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+            Err(_) => Err(ConnectionWorkersSchedulerError::RuntimeShutdown),
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "connection_workers_scheduler", skip_all)
+    )]
+    async fn run_main_loop<Broadcaster: WorkersBroadcaster>(
         self,
         ConnectionWorkersSchedulerConfig {
             bind,
@@ -214,26 +911,132 @@ impl ConnectionWorkersScheduler {
             skip_check_transaction_age,
             worker_channel_size,
             max_reconnect_attempts,
-            leaders_fanout,
+            mut leaders_fanout,
         }: ConnectionWorkersSchedulerConfig,
     ) -> Result<Arc<SendTransactionStats>, ConnectionWorkersSchedulerError> {
         let ConnectionWorkersScheduler {
             mut leader_updater,
             mut transaction_receiver,
             mut update_identity_receiver,
+            mut leaders_fanout_receiver,
+            block_height_receiver,
+            mut verbosity_receiver,
+            prefer_successful_leaders,
+            eviction_policy,
+            health_check_interval,
+            cache_warming,
+            leader_probing,
+            pinned_peers,
+            workers_snapshot_sender,
+            max_concurrent_shutdowns,
+            shutdown_deadline,
+            soft_num_connections,
+            max_idle_worker_tasks,
+            max_worker_lifetime,
+            workers_cache_events_sender,
+            initial_peers,
+            max_concurrent_handshakes,
+            crypto_provider,
+            server_cert_verifier,
+            runtime: _,
+            leader_update_sender,
+            negotiated_parameters_sender,
             cancel,
             stats,
         } = self;
-        let mut endpoint = setup_endpoint(bind, stake_identity)?;
+        if let Some(verbosity_receiver) = verbosity_receiver.as_mut() {
+            set_verbosity(*verbosity_receiver.borrow_and_update());
+        }
+        stats
+            .identities
+            .set_current(sending_identity(stake_identity.as_ref()));
+        let mut endpoint = setup_endpoint(
+            bind,
+            stake_identity,
+            crypto_provider.clone(),
+            server_cert_verifier.clone(),
+        )?;
 
-        debug!("Client endpoint bind address: {:?}", endpoint.local_addr());
-        let mut workers = WorkersCache::new(num_connections, cancel.clone());
+        log_event!(
+            debug,
+            "Client endpoint bound",
+            address = DebugValue(endpoint.local_addr()),
+        );
+        let mut workers = match eviction_policy {
+            Some(eviction_policy) => {
+                WorkersCache::with_eviction_policy(num_connections, cancel.clone(), eviction_policy)
+            }
+            None => WorkersCache::new(num_connections, cancel.clone()),
+        }
+        .with_stats(stats.clone())
+        .with_shutdown_limits(max_concurrent_shutdowns, shutdown_deadline)
+        .with_soft_capacity(soft_num_connections.unwrap_or(num_connections));
+        if let Some(max_worker_lifetime) = max_worker_lifetime {
+            workers = workers.with_max_worker_lifetime(max_worker_lifetime);
+        }
+        if let Some(events_sender) = workers_cache_events_sender {
+            workers = workers.with_events_sender(events_sender);
+        }
+        for peer in pinned_peers {
+            workers.pin(peer);
+        }
+        let worker_settings = WorkerSettings {
+            worker_channel_size,
+            skip_check_transaction_age,
+            max_reconnect_attempts,
+            task_pool: max_idle_worker_tasks.map(WorkerTaskPool::new),
+            connect_permits: max_concurrent_handshakes.map(|max_concurrent_handshakes| {
+                Arc::new(Semaphore::new(max_concurrent_handshakes))
+            }),
+        };
+
+        update_error_records_leaders(leader_updater.as_ref(), &stats);
+        spawn_missing_workers(
+            &endpoint,
+            &mut workers,
+            leader_updater.as_mut(),
+            initial_peers,
+            &worker_settings,
+            &stats,
+        );
 
         let mut last_error = None;
         // flag to ensure that the section handling
         // `update_identity_receiver.changed()` is entered only once when the
         // channel is dropped.
         let mut identity_updater_is_active = true;
+        let mut health_check = interval_at(
+            Instant::now() + health_check_interval,
+            health_check_interval,
+        );
+        health_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let num_warm_leaders = cache_warming
+            .as_ref()
+            .map_or(0, CacheWarmingConfig::num_leaders);
+        let mut cache_warming_tick = cache_warming.map(|cache_warming| {
+            let mut tick = interval(cache_warming.interval);
+            tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            tick
+        });
+        let num_probed_leaders = leader_probing
+            .as_ref()
+            .map_or(0, LeaderProbingConfig::num_leaders);
+        let mut leader_prober = leader_probing.as_ref().map(|leader_probing| {
+            LeaderProber::new(leader_probing.handshake_timeout, cancel.clone())
+        });
+        let mut leader_probing_tick = leader_probing.map(|leader_probing| {
+            let mut tick = interval(leader_probing.interval);
+            tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            tick
+        });
+        let mut fanout_precreation_tick = max_concurrent_handshakes.map(|_| {
+            let mut tick = interval(FANOUT_PRECREATION_INTERVAL);
+            tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            tick
+        });
+        let mut fanout_leaders = Vec::new();
+        let mut estimated_slot = leader_updater.estimated_current_slot();
+        leader_update_sender.send_replace(Some(Instant::now()));
 
         loop {
             let transaction_batch: TransactionBatch = tokio::select! {
@@ -252,7 +1055,16 @@ impl ConnectionWorkersScheduler {
                         continue;
                     };
 
-                    let client_config = build_client_config(update_identity_receiver.borrow_and_update().as_ref());
+                    let stake_identity = update_identity_receiver.borrow_and_update();
+                    // Set before flushing, so that the workers spawned from
+                    // now on account to the new identity.
+                    stats.identities.set_current(sending_identity(stake_identity.as_ref()));
+                    let client_config = build_client_config(
+                        stake_identity.as_ref(),
+                        crypto_provider.clone(),
+                        server_cert_verifier.clone(),
+                    );
+                    drop(stake_identity);
                     endpoint.set_default_client_config(client_config);
                     // Flush workers since they are handling connections created
                     // with outdated certificate.
@@ -260,42 +1072,164 @@ impl ConnectionWorkersScheduler {
                     debug!("Updated certificate.");
                     continue;
                 },
+                res = async { verbosity_receiver.as_mut().unwrap().changed().await },
+                    if verbosity_receiver.is_some() =>
+                {
+                    match res {
+                        Ok(()) => {
+                            let verbosity =
+                                *verbosity_receiver.as_mut().unwrap().borrow_and_update();
+                            set_verbosity(verbosity);
+                        }
+                        Err(_) => verbosity_receiver = None,
+                    }
+                    continue;
+                },
+                _ = health_check.tick() => {
+                    for peer in workers.remove_stopped() {
+                        log_event!(debug, "Worker has stopped, invalidate leader address", peer = peer);
+                        leader_updater.invalidate_leader_address(&peer);
+                    }
+                    if let Some(sender) = &workers_snapshot_sender {
+                        sender.send_replace(workers.snapshot());
+                    }
+                    if negotiated_parameters_sender.borrow().is_none() {
+                        if let Some(parameters) = NegotiatedParameters::from_workers(
+                            stats.identities.current_identity(),
+                            workers.snapshot(),
+                        ) {
+                            negotiated_parameters_sender.send_replace(Some(parameters));
+                        }
+                    }
+                    update_error_records_leaders(leader_updater.as_ref(), &stats);
+                    stats
+                        .queue_depth
+                        .update(transaction_receiver.len(), workers.queued_batches());
+                    let next_estimated_slot = leader_updater.estimated_current_slot();
+                    if next_estimated_slot.is_none() || next_estimated_slot != estimated_slot {
+                        estimated_slot = next_estimated_slot;
+                        leader_update_sender.send_replace(Some(Instant::now()));
+                    }
+                    continue;
+                },
+                _ = async { cache_warming_tick.as_mut().unwrap().tick().await },
+                    if cache_warming_tick.is_some() =>
+                {
+                    let warm_leaders = leader_updater.next_leaders(num_warm_leaders);
+                    spawn_missing_workers(
+                        &endpoint,
+                        &mut workers,
+                        leader_updater.as_mut(),
+                        warm_leaders,
+                        &worker_settings,
+                        &stats,
+                    );
+                    continue;
+                },
+                _ = async { leader_probing_tick.as_mut().unwrap().tick().await },
+                    if leader_probing_tick.is_some() =>
+                {
+                    let probed_leaders = leader_updater.next_leaders(num_probed_leaders);
+                    if let Some(leader_prober) = leader_prober.as_mut() {
+                        leader_prober.probe(&endpoint, probed_leaders, &workers, &stats);
+                    }
+                    continue;
+                },
+                _ = async { fanout_precreation_tick.as_mut().unwrap().tick().await },
+                    if fanout_precreation_tick.is_some() =>
+                {
+                    update_leaders_fanout(leaders_fanout_receiver.as_mut(), &mut leaders_fanout);
+                    let next_fanout_leaders = leader_updater.next_leaders(leaders_fanout.connect);
+                    // Skip the leaders which have been handled already, so
+                    // that the cache hits are not counted on every tick.
+                    if next_fanout_leaders != fanout_leaders {
+                        spawn_missing_workers(
+                            &endpoint,
+                            &mut workers,
+                            leader_updater.as_mut(),
+                            next_fanout_leaders.clone(),
+                            &worker_settings,
+                            &stats,
+                        );
+                        fanout_leaders = next_fanout_leaders;
+                    }
+                    continue;
+                },
                 () = cancel.cancelled() => {
                     debug!("Cancelled: Shutting down");
                     break;
                 }
             };
 
-            let connect_leaders = leader_updater.next_leaders(leaders_fanout.connect);
-            let send_leaders = extract_send_leaders(&connect_leaders, leaders_fanout.send);
+            stats
+                .queue_depth
+                .update(transaction_receiver.len(), workers.queued_batches());
+            if is_blockhash_expired(&transaction_batch, block_height_receiver.as_ref()) {
+                log_event!(
+                    debug,
+                    "Drop transaction batch with an expired blockhash",
+                    batch_size = transaction_batch.len(),
+                );
+                stats.record_dropped(None, DropReason::BlockhashExpired, transaction_batch.len());
+                continue;
+            }
+            update_leaders_fanout(leaders_fanout_receiver.as_mut(), &mut leaders_fanout);
+            let batch_fanout = match transaction_batch.leaders_fanout() {
+                Some(requested_fanout) => leaders_fanout.max(requested_fanout),
+                None => leaders_fanout.clone(),
+            };
+
+            let mut connect_leaders = leader_updater.next_leaders(batch_fanout.connect);
+            if prefer_successful_leaders {
+                sort_by_success_rate(&mut connect_leaders, &stats.leaders);
+            }
+            let num_leaders_without_tpu_socket =
+                leader_updater.take_num_leaders_without_tpu_socket();
+            if num_leaders_without_tpu_socket > 0 {
+                stats
+                    .leader_without_tpu_socket
+                    .fetch_add(num_leaders_without_tpu_socket, Ordering::Relaxed);
+            }
+            let send_leaders = extract_send_leaders(&connect_leaders, batch_fanout.send);
+            if send_leaders.is_empty() {
+                stats.record_dropped(None, DropReason::NoLeader, transaction_batch.len());
+            }
 
             // add future leaders to the cache to hide the latency of opening
             // the connection.
-            for peer in connect_leaders {
-                if !workers.contains(&peer) {
-                    let worker = spawn_worker(
-                        &endpoint,
-                        &peer,
-                        worker_channel_size,
-                        skip_check_transaction_age,
-                        max_reconnect_attempts,
-                        DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
-                        stats.clone(),
-                    );
-                    if let Some(pop_worker) = workers.push(peer, worker) {
-                        shutdown_worker(pop_worker)
-                    }
-                }
-            }
+            spawn_missing_workers(
+                &endpoint,
+                &mut workers,
+                leader_updater.as_mut(),
+                connect_leaders,
+                &worker_settings,
+                &stats,
+            );
 
-            if let Err(error) =
-                Broadcaster::send_to_workers(&mut workers, &send_leaders, transaction_batch).await
-            {
+            #[cfg(feature = "tracing")]
+            let span = tracing::info_span!(
+                "schedule_transaction_batch",
+                batch_id = transaction_batch.id(),
+                num_transactions = transaction_batch.len(),
+                num_leaders = send_leaders.len(),
+            );
+            let send = Broadcaster::send_to_workers(&mut workers, &send_leaders, transaction_batch);
+            #[cfg(feature = "tracing")]
+            let send = tracing::Instrument::instrument(send, span);
+            if let Err(error) = send.await {
                 last_error = Some(error);
                 break;
             }
+            workers.evict_excess();
         }
 
+        if cancel.is_cancelled() {
+            let mut num_transactions = 0usize;
+            while let Ok(batch) = transaction_receiver.try_recv() {
+                num_transactions = num_transactions.saturating_add(batch.len());
+            }
+            stats.record_dropped(None, DropReason::Cancelled, num_transactions);
+        }
         workers.shutdown().await;
 
         endpoint.close(0u32.into(), b"Closing connection");
@@ -307,23 +1241,144 @@ impl ConnectionWorkersScheduler {
     }
 }
 
+/// Spawns workers for the `leaders` which don't have one in the `workers`
+/// cache yet. Existing workers are reused, so that a leader appearing several
+/// times within the lookahead window keeps its connection, unless they have
+/// outlived the maximal worker lifetime and are replaced. Stopped workers are
+/// removed instead and the addresses of their leaders are invalidated.
+///
+/// The outcomes are accounted as workers cache hits, misses and expirations in
+/// the stats of the leader, see [`SendTransactionStats::leaders`].
+fn spawn_missing_workers(
+    endpoint: &Endpoint,
+    workers: &mut WorkersCache,
+    leader_updater: &mut dyn LeaderUpdater,
+    leaders: Vec<SocketAddr>,
+    settings: &WorkerSettings,
+    stats: &Arc<SendTransactionStats>,
+) {
+    for peer in remove_duplicates(&leaders) {
+        let leader_stats = stats.leaders.get_or_insert(peer);
+        if workers.reuse(&peer) {
+            leader_stats.cache_hits.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        if workers.is_stopped(&peer) {
+            leader_stats.cache_expired.fetch_add(1, Ordering::Relaxed);
+            // The worker has given up connecting to this peer, so the
+            // leader might have changed its TPU address.
+            log_event!(
+                debug,
+                "Worker has stopped, invalidate leader address",
+                peer = peer
+            );
+            leader_updater.invalidate_leader_address(&peer);
+            if let Some(pop_worker) = workers.pop(peer) {
+                workers.spawn_shutdown(pop_worker)
+            }
+            continue;
+        }
+        if workers.is_outlived(&peer) {
+            leader_stats.cache_expired.fetch_add(1, Ordering::Relaxed);
+            stats.workers_recycled.fetch_add(1, Ordering::Relaxed);
+        } else {
+            leader_stats.cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+        let worker = spawn_worker(
+            endpoint,
+            &peer,
+            settings.worker_channel_size,
+            settings.skip_check_transaction_age,
+            settings.max_reconnect_attempts,
+            DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
+            stats.clone(),
+            leader_stats.clone(),
+            settings.task_pool.as_ref(),
+            settings.connect_permits.clone(),
+            workers.events_sender(),
+        );
+        if let Some(pop_worker) = workers.push(peer, worker) {
+            workers.spawn_shutdown(pop_worker)
+        }
+    }
+}
+
+/// Returns whether the block height read from `block_height_receiver` is past
+/// the last one at which the transactions of `batch` can land.
+fn is_blockhash_expired(
+    batch: &TransactionBatch,
+    block_height_receiver: Option<&watch::Receiver<u64>>,
+) -> bool {
+    match (batch.last_valid_block_height(), block_height_receiver) {
+        (Some(last_valid_block_height), Some(receiver)) => {
+            *receiver.borrow() > last_valid_block_height
+        }
+        _ => false,
+    }
+}
+
+/// Updates `leaders_fanout` if a new one has been received over
+/// `leaders_fanout_receiver`.
+fn update_leaders_fanout(
+    leaders_fanout_receiver: Option<&mut watch::Receiver<Fanout>>,
+    leaders_fanout: &mut Fanout,
+) {
+    if let Some(receiver) = leaders_fanout_receiver {
+        if receiver.has_changed().unwrap_or(false) {
+            *leaders_fanout = receiver.borrow_and_update().clone();
+            debug!("Updated leaders fanout: {leaders_fanout:?}.");
+        }
+    }
+}
+
 /// Sets up the QUIC endpoint for the scheduler to handle connections.
 #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
 fn setup_endpoint(
     bind: BindTarget,
     stake_identity: Option<StakeIdentity>,
+    crypto_provider: Arc<CryptoProvider>,
+    server_cert_verifier: Option<Arc<dyn ServerCertVerifier>>,
 ) -> Result<Endpoint, ConnectionWorkersSchedulerError> {
-    let client_config = build_client_config(stake_identity.as_ref());
+    let client_config = build_client_config(
+        stake_identity.as_ref(),
+        crypto_provider,
+        server_cert_verifier,
+    );
     let endpoint = create_client_endpoint(bind, client_config)?;
     Ok(endpoint)
 }
 
-fn build_client_config(stake_identity: Option<&StakeIdentity>) -> ClientConfig {
+/// Provides the identities of the leaders to [`ErrorRecords`], if
+/// `leader_updater` knows them.
+///
+/// [`ErrorRecords`]: crate::send_transaction_stats::ErrorRecords
+fn update_error_records_leaders(leader_updater: &dyn LeaderUpdater, stats: &SendTransactionStats) {
+    if let Some(known_peers) = leader_updater.known_peers() {
+        let leaders = known_peers
+            .tpu_sockets
+            .into_iter()
+            .map(|(leader, address)| (address, leader))
+            .collect();
+        stats.error_records.set_leaders(leaders);
+    }
+}
+
+fn sending_identity(stake_identity: Option<&StakeIdentity>) -> SendingIdentity {
+    stake_identity.map_or(SendingIdentity::Unstaked, |identity| {
+        SendingIdentity::Staked(identity.pubkey())
+    })
+}
+
+fn build_client_config(
+    stake_identity: Option<&StakeIdentity>,
+    crypto_provider: Arc<CryptoProvider>,
+    server_cert_verifier: Option<Arc<dyn ServerCertVerifier>>,
+) -> ClientConfig {
     let client_certificate = match stake_identity {
         Some(identity) => identity.as_certificate(),
         None => &QuicClientCertificate::new(None),
     };
-    create_client_config(client_certificate)
+    create_client_config_with_provider(client_certificate, crypto_provider, server_cert_verifier)
 }
 
 /// [`NonblockingBroadcaster`] attempts to immediately send transactions to all
@@ -340,7 +1395,13 @@ impl WorkersBroadcaster for NonblockingBroadcaster {
     ) -> Result<(), ConnectionWorkersSchedulerError> {
         for new_leader in leaders {
             if !workers.contains(new_leader) {
-                warn!("No existing worker for {new_leader:?}, skip sending to this leader.");
+                log_event!(
+                    warn,
+                    "No existing worker, skip sending to this leader",
+                    leader = new_leader,
+                    batch_size = transaction_batch.len(),
+                );
+                workers.record_batch_dropped(*new_leader, DropReason::NoLeader, &transaction_batch);
                 continue;
             }
 
@@ -349,16 +1410,26 @@ impl WorkersBroadcaster for NonblockingBroadcaster {
             match send_res {
                 Ok(()) => (),
                 Err(WorkersCacheError::ShutdownError) => {
-                    debug!("Connection to {new_leader} was closed, worker cache shutdown");
+                    log_event!(
+                        debug,
+                        "Connection was closed, worker cache shutdown",
+                        leader = new_leader,
+                    );
                 }
                 Err(WorkersCacheError::ReceiverDropped) => {
                     // Remove the worker from the cache, if the peer has disconnected.
                     if let Some(pop_worker) = workers.pop(*new_leader) {
-                        shutdown_worker(pop_worker)
+                        workers.spawn_shutdown(pop_worker)
                     }
                 }
                 Err(err) => {
-                    warn!("Connection to {new_leader} was closed, worker error: {err}");
+                    log_event!(
+                        warn,
+                        "Connection was closed",
+                        leader = new_leader,
+                        batch_size = transaction_batch.len(),
+                        error = err,
+                    );
                     // If we have failed to send batch, it will be dropped.
                 }
             }
@@ -367,6 +1438,32 @@ impl WorkersBroadcaster for NonblockingBroadcaster {
     }
 }
 
+/// Reorders the upcoming `leaders`, except for the first one which is the
+/// current leader, so that the leaders with higher success rate come first.
+/// The share of successful handshakes, then of successful probes, stands in
+/// for the success rate of the leaders which haven't been sent to. Leaders
+/// without history are treated as fully successful, so that they are tried
+/// out. Leaders with equal success rate keep the leader schedule order.
+fn sort_by_success_rate(leaders: &mut [SocketAddr], leaders_send_stats: &LeadersSendStats) {
+    let Some((_current_leader, upcoming_leaders)) = leaders.split_first_mut() else {
+        return;
+    };
+    upcoming_leaders.sort_by_cached_key(|leader| {
+        let success_rate = leaders_send_stats
+            .get(leader)
+            .and_then(|stats| {
+                stats
+                    .success_rate()
+                    .or_else(|| stats.handshake_success_rate())
+                    .or_else(|| stats.probe_success_rate())
+            })
+            .unwrap_or(1.0);
+        // Per-mille precision is enough to compare leaders and allows to use
+        // integer keys.
+        Reverse((success_rate * 1000.0) as u64)
+    });
+}
+
 /// Extracts a list of unique leader addresses to which transactions will be sent.
 ///
 /// This function selects up to `send_fanout` addresses from the `leaders` list, ensuring that
@@ -388,3 +1485,145 @@ fn remove_duplicates(input: &[SocketAddr]) -> Vec<SocketAddr> {
     }
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::net::{IpAddr, Ipv4Addr},
+    };
+
+    #[test]
+    fn test_deserialize_config() {
+        let toml = r#"
+            bind = "127.0.0.1:8000"
+            num_connections = 8
+            skip_check_transaction_age = true
+
+            [leaders_fanout]
+            send = 1
+            connect = 2
+        "#;
+        let config: ConnectionWorkersSchedulerConfig = toml::from_str(toml).unwrap();
+        let default = ConnectionWorkersSchedulerConfig::default();
+        assert!(matches!(
+            config.bind,
+            BindTarget::Address(address) if address == SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8000)
+        ));
+        assert!(config.stake_identity.is_none());
+        assert_eq!(config.num_connections, 8);
+        assert!(config.skip_check_transaction_age);
+        assert_eq!(config.worker_channel_size, default.worker_channel_size);
+        assert_eq!(
+            config.max_reconnect_attempts,
+            default.max_reconnect_attempts
+        );
+        assert_eq!(
+            config.leaders_fanout,
+            Fanout {
+                send: 1,
+                connect: 2
+            }
+        );
+
+        let config: ConnectionWorkersSchedulerConfig =
+            serde_json::from_str(r#"{"worker_channel_size": 16}"#).unwrap();
+        assert_eq!(config.worker_channel_size, 16);
+        assert_eq!(config.num_connections, default.num_connections);
+
+        for (json, err) in [
+            (
+                r#"{"num_connections": 0}"#,
+                ConnectionWorkersSchedulerConfigError::NoConnections,
+            ),
+            (
+                r#"{"leaders_fanout": {"send": 3, "connect": 2}}"#,
+                ConnectionWorkersSchedulerConfigError::ConnectFanoutSmallerThanSend {
+                    send: 3,
+                    connect: 2,
+                },
+            ),
+            (
+                r#"{"num_connections": 2}"#,
+                ConnectionWorkersSchedulerConfigError::ConnectFanoutExceedsConnections {
+                    num_connections: 2,
+                    connect: 3,
+                },
+            ),
+        ] {
+            let result = serde_json::from_str::<ConnectionWorkersSchedulerConfig>(json);
+            assert_eq!(result.err().unwrap().to_string(), err.to_string());
+        }
+        assert!(
+            serde_json::from_str::<ConnectionWorkersSchedulerConfig>(r#"{"fanout": 1}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_is_blockhash_expired() {
+        let (block_height_sender, block_height_receiver) = watch::channel(0);
+        let batch = TransactionBatch::new(vec![vec![0u8]]);
+        let tagged_batch = batch.clone().with_last_valid_block_height(100);
+
+        assert!(!is_blockhash_expired(&tagged_batch, None));
+        block_height_sender.send_replace(100);
+        assert!(!is_blockhash_expired(
+            &tagged_batch,
+            Some(&block_height_receiver)
+        ));
+        block_height_sender.send_replace(101);
+        assert!(is_blockhash_expired(
+            &tagged_batch,
+            Some(&block_height_receiver)
+        ));
+        assert!(!is_blockhash_expired(&batch, Some(&block_height_receiver)));
+    }
+
+    #[test]
+    fn test_cache_warming_num_leaders() {
+        let config = |lookahead_slots| CacheWarmingConfig {
+            lookahead_slots,
+            interval: Duration::from_millis(DEFAULT_MS_PER_SLOT),
+        };
+        assert_eq!(config(0).num_leaders(), 1);
+        assert_eq!(config(1).num_leaders(), 1);
+        assert_eq!(config(NUM_CONSECUTIVE_LEADER_SLOTS).num_leaders(), 1);
+        assert_eq!(config(NUM_CONSECUTIVE_LEADER_SLOTS + 1).num_leaders(), 2);
+        assert_eq!(CacheWarmingConfig::default().num_leaders(), 2);
+    }
+
+    #[test]
+    fn test_sort_by_success_rate() {
+        let leaders: Vec<_> = (0..6)
+            .map(|i| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8000 + i))
+            .collect();
+        let leaders_send_stats = LeadersSendStats::default();
+        for (leader, (successfully_sent, failed)) in leaders.iter().zip([(0, 10), (1, 9), (9, 1)]) {
+            let stats = leaders_send_stats.get_or_insert(*leader);
+            stats
+                .successfully_sent
+                .store(successfully_sent, Ordering::Relaxed);
+            stats.failed.store(failed, Ordering::Relaxed);
+        }
+        let probed_stats = leaders_send_stats.get_or_insert(leaders[4]);
+        probed_stats.record_probe(Some(Duration::from_millis(1)));
+        probed_stats.record_probe(None);
+        let connected_stats = leaders_send_stats.get_or_insert(leaders[5]);
+        connected_stats.record_handshake(Duration::from_millis(1));
+        connected_stats
+            .handshakes_failed
+            .fetch_add(3, Ordering::Relaxed);
+        connected_stats.record_probe(Some(Duration::from_millis(1)));
+
+        let mut sorted_leaders = leaders.clone();
+        sort_by_success_rate(&mut sorted_leaders, &leaders_send_stats);
+        // The current leader stays first, leaders without history go before
+        // the ones with a lower success rate, and the share of successful
+        // handshakes, then of successful probes, stands in for the success
+        // rate of the leaders which haven't been sent to.
+        assert_eq!(
+            sorted_leaders,
+            vec![leaders[0], leaders[3], leaders[2], leaders[4], leaders[5], leaders[1]]
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! This module provides [`HealthProbe`], which reports the [`Health`] of a
+//! [`ConnectionWorkersScheduler`] in a form suitable for readiness probes and
+//! admin endpoints.
+//!
+//! [`ConnectionWorkersScheduler`]: crate::ConnectionWorkersScheduler
+
+use {
+    crate::{connection_workers_scheduler::DEFAULT_HEALTH_CHECK_INTERVAL, SendTransactionStats},
+    serde_derive::Serialize,
+    std::sync::Arc,
+    tokio::{
+        sync::watch,
+        time::{Duration, Instant},
+    },
+};
+
+/// Default value of [`HealthThresholds::max_leader_update_age`].
+pub const DEFAULT_MAX_LEADER_UPDATE_AGE: Duration =
+    Duration::from_secs(4 * DEFAULT_HEALTH_CHECK_INTERVAL.as_secs());
+
+/// Default value of [`HealthThresholds::max_queued_batches`].
+pub const DEFAULT_MAX_QUEUED_BATCHES: u64 = 1024;
+
+/// [`HealthThresholds`] define when the scheduler stops being ready.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthThresholds {
+    /// The leader updates are stale once the last one is older than this. It
+    /// must be larger than the health check interval of the scheduler, see
+    /// [`ConnectionWorkersScheduler::with_health_check_interval`].
+    ///
+    /// [`ConnectionWorkersScheduler::with_health_check_interval`]: crate::ConnectionWorkersScheduler::with_health_check_interval
+    pub max_leader_update_age: Duration,
+    /// Maximal number of batches queued in the channel of the scheduler and
+    /// in the channels of the workers, see
+    /// [`QueueDepthStats`](crate::send_transaction_stats::QueueDepthStats).
+    pub max_queued_batches: u64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            max_leader_update_age: DEFAULT_MAX_LEADER_UPDATE_AGE,
+            max_queued_batches: DEFAULT_MAX_QUEUED_BATCHES,
+        }
+    }
+}
+
+/// [`Health`] is the status of the scheduler at the moment
+/// [`HealthProbe::health`] is called.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Health {
+    /// Whether the scheduler has started and hasn't stopped yet.
+    pub is_running: bool,
+    /// Time since the last leader update observed by the scheduler, `None`
+    /// before the scheduler has started.
+    pub since_leader_update: Option<Duration>,
+    pub leader_updates_fresh: bool,
+    /// The number of connections to the leaders which are currently open.
+    pub num_active_connections: u64,
+    pub queued_batches: u64,
+    pub queue_below_threshold: bool,
+}
+
+impl Health {
+    /// Returns whether the scheduler is running, its leader updates are fresh,
+    /// it has at least one connection open and its queues are below the
+    /// threshold.
+    pub fn is_ready(&self) -> bool {
+        self.is_running
+            && self.leader_updates_fresh
+            && self.num_active_connections > 0
+            && self.queue_below_threshold
+    }
+}
+
+/// [`HealthProbe`] computes the [`Health`] of the scheduler it has been
+/// obtained from with
+/// [`ConnectionWorkersScheduler::health_probe`](crate::ConnectionWorkersScheduler::health_probe).
+/// It can be cloned and queried from any thread.
+///
+/// The scheduler reports a leader update on every health check when the
+/// estimated slot of its [`LeaderUpdater`] has advanced. The leader updaters
+/// which don't estimate the slot are considered to be updated on every health
+/// check, so that the freshness only tells whether the scheduler is
+/// responsive.
+///
+/// [`LeaderUpdater`]: crate::leader_updater::LeaderUpdater
+#[derive(Clone)]
+pub struct HealthProbe {
+    last_leader_update: watch::Receiver<Option<Instant>>,
+    stats: Arc<SendTransactionStats>,
+    thresholds: HealthThresholds,
+}
+
+impl HealthProbe {
+    pub(crate) fn new(
+        last_leader_update: watch::Receiver<Option<Instant>>,
+        stats: Arc<SendTransactionStats>,
+    ) -> Self {
+        Self {
+            last_leader_update,
+            stats,
+            thresholds: HealthThresholds::default(),
+        }
+    }
+
+    /// Replaces the default [`HealthThresholds`].
+    pub fn with_thresholds(mut self, thresholds: HealthThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn health(&self) -> Health {
+        let last_leader_update = *self.last_leader_update.borrow();
+        // The scheduler drops the sender when it stops.
+        let is_running =
+            last_leader_update.is_some() && self.last_leader_update.has_changed().is_ok();
+        let since_leader_update = last_leader_update.map(|instant| instant.elapsed());
+        let leader_updates_fresh = since_leader_update
+            .is_some_and(|elapsed| elapsed <= self.thresholds.max_leader_update_age);
+
+        let leaders = self.stats.leaders.aggregate();
+        let num_active_connections = leaders
+            .connections_established
+            .saturating_sub(leaders.connections_closed);
+
+        let queue_depth = self.stats.queue_depth.snapshot();
+        let queued_batches = queue_depth
+            .scheduler_queue
+            .saturating_add(queue_depth.worker_queues);
+
+        Health {
+            is_running,
+            since_leader_update,
+            leader_updates_fresh,
+            num_active_connections,
+            queued_batches,
+            queue_below_threshold: queued_batches <= self.thresholds.max_queued_batches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::sync::atomic::Ordering};
+
+    #[test]
+    fn test_health() {
+        let stats = Arc::new(SendTransactionStats::default());
+        let (sender, receiver) = watch::channel(None);
+        let probe = HealthProbe::new(receiver, stats.clone()).with_thresholds(HealthThresholds {
+            max_leader_update_age: Duration::from_secs(10),
+            max_queued_batches: 4,
+        });
+
+        // Not started.
+        let health = probe.health();
+        assert!(!health.is_running);
+        assert!(!health.leader_updates_fresh);
+        assert!(!health.is_ready());
+
+        sender.send_replace(Some(Instant::now()));
+        let health = probe.health();
+        assert!(health.is_running);
+        assert!(health.leader_updates_fresh);
+        // No connection yet.
+        assert_eq!(health.num_active_connections, 0);
+        assert!(!health.is_ready());
+
+        let leader_stats = stats
+            .leaders
+            .get_or_insert("127.0.0.1:8000".parse().unwrap());
+        leader_stats
+            .connections_established
+            .fetch_add(2, Ordering::Relaxed);
+        leader_stats
+            .connections_closed
+            .fetch_add(1, Ordering::Relaxed);
+        let health = probe.health();
+        assert_eq!(health.num_active_connections, 1);
+        assert!(health.is_ready());
+
+        stats.queue_depth.update(3, 2);
+        let health = probe.health();
+        assert_eq!(health.queued_batches, 5);
+        assert!(!health.queue_below_threshold);
+        assert!(!health.is_ready());
+        stats.queue_depth.update(0, 0);
+
+        sender.send_replace(Instant::now().checked_sub(Duration::from_secs(11)));
+        let health = probe.health();
+        assert!(health.since_leader_update.unwrap() >= Duration::from_secs(11));
+        assert!(!health.leader_updates_fresh);
+        assert!(!health.is_ready());
+
+        drop(sender);
+        assert!(!probe.health().is_running);
+    }
+}
@@ -0,0 +1,111 @@
+//! This module defines [`LeaderProber`] which checks that the upcoming leaders
+//! are reachable before any transaction is sent to them.
+
+use {
+    crate::{
+        logging::{log_event, DebugValue},
+        send_transaction_stats::LeaderSendStats,
+        task_names::spawn_named,
+        workers_cache::WorkersCache,
+        SendTransactionStats,
+    },
+    quinn::{ConnectError, Endpoint},
+    solana_tls_utils::socket_addr_to_quic_server_name,
+    std::{collections::HashSet, net::SocketAddr, sync::Arc},
+    tokio::time::{timeout, Duration},
+    tokio_util::sync::CancellationToken,
+};
+
+/// [`LeaderProber`] performs a QUIC handshake with the upcoming leaders which
+/// are not served by a worker yet and closes the connection right away. The
+/// outcome and the RTT are recorded in the [`LeaderSendStats`] of the leader,
+/// which the scheduler uses to prefer the reachable leaders.
+///
+/// A leader is probed once each time it enters the lookahead window, so that
+/// probing does not cost a handshake per tick.
+pub(crate) struct LeaderProber {
+    handshake_timeout: Duration,
+    probed_leaders: HashSet<SocketAddr>,
+    cancel: CancellationToken,
+}
+
+impl LeaderProber {
+    pub(crate) fn new(handshake_timeout: Duration, cancel: CancellationToken) -> Self {
+        Self {
+            handshake_timeout,
+            probed_leaders: HashSet::new(),
+            cancel,
+        }
+    }
+
+    /// Spawns a probe for each of the `leaders` which has neither been probed
+    /// while in the previous lookahead window nor has a worker in `workers`.
+    pub(crate) fn probe(
+        &mut self,
+        endpoint: &Endpoint,
+        leaders: Vec<SocketAddr>,
+        workers: &WorkersCache,
+        stats: &SendTransactionStats,
+    ) {
+        let leaders: HashSet<SocketAddr> = leaders.into_iter().collect();
+        for peer in &leaders {
+            if self.probed_leaders.contains(peer) || workers.contains(peer) {
+                continue;
+            }
+            let endpoint = endpoint.clone();
+            let peer = *peer;
+            let leader_stats = stats.leaders.get_or_insert(peer);
+            let handshake_timeout = self.handshake_timeout;
+            let cancel = self.cancel.clone();
+            spawn_named(|| format!("tpu-probe-{peer}"), async move {
+                tokio::select! {
+                    () = probe_leader(endpoint, peer, handshake_timeout, leader_stats) => {},
+                    () = cancel.cancelled() => {},
+                }
+            });
+        }
+        self.probed_leaders = leaders;
+    }
+}
+
+/// Performs a handshake with `peer` and records its outcome in
+/// `leader_stats`.
+async fn probe_leader(
+    endpoint: Endpoint,
+    peer: SocketAddr,
+    handshake_timeout: Duration,
+    leader_stats: Arc<LeaderSendStats>,
+) {
+    let server_name = socket_addr_to_quic_server_name(peer);
+    let connecting = match endpoint.connect(peer, &server_name) {
+        Ok(connecting) => connecting,
+        // The scheduler is shutting down, which says nothing about the leader.
+        Err(ConnectError::EndpointStopping) => return,
+        Err(err) => {
+            log_event!(debug, "Failed to probe", peer = peer, error = err);
+            leader_stats.record_probe(None);
+            return;
+        }
+    };
+    match timeout(handshake_timeout, connecting).await {
+        Ok(Ok(connection)) => {
+            let rtt = connection.rtt();
+            log_event!(debug, "Probe succeeded", peer = peer, rtt = DebugValue(rtt));
+            leader_stats.record_probe(Some(rtt));
+            connection.close(0u32.into(), b"probe");
+        }
+        Ok(Err(err)) => {
+            log_event!(debug, "Probe failed", peer = peer, error = err);
+            leader_stats.record_probe(None);
+        }
+        Err(_) => {
+            log_event!(
+                debug,
+                "Probe timed out",
+                peer = peer,
+                timeout = DebugValue(handshake_timeout),
+            );
+            leader_stats.record_probe(None);
+        }
+    }
+}
@@ -9,9 +9,9 @@
 //! Yet, it also allows to implement custom leader estimation.
 
 use {
-    crate::logging::error,
+    crate::logging::{error, log_event},
     async_trait::async_trait,
-    solana_clock::NUM_CONSECUTIVE_LEADER_SLOTS,
+    solana_clock::Slot,
     solana_connection_cache::connection_cache::Protocol,
     solana_rpc_client::nonblocking::rpc_client::RpcClient,
     solana_tpu_client::nonblocking::tpu_client::LeaderTpuService,
@@ -24,6 +24,12 @@ use {
         },
     },
     thiserror::Error,
+    tokio::sync::watch,
+    url::Url,
+};
+
+pub use solana_tpu_client::nonblocking::tpu_client::{
+    KnownPeers, LeaderScheduleSnapshot, LeaderTpuServiceConfig, LeaderTpuSocket,
 };
 
 /// [`LeaderUpdater`] trait abstracts out functionality required for the
@@ -34,13 +40,68 @@ pub trait LeaderUpdater: Send {
     /// Returns next leaders for the next `lookahead_leaders` starting from
     /// current estimated slot.
     ///
-    /// Leaders are returned per [`NUM_CONSECUTIVE_LEADER_SLOTS`] to avoid unnecessary repetition.
+    /// Leaders are returned per [`solana_clock::NUM_CONSECUTIVE_LEADER_SLOTS`]
+    /// to avoid unnecessary repetition. Implementations should also merge
+    /// consecutive slot groups of the same leader, so that `lookahead_leaders`
+    /// covers that many distinct validators.
     ///
     /// If the current leader estimation is incorrect and transactions are sent to
     /// only one estimated leader, there is a risk of losing all the transactions,
     /// depending on the forwarding policy.
     fn next_leaders(&mut self, lookahead_leaders: usize) -> Vec<SocketAddr>;
 
+    /// Notifies that the leader with the given TPU `address` couldn't be
+    /// reached after repeated connection attempts.
+    ///
+    /// Implementations caching the resolved leader addresses should use it to
+    /// resolve the address again instead of waiting for the next full refresh.
+    /// The default implementation does nothing.
+    fn invalidate_leader_address(&mut self, _address: &SocketAddr) {}
+
+    /// Returns the number of distinct upcoming leaders found without
+    /// reachable TPU socket since the previous call, so that such leaders are
+    /// accounted in [`SendTransactionStats`](crate::SendTransactionStats). The
+    /// default implementation returns 0.
+    fn take_num_leaders_without_tpu_socket(&mut self) -> u64 {
+        0
+    }
+
+    /// Returns a snapshot of the leader schedule which can be saved and used
+    /// later to create a [`LeaderUpdater`] with
+    /// [`create_leader_updater_from_snapshot`]. The default implementation
+    /// returns `None`.
+    fn leader_schedule_snapshot(&self) -> Option<LeaderScheduleSnapshot> {
+        None
+    }
+
+    /// Returns the last known good TPU sockets of the leaders, which can be
+    /// saved and used after restart to start connecting before the first
+    /// leader update, see
+    /// [`ConnectionWorkersScheduler::with_initial_peers`](crate::ConnectionWorkersScheduler::with_initial_peers).
+    /// The default implementation returns `None`.
+    fn known_peers(&self) -> Option<KnownPeers> {
+        None
+    }
+
+    /// Subscribes to the changes of the next `lookahead_leaders` leaders, so
+    /// that applications can align their work with leader transitions without
+    /// polling. Returns `None` if the implementation doesn't support it, which
+    /// is the default.
+    fn subscribe_leader_changes(
+        &self,
+        _lookahead_leaders: usize,
+    ) -> Option<watch::Receiver<Vec<LeaderTpuSocket>>> {
+        None
+    }
+
+    /// Returns the estimated current slot, which the scheduler watches to tell
+    /// whether the leader updates are fresh, see
+    /// [`HealthProbe`](crate::health::HealthProbe). Returns `None` if the
+    /// implementation doesn't estimate the slot, which is the default.
+    fn estimated_current_slot(&self) -> Option<Slot> {
+        None
+    }
+
     /// Stop [`LeaderUpdater`] and releases all associated resources.
     async fn stop(&mut self);
 }
@@ -72,6 +133,23 @@ pub async fn create_leader_updater(
     rpc_client: Arc<RpcClient>,
     websocket_url: String,
     pinned_address: Option<SocketAddr>,
+) -> Result<Box<dyn LeaderUpdater>, LeaderUpdaterError> {
+    create_leader_updater_with_config(
+        rpc_client,
+        websocket_url,
+        pinned_address,
+        LeaderTpuServiceConfig::default(),
+    )
+    .await
+}
+
+/// Creates a [`LeaderUpdater`] like [`create_leader_updater`] does, but allows
+/// to configure how the [`LeaderTpuService`] resolves the leaders TPU sockets.
+pub async fn create_leader_updater_with_config(
+    rpc_client: Arc<RpcClient>,
+    websocket_url: String,
+    pinned_address: Option<SocketAddr>,
+    config: LeaderTpuServiceConfig,
 ) -> Result<Box<dyn LeaderUpdater>, LeaderUpdaterError> {
     if let Some(pinned_address) = pinned_address {
         return Ok(Box::new(PinnedLeaderUpdater {
@@ -80,19 +158,119 @@ pub async fn create_leader_updater(
     }
 
     let exit = Arc::new(AtomicBool::new(false));
-    let leader_tpu_service =
-        LeaderTpuService::new(rpc_client, &websocket_url, Protocol::QUIC, exit.clone())
-            .await
-            .map_err(|error| {
-                error!("Failed to create a LeaderTpuService: {error}");
-                LeaderUpdaterError
-            })?;
+    let leader_tpu_service = LeaderTpuService::new_with_config(
+        rpc_client,
+        &websocket_url,
+        Protocol::QUIC,
+        exit.clone(),
+        config,
+    )
+    .await
+    .map_err(|error| {
+        error!("Failed to create a LeaderTpuService: {error}");
+        LeaderUpdaterError
+    })?;
     Ok(Box::new(LeaderUpdaterService {
         leader_tpu_service,
         exit,
     }))
 }
 
+/// Returns the websocket URL of the node serving the JSON RPC at `rpc_url`,
+/// which uses `ws` or `wss` and the next port if the port is explicit, like
+/// the CLI assumes.
+pub fn websocket_url_from_rpc_url(rpc_url: &str) -> Result<String, LeaderUpdaterError> {
+    let mut url = Url::parse(rpc_url).map_err(|error| {
+        error!("Failed to parse the RPC URL {rpc_url}: {error}");
+        LeaderUpdaterError
+    })?;
+    let scheme = if url.scheme().eq_ignore_ascii_case("https") {
+        "wss"
+    } else {
+        "ws"
+    };
+    let next_port = url.port().map(|port| port.checked_add(1));
+    url.set_scheme(scheme)
+        .and_then(|()| match next_port {
+            Some(Some(port)) => url.set_port(Some(port)),
+            Some(None) => Err(()),
+            None => Ok(()),
+        })
+        .map_err(|()| {
+            error!("Failed to derive the websocket URL from {rpc_url}");
+            LeaderUpdaterError
+        })?;
+    Ok(url.to_string())
+}
+
+/// Creates a [`LeaderUpdater`] from a previously saved leader schedule
+/// `snapshot`.
+///
+/// Unlike [`create_leader_updater_with_config`], it doesn't need the RPC to be
+/// reachable, which allows offline testing and fast cold starts. The leader
+/// schedule is refreshed in the background once the RPC becomes available.
+pub async fn create_leader_updater_from_snapshot(
+    rpc_client: Arc<RpcClient>,
+    websocket_url: String,
+    config: LeaderTpuServiceConfig,
+    snapshot: LeaderScheduleSnapshot,
+) -> Box<dyn LeaderUpdater> {
+    let exit = Arc::new(AtomicBool::new(false));
+    let leader_tpu_service = LeaderTpuService::new_from_snapshot(
+        rpc_client,
+        &websocket_url,
+        Protocol::QUIC,
+        exit.clone(),
+        config,
+        snapshot,
+    )
+    .await;
+    Box::new(LeaderUpdaterService {
+        leader_tpu_service,
+        exit,
+    })
+}
+
+/// Creates a [`LeaderUpdater`] for a single node cluster, like the one started
+/// by `solana-test-validator`.
+///
+/// The node serving the RPC is the only leader, so its QUIC TPU address is
+/// looked up once in the cluster contact info by the node identity. The
+/// returned updater behaves like the one created with a `pinned_address`,
+/// which avoids the leader schedule machinery in integration tests.
+pub async fn create_local_leader_updater(
+    rpc_client: &RpcClient,
+) -> Result<Box<dyn LeaderUpdater>, LeaderUpdaterError> {
+    let identity = rpc_client.get_identity().await.map_err(|error| {
+        log_event!(
+            error,
+            "Failed to get the identity of the local node",
+            error = error,
+        );
+        LeaderUpdaterError
+    })?;
+    let cluster_nodes = rpc_client.get_cluster_nodes().await.map_err(|error| {
+        log_event!(error, "Failed to get the cluster nodes", error = error);
+        LeaderUpdaterError
+    })?;
+    let identity = identity.to_string();
+    let Some(address) = cluster_nodes
+        .into_iter()
+        .find(|node| node.pubkey == identity)
+        .and_then(|node| node.tpu_quic)
+    else {
+        log_event!(
+            error,
+            "Local node doesn't advertise a QUIC TPU address",
+            identity = identity,
+        );
+        return Err(LeaderUpdaterError);
+    };
+    Ok(Box::new(PinnedLeaderUpdater {
+        address: vec![address],
+    }))
+}
+
 /// `LeaderUpdaterService` is an implementation of the [`LeaderUpdater`] trait
 /// that dynamically retrieves the current and upcoming leaders by communicating
 /// with the Solana network using [`LeaderTpuService`].
@@ -104,9 +282,43 @@ struct LeaderUpdaterService {
 #[async_trait]
 impl LeaderUpdater for LeaderUpdaterService {
     fn next_leaders(&mut self, lookahead_leaders: usize) -> Vec<SocketAddr> {
-        let lookahead_slots =
-            (lookahead_leaders as u64).saturating_mul(NUM_CONSECUTIVE_LEADER_SLOTS);
-        self.leader_tpu_service.leader_tpu_sockets(lookahead_slots)
+        self.leader_tpu_service
+            .leader_tpu_slot_spans(lookahead_leaders)
+            .into_iter()
+            .map(|leader| leader.socket)
+            .collect()
+    }
+
+    fn invalidate_leader_address(&mut self, address: &SocketAddr) {
+        self.leader_tpu_service
+            .invalidate_leader_tpu_socket(address);
+    }
+
+    fn take_num_leaders_without_tpu_socket(&mut self) -> u64 {
+        self.leader_tpu_service
+            .take_num_leaders_without_tpu_socket()
+    }
+
+    fn leader_schedule_snapshot(&self) -> Option<LeaderScheduleSnapshot> {
+        Some(self.leader_tpu_service.leader_schedule_snapshot())
+    }
+
+    fn known_peers(&self) -> Option<KnownPeers> {
+        Some(self.leader_tpu_service.known_peers())
+    }
+
+    fn subscribe_leader_changes(
+        &self,
+        lookahead_leaders: usize,
+    ) -> Option<watch::Receiver<Vec<LeaderTpuSocket>>> {
+        Some(
+            self.leader_tpu_service
+                .subscribe_leader_changes(lookahead_leaders),
+        )
+    }
+
+    fn estimated_current_slot(&self) -> Option<Slot> {
+        Some(self.leader_tpu_service.estimated_current_slot())
     }
 
     async fn stop(&mut self) {
@@ -129,3 +341,35 @@ impl LeaderUpdater for PinnedLeaderUpdater {
 
     async fn stop(&mut self) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::net::Ipv4Addr};
+
+    #[test]
+    fn test_websocket_url_from_rpc_url() {
+        assert_eq!(
+            websocket_url_from_rpc_url("http://127.0.0.1:8899").unwrap(),
+            "ws://127.0.0.1:8900/"
+        );
+        assert_eq!(
+            websocket_url_from_rpc_url("https://api.mainnet-beta.solana.com").unwrap(),
+            "wss://api.mainnet-beta.solana.com/"
+        );
+        assert!(websocket_url_from_rpc_url("not a url").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_leader_updater() {
+        // The mock node advertises its own contact info in the cluster nodes.
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let mut leader_updater = create_local_leader_updater(&rpc_client).await.unwrap();
+        let expected_address = SocketAddr::new(Ipv4Addr::new(10, 239, 6, 48).into(), 8862);
+        assert_eq!(leader_updater.next_leaders(1), vec![expected_address]);
+        assert_eq!(leader_updater.next_leaders(4), vec![expected_address]);
+        leader_updater.stop().await;
+
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+        assert!(create_local_leader_updater(&rpc_client).await.is_err());
+    }
+}
@@ -1,17 +1,75 @@
 //! # Feature flags
 //!
-//! Tpu-client-next supports three features:
+//! Tpu-client-next supports the following features:
 //!
 //! - **`metrics`**: Enables implementation of the method `report_to_influxdb` for
 //!   [`SendTransactionStats`] structure.
+//! - **`prometheus`**: Enables encoding [`SendTransactionStats`] and the state of
+//!   the workers cache in the Prometheus text exposition format.
 //! - **`log`**: Enables logging using `log` crate. It is enabled by default.
 //! - **`tracing`**: Enables logging using `tracing` crate instead of `log`. This feature is
-//!   mutually exclusive with `log`.
+//!   mutually exclusive with `log`. The connection workers and the handling of
+//!   each batch are instrumented with spans carrying the leader and the batch
+//!   identifier.
+//! - **`no-log`**: Compiles out the logging, so that no time is spent formatting
+//!   the events, for the users who rely on [`SendTransactionStats`] and the
+//!   workers cache events only. This feature is mutually exclusive with `log`
+//!   and `tracing`.
+//! - **`statsd`**: Enables implementation of the method `report_to_statsd` for
+//!   [`SendTransactionStats`] structure.
+//! - **`otel`**: Emits `tracing` spans, and events with `monotonic_counter.*`
+//!   and `histogram.*` fields, which an external `tracing-opentelemetry` layer
+//!   turns into OpenTelemetry traces and metrics. The crate doesn't export
+//!   them over OTLP itself. It requires disabling `log`.
+//! - **`test-utils`**: Enables the `test_utils` module with an in-process QUIC
+//!   server which records the transactions it receives and can inject faults,
+//!   and a `LeaderUpdater` returning scripted leaders, to test the users of
+//!   this crate without running a validator. It also enables the injection of
+//!   dropped connections, delays and throttling in the transport, configured
+//!   per peer, and a mock clock which drives the slot ticks, the timeouts and
+//!   the backoffs of the scheduler, to test them without real sleeps.
+//! - **`tokio-console`**: Names the worker tasks after their leader so that they
+//!   can be told apart in tokio-console. Tokio only names the tasks when built
+//!   with `--cfg tokio_unstable`.
+//! - **`transaction`**: Enables the methods of `TransactionBatch` which handle
+//!   `VersionedTransaction`s: `try_from_transactions`, which serializes them,
+//!   and `sort_by_compute_unit_price`. It also enables `TpuClientNext`, which
+//!   sends transactions and waits for their confirmation. Like in
+//!   `solana-client`, the async client is in `nonblocking::tpu_client` and the
+//!   blocking one, which runs it on a runtime of its own, in `tpu_client`.
+//! - **`load-gen`**: Builds the `solana-tpu-client-next-load-gen` binary, which
+//!   sends transfers at a configurable rate to the leaders of a cluster and
+//!   prints the statistics of the scheduler, to benchmark the leaders and to
+//!   validate the tuning of the scheduler. It enables `transaction`.
+//! - **`connection-cache`**: Enables the `connection_cache` module, which
+//!   implements the connection pool traits of `solana-connection-cache` with the
+//!   connection workers of this crate, so that the users of a `ConnectionCache`
+//!   built on `solana-quic-client` can switch to this crate.
+//! - **`compat`**: Enables the `compat` module, which eases the migration from
+//!   `solana-tpu-client` with deprecated aliases of its clients and
+//!   conversions of its configuration, errors and stats. It enables
+//!   `transaction`.
+//! - **`bench`**: Enables the benchmarks of the send pipeline, run with
+//!   `cargo bench --features bench`. It enables `test-utils` and
+//!   `agave-unstable-api`.
 
+#[cfg(feature = "compat")]
+pub mod compat;
+#[cfg(feature = "connection-cache")]
+pub mod connection_cache;
 pub(crate) mod connection_worker;
 pub mod connection_workers_scheduler;
+pub mod health;
+pub(crate) mod leader_prober;
+pub mod negotiated_parameters;
+#[cfg(feature = "transaction")]
+pub mod nonblocking;
 pub mod send_transaction_stats;
+#[cfg(feature = "transaction")]
+pub mod tpu_client;
 pub mod workers_cache;
+#[cfg(feature = "transaction")]
+pub use crate::nonblocking::tpu_client::TpuClientNext;
 pub use crate::{
     connection_workers_scheduler::{ConnectionWorkersScheduler, ConnectionWorkersSchedulerError},
     send_transaction_stats::SendTransactionStats,
@@ -24,5 +82,20 @@ pub mod transaction_batch;
 #[cfg(feature = "metrics")]
 pub mod metrics;
 
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+
+#[cfg(feature = "otel")]
+pub mod otel;
+
+#[cfg(feature = "statsd")]
+pub mod statsd;
+
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+
 // Logging abstraction module
-pub(crate) mod logging;
+pub mod logging;
+
+pub(crate) mod clock;
+pub(crate) mod task_names;
@@ -3,21 +3,837 @@
 //! This module provides a unified logging interface that can be configured
 //! to use either the `log` crate (default) or the `tracing` crate.
 //! The features are mutually exclusive - only one can be enabled at a time.
+//!
+//! The significant events, e.g. the connection failures, are logged with
+//! [`log_event!`] along with their context as fields, like the peer or the
+//! number of transactions. With `tracing` they are recorded as the fields of
+//! the event, so that the subscriber formats them, e.g. as JSON. With `log`
+//! they are appended to the message in the [`LogFormat`] set with
+//! [`set_log_format`].
+//!
+//! To keep an outage from flooding the logs, the application can set a
+//! window with [`set_log_suppression_window`], the suppression is disabled by
+//! default. An event identical to one logged less than the window ago, i.e.
+//! with the same message and field values, is then suppressed. The first
+//! identical event after the window is logged along with the number of
+//! events suppressed in between as the `suppressed` field. If no identical
+//! event follows, that number is logged once the window has expired, when
+//! the next event is checked.
+//!
+//! The target of the events is the path of the module which logs them, e.g.
+//! `solana_tpu_client_next::connection_worker`, so that the verbosity can be
+//! set per module. With `log`, the crate name can be replaced by the prefix
+//! set with [`set_log_target_prefix`], e.g. to tell apart the clients of
+//! several services. The targets of `tracing` are static, so they are always
+//! the module paths.
+//!
+//! With the redaction enabled with [`set_log_redaction`], the fields which
+//! hold the identity or the address of a node, e.g. `peer` or `leader`, are
+//! replaced by a hash of their value in the logs, so that the events of a
+//! node can still be correlated.
+//!
+//! On top of the filters of the logger, the events of this crate are filtered
+//! by the [`Verbosity`] set with [`set_verbosity`], which can be changed at
+//! runtime.
+//!
+//! With the `no-log` feature instead of a backend, the logging calls are
+//! compiled out, so that the hot path spends no time on formatting. The
+//! [`LogHook`] is not called either, the [`SendTransactionStats`] and the
+//! [`WorkersCacheEvent`]s are left to observe the client.
+//!
+//! [`SendTransactionStats`]: crate::SendTransactionStats
+//! [`WorkersCacheEvent`]: crate::workers_cache::WorkersCacheEvent
+
+// Without a backend, most of this module is only used by the macros which are
+// compiled out.
+#![cfg_attr(feature = "no-log", allow(dead_code))]
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::{self, Display},
+    hash::{BuildHasher, Hasher, RandomState},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+        Arc, LazyLock, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "log")]
+use std::borrow::Cow;
+
+#[cfg(not(any(feature = "log", feature = "tracing", feature = "no-log")))]
+compile_error!("Either 'log', 'tracing' or 'no-log' feature must be enabled");
+
+#[cfg(any(
+    all(feature = "log", feature = "tracing"),
+    all(feature = "log", feature = "no-log"),
+    all(feature = "tracing", feature = "no-log"),
+))]
+compile_error!("'log', 'tracing' and 'no-log' features are mutually exclusive");
+
+static LOG_TARGET_PREFIX: RwLock<Option<String>> = RwLock::new(None);
+
+/// Sets the prefix which replaces the crate name in the targets of the events
+/// logged with the `log` backend, e.g. `tpu` for `tpu::connection_worker`.
+/// `None` restores the module paths.
+pub fn set_log_target_prefix(prefix: Option<String>) {
+    *LOG_TARGET_PREFIX.write().unwrap() = prefix;
+}
+
+/// Returns the target of the events logged from `module_path`.
+#[cfg(feature = "log")]
+pub(crate) fn log_target(module_path: &'static str) -> Cow<'static, str> {
+    prefixed_target(LOG_TARGET_PREFIX.read().unwrap().as_deref(), module_path)
+}
+
+#[cfg(feature = "log")]
+fn prefixed_target(prefix: Option<&str>, module_path: &'static str) -> Cow<'static, str> {
+    let Some(prefix) = prefix else {
+        return Cow::Borrowed(module_path);
+    };
+    match module_path.split_once("::") {
+        Some((_crate_name, module)) => Cow::Owned(format!("{prefix}::{module}")),
+        None => Cow::Owned(prefix.to_owned()),
+    }
+}
+
+/// Maximum level of the events logged by this crate, which applies on top of
+/// the filters of the logger, see [`set_verbosity`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    #[default]
+    Trace,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Trace as u8);
+
+/// Sets the maximum level of the events logged by this crate from then on, in
+/// the whole process, regardless of the filters of the logger which apply to
+/// the other crates too. It can only restrict what the logger lets through: to
+/// turn up the detail during an incident, let the logger accept the debug
+/// events of this crate, keep the verbosity at [`Verbosity::Warn`] and raise
+/// it to [`Verbosity::Debug`] when needed, e.g. with
+/// [`ConnectionWorkersScheduler::with_verbosity_receiver`].
+///
+/// [`ConnectionWorkersScheduler::with_verbosity_receiver`]: crate::ConnectionWorkersScheduler::with_verbosity_receiver
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Error,
+        1 => Verbosity::Warn,
+        2 => Verbosity::Info,
+        3 => Verbosity::Debug,
+        _ => Verbosity::Trace,
+    }
+}
+
+/// Returns whether the events of `level` are logged at the current verbosity.
+pub(crate) fn is_verbose(level: Verbosity) -> bool {
+    level as u8 <= VERBOSITY.load(Ordering::Relaxed)
+}
+
+/// An event of this crate forwarded to the [`LogHook`].
+#[derive(Clone, Copy)]
+#[non_exhaustive]
+pub struct LogEvent<'a> {
+    pub level: Verbosity,
+    /// Path of the module which has logged the event.
+    pub module_path: &'static str,
+    pub message: &'static str,
+    pub fields: &'a [(&'static str, &'a dyn Display)],
+    /// Number of the identical events suppressed since this one was last
+    /// logged, see [`set_log_suppression_window`].
+    pub suppressed: u64,
+}
+
+impl fmt::Debug for LogEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<_> = self
+            .fields
+            .iter()
+            .map(|(key, value)| (*key, value.to_string()))
+            .collect();
+        f.debug_struct("LogEvent")
+            .field("level", &self.level)
+            .field("module_path", &self.module_path)
+            .field("message", &self.message)
+            .field("fields", &fields)
+            .field("suppressed", &self.suppressed)
+            .finish()
+    }
+}
+
+/// [`LogHook`] receives the events of this crate which carry fields, e.g. the
+/// connection failures or the dropped batches, so that the application can
+/// route them, e.g. into its alerting, without going through a logger.
+///
+/// The events are forwarded regardless of the filters of the logger, but
+/// only if their level is within the [`Verbosity`] and they are not
+/// suppressed as repeats. The hook is called from the workers and the
+/// scheduler, so it must return quickly and never block.
+///
+/// It is also the way to log the events with a library which is not a
+/// backend of this crate, e.g. `slog`, by passing the fields of the event as
+/// the key-value pairs of the record.
+pub trait LogHook: Send + Sync {
+    fn on_event(&self, event: &LogEvent<'_>);
+}
+
+static LOG_HOOK: RwLock<Option<Arc<dyn LogHook>>> = RwLock::new(None);
+
+/// Installs `hook`, which receives the events logged from then on. It
+/// replaces the hook installed before, if any, and `None` removes it.
+pub fn set_log_hook(hook: Option<Arc<dyn LogHook>>) {
+    *LOG_HOOK.write().unwrap() = hook;
+}
+
+pub(crate) fn log_hook() -> Option<Arc<dyn LogHook>> {
+    LOG_HOOK.read().unwrap().clone()
+}
+
+/// Fields which hold the identity or the address of a node.
+const REDACTED_FIELDS: &[&str] = &["peer", "leader", "identity", "address"];
+
+static LOG_REDACTION: AtomicBool = AtomicBool::new(false);
+
+// Keyed per process, so that the hashes can't be reversed by hashing all the
+// addresses.
+static REDACTION_HASHER: LazyLock<RandomState> = LazyLock::new(RandomState::new);
+
+/// Enables or disables the redaction of the identities and the addresses in
+/// the logs from then on. It doesn't apply to the events forwarded to the
+/// [`LogHook`].
+pub fn set_log_redaction(enabled: bool) {
+    LOG_REDACTION.store(enabled, Ordering::Relaxed);
+}
+
+pub fn log_redaction() -> bool {
+    LOG_REDACTION.load(Ordering::Relaxed)
+}
+
+/// Displays the value of the field `key`, or its hash if it is to be
+/// redacted, see [`set_log_redaction`].
+pub(crate) struct Redacted<'a> {
+    value: &'a dyn Display,
+    redact: bool,
+}
+
+impl<'a> Redacted<'a> {
+    pub(crate) fn new(key: &str, value: &'a dyn Display) -> Self {
+        Self::with_redaction(key, value, log_redaction())
+    }
+
+    fn with_redaction(key: &str, value: &'a dyn Display, enabled: bool) -> Self {
+        Self {
+            value,
+            redact: enabled && REDACTED_FIELDS.contains(&key),
+        }
+    }
+}
+
+impl Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.redact {
+            return self.value.fmt(f);
+        }
+        let mut hasher = HashWriter(REDACTION_HASHER.build_hasher());
+        fmt::write(&mut hasher, format_args!("{}", self.value))?;
+        write!(f, "redacted:{:012x}", hasher.0.finish() >> 16)
+    }
+}
+
+/// Format of the events logged with the `log` backend.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LogFormat {
+    /// The message followed by the fields as `key=value`.
+    #[default]
+    Text,
+    /// A JSON object holding the message and the fields as strings, e.g.
+    /// `{"message":"Connection timed out","peer":"127.0.0.1:8009"}`.
+    Json,
+}
+
+static LOG_FORMAT: AtomicU8 = AtomicU8::new(LogFormat::Text as u8);
+
+/// Sets the format of the events logged from then on. It only applies to the
+/// `log` backend, `tracing` subscribers format the fields themselves.
+pub fn set_log_format(format: LogFormat) {
+    LOG_FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+pub fn log_format() -> LogFormat {
+    match LOG_FORMAT.load(Ordering::Relaxed) {
+        x if x == LogFormat::Json as u8 => LogFormat::Json,
+        _ => LogFormat::Text,
+    }
+}
+
+/// Default window of [`set_log_suppression_window`], the suppression is
+/// disabled unless the application opts in.
+pub const DEFAULT_LOG_SUPPRESSION_WINDOW: Duration = Duration::ZERO;
+
+// Bounds the memory used to track the events, the events which don't fit are
+// not suppressed.
+const MAX_TRACKED_EVENTS: usize = 1024;
+
+pub(crate) static LOG_SUPPRESSOR: LogSuppressor =
+    LogSuppressor::new(DEFAULT_LOG_SUPPRESSION_WINDOW);
+
+/// Sets the window during which the repeats of an event are suppressed.
+/// [`Duration::ZERO`] disables the suppression, which is the default, and
+/// logs the repeats suppressed so far.
+pub fn set_log_suppression_window(window: Duration) {
+    for event in LOG_SUPPRESSOR.set_window(window) {
+        log_suppressed(&event);
+    }
+}
+
+/// [`LogSuppressor`] tracks when each distinct event was last logged and how
+/// many of its repeats were suppressed since.
+///
+/// The events whose window has expired without a repeat being logged are
+/// swept once per window, when an event is checked, and the number of their
+/// suppressed repeats is logged then.
+pub(crate) struct LogSuppressor {
+    window_ms: AtomicU64,
+    hasher: LazyLock<RandomState>,
+    tracked: LazyLock<Mutex<TrackedEvents>>,
+}
+
+struct TrackedEvents {
+    events: HashMap<u64, RepeatedEvent>,
+    swept_at: Instant,
+}
+
+struct RepeatedEvent {
+    level: Verbosity,
+    module_path: &'static str,
+    message: &'static str,
+    fields: Vec<(&'static str, String)>,
+    logged_at: Instant,
+    suppressed: u64,
+}
+
+impl LogSuppressor {
+    const fn new(window: Duration) -> Self {
+        Self {
+            window_ms: AtomicU64::new(window.as_millis() as u64),
+            hasher: LazyLock::new(RandomState::new),
+            tracked: LazyLock::new(|| {
+                Mutex::new(TrackedEvents {
+                    events: HashMap::new(),
+                    swept_at: Instant::now(),
+                })
+            }),
+        }
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_millis(self.window_ms.load(Ordering::Relaxed))
+    }
+
+    /// Sets the window and, if it disables the suppression, returns the
+    /// events whose repeats were suppressed.
+    fn set_window(&self, window: Duration) -> Vec<RepeatedEvent> {
+        self.window_ms
+            .store(window.as_millis() as u64, Ordering::Relaxed);
+        if !window.is_zero() {
+            return Vec::new();
+        }
+        let mut tracked = self.tracked.lock().unwrap();
+        tracked
+            .events
+            .drain()
+            .map(|(_, event)| event)
+            .filter(|event| event.suppressed > 0)
+            .collect()
+    }
+
+    /// Returns `None` if the event is to be suppressed, otherwise the number
+    /// of its repeats suppressed since it was last logged. Without a window,
+    /// the event is neither hashed nor tracked.
+    pub(crate) fn check(
+        &self,
+        level: Verbosity,
+        module_path: &'static str,
+        message: &'static str,
+        fields: &[(&'static str, &dyn Display)],
+    ) -> Option<u64> {
+        let window = self.window();
+        if window.is_zero() {
+            return Some(0);
+        }
+        let now = Instant::now();
+        let suppressed = self.check_at(level, module_path, message, fields, window, now);
+        for event in self.sweep_at(window, now) {
+            log_suppressed(&event);
+        }
+        suppressed
+    }
+
+    fn check_at(
+        &self,
+        level: Verbosity,
+        module_path: &'static str,
+        message: &'static str,
+        fields: &[(&'static str, &dyn Display)],
+        window: Duration,
+        now: Instant,
+    ) -> Option<u64> {
+        let mut hasher = HashWriter(self.hasher.build_hasher());
+        hasher.0.write(message.as_bytes());
+        for (key, value) in fields {
+            hasher.0.write(key.as_bytes());
+            let _ = fmt::write(&mut hasher, format_args!("{value}"));
+        }
+        let key = hasher.0.finish();
+
+        let mut tracked = self.tracked.lock().unwrap();
+        let events = &mut tracked.events;
+        if events.len() >= MAX_TRACKED_EVENTS && !events.contains_key(&key) {
+            return Some(0);
+        }
+        match events.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let event = entry.get_mut();
+                if now.saturating_duration_since(event.logged_at) < window {
+                    event.suppressed = event.suppressed.saturating_add(1);
+                    None
+                } else {
+                    event.logged_at = now;
+                    Some(std::mem::take(&mut event.suppressed))
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(RepeatedEvent {
+                    level,
+                    module_path,
+                    message,
+                    fields: fields
+                        .iter()
+                        .map(|(key, value)| (*key, value.to_string()))
+                        .collect(),
+                    logged_at: now,
+                    suppressed: 0,
+                });
+                Some(0)
+            }
+        }
+    }
+
+    /// Forgets the events whose window has expired, at most once per window,
+    /// and returns the ones whose repeats were suppressed meanwhile.
+    fn sweep_at(&self, window: Duration, now: Instant) -> Vec<RepeatedEvent> {
+        let mut tracked = self.tracked.lock().unwrap();
+        if now.saturating_duration_since(tracked.swept_at) < window {
+            return Vec::new();
+        }
+        tracked.swept_at = now;
+        let expired: Vec<_> = tracked
+            .events
+            .iter()
+            .filter(|(_, event)| now.saturating_duration_since(event.logged_at) >= window)
+            .map(|(key, _)| *key)
+            .collect();
+        expired
+            .iter()
+            .filter_map(|key| tracked.events.remove(key))
+            .filter(|event| event.suppressed > 0)
+            .collect()
+    }
+}
+
+/// Logs the number of the repeats of `event` suppressed since it was last
+/// logged, along with its message and fields. With `tracing`, whose targets
+/// are static, the module which logged the event is the `module_path` field.
+fn log_suppressed(event: &RepeatedEvent) {
+    if !is_verbose(event.level) {
+        return;
+    }
+    let fields: Vec<(&str, &dyn Display)> = event
+        .fields
+        .iter()
+        .map(|(key, value)| (*key, value as &dyn Display))
+        .collect();
+    if let Some(hook) = log_hook() {
+        hook.on_event(&LogEvent {
+            level: event.level,
+            module_path: event.module_path,
+            message: event.message,
+            fields: &fields,
+            suppressed: event.suppressed,
+        });
+    }
+    #[cfg(feature = "log")]
+    {
+        let level = match event.level {
+            Verbosity::Error => log::Level::Error,
+            Verbosity::Warn => log::Level::Warn,
+            Verbosity::Info => log::Level::Info,
+            Verbosity::Debug => log::Level::Debug,
+            Verbosity::Trace => log::Level::Trace,
+        };
+        let target = log_target(event.module_path);
+        if log::log_enabled!(target: &target, level) {
+            let line = format_event(event.message, &fields, event.suppressed);
+            log::log!(target: &target, level, "{line}");
+        }
+    }
+    #[cfg(feature = "tracing")]
+    {
+        let rendered_fields = fields
+            .iter()
+            .map(|(key, value)| format!("{key}={}", Redacted::new(key, *value)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        macro_rules! event_at {
+            ($level:expr) => {
+                tracing::event!(
+                    $level,
+                    module_path = event.module_path,
+                    fields = rendered_fields,
+                    suppressed = event.suppressed,
+                    "{}",
+                    event.message
+                )
+            };
+        }
+        match event.level {
+            Verbosity::Error => event_at!(tracing::Level::ERROR),
+            Verbosity::Warn => event_at!(tracing::Level::WARN),
+            Verbosity::Info => event_at!(tracing::Level::INFO),
+            Verbosity::Debug => event_at!(tracing::Level::DEBUG),
+            Verbosity::Trace => event_at!(tracing::Level::TRACE),
+        }
+    }
+}
+
+// Feeds the rendered field values to the hasher without allocating.
+struct HashWriter<H>(H);
+
+impl<H: Hasher> fmt::Write for HashWriter<H> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Renders the `message` of an event along with its `fields`, and the number
+/// of its `suppressed` repeats if any, in the current [`LogFormat`].
+#[cfg(feature = "log")]
+pub(crate) fn format_event(
+    message: &str,
+    fields: &[(&str, &dyn Display)],
+    suppressed: u64,
+) -> String {
+    use std::fmt::Write;
+
+    let suppressed_field: [(&str, &dyn Display); 1] = [("suppressed", &suppressed)];
+    let suppressed_field = if suppressed > 0 {
+        &suppressed_field[..]
+    } else {
+        &[]
+    };
+    let fields = fields.iter().chain(suppressed_field);
+    let mut line = String::new();
+    match log_format() {
+        LogFormat::Text => {
+            line.push_str(message);
+            for (key, value) in fields {
+                let _ = write!(line, " {key}={}", Redacted::new(key, *value));
+            }
+        }
+        LogFormat::Json => {
+            line.push_str("{\"message\":");
+            write_json_string(&mut line, message);
+            for (key, value) in fields {
+                line.push(',');
+                write_json_string(&mut line, key);
+                line.push(':');
+                write_json_string(&mut line, &Redacted::new(key, *value).to_string());
+            }
+            line.push('}');
+        }
+    }
+    line
+}
 
 #[cfg(feature = "log")]
-pub use log::{debug, error, trace, warn};
+fn write_json_string(line: &mut String, value: &str) {
+    use std::fmt::Write;
+
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(line, "\\u{:04x}", c as u32);
+            }
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+// Forwarded by `format_event` to `Display` for the values which are only
+// `Debug`, e.g. `Duration`.
+pub(crate) struct DebugValue<T>(pub(crate) T);
+
+impl<T: fmt::Debug> Display for DebugValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+/// Logs an event at `level` (`error`, `warn`, `info`, `debug` or `trace`)
+/// with `message` and the `key = value` fields, whose values implement
+/// `Display`, and forwards it to the [`LogHook`] if any. The repeats of an
+/// event are suppressed, see the module documentation.
+#[cfg(not(feature = "no-log"))]
+macro_rules! log_event {
+    ($level:ident, $message:literal $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::logging::is_verbose($crate::logging::verbosity_of!($level)) {
+            let hook = $crate::logging::log_hook();
+            let enabled = $crate::logging::log_event_enabled!($level);
+            if enabled || hook.is_some() {
+                match ($(&$value,)*) {
+                    ($($key,)*) => {
+                        let fields: &[(&'static str, &dyn std::fmt::Display)] =
+                            &[$((stringify!($key), $key as &dyn std::fmt::Display)),*];
+                        if let Some(suppressed) =
+                            $crate::logging::LOG_SUPPRESSOR.check(
+                                $crate::logging::verbosity_of!($level),
+                                module_path!(),
+                                $message,
+                                fields,
+                            )
+                        {
+                            if let Some(hook) = hook {
+                                hook.on_event(&$crate::logging::LogEvent {
+                                    level: $crate::logging::verbosity_of!($level),
+                                    module_path: module_path!(),
+                                    message: $message,
+                                    fields,
+                                    suppressed,
+                                });
+                            }
+                            if enabled {
+                                $crate::logging::log_event_backend!(
+                                    $level,
+                                    $message,
+                                    fields,
+                                    suppressed
+                                    $(, $key)*
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+}
+
+// The values are still borrowed so that they count as used.
+#[cfg(feature = "no-log")]
+macro_rules! log_event {
+    ($level:ident, $message:literal $(, $key:ident = $value:expr)* $(,)?) => {
+        if false {
+            let _ = ($(&$value,)*);
+        }
+    };
+}
+
+#[cfg(feature = "log")]
+macro_rules! log_event_enabled {
+    ($level:ident) => {
+        log::log_enabled!(
+            target: &$crate::logging::log_target(module_path!()),
+            $crate::logging::log_level!($level)
+        )
+    };
+}
+
 #[cfg(feature = "tracing")]
-pub use tracing::{debug, error, trace, warn};
+macro_rules! log_event_enabled {
+    ($level:ident) => {
+        tracing::enabled!($crate::logging::tracing_level!($level))
+    };
+}
 
-#[cfg(not(any(feature = "log", feature = "tracing")))]
-compile_error!("Either 'log' or 'tracing' feature must be enabled");
+#[cfg(feature = "log")]
+macro_rules! log_event_backend {
+    ($level:ident, $message:literal, $fields:ident, $suppressed:ident $(, $key:ident)*) => {
+        let line = $crate::logging::format_event($message, $fields, $suppressed);
+        log::log!(
+            target: &$crate::logging::log_target(module_path!()),
+            $crate::logging::log_level!($level),
+            "{line}"
+        );
+    };
+}
 
-#[cfg(all(feature = "log", feature = "tracing"))]
-compile_error!("'log' and 'tracing' features are mutually exclusive");
+#[cfg(feature = "tracing")]
+macro_rules! log_event_backend {
+    ($level:ident, $message:literal, $fields:ident, $suppressed:ident $(, $key:ident)*) => {
+        if $suppressed == 0 {
+            tracing::event!(
+                $crate::logging::tracing_level!($level),
+                $($key = %$crate::logging::Redacted::new(stringify!($key), $key),)*
+                $message
+            );
+        } else {
+            tracing::event!(
+                $crate::logging::tracing_level!($level),
+                $($key = %$crate::logging::Redacted::new(stringify!($key), $key),)*
+                suppressed = $suppressed,
+                $message
+            );
+        }
+    };
+}
+
+// The macros of the backend, filtered by the verbosity and, with `log`, with
+// the target of the module, see `log_target`.
+macro_rules! log_debug {
+    ($($arg:tt)+) => {
+        $crate::logging::log_at!(debug, $($arg)+)
+    };
+}
+
+macro_rules! log_error {
+    ($($arg:tt)+) => {
+        $crate::logging::log_at!(error, $($arg)+)
+    };
+}
+
+#[allow(unused_macros)]
+macro_rules! log_trace {
+    ($($arg:tt)+) => {
+        $crate::logging::log_at!(trace, $($arg)+)
+    };
+}
+
+#[allow(unused_macros)]
+macro_rules! log_warn {
+    ($($arg:tt)+) => {
+        $crate::logging::log_at!(warn, $($arg)+)
+    };
+}
+
+#[cfg(feature = "log")]
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)+) => {
+        if $crate::logging::is_verbose($crate::logging::verbosity_of!($level)) {
+            log::$level!(target: &$crate::logging::log_target(module_path!()), $($arg)+)
+        }
+    };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)+) => {
+        if $crate::logging::is_verbose($crate::logging::verbosity_of!($level)) {
+            tracing::$level!($($arg)+)
+        }
+    };
+}
+
+#[cfg(feature = "no-log")]
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)+) => {
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
+}
+
+#[cfg_attr(feature = "no-log", allow(unused_macros))]
+macro_rules! verbosity_of {
+    (error) => {
+        $crate::logging::Verbosity::Error
+    };
+    (warn) => {
+        $crate::logging::Verbosity::Warn
+    };
+    (info) => {
+        $crate::logging::Verbosity::Info
+    };
+    (debug) => {
+        $crate::logging::Verbosity::Debug
+    };
+    (trace) => {
+        $crate::logging::Verbosity::Trace
+    };
+}
+
+#[cfg(feature = "log")]
+macro_rules! log_level {
+    (error) => {
+        log::Level::Error
+    };
+    (warn) => {
+        log::Level::Warn
+    };
+    (info) => {
+        log::Level::Info
+    };
+    (debug) => {
+        log::Level::Debug
+    };
+    (trace) => {
+        log::Level::Trace
+    };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! tracing_level {
+    (error) => {
+        tracing::Level::ERROR
+    };
+    (warn) => {
+        tracing::Level::WARN
+    };
+    (info) => {
+        tracing::Level::INFO
+    };
+    (debug) => {
+        tracing::Level::DEBUG
+    };
+    (trace) => {
+        tracing::Level::TRACE
+    };
+}
+
+#[cfg(feature = "log")]
+pub(crate) use log_level;
+#[cfg(feature = "tracing")]
+pub(crate) use tracing_level;
+// Not all of the macros are used with every set of features.
+#[allow(unused_imports)]
+pub(crate) use {
+    log_at, log_debug as debug, log_error as error, log_event, log_trace as trace,
+    log_warn as warn, verbosity_of,
+};
+#[cfg(not(feature = "no-log"))]
+pub(crate) use {log_event_backend, log_event_enabled};
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use {super::*, std::net::SocketAddr};
 
     #[test]
     fn test_logging_macros_available() {
@@ -27,5 +843,196 @@ mod tests {
         error!("Test error message");
         trace!("Test trace message");
         warn!("Test warn message");
+        let peer: SocketAddr = "127.0.0.1:8009".parse().unwrap();
+        log_event!(warn, "Test event", peer = peer, num_transactions = 3);
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn test_format_event() {
+        let fields: [(&str, &dyn Display); 2] =
+            [("peer", &"127.0.0.1:8009"), ("error", &"a \"b\"\n")];
+        assert_eq!(
+            format_event("Send failed", &fields, 0),
+            "Send failed peer=127.0.0.1:8009 error=a \"b\"\n"
+        );
+        set_log_format(LogFormat::Json);
+        let json = format_event("Send failed", &fields, 3);
+        set_log_format(LogFormat::Text);
+        assert_eq!(
+            json,
+            r#"{"message":"Send failed","peer":"127.0.0.1:8009","error":"a \"b\"\n","suppressed":"3"}"#
+        );
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["error"], "a \"b\"\n");
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn test_prefixed_target() {
+        let module_path = "solana_tpu_client_next::connection_worker";
+        assert_eq!(prefixed_target(None, module_path), module_path);
+        assert_eq!(
+            prefixed_target(Some("tpu"), module_path),
+            "tpu::connection_worker"
+        );
+        assert_eq!(
+            prefixed_target(Some("tpu"), "solana_tpu_client_next"),
+            "tpu"
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-log"))]
+    fn test_log_hook() {
+        #[derive(Default)]
+        struct Events(Mutex<Vec<String>>);
+
+        impl LogHook for Events {
+            fn on_event(&self, event: &LogEvent<'_>) {
+                // Other tests may log concurrently.
+                if event.message.starts_with("Test hook") {
+                    let fields: Vec<_> = event
+                        .fields
+                        .iter()
+                        .map(|(key, value)| format!("{key}={value}"))
+                        .collect();
+                    self.0.lock().unwrap().push(format!(
+                        "{:?} {} {}",
+                        event.level,
+                        event.message,
+                        fields.join(" ")
+                    ));
+                }
+            }
+        }
+
+        let events = Arc::new(Events::default());
+        set_log_hook(Some(events.clone()));
+        let peer: SocketAddr = "127.0.0.1:8009".parse().unwrap();
+        log_event!(warn, "Test hook event", peer = peer, num_transactions = 3);
+        set_log_hook(None);
+        log_event!(warn, "Test hook event after removal", peer = peer);
+        assert_eq!(
+            *events.0.lock().unwrap(),
+            vec!["Warn Test hook event peer=127.0.0.1:8009 num_transactions=3"]
+        );
+    }
+
+    #[test]
+    fn test_redacted() {
+        let redacted = |key, value: &'static str, enabled| {
+            Redacted::with_redaction(key, &value, enabled).to_string()
+        };
+        let peer = "127.0.0.1:8009";
+        assert_eq!(redacted("peer", peer, false), peer);
+        assert_eq!(redacted("num_transactions", peer, true), peer);
+
+        let hashed_peer = redacted("peer", peer, true);
+        assert!(hashed_peer.starts_with("redacted:"));
+        assert!(!hashed_peer.contains(peer));
+        // The same value is always hashed the same, so that the events can be
+        // correlated.
+        assert_eq!(redacted("leader", peer, true), hashed_peer);
+        assert_ne!(redacted("peer", "127.0.0.1:8010", true), hashed_peer);
+    }
+
+    #[test]
+    fn test_verbosity() {
+        assert_eq!(verbosity(), Verbosity::Trace);
+        set_verbosity(Verbosity::Warn);
+        assert_eq!(verbosity(), Verbosity::Warn);
+        assert!(is_verbose(Verbosity::Error));
+        assert!(is_verbose(Verbosity::Warn));
+        assert!(!is_verbose(Verbosity::Info));
+        assert!(!is_verbose(Verbosity::Debug));
+        set_verbosity(Verbosity::Trace);
+        assert!(is_verbose(Verbosity::Trace));
+    }
+
+    #[test]
+    fn test_log_suppressor() {
+        let window = Duration::from_secs(10);
+        let suppressor = LogSuppressor::new(window);
+        let start = Instant::now();
+        let fields =
+            |peer: &'static &'static str| -> [(&str, &dyn Display); 1] { [("peer", peer)] };
+        let peer = &"127.0.0.1:8009";
+        let other_peer = &"127.0.0.1:8010";
+        let check = |message, fields: &[(&'static str, &dyn Display)], now| {
+            suppressor.check_at(
+                Verbosity::Warn,
+                module_path!(),
+                message,
+                fields,
+                window,
+                now,
+            )
+        };
+
+        assert_eq!(check("Timed out", &fields(peer), start), Some(0));
+        // The repeats are suppressed during the window, unlike the events
+        // with another message or other field values.
+        let now = start + Duration::from_secs(1);
+        assert_eq!(check("Timed out", &fields(peer), now), None);
+        assert_eq!(check("Timed out", &fields(peer), now), None);
+        assert_eq!(check("Reset", &fields(peer), now), Some(0));
+        assert_eq!(check("Timed out", &fields(other_peer), now), Some(0));
+        // The first repeat after the window reports the suppressed ones.
+        let now = start + window;
+        assert_eq!(check("Timed out", &fields(peer), now), Some(2));
+        assert_eq!(check("Timed out", &fields(peer), now), None);
+
+        // Disabling the suppression returns the suppressed repeats.
+        let suppressed = suppressor.set_window(Duration::ZERO);
+        assert_eq!(suppressed.len(), 1);
+        assert_eq!(suppressed[0].message, "Timed out");
+        assert_eq!(suppressed[0].suppressed, 1);
+        assert!(suppressor.tracked.lock().unwrap().events.is_empty());
+    }
+
+    #[test]
+    fn test_log_suppressor_sweep() {
+        let window = Duration::from_secs(10);
+        let suppressor = LogSuppressor::new(window);
+        let start = suppressor.tracked.lock().unwrap().swept_at;
+        let peer = &"127.0.0.1:8009";
+        let fields: [(&str, &dyn Display); 1] = [("peer", peer)];
+        let check = |message, now| {
+            suppressor.check_at(
+                Verbosity::Warn,
+                module_path!(),
+                message,
+                &fields,
+                window,
+                now,
+            )
+        };
+
+        assert_eq!(check("Timed out", start), Some(0));
+        assert_eq!(check("Timed out", start + Duration::from_secs(1)), None);
+        assert_eq!(check("Reset", start + Duration::from_secs(5)), Some(0));
+        assert!(suppressor
+            .sweep_at(window, start + Duration::from_secs(9))
+            .is_empty());
+
+        // Once the window has expired without a repeat being logged, the
+        // sweep returns the suppressed repeats and forgets the event.
+        let now = start + window;
+        let suppressed = suppressor.sweep_at(window, now);
+        assert_eq!(suppressed.len(), 1);
+        assert_eq!(suppressed[0].message, "Timed out");
+        assert_eq!(
+            suppressed[0].fields,
+            vec![("peer", "127.0.0.1:8009".to_string())]
+        );
+        assert_eq!(suppressed[0].suppressed, 1);
+        assert_eq!(check("Timed out", now), Some(0));
+        // The events are swept at most once per window.
+        assert!(suppressor
+            .sweep_at(window, start + window * 2 - Duration::from_secs(1))
+            .is_empty());
+        assert!(suppressor.sweep_at(window, start + window * 2).is_empty());
+        assert!(suppressor.tracked.lock().unwrap().events.is_empty());
     }
 }
@@ -2,53 +2,182 @@
 //! method for [`SendTransactionStats`] which periodically reports transaction
 //! sending statistics to InfluxDB.
 use {
-    crate::SendTransactionStats,
-    solana_metrics::datapoint_info,
+    crate::{send_transaction_stats::SendTransactionStatsNonAtomic, SendTransactionStats},
+    solana_metrics::datapoint::DataPoint,
     std::{sync::Arc, time::Duration},
     tokio::{select, time::interval},
     tokio_util::sync::CancellationToken,
 };
 
+/// Configuration of [`SendTransactionStats::report_to_influxdb_with_config`].
+///
+/// Schedulers running in the same process should use different `name`s, so
+/// that their datapoints are not mixed up.
+#[derive(Debug, Clone)]
+pub struct MetricsReportConfig {
+    /// Name of the datapoint.
+    pub name: &'static str,
+    pub reporting_interval: Duration,
+    pub fields: MetricsFields,
+    /// Tags attached to every datapoint, e.g. the cluster or the region, so
+    /// that the data of a fleet can be sliced.
+    pub tags: Vec<(&'static str, String)>,
+}
+
+/// Fields included in the reported datapoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MetricsFields {
+    /// The errors aggregated into `connect_error`, `connection_error` and
+    /// `write_error`, along with `successfully_sent`.
+    #[default]
+    Compact,
+    /// The aggregated errors along with all the counters.
+    All,
+    /// Only the listed fields, out of the ones reported with
+    /// [`MetricsFields::All`].
+    Only(Vec<&'static str>),
+}
+
 impl SendTransactionStats {
     /// Report the statistics to influxdb in a compact form.
-    #[allow(clippy::arithmetic_side_effects)]
     pub async fn report_to_influxdb(
         self: Arc<Self>,
         name: &'static str,
         reporting_interval: Duration,
         cancel: CancellationToken,
     ) {
-        let mut interval = interval(reporting_interval);
+        let config = MetricsReportConfig {
+            name,
+            reporting_interval,
+            fields: MetricsFields::Compact,
+            tags: Vec::new(),
+        };
+        self.report_to_influxdb_with_config(config, cancel).await
+    }
+
+    /// Report the statistics to influxdb as a datapoint configured by
+    /// `config`.
+    pub async fn report_to_influxdb_with_config(
+        self: Arc<Self>,
+        config: MetricsReportConfig,
+        cancel: CancellationToken,
+    ) {
+        let mut interval = interval(config.reporting_interval);
         let stats = self.clone();
         loop {
             select! {
-                    _ = interval.tick() => {
-                    let view = stats.read_and_reset();
-                    let connect_error = view.connect_error_cids_exhausted
-                        + view.connect_error_other
-                        + view.connect_error_invalid_remote_address;
-                    let connection_error = view.connection_error_reset
-                        + view.connection_error_cids_exhausted
-                        + view.connection_error_timed_out
-                        + view.connection_error_application_closed
-                        + view.connection_error_transport_error
-                        + view.connection_error_version_mismatch
-                        + view.connection_error_locally_closed;
-                    let write_error = view.write_error_stopped
-                        + view.write_error_closed_stream
-                        + view.write_error_connection_lost
-                        + view.write_error_zero_rtt_rejected;
-
-                    datapoint_info!(
-                        name,
-                        ("connect_error", connect_error, i64),
-                        ("connection_error", connection_error, i64),
-                        ("successfully_sent", view.successfully_sent, i64),
-                        ("write_error", write_error, i64),
-                    );
+                _ = interval.tick() => {
+                    let view = stats.reset().counters;
+                    solana_metrics::submit(datapoint(&view, &config), log::Level::Info);
                 }
                 _ = cancel.cancelled() => break,
             }
         }
     }
 }
+
+fn datapoint(view: &SendTransactionStatsNonAtomic, config: &MetricsReportConfig) -> DataPoint {
+    let mut point = DataPoint::new(config.name);
+    for (tag, value) in &config.tags {
+        point.add_tag(tag, value);
+    }
+    for (field, value) in datapoint_fields(view, &config.fields) {
+        point.add_field_i64(field, value as i64);
+    }
+    point
+}
+
+fn datapoint_fields(
+    view: &SendTransactionStatsNonAtomic,
+    fields: &MetricsFields,
+) -> Vec<(&'static str, u64)> {
+    let aggregated_errors = [
+        ("connect_error", view.connect_errors()),
+        ("connection_error", view.connection_errors()),
+        ("write_error", view.write_errors()),
+    ];
+    match fields {
+        MetricsFields::Compact => {
+            let [connect_error, connection_error, write_error] = aggregated_errors;
+            vec![
+                connect_error,
+                connection_error,
+                ("successfully_sent", view.successfully_sent),
+                write_error,
+            ]
+        }
+        MetricsFields::All => aggregated_errors.into_iter().chain(view.fields()).collect(),
+        MetricsFields::Only(included) => aggregated_errors
+            .into_iter()
+            .chain(view.fields())
+            .filter(|(field, _)| included.contains(field))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_datapoint_fields() {
+        let view = SendTransactionStatsNonAtomic {
+            successfully_sent: 3,
+            connection_error_reset: 1,
+            connection_error_timed_out: 2,
+            ..Default::default()
+        };
+
+        let compact = datapoint_fields(&view, &MetricsFields::Compact);
+        assert_eq!(
+            compact,
+            vec![
+                ("connect_error", 0),
+                ("connection_error", 3),
+                ("successfully_sent", 3),
+                ("write_error", 0)
+            ]
+        );
+
+        let all = datapoint_fields(&view, &MetricsFields::All);
+        assert!(all.contains(&("connection_error", 3)));
+        assert!(all.contains(&("connection_error_reset", 1)));
+
+        let only = datapoint_fields(
+            &view,
+            &MetricsFields::Only(vec!["successfully_sent", "connection_error_timed_out"]),
+        );
+        assert_eq!(
+            only,
+            vec![("successfully_sent", 3), ("connection_error_timed_out", 2)]
+        );
+    }
+
+    #[test]
+    fn test_datapoint_tags() {
+        let view = SendTransactionStatsNonAtomic {
+            successfully_sent: 3,
+            ..Default::default()
+        };
+        let config = MetricsReportConfig {
+            name: "tpu-client-next",
+            reporting_interval: Duration::from_secs(1),
+            fields: MetricsFields::Only(vec!["successfully_sent"]),
+            tags: vec![
+                ("cluster", "mainnet".to_string()),
+                ("region", "eu".to_string()),
+            ],
+        };
+
+        let point = datapoint(&view, &config);
+        assert_eq!(point.name, "tpu-client-next");
+        assert_eq!(
+            point.tags,
+            vec![
+                ("cluster", "mainnet".to_string()),
+                ("region", "eu".to_string())
+            ]
+        );
+        assert_eq!(point.fields.len(), 1);
+    }
+}
@@ -0,0 +1,96 @@
+//! This module defines [`NegotiatedParameters`], the summary of the transport
+//! settings in effect on the connections of a [`ConnectionWorkersScheduler`],
+//! to make its tuning reproducible and debuggable.
+//!
+//! [`ConnectionWorkersScheduler`]: crate::ConnectionWorkersScheduler
+
+use {
+    crate::{
+        send_transaction_stats::SendingIdentity,
+        workers_cache::{PeerCapabilities, WorkerSnapshot},
+    },
+    serde_derive::Serialize,
+    solana_quic_definitions::{
+        QUIC_KEEP_ALIVE, QUIC_MAX_STAKED_CONCURRENT_STREAMS, QUIC_MAX_TIMEOUT,
+        QUIC_MAX_UNSTAKED_CONCURRENT_STREAMS, QUIC_MIN_STAKED_CONCURRENT_STREAMS,
+    },
+    std::net::SocketAddr,
+    tokio::time::Duration,
+};
+
+/// [`StreamLimits`] are the numbers of concurrent streams Agave validators
+/// grant to a client depending on its class. The limit of a staked client
+/// grows with its share of the stake, from `min_staked` to `max_staked`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StreamLimits {
+    pub unstaked: usize,
+    pub min_staked: usize,
+    pub max_staked: usize,
+}
+
+impl Default for StreamLimits {
+    fn default() -> Self {
+        Self {
+            unstaked: QUIC_MAX_UNSTAKED_CONCURRENT_STREAMS,
+            min_staked: QUIC_MIN_STAKED_CONCURRENT_STREAMS,
+            max_staked: QUIC_MAX_STAKED_CONCURRENT_STREAMS,
+        }
+    }
+}
+
+/// [`NegotiatedParameters`] is published by the scheduler once its first
+/// connections are established, see
+/// [`ConnectionWorkersScheduler::negotiated_parameters`](crate::ConnectionWorkersScheduler::negotiated_parameters).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NegotiatedParameters {
+    /// The idle timeout and the keep alive interval set on the connections.
+    pub max_idle_timeout: Duration,
+    pub keep_alive_interval: Duration,
+    /// The identity the connections have been established with, which
+    /// determines the class of the client for the peers.
+    pub identity: SendingIdentity,
+    pub stream_limits: StreamLimits,
+    /// The parameters detected on each connection, sorted by peer.
+    pub peers: Vec<(SocketAddr, PeerCapabilities)>,
+}
+
+impl NegotiatedParameters {
+    /// Summarizes the connected workers of `snapshot`, returns `None` if none
+    /// of them has connected yet.
+    pub(crate) fn from_workers(
+        identity: SendingIdentity,
+        snapshot: Vec<WorkerSnapshot>,
+    ) -> Option<Self> {
+        let peers: Vec<_> = snapshot
+            .into_iter()
+            .filter_map(|worker| Some((worker.peer, worker.capabilities?)))
+            .collect();
+        if peers.is_empty() {
+            return None;
+        }
+        Some(Self {
+            max_idle_timeout: QUIC_MAX_TIMEOUT,
+            keep_alive_interval: QUIC_KEEP_ALIVE,
+            identity,
+            stream_limits: StreamLimits::default(),
+            peers,
+        })
+    }
+
+    /// Returns the smallest MTU in use among the connections.
+    pub fn min_mtu(&self) -> Option<u16> {
+        self.peers
+            .iter()
+            .map(|(_, capabilities)| capabilities.mtu)
+            .min()
+    }
+
+    /// Returns the peers which have blocked the client on their stream limit,
+    /// which likely treat it as unstaked.
+    pub fn stream_limited_peers(&self) -> impl Iterator<Item = &SocketAddr> {
+        self.peers
+            .iter()
+            .filter(|(_, capabilities)| capabilities.stream_limit_reached)
+            .map(|(peer, _)| peer)
+    }
+}
@@ -0,0 +1,9 @@
+//! The async clients of this crate, mirroring the layout of `solana-client`.
+//! The blocking counterparts are in the top-level modules of the same name.
+//!
+//! The [`ConnectionWorkersScheduler`], on which these clients are built, is
+//! async as well.
+//!
+//! [`ConnectionWorkersScheduler`]: crate::ConnectionWorkersScheduler
+
+pub mod tpu_client;
@@ -0,0 +1,349 @@
+//! This module provides the async [`TpuClientNext`], which sends transactions
+//! to the upcoming leaders and waits for their confirmation, like the legacy
+//! `TpuClient` does.
+
+use {
+    crate::{
+        connection_workers_scheduler::{
+            ConnectionWorkersSchedulerConfig, RpcScheduler, StakeIdentity,
+        },
+        leader_updater::LeaderUpdaterError,
+        transaction_batch::{TransactionBatch, TransactionBatchError},
+        ConnectionWorkersScheduler, ConnectionWorkersSchedulerError, SendTransactionStats,
+    },
+    solana_rpc_client::nonblocking::rpc_client::RpcClient,
+    solana_rpc_client_api::{
+        client_error::Error as ClientError, request::MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS,
+    },
+    solana_transaction::versioned::VersionedTransaction,
+    solana_transaction_error::TransactionError,
+    std::{collections::HashMap, sync::Arc, time::Duration},
+    thiserror::Error,
+    tokio::{
+        sync::{mpsc, watch},
+        task::JoinHandle,
+        time::sleep,
+    },
+    tokio_util::sync::CancellationToken,
+};
+
+/// Default interval between the submissions of the unconfirmed transactions
+/// by [`TpuClientNext::send_and_confirm_transactions`].
+pub const DEFAULT_RESEND_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What happened to a transaction sent with
+/// [`TpuClientNext::send_and_confirm_transactions`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransactionOutcome {
+    /// The transaction was executed successfully and reached the commitment
+    /// of the RPC client.
+    Confirmed,
+    /// The transaction was executed with this error.
+    Failed(TransactionError),
+    /// The blockhash of the transaction expired before it was confirmed, so
+    /// it can't be executed anymore.
+    Expired,
+}
+
+/// Errors of [`TpuClientNext`].
+#[derive(Debug, Error)]
+pub enum TpuClientNextError {
+    #[error(transparent)]
+    RpcError(#[from] Box<ClientError>),
+    #[error(transparent)]
+    TransactionBatchError(#[from] TransactionBatchError),
+    #[error(transparent)]
+    LeaderUpdaterError(#[from] LeaderUpdaterError),
+    #[error("The scheduler has stopped.")]
+    SchedulerStopped,
+    #[error("Failed to build the runtime: {0}")]
+    RuntimeError(#[from] std::io::Error),
+}
+
+impl From<ClientError> for TpuClientNextError {
+    fn from(err: ClientError) -> Self {
+        Self::RpcError(Box::new(err))
+    }
+}
+
+type SchedulerHandle =
+    JoinHandle<Result<Arc<SendTransactionStats>, ConnectionWorkersSchedulerError>>;
+
+/// [`TpuClientNext`] runs a [`ConnectionWorkersScheduler`] tracking the
+/// leaders with the RPC, and provides
+/// [`TpuClientNext::send_and_confirm_transactions`] on top of it, which
+/// resubmits the transactions until they are confirmed or expire.
+///
+/// The clients needing more control over the scheduler can create it with
+/// [`ConnectionWorkersScheduler::from_rpc`] and pass it to
+/// [`TpuClientNext::from_scheduler`].
+pub struct TpuClientNext {
+    rpc_client: Arc<RpcClient>,
+    transaction_sender: mpsc::Sender<TransactionBatch>,
+    update_identity_sender: watch::Sender<Option<StakeIdentity>>,
+    stats: Arc<SendTransactionStats>,
+    cancel: CancellationToken,
+    scheduler_handle: SchedulerHandle,
+    resend_interval: Duration,
+}
+
+impl TpuClientNext {
+    /// Creates a client sending to the leaders tracked with `rpc_client`, and
+    /// starts its scheduler with `config`.
+    pub async fn new(
+        rpc_client: Arc<RpcClient>,
+        config: ConnectionWorkersSchedulerConfig,
+    ) -> Result<Self, TpuClientNextError> {
+        let rpc_scheduler =
+            ConnectionWorkersScheduler::from_rpc(rpc_client.clone(), config).await?;
+        Ok(Self::from_scheduler(rpc_client, rpc_scheduler))
+    }
+
+    /// Creates a client which starts `rpc_scheduler` and checks the status of
+    /// the transactions with `rpc_client`.
+    pub fn from_scheduler(rpc_client: Arc<RpcClient>, rpc_scheduler: RpcScheduler) -> Self {
+        let RpcScheduler {
+            scheduler,
+            config,
+            transaction_sender,
+            update_identity_sender,
+            cancel,
+        } = rpc_scheduler;
+        let stats = scheduler.get_stats();
+        let scheduler_handle = tokio::spawn(scheduler.run(config));
+        Self {
+            rpc_client,
+            transaction_sender,
+            update_identity_sender,
+            stats,
+            cancel,
+            scheduler_handle,
+            resend_interval: DEFAULT_RESEND_INTERVAL,
+        }
+    }
+
+    /// Sets how often the unconfirmed transactions are submitted again by
+    /// [`TpuClientNext::send_and_confirm_transactions`].
+    pub fn with_resend_interval(mut self, resend_interval: Duration) -> Self {
+        self.resend_interval = resend_interval;
+        self
+    }
+
+    /// Returns the statistics of the scheduler.
+    pub fn stats(&self) -> &Arc<SendTransactionStats> {
+        &self.stats
+    }
+
+    /// Replaces the identity used to connect to the leaders, see
+    /// [`StakeIdentity`].
+    pub fn update_identity(&self, identity: Option<StakeIdentity>) {
+        self.update_identity_sender.send_replace(identity);
+    }
+
+    /// Sends `transactions` to the upcoming leaders once, without waiting for
+    /// their confirmation.
+    pub async fn send_transactions(
+        &self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<(), TpuClientNextError> {
+        let batch = TransactionBatch::try_from_transactions(transactions)?;
+        self.transaction_sender
+            .send(batch)
+            .await
+            .map_err(|_| TpuClientNextError::SchedulerStopped)
+    }
+
+    /// Sends `transactions` to the upcoming leaders and submits the
+    /// unconfirmed ones again every resend interval, until all of them are
+    /// confirmed with the commitment of the RPC client or their blockhash
+    /// has expired.
+    ///
+    /// Returns the outcome of each transaction, in the order of
+    /// `transactions`.
+    pub async fn send_and_confirm_transactions(
+        &self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<Vec<TransactionOutcome>, TpuClientNextError> {
+        let mut outcomes: Vec<Option<TransactionOutcome>> = vec![None; transactions.len()];
+        let mut pending: Vec<usize> = (0..transactions.len()).collect();
+        while !pending.is_empty() {
+            let to_send: Vec<VersionedTransaction> = pending
+                .iter()
+                .map(|index| transactions[*index].clone())
+                .collect();
+            self.send_transactions(&to_send).await?;
+            sleep(self.resend_interval).await;
+
+            for chunk in pending.chunks(MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS) {
+                let signatures: Vec<_> = chunk
+                    .iter()
+                    .map(|index| transactions[*index].signatures[0])
+                    .collect();
+                let statuses = self
+                    .rpc_client
+                    .get_signature_statuses(&signatures)
+                    .await?
+                    .value;
+                for (index, status) in chunk.iter().zip(statuses) {
+                    let Some(status) = status else {
+                        continue;
+                    };
+                    if !status.satisfies_commitment(self.rpc_client.commitment()) {
+                        continue;
+                    }
+                    outcomes[*index] = Some(match status.err {
+                        None => TransactionOutcome::Confirmed,
+                        Some(err) => TransactionOutcome::Failed(err),
+                    });
+                }
+            }
+            pending.retain(|index| outcomes[*index].is_none());
+
+            // The transactions which are not confirmed yet can still land
+            // while their blockhash is valid.
+            let mut is_blockhash_valid = HashMap::new();
+            for index in &pending {
+                let blockhash = transactions[*index].message.recent_blockhash();
+                let is_valid = match is_blockhash_valid.get(blockhash) {
+                    Some(is_valid) => *is_valid,
+                    None => {
+                        let is_valid = self
+                            .rpc_client
+                            .is_blockhash_valid(blockhash, self.rpc_client.commitment())
+                            .await?;
+                        is_blockhash_valid.insert(*blockhash, is_valid);
+                        is_valid
+                    }
+                };
+                if !is_valid {
+                    outcomes[*index] = Some(TransactionOutcome::Expired);
+                }
+            }
+            pending.retain(|index| outcomes[*index].is_none());
+        }
+        Ok(outcomes.into_iter().flatten().collect())
+    }
+
+    /// Stops the scheduler and returns its statistics.
+    pub async fn shutdown(self) -> Result<Arc<SendTransactionStats>, TpuClientNextError> {
+        self.cancel.cancel();
+        match self.scheduler_handle.await {
+            Ok(Ok(stats)) => Ok(stats),
+            _ => Err(TpuClientNextError::SchedulerStopped),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::{
+            connection_workers_scheduler::{BindTarget, Fanout},
+            leader_updater::create_leader_updater,
+        },
+        serde_json::json,
+        solana_keypair::Keypair,
+        solana_rpc_client_api::request::RpcRequest,
+        solana_signer::Signer,
+        solana_system_transaction::transfer,
+        std::net::{IpAddr, Ipv4Addr, SocketAddr},
+    };
+
+    /// Creates a scheduler pinned to a leader on which nothing listens, so
+    /// that the outcomes only depend on the mocked RPC.
+    pub(crate) async fn test_scheduler(rpc_client: Arc<RpcClient>) -> RpcScheduler {
+        let leader = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+        let leader_updater = create_leader_updater(rpc_client, String::new(), Some(leader))
+            .await
+            .unwrap();
+        let (transaction_sender, transaction_receiver) = mpsc::channel(16);
+        let (update_identity_sender, update_identity_receiver) = watch::channel(None);
+        let cancel = CancellationToken::new();
+        let scheduler = ConnectionWorkersScheduler::new(
+            leader_updater,
+            transaction_receiver,
+            update_identity_receiver,
+            cancel.clone(),
+        );
+        let config = ConnectionWorkersSchedulerConfig {
+            bind: BindTarget::Address(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)),
+            leaders_fanout: Fanout {
+                send: 1,
+                connect: 1,
+            },
+            ..ConnectionWorkersSchedulerConfig::default()
+        };
+        RpcScheduler {
+            scheduler,
+            config,
+            transaction_sender,
+            update_identity_sender,
+            cancel,
+        }
+    }
+
+    async fn test_client(rpc_client: RpcClient) -> TpuClientNext {
+        let rpc_client = Arc::new(rpc_client);
+        let rpc_scheduler = test_scheduler(rpc_client.clone()).await;
+        TpuClientNext::from_scheduler(rpc_client, rpc_scheduler)
+            .with_resend_interval(Duration::from_millis(10))
+    }
+
+    pub(crate) fn transactions() -> Vec<VersionedTransaction> {
+        let payer = Keypair::new();
+        (1..=3)
+            .map(|lamports| {
+                transfer(
+                    &payer,
+                    &Keypair::new().pubkey(),
+                    lamports,
+                    Default::default(),
+                )
+                .into()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_send_and_confirm_transactions() {
+        let client = test_client(RpcClient::new_mock("succeeds".to_string())).await;
+        assert_eq!(
+            client
+                .send_and_confirm_transactions(&transactions())
+                .await
+                .unwrap(),
+            vec![TransactionOutcome::Confirmed; 3]
+        );
+        client.shutdown().await.unwrap();
+
+        let client = test_client(RpcClient::new_mock("account_in_use".to_string())).await;
+        assert_eq!(
+            client
+                .send_and_confirm_transactions(&transactions())
+                .await
+                .unwrap(),
+            vec![TransactionOutcome::Failed(TransactionError::AccountInUse); 3]
+        );
+        client.shutdown().await.unwrap();
+
+        let mocks = [(
+            RpcRequest::IsBlockhashValid,
+            json!({"context": {"slot": 1}, "value": false}),
+        )]
+        .into();
+        let client = test_client(RpcClient::new_mock_with_mocks(
+            "sig_not_found".to_string(),
+            mocks,
+        ))
+        .await;
+        assert_eq!(
+            client
+                .send_and_confirm_transactions(&transactions())
+                .await
+                .unwrap(),
+            vec![TransactionOutcome::Expired; 3]
+        );
+        client.shutdown().await.unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+//! If `otel` feature is activated, this module provides OpenTelemetry
+//! instrumentation on top of the `tracing` crate. It doesn't export anything
+//! by itself: it emits `tracing` spans, and events whose
+//! `monotonic_counter.*` and `histogram.*` fields follow the conventions of
+//! `tracing-opentelemetry`. The application turns them into OpenTelemetry
+//! traces and metrics, and exports them e.g. over OTLP, by installing the
+//! `OpenTelemetryLayer` and the `MetricsLayer` of `tracing-opentelemetry` in
+//! its subscriber.
+//!
+//! The latency histograms are recorded once per batch sent to a leader, and
+//! only when the [`Verbosity`] is [`Verbosity::Trace`].
+//!
+//! Every [`TransactionBatch`] captures the span which is current when the
+//! batch is created, typically the span of the API request which has produced
+//! the transactions. The span of sending the batch to a leader is its child, so
+//! the distributed trace follows the transactions up to the TPU.
+//!
+//! [`TransactionBatch`]: crate::transaction_batch::TransactionBatch
+//! [`Verbosity`]: crate::logging::Verbosity
+//! [`Verbosity::Trace`]: crate::logging::Verbosity::Trace
+use {
+    crate::{
+        logging::{is_verbose, Redacted, Verbosity},
+        SendTransactionStats,
+    },
+    std::{net::SocketAddr, sync::Arc, time::Duration},
+    tokio::{select, time::interval},
+    tokio_util::sync::CancellationToken,
+    tracing::{field::Empty, info, info_span, trace, Span},
+};
+
+/// Span which was current when the batch was created.
+#[derive(Clone, Debug)]
+pub(crate) struct BatchSpan(Span);
+
+impl BatchSpan {
+    pub(crate) fn current() -> Self {
+        Self(Span::current())
+    }
+}
+
+// The batches are equal if they were created in the same span, e.g. the
+// clones of a batch, or outside of any enabled span.
+impl PartialEq for BatchSpan {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id() == other.0.id()
+    }
+}
+
+/// Creates the span of sending a batch to `peer`, see [`record_batch_sent`].
+pub(crate) fn send_batch_span(parent: &BatchSpan, peer: &SocketAddr, batch_id: u64) -> Span {
+    info_span!(
+        parent: &parent.0,
+        "send_transaction_batch",
+        otel.kind = "client",
+        otel.status_code = Empty,
+        peer = %Redacted::new("peer", peer),
+        batch_id,
+        num_transactions = Empty,
+        num_sent = Empty,
+    )
+}
+
+/// Records the outcome of sending the batch in its `span`.
+pub(crate) fn record_batch_sent(span: &Span, num_transactions: usize, num_sent: usize) {
+    span.record("num_transactions", num_transactions);
+    span.record("num_sent", num_sent);
+    let status_code = if num_sent == num_transactions {
+        "ok"
+    } else {
+        "error"
+    };
+    span.record("otel.status_code", status_code);
+}
+
+/// Records the latencies of the last transaction sent from a batch into
+/// OpenTelemetry histograms, once per batch to keep the hot path cheap.
+pub(crate) fn record_batch_latency(sending: Duration, end_to_end: Duration) {
+    if !is_verbose(Verbosity::Trace) {
+        return;
+    }
+    trace!(
+        histogram.tpu_client_next.sending_latency_us = sending.as_micros() as u64,
+        histogram.tpu_client_next.end_to_end_latency_us = end_to_end.as_micros() as u64,
+    );
+}
+
+impl SendTransactionStats {
+    /// Reports the increments of the main counters as OpenTelemetry counters.
+    ///
+    /// Unlike `report_to_influxdb`, the stats are not reset, so that both can
+    /// be used at the same time.
+    pub async fn report_to_otel(
+        self: Arc<Self>,
+        reporting_interval: Duration,
+        cancel: CancellationToken,
+    ) {
+        let mut interval = interval(reporting_interval);
+        let mut last = self.to_non_atomic();
+        loop {
+            select! {
+                _ = interval.tick() => {
+                    let view = self.to_non_atomic();
+                    // The counters only decrease if the stats are reset
+                    // elsewhere, in that case the whole value is reported.
+                    let delta = |value: u64, last: u64| value.checked_sub(last).unwrap_or(value);
+
+                    info!(
+                        monotonic_counter.tpu_client_next.successfully_sent =
+                            delta(view.successfully_sent, last.successfully_sent),
+                        monotonic_counter.tpu_client_next.bytes_sent =
+                            delta(view.bytes_sent, last.bytes_sent),
+                        monotonic_counter.tpu_client_next.connect_error =
+                            delta(view.connect_errors(), last.connect_errors()),
+                        monotonic_counter.tpu_client_next.connection_error =
+                            delta(view.connection_errors(), last.connection_errors()),
+                        monotonic_counter.tpu_client_next.write_error =
+                            delta(view.write_errors(), last.write_errors()),
+                        monotonic_counter.tpu_client_next.leader_without_tpu_socket =
+                            delta(view.leader_without_tpu_socket, last.leader_without_tpu_socket),
+                        monotonic_counter.tpu_client_next.workers_evicted =
+                            delta(view.workers_evicted, last.workers_evicted),
+                        monotonic_counter.tpu_client_next.workers_reinserted =
+                            delta(view.workers_reinserted, last.workers_reinserted),
+                        monotonic_counter.tpu_client_next.workers_recycled =
+                            delta(view.workers_recycled, last.workers_recycled),
+                    );
+                    last = view;
+                }
+                _ = cancel.cancelled() => break,
+            }
+        }
+    }
+}
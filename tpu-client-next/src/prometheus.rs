@@ -0,0 +1,241 @@
+//! If `prometheus` feature is activated, this module provides methods encoding
+//! [`SendTransactionStats`] and the state of the workers cache in the
+//! Prometheus text exposition format, so that they can be served by the scrape
+//! endpoint of the application.
+use {
+    crate::{
+        send_transaction_stats::{
+            IdentitySendStatsNonAtomic, LatencyHistogramSnapshot, LeaderSendStatsNonAtomic,
+        },
+        workers_cache::WorkerSnapshot,
+        SendTransactionStats,
+    },
+    std::fmt::Write,
+};
+
+/// Quantiles of the latency histograms reported as Prometheus summaries.
+const LATENCY_QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 0.999];
+
+type WorkerGauge = fn(&WorkerSnapshot) -> f64;
+
+impl SendTransactionStats {
+    /// Encodes the counters, the per-leader and per-identity counters, the QUIC
+    /// errors, the queue depths and the latency histograms, with the metric
+    /// names prefixed by `namespace`.
+    ///
+    /// Prometheus expects counters to only grow, so the stats exported this
+    /// way must not be reset with [`SendTransactionStats::read_and_reset`].
+    pub fn encode_prometheus(&self, namespace: &str) -> String {
+        let mut out = String::new();
+        for (field, value) in self.to_non_atomic().fields() {
+            let name = format!("{namespace}_{field}_total");
+            write_type(&mut out, &name, "counter");
+            write_sample(&mut out, &name, &[], value as f64);
+        }
+
+        let leaders: Vec<_> = self
+            .leaders
+            .to_non_atomic()
+            .into_iter()
+            .map(|(leader, stats)| (leader.to_string(), stats.fields()))
+            .collect();
+        write_labeled_counters(
+            &mut out,
+            &format!("{namespace}_leader"),
+            "leader",
+            LeaderSendStatsNonAtomic::default().fields(),
+            leaders,
+        );
+
+        let identities: Vec<_> = self
+            .identities
+            .to_non_atomic()
+            .into_iter()
+            .map(|(identity, stats)| (identity.to_string(), stats.fields()))
+            .collect();
+        write_labeled_counters(
+            &mut out,
+            &format!("{namespace}_identity"),
+            "identity",
+            IdentitySendStatsNonAtomic::default().fields(),
+            identities,
+        );
+
+        let name = format!("{namespace}_quic_errors_total");
+        write_type(&mut out, &name, "counter");
+        for (code, count) in self.error_codes.to_non_atomic() {
+            write_sample(
+                &mut out,
+                &name,
+                &[("code", &code.to_string())],
+                count as f64,
+            );
+        }
+
+        let queue_depth = self.queue_depth.snapshot();
+        for (gauge, value) in [
+            ("scheduler_queue_depth", queue_depth.scheduler_queue),
+            ("worker_queues_depth", queue_depth.worker_queues),
+            (
+                "scheduler_queue_high_water_mark",
+                queue_depth.scheduler_queue_high_water_mark,
+            ),
+            (
+                "worker_queues_high_water_mark",
+                queue_depth.worker_queues_high_water_mark,
+            ),
+        ] {
+            let name = format!("{namespace}_{gauge}");
+            write_type(&mut out, &name, "gauge");
+            write_sample(&mut out, &name, &[], value as f64);
+        }
+
+        let name = format!("{namespace}_latency_seconds");
+        write_type(&mut out, &name, "summary");
+        let latency = &self.latency;
+        for (stage, histogram) in [
+            ("queueing", &latency.queueing),
+            ("sending", &latency.sending),
+            ("end_to_end", &latency.end_to_end),
+        ] {
+            write_summary(&mut out, &name, stage, &histogram.snapshot());
+        }
+        out
+    }
+}
+
+/// Encodes the workers cache `snapshot`, see
+/// [`ConnectionWorkersScheduler::with_workers_snapshot_sender`], with the
+/// metric names prefixed by `namespace`.
+///
+/// [`ConnectionWorkersScheduler::with_workers_snapshot_sender`]: crate::ConnectionWorkersScheduler::with_workers_snapshot_sender
+pub fn encode_workers_snapshot(namespace: &str, snapshot: &[WorkerSnapshot]) -> String {
+    let mut out = String::new();
+    let name = format!("{namespace}_workers");
+    write_type(&mut out, &name, "gauge");
+    write_sample(&mut out, &name, &[], snapshot.len() as f64);
+
+    let gauges: [(&str, WorkerGauge); 4] = [
+        ("queued_batches", |worker| worker.queued_batches as f64),
+        ("age_seconds", |worker| worker.age.as_secs_f64()),
+        ("pinned", |worker| f64::from(u8::from(worker.is_pinned))),
+        ("stopped", |worker| f64::from(u8::from(worker.is_stopped))),
+    ];
+    for (gauge, value) in gauges {
+        let name = format!("{namespace}_worker_{gauge}");
+        write_type(&mut out, &name, "gauge");
+        for worker in snapshot {
+            let peer = worker.peer.to_string();
+            write_sample(&mut out, &name, &[("peer", &peer)], value(worker));
+        }
+    }
+    out
+}
+
+fn write_type(out: &mut String, name: &str, metric_type: &str) {
+    // Writing to a `String` never fails.
+    let _ = writeln!(out, "# TYPE {name} {metric_type}");
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    let _ = write!(out, "{name}");
+    if !labels.is_empty() {
+        let labels: Vec<_> = labels
+            .iter()
+            .map(|(label, value)| format!("{label}=\"{value}\""))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+/// Writes one counter per field of `fields`, with a sample per entry of
+/// `rows` labeled by `label`.
+fn write_labeled_counters(
+    out: &mut String,
+    prefix: &str,
+    label: &str,
+    fields: Vec<(&str, u64)>,
+    mut rows: Vec<(String, Vec<(&str, u64)>)>,
+) {
+    rows.sort_unstable();
+    for (index, (field, _)) in fields.into_iter().enumerate() {
+        let name = format!("{prefix}_{field}_total");
+        write_type(out, &name, "counter");
+        for (value, fields) in &rows {
+            write_sample(out, &name, &[(label, value)], fields[index].1 as f64);
+        }
+    }
+}
+
+fn write_summary(out: &mut String, name: &str, stage: &str, snapshot: &LatencyHistogramSnapshot) {
+    for quantile in LATENCY_QUANTILES {
+        let Some(latency) = snapshot.percentile(quantile * 100.0) else {
+            break;
+        };
+        let quantile = quantile.to_string();
+        write_sample(
+            out,
+            name,
+            &[("stage", stage), ("quantile", &quantile)],
+            latency.as_secs_f64(),
+        );
+    }
+    write_sample(
+        out,
+        &format!("{name}_count"),
+        &[("stage", stage)],
+        snapshot.count() as f64,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::send_transaction_stats::QuicErrorCode,
+        std::{
+            net::{IpAddr, Ipv4Addr, SocketAddr},
+            sync::atomic::Ordering,
+            time::Duration,
+        },
+    };
+
+    #[test]
+    fn test_encode_prometheus() {
+        let stats = SendTransactionStats::default();
+        stats.successfully_sent.fetch_add(3, Ordering::Relaxed);
+        let leader = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8000);
+        stats
+            .leaders
+            .get_or_insert(leader)
+            .failed
+            .fetch_add(2, Ordering::Relaxed);
+        stats.latency.sending.record(Duration::from_micros(4));
+        stats.error_codes.record(QuicErrorCode::TlsAlert(42));
+        stats.queue_depth.update(3, 7);
+        stats
+            .identities
+            .current()
+            .rejected_by_leader
+            .fetch_add(1, Ordering::Relaxed);
+
+        let encoded = stats.encode_prometheus("tpu_client");
+        assert!(encoded.contains(
+            "# TYPE tpu_client_successfully_sent_total counter\ntpu_client_successfully_sent_total 3\n"
+        ));
+        assert!(encoded.contains("tpu_client_workers_evicted_total 0\n"));
+        assert!(encoded.contains("tpu_client_leader_failed_total{leader=\"127.0.0.1:8000\"} 2\n"));
+        assert!(encoded
+            .contains("tpu_client_identity_rejected_by_leader_total{identity=\"unstaked\"} 1\n"));
+        assert!(encoded.contains(
+            "# TYPE tpu_client_worker_queues_depth gauge\ntpu_client_worker_queues_depth 7\n"
+        ));
+        assert!(encoded.contains("tpu_client_scheduler_queue_high_water_mark 3\n"));
+        assert!(encoded.contains("tpu_client_quic_errors_total{code=\"tls_alert_42\"} 1\n"));
+        assert!(encoded
+            .contains("tpu_client_latency_seconds{stage=\"sending\",quantile=\"0.5\"} 0.000004\n"));
+        assert!(encoded.contains("tpu_client_latency_seconds_count{stage=\"sending\"} 1\n"));
+        assert!(encoded.contains("tpu_client_latency_seconds_count{stage=\"queueing\"} 0\n"));
+    }
+}
@@ -6,10 +6,14 @@ use {
         crypto::rustls::QuicClientConfig, default_runtime, ClientConfig, Connection, Endpoint,
         EndpointConfig, IdleTimeout, TransportConfig,
     },
+    rustls::{client::danger::ServerCertVerifier, crypto::CryptoProvider},
     solana_quic_definitions::{QUIC_KEEP_ALIVE, QUIC_MAX_TIMEOUT, QUIC_SEND_FAIRNESS},
     solana_streamer::nonblocking::quic::ALPN_TPU_PROTOCOL_ID,
-    solana_tls_utils::tls_client_config_builder,
+    solana_tls_utils::{
+        tls_client_config_builder_with_provider, tls_client_config_builder_with_verifier,
+    },
     std::sync::Arc,
+    tokio_util::bytes::Bytes,
 };
 
 pub mod error;
@@ -19,8 +23,29 @@ pub use {
     solana_tls_utils::QuicClientCertificate,
 };
 
+#[cfg(test)]
 pub(crate) fn create_client_config(client_certificate: &QuicClientCertificate) -> ClientConfig {
-    let mut crypto = tls_client_config_builder()
+    create_client_config_with_provider(
+        client_certificate,
+        Arc::new(solana_tls_utils::crypto_provider()),
+        None,
+    )
+}
+
+/// Same as [`create_client_config`], but using `provider` for the TLS
+/// handshake instead of the default one of `solana-tls-utils`. The certificate
+/// of the server is checked with `server_cert_verifier` if provided, otherwise
+/// any certificate is accepted.
+pub(crate) fn create_client_config_with_provider(
+    client_certificate: &QuicClientCertificate,
+    provider: Arc<CryptoProvider>,
+    server_cert_verifier: Option<Arc<dyn ServerCertVerifier>>,
+) -> ClientConfig {
+    let builder = match server_cert_verifier {
+        Some(verifier) => tls_client_config_builder_with_verifier(provider, verifier),
+        None => tls_client_config_builder_with_provider(provider),
+    };
+    let mut crypto = builder
         .with_client_auth_cert(
             vec![client_certificate.certificate.clone()],
             client_certificate.key.clone_key(),
@@ -66,13 +91,33 @@ pub(crate) fn create_client_endpoint(
     Ok(endpoint)
 }
 
+/// Sends `data` over a new unidirectional stream. The data is handed to quinn
+/// as it is, without copying it into the send buffer.
 pub(crate) async fn send_data_over_stream(
     connection: &Connection,
-    data: &[u8],
+    data: Bytes,
 ) -> Result<(), QuicError> {
+    #[cfg(feature = "test-utils")]
+    crate::test_utils::chaos::inject_write_fault(connection).await?;
     let mut send_stream = connection.open_uni().await?;
-    send_stream.write_all(data).await.map_err(QuicError::from)?;
+    send_stream
+        .write_chunk(data)
+        .await
+        .map_err(QuicError::from)?;
 
     // Stream will be finished when dropped. Finishing here explicitly is a noop.
     Ok(())
 }
+
+/// Same as [`send_data_over_stream`] for `data` borrowed from the caller,
+/// which quinn copies into the send buffer.
+pub(crate) async fn send_slice_over_stream(
+    connection: &Connection,
+    data: &[u8],
+) -> Result<(), QuicError> {
+    #[cfg(feature = "test-utils")]
+    crate::test_utils::chaos::inject_write_fault(connection).await?;
+    let mut send_stream = connection.open_uni().await?;
+    send_stream.write_all(data).await.map_err(QuicError::from)?;
+    Ok(())
+}
@@ -3,19 +3,62 @@
 //! events from all transactions and all leaders. Stats can be reset at
 //! any time to start a new monitoring period.
 
+pub mod batch_size_histogram;
+pub mod error_records;
+pub mod latency_histogram;
+pub mod quic_error_codes;
+pub mod rolling_stats;
+pub mod stats_observer;
+pub mod throughput;
+
 use {
     super::QuicError,
+    crate::transaction_batch::{TransactionBatch, TransactionMetadata},
     quinn::{ConnectError, ConnectionError, WriteError},
+    serde_derive::Serialize,
+    solana_pubkey::Pubkey,
+    stats_observer::ObserverSlot,
     std::{
+        collections::{BTreeMap, HashMap},
         fmt,
-        sync::atomic::{AtomicU64, Ordering},
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    },
+    tokio::{select, sync::watch, time::interval},
+    tokio_util::sync::CancellationToken,
+};
+pub use {
+    batch_size_histogram::{BatchSizeHistogram, BatchSizeHistogramSnapshot},
+    error_records::{ErrorKind, ErrorRecord, ErrorRecords, DEFAULT_MAX_ERROR_RECORDS},
+    latency_histogram::{
+        LatencyHistogram, LatencyHistogramSnapshot, LatencyPercentiles, SlidingLatencyHistogram,
+        LATENCY_WINDOW,
     },
+    quic_error_codes::{QuicErrorCode, QuicErrorCodeStats, MAX_TRACKED_ERROR_CODES},
+    rolling_stats::{RollingStats, RollingWindow, MAX_ROLLING_WINDOW},
+    stats_observer::StatsObserver,
+    throughput::{Throughput, ThroughputStats, THROUGHPUT_TIME_CONSTANT},
 };
 
+/// Default maximum number of leaders tracked by [`LeadersSendStats`].
+pub const DEFAULT_MAX_TRACKED_LEADERS: usize = 1024;
+
+/// Version of the format of [`SendTransactionStatsSnapshot`], see
+/// [`SendTransactionStatsSnapshot::version`].
+pub const SNAPSHOT_VERSION: u32 = 1;
+
 /// [`SendTransactionStats`] aggregates counters related to sending transactions.
 #[derive(Debug, Default)]
+#[non_exhaustive]
 pub struct SendTransactionStats {
     pub successfully_sent: AtomicU64,
+    /// Bytes of the transactions successfully sent. The QUIC and UDP overhead
+    /// is not included.
+    pub bytes_sent: AtomicU64,
     pub connect_error_cids_exhausted: AtomicU64,
     pub connect_error_invalid_remote_address: AtomicU64,
     pub connect_error_other: AtomicU64,
@@ -31,10 +74,988 @@ pub struct SendTransactionStats {
     pub write_error_connection_lost: AtomicU64,
     pub write_error_stopped: AtomicU64,
     pub write_error_zero_rtt_rejected: AtomicU64,
+    pub leader_without_tpu_socket: AtomicU64,
+    pub workers_evicted: AtomicU64,
+    pub evicted_worker_queued_batches: AtomicU64,
+    pub workers_reinserted: AtomicU64,
+    pub unhealthy_workers_removed: AtomicU64,
+    pub worker_shutdown_stragglers: AtomicU64,
+    pub workers_recycled: AtomicU64,
+    /// Per-leader breakdown of the counters, which is not reset together with
+    /// the counters above.
+    pub leaders: LeadersSendStats,
+    /// Per-identity breakdown of the transactions sent and the errors, which
+    /// is not reset together with the counters above.
+    pub identities: IdentitiesSendStats,
+    /// Breakdown of the connection and write errors by QUIC error, which is
+    /// reset by [`SendTransactionStats::reset`].
+    pub error_codes: QuicErrorCodeStats,
+    /// The last errors along with the leader they happened with, which are
+    /// neither part of the snapshots nor reset by
+    /// [`SendTransactionStats::reset`].
+    pub error_records: ErrorRecords,
+    /// Latencies of the transactions going through the pipeline, which are
+    /// reset separately from the counters above.
+    pub latency: PipelineLatencyStats,
+    /// Transactions sent and errors over the last few minutes.
+    pub rolling: RollingStats,
+    /// Exponentially weighted rates of the transactions and batches sent.
+    pub throughput: ThroughputStats,
+    /// Sizes of the batches handled by the workers, which are reset by
+    /// [`SendTransactionStats::reset`].
+    pub batch_sizes: BatchSizeStats,
+    /// Reconnections of the workers, which are reset by
+    /// [`SendTransactionStats::reset`].
+    pub retries: RetryStats,
+    /// Transactions dropped without being sent by [`DropReason`], which are
+    /// reset by [`SendTransactionStats::reset`].
+    pub dropped: DroppedTransactionStats,
+    /// Batches waiting to be sent, whose high water marks are reset by
+    /// [`SendTransactionStats::reset`].
+    pub queue_depth: QueueDepthStats,
+    /// The number of times the stats have been reset with
+    /// [`SendTransactionStats::reset`].
+    epoch: AtomicU64,
+    observer: ObserverSlot,
+}
+
+/// Owned copy of [`SendTransactionStats`], see
+/// [`SendTransactionStats::snapshot`].
+///
+/// It serializes to a stable shape, which is also the one of
+/// [`SendTransactionStats`]:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "epoch": 0,
+///   "counters": { "successfully_sent": 10, "connect_error_cids_exhausted": 0, ... },
+///   "leaders": { "127.0.0.1:8009": { "successfully_sent": 10, "failed": 0, ... } },
+///   "identities": { "unstaked": { "successfully_sent": 10, "rejected_by_leader": 0, ... } },
+///   "error_codes": { "application_close_1": 2, "tls_alert_42": 1 },
+///   "latency": {
+///     "queueing": { "count": 10, "p50_us": 15, "p90_us": 23, "p99_us": 23, "max_us": 23 },
+///     ...
+///   },
+///   "throughput": { "transactions_per_second": 640.0, "batches_per_second": 10.0 },
+///   "batch_sizes": {
+///     "transactions": { "count": 10, "mean": 64.0, "p50": 127, "p90": 127, "p99": 127, "max": 127 },
+///     ...
+///   },
+///   "retries": { "reconnect_attempts": 2, "reconnected_at_first_attempt": 1, ... },
+///   "dropped": { "overflow": 0, "deadline": 64, ... },
+///   "queue_depth": { "scheduler_queue": 3, "worker_queues": 12, ... }
+/// }
+/// ```
+///
+/// New fields may be added within a version, while [`SNAPSHOT_VERSION`] is
+/// bumped whenever existing ones are renamed, removed or change meaning. The
+/// struct is non-exhaustive for the same reason, the fields can be read
+/// directly or with the accessors.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct SendTransactionStatsSnapshot {
+    /// The format of the snapshot, which is [`SNAPSHOT_VERSION`] for the
+    /// snapshots taken by this version of the crate.
+    pub version: u32,
+    /// The epoch of the stats when the snapshot was taken, see
+    /// [`SendTransactionStats::epoch`].
+    pub epoch: u64,
+    pub counters: SendTransactionStatsNonAtomic,
+    pub leaders: BTreeMap<SocketAddr, LeaderSendStatsNonAtomic>,
+    pub identities: BTreeMap<SendingIdentity, IdentitySendStatsNonAtomic>,
+    pub error_codes: BTreeMap<QuicErrorCode, u64>,
+    pub latency: PipelineLatencySnapshot,
+    /// The rates when the snapshot was taken, they are not diffed.
+    pub throughput: Throughput,
+    pub batch_sizes: BatchSizeSnapshot,
+    pub retries: RetryStatsNonAtomic,
+    pub dropped: DroppedTransactionStatsNonAtomic,
+    /// The gauges when the snapshot was taken, they are not diffed.
+    pub queue_depth: QueueDepthSnapshot,
+}
+
+/// [`BatchSizeStats`] records the size of each batch picked up by a worker,
+/// in transactions and in bytes, before any of its transactions is sent. A
+/// batch sent to several leaders is recorded once per leader.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct BatchSizeStats {
+    pub transactions: BatchSizeHistogram,
+    pub bytes: BatchSizeHistogram,
+}
+
+/// Owned copy of [`BatchSizeStats`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct BatchSizeSnapshot {
+    pub transactions: BatchSizeHistogramSnapshot,
+    pub bytes: BatchSizeHistogramSnapshot,
+}
+
+/// Owned copy of [`PipelineLatencyStats`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct PipelineLatencySnapshot {
+    pub queueing: LatencyHistogramSnapshot,
+    pub sending: LatencyHistogramSnapshot,
+    pub end_to_end: LatencyHistogramSnapshot,
+}
+
+impl SendTransactionStats {
+    /// Returns a copy of the counters, the per-leader counters and the
+    /// latency histograms, without resetting them.
+    pub fn snapshot(&self) -> SendTransactionStatsSnapshot {
+        SendTransactionStatsSnapshot {
+            version: SNAPSHOT_VERSION,
+            epoch: self.epoch(),
+            counters: self.to_non_atomic(),
+            leaders: self.leaders.to_non_atomic(),
+            identities: self.identities.to_non_atomic(),
+            error_codes: self.error_codes.to_non_atomic(),
+            latency: PipelineLatencySnapshot {
+                queueing: self.latency.queueing.snapshot(),
+                sending: self.latency.sending.snapshot(),
+                end_to_end: self.latency.end_to_end.snapshot(),
+            },
+            throughput: self.throughput.throughput(),
+            batch_sizes: BatchSizeSnapshot {
+                transactions: self.batch_sizes.transactions.snapshot(),
+                bytes: self.batch_sizes.bytes.snapshot(),
+            },
+            retries: self.retries.to_non_atomic(),
+            dropped: self.dropped.to_non_atomic(),
+            queue_depth: self.queue_depth.snapshot(),
+        }
+    }
+
+    /// Resets the counters and the histograms, returning what they
+    /// held, and starts a new epoch. Each counter is swapped atomically, so
+    /// concurrent updates are accounted either in the returned snapshot or in
+    /// the new epoch, never lost.
+    ///
+    /// The per-leader and the per-identity counters are kept, see
+    /// [`LeaderSendStats`] and [`IdentitySendStats`].
+    pub fn reset(&self) -> SendTransactionStatsSnapshot {
+        let epoch = self.epoch.fetch_add(1, Ordering::AcqRel);
+        SendTransactionStatsSnapshot {
+            version: SNAPSHOT_VERSION,
+            epoch,
+            counters: self.read_and_reset(),
+            leaders: self.leaders.to_non_atomic(),
+            identities: self.identities.to_non_atomic(),
+            error_codes: self.error_codes.read_and_reset(),
+            latency: PipelineLatencySnapshot {
+                queueing: self.latency.queueing.read_and_reset(),
+                sending: self.latency.sending.read_and_reset(),
+                end_to_end: self.latency.end_to_end.read_and_reset(),
+            },
+            throughput: self.throughput.throughput(),
+            batch_sizes: BatchSizeSnapshot {
+                transactions: self.batch_sizes.transactions.read_and_reset(),
+                bytes: self.batch_sizes.bytes.read_and_reset(),
+            },
+            retries: self.retries.read_and_reset(),
+            dropped: self.dropped.read_and_reset(),
+            queue_depth: self.queue_depth.read_and_reset_high_water_marks(),
+        }
+    }
+
+    /// Returns the number of times the stats have been reset with
+    /// [`SendTransactionStats::reset`]. Snapshots taken in different epochs
+    /// are not comparable.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    /// Installs `observer`, which is notified of the events accounted in the
+    /// stats from then on. It replaces the observer installed before, if any.
+    pub fn set_observer(&self, observer: Arc<dyn StatsObserver>) {
+        self.observer.set(observer);
+    }
+
+    pub(crate) fn observer(&self) -> Option<Arc<dyn StatsObserver>> {
+        self.observer.get()
+    }
+
+    /// Counts `num_transactions` dropped for `reason` and notifies the
+    /// observer if the drop concerns a `peer`.
+    pub(crate) fn record_dropped(
+        &self,
+        peer: Option<SocketAddr>,
+        reason: DropReason,
+        num_transactions: usize,
+    ) {
+        if num_transactions == 0 {
+            return;
+        }
+        self.dropped.record(reason, num_transactions);
+        if let (Some(peer), Some(observer)) = (peer, self.observer()) {
+            observer.on_batch_dropped(peer, reason, num_transactions);
+        }
+    }
+
+    /// Counts the transactions of `batch` dropped for `reason` and, if the
+    /// drop concerns a `peer`, notifies the observer of the batch and of each
+    /// of its transactions.
+    pub(crate) fn record_batch_dropped(
+        &self,
+        peer: Option<SocketAddr>,
+        reason: DropReason,
+        batch: &TransactionBatch,
+    ) {
+        self.record_dropped(peer, reason, batch.len());
+        if let Some(peer) = peer {
+            self.notify_transactions_dropped(peer, reason, batch.metadata());
+        }
+    }
+
+    /// Notifies the observer of the transactions carrying `metadata` dropped
+    /// for `reason` without being sent to `peer`.
+    pub(crate) fn notify_transactions_dropped(
+        &self,
+        peer: SocketAddr,
+        reason: DropReason,
+        metadata: &[TransactionMetadata],
+    ) {
+        if metadata.is_empty() {
+            return;
+        }
+        if let Some(observer) = self.observer() {
+            for metadata in metadata {
+                observer.on_transaction_dropped(peer, reason, *metadata);
+            }
+        }
+    }
+
+    /// Publishes a snapshot of the stats over `sender` every
+    /// `publishing_interval`, so that the consumers can wait for changes with
+    /// [`watch::Receiver::changed`] and [`SendTransactionStatsSnapshot::diff`]
+    /// consecutive snapshots. The stats are not reset.
+    ///
+    /// Returns once `cancel` is cancelled or all the receivers are dropped.
+    pub async fn publish_snapshots(
+        self: Arc<Self>,
+        sender: watch::Sender<SendTransactionStatsSnapshot>,
+        publishing_interval: Duration,
+        cancel: CancellationToken,
+    ) {
+        let mut interval = interval(publishing_interval);
+        loop {
+            select! {
+                _ = interval.tick() => {
+                    if sender.send(self.snapshot()).is_err() {
+                        break;
+                    }
+                }
+                _ = sender.closed() => break,
+                _ = cancel.cancelled() => break,
+            }
+        }
+    }
+
+    /// Returns the p50, p90 and p99 latencies of the transactions sent over
+    /// the last [`LATENCY_WINDOW`].
+    pub fn latency_percentiles(&self) -> RecentLatencyPercentiles {
+        RecentLatencyPercentiles {
+            sending: self.latency.recent_sending.percentiles(),
+            end_to_end: self.latency.recent_end_to_end.percentiles(),
+        }
+    }
+}
+
+// Serialized as its snapshot, see `SendTransactionStatsSnapshot`.
+impl serde::Serialize for SendTransactionStats {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.snapshot(), serializer)
+    }
+}
+
+impl Default for SendTransactionStatsSnapshot {
+    fn default() -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            epoch: 0,
+            counters: SendTransactionStatsNonAtomic::default(),
+            leaders: BTreeMap::new(),
+            identities: BTreeMap::new(),
+            error_codes: BTreeMap::new(),
+            latency: PipelineLatencySnapshot::default(),
+            throughput: Throughput::default(),
+            batch_sizes: BatchSizeSnapshot::default(),
+            retries: RetryStatsNonAtomic::default(),
+            dropped: DroppedTransactionStatsNonAtomic::default(),
+            queue_depth: QueueDepthSnapshot::default(),
+        }
+    }
+}
+
+impl SendTransactionStatsSnapshot {
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn counters(&self) -> &SendTransactionStatsNonAtomic {
+        &self.counters
+    }
+
+    pub fn leaders(&self) -> &BTreeMap<SocketAddr, LeaderSendStatsNonAtomic> {
+        &self.leaders
+    }
+
+    pub fn identities(&self) -> &BTreeMap<SendingIdentity, IdentitySendStatsNonAtomic> {
+        &self.identities
+    }
+
+    pub fn error_codes(&self) -> &BTreeMap<QuicErrorCode, u64> {
+        &self.error_codes
+    }
+
+    pub fn latency(&self) -> &PipelineLatencySnapshot {
+        &self.latency
+    }
+
+    pub fn throughput(&self) -> Throughput {
+        self.throughput
+    }
+
+    pub fn batch_sizes(&self) -> &BatchSizeSnapshot {
+        &self.batch_sizes
+    }
+
+    pub fn retries(&self) -> &RetryStatsNonAtomic {
+        &self.retries
+    }
+
+    pub fn dropped(&self) -> &DroppedTransactionStatsNonAtomic {
+        &self.dropped
+    }
+
+    pub fn queue_depth(&self) -> QueueDepthSnapshot {
+        self.queue_depth
+    }
+
+    /// Returns what has been recorded since the `older` snapshot. Leaders and
+    /// identities missing from `older` are reported with all their counters.
+    ///
+    /// If the stats have been reset in between, the counters and the latencies
+    /// of `self` are returned as they are, as they were recorded after
+    /// `older`.
+    pub fn diff(&self, older: &Self) -> Self {
+        let leaders = diff_by_key(
+            &self.leaders,
+            &older.leaders,
+            LeaderSendStatsNonAtomic::diff,
+        );
+        let identities = diff_by_key(
+            &self.identities,
+            &older.identities,
+            IdentitySendStatsNonAtomic::diff,
+        );
+        if self.epoch != older.epoch {
+            return Self {
+                leaders,
+                identities,
+                ..self.clone()
+            };
+        }
+        Self {
+            version: self.version,
+            epoch: self.epoch,
+            counters: self.counters.diff(&older.counters),
+            leaders,
+            identities,
+            error_codes: self
+                .error_codes
+                .iter()
+                .map(|(code, count)| {
+                    let older = older.error_codes.get(code).copied().unwrap_or_default();
+                    (*code, count.saturating_sub(older))
+                })
+                .collect(),
+            latency: PipelineLatencySnapshot {
+                queueing: self.latency.queueing.diff(&older.latency.queueing),
+                sending: self.latency.sending.diff(&older.latency.sending),
+                end_to_end: self.latency.end_to_end.diff(&older.latency.end_to_end),
+            },
+            throughput: self.throughput,
+            batch_sizes: BatchSizeSnapshot {
+                transactions: self
+                    .batch_sizes
+                    .transactions
+                    .diff(&older.batch_sizes.transactions),
+                bytes: self.batch_sizes.bytes.diff(&older.batch_sizes.bytes),
+            },
+            retries: self.retries.diff(&older.retries),
+            dropped: self.dropped.diff(&older.dropped),
+            queue_depth: self.queue_depth,
+        }
+    }
+}
+
+/// [`QueueDepthStats`] are gauges of the batches waiting in the queue of the
+/// scheduler and in the channels of the workers, which are updated by the
+/// scheduler whenever it picks a batch up and on health checks. When the
+/// latency spikes, they tell whether the transactions pile up before reaching
+/// the workers or in the workers.
+///
+/// The high water marks are the largest depths since the last
+/// [`SendTransactionStats::reset`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct QueueDepthStats {
+    pub scheduler_queue: AtomicU64,
+    /// Sum of the batches queued in the channels of all the workers.
+    pub worker_queues: AtomicU64,
+    pub scheduler_queue_high_water_mark: AtomicU64,
+    pub worker_queues_high_water_mark: AtomicU64,
+}
+
+/// Owned copy of [`QueueDepthStats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct QueueDepthSnapshot {
+    pub scheduler_queue: u64,
+    pub worker_queues: u64,
+    pub scheduler_queue_high_water_mark: u64,
+    pub worker_queues_high_water_mark: u64,
+}
+
+impl QueueDepthStats {
+    /// Sets the current depths, raising the high water marks if needed.
+    pub fn update(&self, scheduler_queue: usize, worker_queues: usize) {
+        let scheduler_queue = scheduler_queue as u64;
+        let worker_queues = worker_queues as u64;
+        self.scheduler_queue
+            .store(scheduler_queue, Ordering::Relaxed);
+        self.worker_queues.store(worker_queues, Ordering::Relaxed);
+        self.scheduler_queue_high_water_mark
+            .fetch_max(scheduler_queue, Ordering::Relaxed);
+        self.worker_queues_high_water_mark
+            .fetch_max(worker_queues, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> QueueDepthSnapshot {
+        QueueDepthSnapshot {
+            scheduler_queue: self.scheduler_queue.load(Ordering::Relaxed),
+            worker_queues: self.worker_queues.load(Ordering::Relaxed),
+            scheduler_queue_high_water_mark: self
+                .scheduler_queue_high_water_mark
+                .load(Ordering::Relaxed),
+            worker_queues_high_water_mark: self
+                .worker_queues_high_water_mark
+                .load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the gauges and lowers the high water marks to the current
+    /// depths.
+    fn read_and_reset_high_water_marks(&self) -> QueueDepthSnapshot {
+        let scheduler_queue = self.scheduler_queue.load(Ordering::Relaxed);
+        let worker_queues = self.worker_queues.load(Ordering::Relaxed);
+        QueueDepthSnapshot {
+            scheduler_queue,
+            worker_queues,
+            scheduler_queue_high_water_mark: self
+                .scheduler_queue_high_water_mark
+                .swap(scheduler_queue, Ordering::Relaxed),
+            worker_queues_high_water_mark: self
+                .worker_queues_high_water_mark
+                .swap(worker_queues, Ordering::Relaxed),
+        }
+    }
+}
+
+/// Diffs the values of `newer` with the ones of `older` under the same key,
+/// the values missing from `older` are returned as they are.
+fn diff_by_key<K: Copy + Ord, V: Clone>(
+    newer: &BTreeMap<K, V>,
+    older: &BTreeMap<K, V>,
+    diff: fn(&V, &V) -> V,
+) -> BTreeMap<K, V> {
+    newer
+        .iter()
+        .map(|(key, value)| {
+            let value = match older.get(key) {
+                Some(older) => diff(value, older),
+                None => value.clone(),
+            };
+            (*key, value)
+        })
+        .collect()
+}
+
+/// [`PipelineLatencyStats`] records the latency of each transaction
+/// successfully sent, split by the stage of the pipeline:
+/// * `queueing`: from the creation of its [`TransactionBatch`] until a
+///   worker picks the batch up.
+/// * `sending`: from the moment the worker picks the batch up until the
+///   transaction's stream is written and finished.
+/// * `end_to_end`: from the creation of the batch until the stream is
+///   finished.
+///
+/// `recent_sending` and `recent_end_to_end` only keep the latencies of the
+/// last [`LATENCY_WINDOW`] and are not reset, see
+/// [`SendTransactionStats::latency_percentiles`].
+///
+/// [`TransactionBatch`]: crate::transaction_batch::TransactionBatch
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct PipelineLatencyStats {
+    pub queueing: LatencyHistogram,
+    pub sending: LatencyHistogram,
+    pub end_to_end: LatencyHistogram,
+    pub recent_sending: SlidingLatencyHistogram,
+    pub recent_end_to_end: SlidingLatencyHistogram,
+}
+
+/// Percentiles of the latencies over the last [`LATENCY_WINDOW`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct RecentLatencyPercentiles {
+    pub sending: LatencyPercentiles,
+    pub end_to_end: LatencyPercentiles,
+}
+
+/// Why transactions have been dropped without being sent, see
+/// [`DroppedTransactionStats`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum DropReason {
+    /// The channel of the worker was full.
+    Overflow,
+    /// The transactions were too old to land.
+    Deadline,
+    /// The transaction is larger than what the leaders accept.
+    Oversize,
+    /// The scheduler or the worker was cancelled while the transactions were
+    /// queued.
+    Cancelled,
+    /// There was no leader to send to, or no worker for the leader.
+    NoLeader,
+    /// The connection failed while the transactions were queued or being
+    /// sent.
+    ConnectionLost,
+    /// The block height went past the last valid block height of the
+    /// blockhash of the transactions.
+    BlockhashExpired,
+}
+
+/// [`DroppedTransactionStats`] counts the transactions which have been
+/// dropped without being sent, by [`DropReason`]. A transaction dropped for
+/// several leaders is counted once per leader.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct DroppedTransactionStats {
+    pub overflow: AtomicU64,
+    pub deadline: AtomicU64,
+    pub oversize: AtomicU64,
+    pub cancelled: AtomicU64,
+    pub no_leader: AtomicU64,
+    pub connection_lost: AtomicU64,
+    pub blockhash_expired: AtomicU64,
+}
+
+impl DroppedTransactionStats {
+    pub fn record(&self, reason: DropReason, num_transactions: usize) {
+        self.counter(reason)
+            .fetch_add(num_transactions as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the number of transactions dropped for `reason`.
+    pub fn get(&self, reason: DropReason) -> u64 {
+        self.counter(reason).load(Ordering::Relaxed)
+    }
+
+    fn counter(&self, reason: DropReason) -> &AtomicU64 {
+        match reason {
+            DropReason::Overflow => &self.overflow,
+            DropReason::Deadline => &self.deadline,
+            DropReason::Oversize => &self.oversize,
+            DropReason::Cancelled => &self.cancelled,
+            DropReason::NoLeader => &self.no_leader,
+            DropReason::ConnectionLost => &self.connection_lost,
+            DropReason::BlockhashExpired => &self.blockhash_expired,
+        }
+    }
+}
+
+/// [`RetryStats`] counts the reconnections of the workers after their
+/// connection has failed, separately from the first connections:
+/// * `reconnect_attempts`: connections attempted to recover a failed one.
+/// * `reconnected_at_first_attempt`: recoveries which succeeded right away.
+/// * `reconnected_after_retry`: recoveries which succeeded after at least one
+///   failed attempt.
+/// * `gave_up`: workers which stopped after `max_reconnect_attempts` failed
+///   attempts.
+/// * `transactions_not_retried`: transactions left unsent in the batch being
+///   sent when the connection failed. Transactions are not requeued, so they
+///   are lost.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct RetryStats {
+    pub reconnect_attempts: AtomicU64,
+    pub reconnected_at_first_attempt: AtomicU64,
+    pub reconnected_after_retry: AtomicU64,
+    pub gave_up: AtomicU64,
+    pub transactions_not_retried: AtomicU64,
+}
+
+/// [`LeaderSendStats`] counts the outcomes of sending transactions to a single
+/// leader. Unlike [`SendTransactionStats`], it is kept across monitoring
+/// periods, so that it reflects how the leader has historically treated the
+/// transactions of this client.
+///
+/// A transaction is counted as successfully sent once it has been written to
+/// the stream, the leader doesn't acknowledge transactions at the application
+/// level.
+///
+/// It also counts how often the leader was targeted while its worker was
+/// cached (hit), missing (miss) or stopped (expired). Misses and expirations
+/// mean that the transactions pay for establishing a new connection.
+///
+/// Finally, it counts the connections established to the leader and how long
+/// they lasted, which makes flapping connections visible, see
+/// [`LeaderSendStatsNonAtomic::mean_connection_uptime`], and how long their
+/// handshakes took, which exposes long RTTs and slow certificate validation,
+/// see [`LeaderSendStatsNonAtomic::mean_handshake_duration`].
+///
+/// When the leader probing is enabled, it also records whether the leader was
+/// reachable before any transaction was sent to it and the RTT measured by
+/// the probes, see [`LeaderSendStatsNonAtomic::mean_probe_rtt`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct LeaderSendStats {
+    pub successfully_sent: AtomicU64,
+    /// Transactions left unsent when the connection to the leader failed.
+    pub failed: AtomicU64,
+    pub bytes_sent: AtomicU64,
+    pub cache_hits: AtomicU64,
+    pub cache_misses: AtomicU64,
+    pub cache_expired: AtomicU64,
+    pub connections_established: AtomicU64,
+    pub connections_closed: AtomicU64,
+    /// Cumulative lifetime of the closed connections.
+    pub connection_uptime_ms: AtomicU64,
+    /// Successful handshakes.
+    pub handshakes: AtomicU64,
+    /// Handshakes which failed or timed out.
+    pub handshakes_failed: AtomicU64,
+    /// Cumulative duration of the successful handshakes.
+    pub handshake_duration_us: AtomicU64,
+    /// Probes completed, including the failed ones.
+    pub probes: AtomicU64,
+    pub probes_failed: AtomicU64,
+    /// Cumulative RTT measured by the successful probes.
+    pub probe_rtt_us: AtomicU64,
+}
+
+impl LeaderSendStats {
+    /// Returns the share of transactions successfully sent to the leader, or
+    /// `None` if nothing has been sent to it yet.
+    pub fn success_rate(&self) -> Option<f64> {
+        let successfully_sent = self.successfully_sent.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        let total = successfully_sent.saturating_add(failed);
+        (total > 0).then(|| successfully_sent as f64 / total as f64)
+    }
+
+    /// Returns how long the connections to the leader have lasted on average,
+    /// or `None` if no connection has been closed yet, see
+    /// [`LeaderSendStatsNonAtomic::mean_connection_uptime`].
+    pub fn mean_connection_uptime(&self) -> Option<Duration> {
+        let connections_closed = self.connections_closed.load(Ordering::Relaxed);
+        let connection_uptime_ms = self.connection_uptime_ms.load(Ordering::Relaxed);
+        (connections_closed > 0)
+            .then(|| Duration::from_millis(connection_uptime_ms / connections_closed))
+    }
+
+    /// Records a successful handshake which took `duration`.
+    pub fn record_handshake(&self, duration: Duration) {
+        self.handshakes.fetch_add(1, Ordering::Relaxed);
+        self.handshake_duration_us
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the share of handshakes with the leader which succeeded, or
+    /// `None` if no connection to it has been attempted yet.
+    pub fn handshake_success_rate(&self) -> Option<f64> {
+        let handshakes = self.handshakes.load(Ordering::Relaxed);
+        let handshakes_failed = self.handshakes_failed.load(Ordering::Relaxed);
+        let total = handshakes.saturating_add(handshakes_failed);
+        (total > 0).then(|| handshakes as f64 / total as f64)
+    }
+
+    /// Returns the share of probes which reached the leader, or `None` if it
+    /// hasn't been probed yet.
+    pub fn probe_success_rate(&self) -> Option<f64> {
+        let probes = self.probes.load(Ordering::Relaxed);
+        let probes_failed = self.probes_failed.load(Ordering::Relaxed);
+        (probes > 0).then(|| probes.saturating_sub(probes_failed) as f64 / probes as f64)
+    }
+
+    /// Records a probe which measured `rtt`, or which failed to reach the
+    /// leader if `rtt` is `None`.
+    pub fn record_probe(&self, rtt: Option<Duration>) {
+        self.probes.fetch_add(1, Ordering::Relaxed);
+        match rtt {
+            Some(rtt) => {
+                self.probe_rtt_us
+                    .fetch_add(rtt.as_micros() as u64, Ordering::Relaxed);
+            }
+            None => {
+                self.probes_failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn num_transactions(&self) -> u64 {
+        self.successfully_sent
+            .load(Ordering::Relaxed)
+            .saturating_add(self.failed.load(Ordering::Relaxed))
+    }
+}
+
+/// [`LeadersSendStats`] keeps [`LeaderSendStats`] for up to `max_leaders`
+/// leaders, keyed by their TPU address. It answers which leaders are dropping
+/// the transactions, which the aggregated counters can't.
+///
+/// When a new leader doesn't fit, the leader which is not served by a worker
+/// and has the fewest transactions is forgotten.
+#[derive(Debug)]
+pub struct LeadersSendStats {
+    leaders: Mutex<HashMap<SocketAddr, Arc<LeaderSendStats>>>,
+    max_leaders: usize,
+}
+
+impl Default for LeadersSendStats {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_TRACKED_LEADERS)
+    }
+}
+
+impl LeadersSendStats {
+    pub fn new(max_leaders: usize) -> Self {
+        Self {
+            leaders: Mutex::default(),
+            max_leaders: max_leaders.max(1),
+        }
+    }
+
+    /// Returns the stats of the `leader`, if it is tracked.
+    pub fn get(&self, leader: &SocketAddr) -> Option<Arc<LeaderSendStats>> {
+        self.leaders.lock().unwrap().get(leader).cloned()
+    }
+
+    /// Returns the stats of the `leader`, starting to track it if needed.
+    pub fn get_or_insert(&self, leader: SocketAddr) -> Arc<LeaderSendStats> {
+        let mut leaders = self.leaders.lock().unwrap();
+        if let Some(leader_stats) = leaders.get(&leader) {
+            return leader_stats.clone();
+        }
+        if leaders.len() >= self.max_leaders {
+            let victim = leaders
+                .iter()
+                .min_by_key(|(_, stats)| (Arc::strong_count(stats) > 1, stats.num_transactions()))
+                .map(|(leader, _)| *leader);
+            if let Some(victim) = victim {
+                leaders.remove(&victim);
+            }
+        }
+        leaders.entry(leader).or_default().clone()
+    }
+
+    /// Returns the number of tracked leaders.
+    pub fn len(&self) -> usize {
+        self.leaders.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the counters of each tracked leader.
+    pub fn to_non_atomic(&self) -> BTreeMap<SocketAddr, LeaderSendStatsNonAtomic> {
+        self.leaders
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(leader, stats)| (*leader, stats.to_non_atomic()))
+            .collect()
+    }
+
+    /// Returns the counters summed over all the tracked leaders.
+    pub fn aggregate(&self) -> LeaderSendStatsNonAtomic {
+        self.to_non_atomic().into_values().fold(
+            LeaderSendStatsNonAtomic::default(),
+            |total, stats| LeaderSendStatsNonAtomic {
+                successfully_sent: total
+                    .successfully_sent
+                    .saturating_add(stats.successfully_sent),
+                failed: total.failed.saturating_add(stats.failed),
+                bytes_sent: total.bytes_sent.saturating_add(stats.bytes_sent),
+                cache_hits: total.cache_hits.saturating_add(stats.cache_hits),
+                cache_misses: total.cache_misses.saturating_add(stats.cache_misses),
+                cache_expired: total.cache_expired.saturating_add(stats.cache_expired),
+                connections_established: total
+                    .connections_established
+                    .saturating_add(stats.connections_established),
+                connections_closed: total
+                    .connections_closed
+                    .saturating_add(stats.connections_closed),
+                connection_uptime_ms: total
+                    .connection_uptime_ms
+                    .saturating_add(stats.connection_uptime_ms),
+                handshakes: total.handshakes.saturating_add(stats.handshakes),
+                handshakes_failed: total
+                    .handshakes_failed
+                    .saturating_add(stats.handshakes_failed),
+                handshake_duration_us: total
+                    .handshake_duration_us
+                    .saturating_add(stats.handshake_duration_us),
+                probes: total.probes.saturating_add(stats.probes),
+                probes_failed: total.probes_failed.saturating_add(stats.probes_failed),
+                probe_rtt_us: total.probe_rtt_us.saturating_add(stats.probe_rtt_us),
+            },
+        )
+    }
+}
+
+/// Identity the transactions are sent with, which is updated through the
+/// `update_identity_receiver` of [`ConnectionWorkersScheduler::new`]. It is
+/// displayed as the base58 pubkey of the stake identity, or `unstaked`.
+///
+/// [`ConnectionWorkersScheduler::new`]: crate::ConnectionWorkersScheduler::new
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SendingIdentity {
+    #[default]
+    Unstaked,
+    Staked(Pubkey),
+}
+
+impl fmt::Display for SendingIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unstaked => f.write_str("unstaked"),
+            Self::Staked(pubkey) => write!(f, "{pubkey}"),
+        }
+    }
+}
+
+impl serde::Serialize for SendingIdentity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// [`IdentitySendStats`] counts the outcomes of sending transactions with a
+/// single identity. The errors are split so that an identity exhausting its
+/// stake quota can be told apart from network problems:
+/// * `rejected_by_leader`: the leader closed the connection or stopped the
+///   stream, which is how it pushes back on the identity.
+/// * `network_errors`: timeouts, resets and transport errors.
+/// * `other_errors`: the errors of the client itself, like the endpoint
+///   stopping.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct IdentitySendStats {
+    pub successfully_sent: AtomicU64,
+    pub bytes_sent: AtomicU64,
+    pub rejected_by_leader: AtomicU64,
+    pub network_errors: AtomicU64,
+    pub other_errors: AtomicU64,
+}
+
+impl IdentitySendStats {
+    pub(crate) fn record_error(&self, err: &QuicError) {
+        let counter = match err {
+            QuicError::Connection(ConnectionError::ApplicationClosed(_))
+            | QuicError::Connection(ConnectionError::ConnectionClosed(_))
+            | QuicError::StreamWrite(WriteError::Stopped(_))
+            | QuicError::StreamWrite(WriteError::ConnectionLost(
+                ConnectionError::ApplicationClosed(_) | ConnectionError::ConnectionClosed(_),
+            )) => &self.rejected_by_leader,
+            QuicError::Connection(
+                ConnectionError::Reset
+                | ConnectionError::TimedOut
+                | ConnectionError::TransportError(_),
+            )
+            | QuicError::StreamWrite(WriteError::ConnectionLost(
+                ConnectionError::Reset
+                | ConnectionError::TimedOut
+                | ConnectionError::TransportError(_),
+            ))
+            | QuicError::HandshakeTimeout => &self.network_errors,
+            _ => &self.other_errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// [`IdentitiesSendStats`] keeps [`IdentitySendStats`] for every identity the
+/// transactions have been sent with. The identities are rotated rarely, so
+/// they are never forgotten.
+///
+/// The workers account their transactions to the identity which was current
+/// when they were spawned, which is the one of their connection as the
+/// workers are flushed when the identity is updated.
+#[derive(Debug, Default)]
+pub struct IdentitiesSendStats {
+    identities: Mutex<HashMap<SendingIdentity, Arc<IdentitySendStats>>>,
+    current: Mutex<SendingIdentity>,
+}
+
+impl IdentitiesSendStats {
+    /// Returns the stats of `identity`, if transactions have been sent with
+    /// it.
+    pub fn get(&self, identity: &SendingIdentity) -> Option<Arc<IdentitySendStats>> {
+        self.identities.lock().unwrap().get(identity).cloned()
+    }
+
+    /// Returns the identity the transactions are currently sent with.
+    pub fn current_identity(&self) -> SendingIdentity {
+        *self.current.lock().unwrap()
+    }
+
+    pub(crate) fn set_current(&self, identity: SendingIdentity) {
+        *self.current.lock().unwrap() = identity;
+    }
+
+    /// Returns the stats of the current identity.
+    pub(crate) fn current(&self) -> Arc<IdentitySendStats> {
+        let current = self.current_identity();
+        self.identities
+            .lock()
+            .unwrap()
+            .entry(current)
+            .or_default()
+            .clone()
+    }
+
+    /// Returns the counters of each identity.
+    pub fn to_non_atomic(&self) -> BTreeMap<SendingIdentity, IdentitySendStatsNonAtomic> {
+        self.identities
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(identity, stats)| (*identity, stats.to_non_atomic()))
+            .collect()
+    }
 }
 
 #[allow(clippy::arithmetic_side_effects)]
 pub fn record_error(err: QuicError, stats: &SendTransactionStats) {
+    if !matches!(err, QuicError::Endpoint(_)) {
+        stats.rolling.record_error();
+    }
+    if let Some(code) = QuicErrorCode::from_quic_error(&err) {
+        stats.error_codes.record(code);
+    }
     match err {
         QuicError::Connect(ConnectError::EndpointStopping) => {
             stats.connect_error_other.fetch_add(1, Ordering::Relaxed);
@@ -146,6 +1167,7 @@ impl fmt::Display for SendTransactionStats {
             self,
             f,
             successfully_sent,
+            bytes_sent,
             connect_error_cids_exhausted,
             connect_error_invalid_remote_address,
             connect_error_other,
@@ -161,16 +1183,27 @@ impl fmt::Display for SendTransactionStats {
             write_error_connection_lost,
             write_error_stopped,
             write_error_zero_rtt_rejected,
+            leader_without_tpu_socket,
+            workers_evicted,
+            evicted_worker_queued_batches,
+            workers_reinserted,
+            unhealthy_workers_removed,
+            worker_shutdown_stragglers,
+            workers_recycled,
         )
     }
 }
 
 /// For external use it is useful to have direct access to data and `PartialEq` but
 /// we cannot have that on top of atomics. This macro creates a structure with the same
-/// fields but of type u64.
+/// fields but of type u64, along with an accessor per field.
+///
+/// The structure is non-exhaustive, so that counters can be added without
+/// breaking the code outside of the crate.
 macro_rules! define_non_atomic_struct_for {
     ($name:ident, $atomic_name:ident, {$($field:ident),* $(,)?}) => {
-        #[derive(Debug, Default, PartialEq)]
+        #[derive(Clone, Debug, Default, PartialEq, Serialize)]
+        #[non_exhaustive]
         pub struct $name {
             $(pub $field: u64),*
         }
@@ -189,6 +1222,26 @@ macro_rules! define_non_atomic_struct_for {
                 }
             }
         }
+
+        impl $name {
+            $(
+                pub fn $field(&self) -> u64 {
+                    self.$field
+                }
+            )*
+
+            /// Returns the names of the fields along with their values
+            pub fn fields(&self) -> Vec<(&'static str, u64)> {
+                vec![$((stringify!($field), self.$field)),*]
+            }
+
+            /// Returns the increments of the counters since `older`
+            pub fn diff(&self, older: &Self) -> Self {
+                $name {
+                    $($field: self.$field.saturating_sub(older.$field)),*
+                }
+            }
+        }
     };
 }
 
@@ -198,6 +1251,7 @@ define_non_atomic_struct_for!(
     SendTransactionStats,
     {
         successfully_sent,
+        bytes_sent,
         connect_error_cids_exhausted,
         connect_error_invalid_remote_address,
         connect_error_other,
@@ -212,6 +1266,520 @@ define_non_atomic_struct_for!(
         write_error_closed_stream,
         write_error_connection_lost,
         write_error_stopped,
-        write_error_zero_rtt_rejected
+        write_error_zero_rtt_rejected,
+        leader_without_tpu_socket,
+        workers_evicted,
+        evicted_worker_queued_batches,
+        workers_reinserted,
+        unhealthy_workers_removed,
+        worker_shutdown_stragglers,
+        workers_recycled
     }
 );
+
+impl SendTransactionStatsNonAtomic {
+    /// Returns the number of errors of establishing connections.
+    pub fn connect_errors(&self) -> u64 {
+        [
+            self.connect_error_cids_exhausted,
+            self.connect_error_other,
+            self.connect_error_invalid_remote_address,
+        ]
+        .iter()
+        .fold(0, |sum, value| sum.saturating_add(*value))
+    }
+
+    /// Returns the number of errors of established connections.
+    pub fn connection_errors(&self) -> u64 {
+        [
+            self.connection_error_reset,
+            self.connection_error_cids_exhausted,
+            self.connection_error_timed_out,
+            self.connection_error_application_closed,
+            self.connection_error_transport_error,
+            self.connection_error_version_mismatch,
+            self.connection_error_locally_closed,
+        ]
+        .iter()
+        .fold(0, |sum, value| sum.saturating_add(*value))
+    }
+
+    /// Returns the number of errors of writing to streams.
+    pub fn write_errors(&self) -> u64 {
+        [
+            self.write_error_stopped,
+            self.write_error_closed_stream,
+            self.write_error_connection_lost,
+            self.write_error_zero_rtt_rejected,
+        ]
+        .iter()
+        .fold(0, |sum, value| sum.saturating_add(*value))
+    }
+}
+
+define_non_atomic_struct_for!(
+    LeaderSendStatsNonAtomic,
+    LeaderSendStats,
+    {
+        successfully_sent,
+        failed,
+        bytes_sent,
+        cache_hits,
+        cache_misses,
+        cache_expired,
+        connections_established,
+        connections_closed,
+        connection_uptime_ms,
+        handshakes,
+        handshakes_failed,
+        handshake_duration_us,
+        probes,
+        probes_failed,
+        probe_rtt_us
+    }
+);
+
+impl LeaderSendStatsNonAtomic {
+    /// Returns how long the connections to the leader have lasted on average,
+    /// which is the mean time between reconnects of a flapping connection, or
+    /// `None` if no connection has been closed yet.
+    pub fn mean_connection_uptime(&self) -> Option<Duration> {
+        (self.connections_closed > 0)
+            .then(|| Duration::from_millis(self.connection_uptime_ms / self.connections_closed))
+    }
+
+    /// Returns how long the handshakes with the leader have taken on average,
+    /// or `None` if no handshake has succeeded yet.
+    pub fn mean_handshake_duration(&self) -> Option<Duration> {
+        (self.handshakes > 0)
+            .then(|| Duration::from_micros(self.handshake_duration_us / self.handshakes))
+    }
+
+    /// Returns the mean RTT measured by the probes of the leader, or `None` if
+    /// no probe has reached it yet.
+    pub fn mean_probe_rtt(&self) -> Option<Duration> {
+        let successful_probes = self.probes.saturating_sub(self.probes_failed);
+        (successful_probes > 0)
+            .then(|| Duration::from_micros(self.probe_rtt_us / successful_probes))
+    }
+}
+
+define_non_atomic_struct_for!(
+    IdentitySendStatsNonAtomic,
+    IdentitySendStats,
+    {
+        successfully_sent,
+        bytes_sent,
+        rejected_by_leader,
+        network_errors,
+        other_errors
+    }
+);
+
+define_non_atomic_struct_for!(
+    DroppedTransactionStatsNonAtomic,
+    DroppedTransactionStats,
+    {
+        overflow,
+        deadline,
+        oversize,
+        cancelled,
+        no_leader,
+        connection_lost,
+        blockhash_expired
+    }
+);
+
+define_non_atomic_struct_for!(
+    RetryStatsNonAtomic,
+    RetryStats,
+    {
+        reconnect_attempts,
+        reconnected_at_first_attempt,
+        reconnected_after_retry,
+        gave_up,
+        transactions_not_retried
+    }
+);
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::net::{IpAddr, Ipv4Addr},
+    };
+
+    fn leader(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+    }
+
+    #[test]
+    fn test_snapshot_diff() {
+        let stats = SendTransactionStats::default();
+        stats.successfully_sent.fetch_add(2, Ordering::Relaxed);
+        stats
+            .leaders
+            .get_or_insert(leader(0))
+            .successfully_sent
+            .fetch_add(2, Ordering::Relaxed);
+        let older = stats.snapshot();
+
+        stats.successfully_sent.fetch_add(3, Ordering::Relaxed);
+        stats.workers_evicted.fetch_add(1, Ordering::Relaxed);
+        stats
+            .leaders
+            .get_or_insert(leader(1))
+            .failed
+            .fetch_add(1, Ordering::Relaxed);
+        stats.latency.sending.record(Duration::from_micros(1));
+
+        let diff = stats.snapshot().diff(&older);
+        assert_eq!(
+            diff.counters,
+            SendTransactionStatsNonAtomic {
+                successfully_sent: 3,
+                workers_evicted: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            diff.leaders[&leader(0)],
+            LeaderSendStatsNonAtomic::default()
+        );
+        assert_eq!(diff.leaders[&leader(1)].failed, 1);
+        assert_eq!(diff.latency.sending.count(), 1);
+        assert_eq!(diff.latency.queueing.count(), 0);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let stats = SendTransactionStats::default();
+        stats.successfully_sent.fetch_add(2, Ordering::Relaxed);
+        stats
+            .leaders
+            .get_or_insert(leader(0))
+            .bytes_sent
+            .fetch_add(100, Ordering::Relaxed);
+        stats.error_codes.record(QuicErrorCode::TlsAlert(42));
+        stats.latency.sending.record(Duration::from_micros(5));
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["version"], SNAPSHOT_VERSION);
+        assert_eq!(json["epoch"], 0);
+        assert_eq!(json["counters"]["successfully_sent"], 2);
+        assert_eq!(json["leaders"]["127.0.0.1:0"]["bytes_sent"], 100);
+        assert_eq!(json["error_codes"]["tls_alert_42"], 1);
+        assert_eq!(
+            json["latency"]["sending"],
+            serde_json::json!({
+                "count": 1,
+                "p50_us": 5,
+                "p90_us": 5,
+                "p99_us": 5,
+                "max_us": 5,
+            })
+        );
+        assert_eq!(
+            json["latency"]["queueing"]["p50_us"],
+            serde_json::Value::Null
+        );
+        assert_eq!(json, serde_json::to_value(stats.snapshot()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_publish_snapshots() {
+        let stats = Arc::new(SendTransactionStats::default());
+        let (sender, mut receiver) = watch::channel(SendTransactionStatsSnapshot::default());
+        let cancel = CancellationToken::new();
+        let publisher = tokio::spawn(stats.clone().publish_snapshots(
+            sender,
+            Duration::from_millis(10),
+            cancel.clone(),
+        ));
+
+        stats.successfully_sent.fetch_add(1, Ordering::Relaxed);
+        while receiver.borrow_and_update().counters.successfully_sent != 1 {
+            receiver.changed().await.unwrap();
+        }
+
+        // The publisher stops once the receivers are gone.
+        drop(receiver);
+        publisher.await.unwrap();
+    }
+
+    #[test]
+    fn test_reset() {
+        let stats = SendTransactionStats::default();
+        stats.successfully_sent.fetch_add(2, Ordering::Relaxed);
+        stats
+            .leaders
+            .get_or_insert(leader(0))
+            .successfully_sent
+            .fetch_add(2, Ordering::Relaxed);
+        stats.latency.sending.record(Duration::from_micros(1));
+        let older = stats.snapshot();
+        assert_eq!(older.epoch, 0);
+
+        let reset = stats.reset();
+        assert_eq!(reset.epoch, 0);
+        assert_eq!(reset.counters.successfully_sent, 2);
+        assert_eq!(reset.latency.sending.count(), 1);
+        assert_eq!(stats.epoch(), 1);
+        assert_eq!(
+            stats.to_non_atomic(),
+            SendTransactionStatsNonAtomic::default()
+        );
+        // The per-leader counters are kept.
+        assert_eq!(
+            stats
+                .leaders
+                .get(&leader(0))
+                .unwrap()
+                .successfully_sent
+                .load(Ordering::Relaxed),
+            2
+        );
+
+        stats.successfully_sent.fetch_add(1, Ordering::Relaxed);
+        let diff = stats.snapshot().diff(&older);
+        assert_eq!(diff.epoch, 1);
+        assert_eq!(diff.counters.successfully_sent, 1);
+        assert_eq!(
+            diff.leaders[&leader(0)],
+            LeaderSendStatsNonAtomic::default()
+        );
+    }
+
+    #[test]
+    fn test_record_dropped() {
+        #[derive(Default)]
+        struct DropCounter(AtomicU64);
+
+        impl StatsObserver for DropCounter {
+            fn on_batch_dropped(
+                &self,
+                _peer: SocketAddr,
+                reason: DropReason,
+                num_transactions: usize,
+            ) {
+                assert_eq!(reason, DropReason::Deadline);
+                self.0.fetch_add(num_transactions as u64, Ordering::Relaxed);
+            }
+        }
+
+        let stats = SendTransactionStats::default();
+        let observer = Arc::new(DropCounter::default());
+        stats.set_observer(observer.clone());
+        stats.record_dropped(Some(leader(0)), DropReason::Deadline, 3);
+        stats.record_dropped(Some(leader(1)), DropReason::Deadline, 0);
+        assert_eq!(observer.0.load(Ordering::Relaxed), 3);
+
+        stats.record_dropped(None, DropReason::NoLeader, 2);
+        assert_eq!(
+            stats.snapshot().dropped,
+            DroppedTransactionStatsNonAtomic {
+                deadline: 3,
+                no_leader: 2,
+                ..DroppedTransactionStatsNonAtomic::default()
+            }
+        );
+        assert_eq!(stats.reset().dropped.deadline, 3);
+        assert_eq!(stats.dropped.get(DropReason::Deadline), 0);
+    }
+
+    #[test]
+    fn test_record_batch_dropped() {
+        #[derive(Default)]
+        struct DroppedIds(Mutex<Vec<Option<u64>>>);
+
+        impl StatsObserver for DroppedIds {
+            fn on_transaction_dropped(
+                &self,
+                _peer: SocketAddr,
+                _reason: DropReason,
+                metadata: TransactionMetadata,
+            ) {
+                self.0.lock().unwrap().push(metadata.id);
+            }
+        }
+
+        let stats = SendTransactionStats::default();
+        let observer = Arc::new(DroppedIds::default());
+        stats.set_observer(observer.clone());
+        let batch =
+            TransactionBatch::new(vec![vec![0u8]; 2]).with_metadata(vec![TransactionMetadata {
+                id: Some(1),
+                priority: Some(10),
+            }]);
+        stats.record_batch_dropped(Some(leader(0)), DropReason::Overflow, &batch);
+        stats.record_batch_dropped(None, DropReason::NoLeader, &batch);
+        stats.record_batch_dropped(
+            Some(leader(0)),
+            DropReason::Overflow,
+            &TransactionBatch::new(vec![vec![0u8]]),
+        );
+        assert_eq!(*observer.0.lock().unwrap(), vec![Some(1), None]);
+        assert_eq!(stats.dropped.get(DropReason::Overflow), 3);
+        assert_eq!(stats.dropped.get(DropReason::NoLeader), 2);
+    }
+
+    #[test]
+    fn test_queue_depth() {
+        let stats = SendTransactionStats::default();
+        stats.queue_depth.update(10, 4);
+        stats.queue_depth.update(2, 1);
+        let older = stats.snapshot();
+        assert_eq!(
+            older.queue_depth,
+            QueueDepthSnapshot {
+                scheduler_queue: 2,
+                worker_queues: 1,
+                scheduler_queue_high_water_mark: 10,
+                worker_queues_high_water_mark: 4,
+            }
+        );
+
+        // The gauges are not diffed.
+        assert_eq!(stats.snapshot().diff(&older).queue_depth, older.queue_depth);
+
+        assert_eq!(stats.reset().queue_depth, older.queue_depth);
+        assert_eq!(
+            stats.snapshot().queue_depth,
+            QueueDepthSnapshot {
+                scheduler_queue: 2,
+                worker_queues: 1,
+                scheduler_queue_high_water_mark: 2,
+                worker_queues_high_water_mark: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_identities_send_stats() {
+        let stats = SendTransactionStats::default();
+        let unstaked = stats.identities.current();
+        unstaked.successfully_sent.fetch_add(1, Ordering::Relaxed);
+        unstaked.record_error(&QuicError::HandshakeTimeout);
+
+        let identity = SendingIdentity::Staked(Pubkey::new_unique());
+        stats.identities.set_current(identity);
+        let staked = stats.identities.current();
+        staked.record_error(&QuicError::StreamWrite(WriteError::Stopped(
+            quinn::VarInt::from_u32(0),
+        )));
+        staked.record_error(&QuicError::Connection(ConnectionError::LocallyClosed));
+        assert_eq!(stats.identities.current_identity(), identity);
+
+        let identities = stats.snapshot().identities;
+        assert_eq!(
+            identities[&SendingIdentity::Unstaked],
+            IdentitySendStatsNonAtomic {
+                successfully_sent: 1,
+                network_errors: 1,
+                ..IdentitySendStatsNonAtomic::default()
+            }
+        );
+        assert_eq!(
+            identities[&identity],
+            IdentitySendStatsNonAtomic {
+                rejected_by_leader: 1,
+                other_errors: 1,
+                ..IdentitySendStatsNonAtomic::default()
+            }
+        );
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["identities"]["unstaked"]["successfully_sent"], 1);
+        assert_eq!(
+            json["identities"][identity.to_string()]["rejected_by_leader"],
+            1
+        );
+    }
+
+    #[test]
+    fn test_mean_connection_uptime() {
+        let stats = LeaderSendStats::default();
+        assert_eq!(stats.mean_connection_uptime(), None);
+        stats
+            .connections_established
+            .fetch_add(3, Ordering::Relaxed);
+        assert_eq!(stats.mean_connection_uptime(), None);
+
+        stats.connections_closed.fetch_add(2, Ordering::Relaxed);
+        stats
+            .connection_uptime_ms
+            .fetch_add(3000, Ordering::Relaxed);
+        assert_eq!(
+            stats.mean_connection_uptime(),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            stats.mean_connection_uptime(),
+            stats.to_non_atomic().mean_connection_uptime()
+        );
+    }
+
+    #[test]
+    fn test_leaders_send_stats() {
+        let leaders_send_stats = LeadersSendStats::new(2);
+        let stats_0 = leaders_send_stats.get_or_insert(leader(0));
+        stats_0.successfully_sent.fetch_add(2, Ordering::Relaxed);
+        stats_0.bytes_sent.fetch_add(100, Ordering::Relaxed);
+        drop(stats_0);
+        let stats_1 = leaders_send_stats.get_or_insert(leader(1));
+        stats_1.failed.fetch_add(1, Ordering::Relaxed);
+        stats_1.connections_closed.fetch_add(2, Ordering::Relaxed);
+        stats_1
+            .connection_uptime_ms
+            .fetch_add(3000, Ordering::Relaxed);
+        stats_1.record_handshake(Duration::from_millis(10));
+        stats_1.record_handshake(Duration::from_millis(20));
+        stats_1.handshakes_failed.fetch_add(2, Ordering::Relaxed);
+        assert_eq!(stats_1.handshake_success_rate(), Some(0.5));
+        stats_1.record_probe(Some(Duration::from_millis(4)));
+        stats_1.record_probe(None);
+        assert_eq!(stats_1.probe_success_rate(), Some(0.5));
+
+        let aggregate = leaders_send_stats.aggregate();
+        assert_eq!(
+            aggregate,
+            LeaderSendStatsNonAtomic {
+                successfully_sent: 2,
+                failed: 1,
+                bytes_sent: 100,
+                connections_closed: 2,
+                connection_uptime_ms: 3000,
+                handshakes: 2,
+                handshakes_failed: 2,
+                handshake_duration_us: 30_000,
+                probes: 2,
+                probes_failed: 1,
+                probe_rtt_us: 4_000,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            aggregate.mean_connection_uptime(),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            aggregate.mean_handshake_duration(),
+            Some(Duration::from_millis(15))
+        );
+        assert_eq!(aggregate.mean_probe_rtt(), Some(Duration::from_millis(4)));
+
+        // The leader 1 has fewer transactions but is still in use, so the
+        // leader 0 is forgotten.
+        leaders_send_stats.get_or_insert(leader(2));
+        assert_eq!(leaders_send_stats.len(), 2);
+        assert!(leaders_send_stats.get(&leader(0)).is_none());
+        assert_eq!(
+            leaders_send_stats
+                .get(&leader(1))
+                .unwrap()
+                .failed
+                .load(Ordering::Relaxed),
+            1
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! This module defines [`BatchSizeHistogram`] which records the sizes of the
+//! batches handled by the workers into power of two buckets.
+
+use {
+    serde::{ser::SerializeStruct, Serializer},
+    std::sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Bucket `0` holds the zeros and bucket `i` the values in
+/// `[2^(i - 1), 2^i)`.
+const NUM_BUCKETS: usize = u64::BITS as usize + 1;
+
+/// [`BatchSizeHistogram`] counts batches by size, either in transactions or in
+/// bytes. Sizes are bucketed by power of two, which is precise enough to tune
+/// the batching upstream of the scheduler.
+#[derive(Debug)]
+pub struct BatchSizeHistogram {
+    buckets: Box<[AtomicU64]>,
+    sum: AtomicU64,
+}
+
+/// Point-in-time copy of a [`BatchSizeHistogram`].
+///
+/// It serializes to its count, mean, percentiles and maximum rather than to its
+/// buckets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchSizeHistogramSnapshot {
+    buckets: Vec<u64>,
+    sum: u64,
+}
+
+impl Default for BatchSizeHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..NUM_BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+}
+
+impl BatchSizeHistogram {
+    pub fn record(&self, size: u64) {
+        self.buckets[bucket_index(size)].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// Returns the recorded sizes.
+    pub fn snapshot(&self) -> BatchSizeHistogramSnapshot {
+        BatchSizeHistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+            sum: self.sum.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the recorded sizes and clears the histogram.
+    pub fn read_and_reset(&self) -> BatchSizeHistogramSnapshot {
+        BatchSizeHistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.swap(0, Ordering::Relaxed))
+                .collect(),
+            sum: self.sum.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+impl BatchSizeHistogramSnapshot {
+    /// Returns the number of recorded batches.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Returns the mean size, or `None` if nothing has been recorded.
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.count();
+        (count > 0).then(|| self.sum as f64 / count as f64)
+    }
+
+    /// Returns the size below which `percentile` percent of the batches fall,
+    /// or `None` if nothing has been recorded. The size is rounded up to the
+    /// upper bound of its bucket.
+    pub fn percentile(&self, percentile: f64) -> Option<u64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0 * count as f64).ceil() as u64).max(1);
+        let mut seen = 0u64;
+        self.buckets
+            .iter()
+            .position(|bucket| {
+                seen = seen.saturating_add(*bucket);
+                seen >= rank
+            })
+            .map(bucket_upper_bound)
+    }
+
+    /// Returns the largest size, rounded up to the upper bound of its bucket.
+    pub fn max(&self) -> Option<u64> {
+        self.buckets
+            .iter()
+            .rposition(|bucket| *bucket > 0)
+            .map(bucket_upper_bound)
+    }
+
+    /// Returns the sizes recorded since `older`.
+    pub fn diff(&self, older: &BatchSizeHistogramSnapshot) -> BatchSizeHistogramSnapshot {
+        BatchSizeHistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .enumerate()
+                .map(|(index, bucket)| {
+                    bucket.saturating_sub(older.buckets.get(index).copied().unwrap_or_default())
+                })
+                .collect(),
+            sum: self.sum.saturating_sub(older.sum),
+        }
+    }
+}
+
+impl serde::Serialize for BatchSizeHistogramSnapshot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("BatchSizeHistogramSnapshot", 6)?;
+        state.serialize_field("count", &self.count())?;
+        state.serialize_field("mean", &self.mean())?;
+        state.serialize_field("p50", &self.percentile(50.0))?;
+        state.serialize_field("p90", &self.percentile(90.0))?;
+        state.serialize_field("p99", &self.percentile(99.0))?;
+        state.serialize_field("max", &self.max())?;
+        state.end()
+    }
+}
+
+fn bucket_index(size: u64) -> usize {
+    (u64::BITS - size.leading_zeros()) as usize
+}
+
+fn bucket_upper_bound(index: usize) -> u64 {
+    match index {
+        0 => 0,
+        64 => u64::MAX,
+        index => (1 << index) - 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_size_histogram() {
+        for size in [0, 1, 2, 3, 4, 1232, u64::MAX] {
+            let index = bucket_index(size);
+            assert!(index < NUM_BUCKETS);
+            assert!(size <= bucket_upper_bound(index));
+        }
+
+        let histogram = BatchSizeHistogram::default();
+        assert_eq!(histogram.snapshot().mean(), None);
+        for size in [1, 1, 1, 64] {
+            histogram.record(size);
+        }
+        let older = histogram.snapshot();
+        histogram.record(100);
+
+        let snapshot = histogram.read_and_reset();
+        assert_eq!(snapshot.count(), 5);
+        assert_eq!(snapshot.mean(), Some(33.4));
+        assert_eq!(snapshot.percentile(50.0), Some(1));
+        assert_eq!(snapshot.percentile(90.0), Some(127));
+        assert_eq!(snapshot.max(), Some(127));
+        assert_eq!(snapshot.diff(&older).mean(), Some(100.0));
+        assert_eq!(histogram.snapshot().count(), 0);
+    }
+}
@@ -0,0 +1,188 @@
+//! This module defines [`ErrorRecords`] which keeps the last errors of the
+//! workers along with the leader they happened with, for postmortems.
+
+use {
+    super::QuicErrorCode,
+    crate::QuicError,
+    serde::{ser::SerializeStruct, Serializer},
+    serde_derive::Serialize,
+    solana_pubkey::Pubkey,
+    solana_time_utils::timestamp,
+    std::{
+        collections::{HashMap, VecDeque},
+        net::SocketAddr,
+        sync::{Mutex, RwLock},
+    },
+};
+
+/// Default number of errors kept by [`ErrorRecords`].
+pub const DEFAULT_MAX_ERROR_RECORDS: usize = 1024;
+
+/// What the worker was doing when the error happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ErrorKind {
+    Connect,
+    Connection,
+    Write,
+    HandshakeTimeout,
+    Endpoint,
+}
+
+/// An error of a worker, see [`ErrorRecords`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ErrorRecord {
+    /// Identity of the leader, if its TPU address was known to the scheduler
+    /// when the error happened.
+    pub leader: Option<Pubkey>,
+    pub peer: SocketAddr,
+    /// Time of the error in milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub kind: ErrorKind,
+    pub code: Option<QuicErrorCode>,
+    pub message: String,
+}
+
+// Serialized with the leader as a base58 string.
+impl serde::Serialize for ErrorRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ErrorRecord", 6)?;
+        state.serialize_field("leader", &self.leader.map(|leader| leader.to_string()))?;
+        state.serialize_field("peer", &self.peer)?;
+        state.serialize_field("timestamp_ms", &self.timestamp_ms)?;
+        state.serialize_field("kind", &self.kind)?;
+        state.serialize_field("code", &self.code)?;
+        state.serialize_field("message", &self.message)?;
+        state.end()
+    }
+}
+
+/// [`ErrorRecords`] keeps the last `max_records` errors of the workers in a
+/// ring buffer, while the counters of [`SendTransactionStats`] only tell how
+/// many errors of each kind happened.
+///
+/// The errors are attributed to the leaders using the TPU addresses provided
+/// by [`LeaderUpdater::known_peers`], which the scheduler refreshes on each
+/// health check.
+///
+/// [`SendTransactionStats`]: crate::SendTransactionStats
+/// [`LeaderUpdater::known_peers`]: crate::leader_updater::LeaderUpdater::known_peers
+#[derive(Debug)]
+pub struct ErrorRecords {
+    records: Mutex<VecDeque<ErrorRecord>>,
+    max_records: usize,
+    leaders: RwLock<HashMap<SocketAddr, Pubkey>>,
+}
+
+impl Default for ErrorRecords {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ERROR_RECORDS)
+    }
+}
+
+impl ErrorRecords {
+    pub fn new(max_records: usize) -> Self {
+        Self {
+            records: Mutex::new(VecDeque::with_capacity(max_records)),
+            max_records,
+            leaders: RwLock::default(),
+        }
+    }
+
+    /// Returns the recorded errors, oldest first.
+    pub fn recent(&self) -> Vec<ErrorRecord> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Returns the recorded errors, oldest first, and clears them.
+    pub fn take(&self) -> Vec<ErrorRecord> {
+        self.records.lock().unwrap().drain(..).collect()
+    }
+
+    /// Replaces the identities of the leaders by TPU address.
+    pub fn set_leaders(&self, leaders: HashMap<SocketAddr, Pubkey>) {
+        *self.leaders.write().unwrap() = leaders;
+    }
+
+    /// Returns the identity of the leader whose TPU address is `peer`.
+    pub fn leader(&self, peer: &SocketAddr) -> Option<Pubkey> {
+        self.leaders.read().unwrap().get(peer).copied()
+    }
+
+    pub(crate) fn record(&self, peer: SocketAddr, err: &QuicError) {
+        if self.max_records == 0 {
+            return;
+        }
+        self.push(self.new_record(peer, err));
+    }
+
+    /// Returns the record of `err`, without keeping it.
+    pub(crate) fn new_record(&self, peer: SocketAddr, err: &QuicError) -> ErrorRecord {
+        let kind = match err {
+            QuicError::Connect(_) => ErrorKind::Connect,
+            QuicError::Connection(_) => ErrorKind::Connection,
+            QuicError::StreamWrite(_) => ErrorKind::Write,
+            QuicError::HandshakeTimeout => ErrorKind::HandshakeTimeout,
+            QuicError::Endpoint(_) => ErrorKind::Endpoint,
+        };
+        ErrorRecord {
+            leader: self.leader(&peer),
+            peer,
+            timestamp_ms: timestamp(),
+            kind,
+            code: QuicErrorCode::from_quic_error(err),
+            message: err.to_string(),
+        }
+    }
+
+    pub(crate) fn push(&self, record: ErrorRecord) {
+        if self.max_records == 0 {
+            return;
+        }
+        let mut records = self.records.lock().unwrap();
+        if records.len() >= self.max_records {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        quinn::ConnectionError,
+        std::net::{IpAddr, Ipv4Addr},
+    };
+
+    #[test]
+    fn test_error_records() {
+        let peer = |port| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+        let leader = Pubkey::new_unique();
+        let records = ErrorRecords::new(2);
+        records.set_leaders(HashMap::from([(peer(1), leader)]));
+
+        records.record(peer(0), &QuicError::HandshakeTimeout);
+        records.record(peer(1), &QuicError::Connection(ConnectionError::TimedOut));
+        records.record(peer(2), &QuicError::Connection(ConnectionError::Reset));
+
+        let recent = records.recent();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].leader, Some(leader));
+        assert_eq!(recent[0].peer, peer(1));
+        assert_eq!(recent[0].kind, ErrorKind::Connection);
+        assert_eq!(recent[0].code, Some(QuicErrorCode::TimedOut));
+        assert_eq!(recent[1].leader, None);
+        assert_eq!(recent[1].peer, peer(2));
+
+        let json = serde_json::to_value(&recent[0]).unwrap();
+        assert_eq!(json["leader"], leader.to_string());
+        assert_eq!(json["kind"], "connection");
+        assert_eq!(json["code"], "timed_out");
+
+        assert_eq!(records.take(), recent);
+        assert!(records.recent().is_empty());
+    }
+}
@@ -0,0 +1,367 @@
+//! This module defines [`LatencyHistogram`] which records latencies into
+//! log-linear buckets, similar to HDR histograms, and
+//! [`SlidingLatencyHistogram`] which only keeps the recent ones.
+
+use {
+    serde::{ser::SerializeStruct, Serializer},
+    serde_derive::Serialize,
+    std::{
+        fmt,
+        sync::atomic::{AtomicU64, Ordering},
+        time::{Duration, Instant},
+    },
+};
+
+/// The window covered by [`SlidingLatencyHistogram`].
+pub const LATENCY_WINDOW: Duration = Duration::from_secs(60);
+
+/// Each power of two range is split into `2^SUB_BUCKET_BITS` buckets, which
+/// bounds the relative error of the reported latencies by 12.5%.
+const SUB_BUCKET_BITS: u32 = 3;
+const NUM_SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+/// Enough buckets to hold any `u64` number of microseconds.
+const NUM_BUCKETS: usize = (u64::BITS - SUB_BUCKET_BITS + 1) as usize * NUM_SUB_BUCKETS;
+
+/// Width of the slots of [`SlidingLatencyHistogram`], the window slides by
+/// this much at a time.
+const SLOT_WIDTH: Duration = Duration::from_secs(10);
+const NUM_SLOTS: usize = (LATENCY_WINDOW.as_secs() / SLOT_WIDTH.as_secs()) as usize;
+
+/// [`LatencyHistogram`] counts latencies, in microseconds, in buckets whose
+/// width grows with the latency. Recording is lock-free, so the histogram can
+/// be shared by all the workers.
+///
+/// Use [`LatencyHistogram::snapshot`] to read the percentiles.
+pub struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+}
+
+/// Point-in-time copy of a [`LatencyHistogram`].
+///
+/// It serializes to its count, percentiles and maximum in microseconds rather
+/// than to its buckets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogramSnapshot {
+    buckets: Vec<u64>,
+}
+
+/// The usual percentiles of a [`LatencyHistogramSnapshot`], `None` if nothing
+/// has been recorded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct LatencyPercentiles {
+    #[serde(rename = "p50_us", serialize_with = "serialize_micros")]
+    pub p50: Option<Duration>,
+    #[serde(rename = "p90_us", serialize_with = "serialize_micros")]
+    pub p90: Option<Duration>,
+    #[serde(rename = "p99_us", serialize_with = "serialize_micros")]
+    pub p99: Option<Duration>,
+}
+
+/// [`SlidingLatencyHistogram`] records latencies like [`LatencyHistogram`]
+/// but only reports the ones recorded over the last [`LATENCY_WINDOW`], so
+/// that the percentiles reflect the current tail latency without having to
+/// reset the histogram.
+///
+/// The window slides in steps of ten seconds. Like in [`RollingStats`], slots
+/// are reused without locking, so a latency recorded at the very moment its
+/// slot is recycled might be lost.
+///
+/// [`RollingStats`]: super::RollingStats
+pub struct SlidingLatencyHistogram {
+    start: Instant,
+    slots: Box<[SlidingSlot]>,
+}
+
+#[derive(Default)]
+struct SlidingSlot {
+    /// The period of [`SLOT_WIDTH`] since `start` recorded in this slot.
+    period: AtomicU64,
+    histogram: LatencyHistogram,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..NUM_BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+}
+
+impl fmt::Debug for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let snapshot = self.snapshot();
+        f.debug_struct("LatencyHistogram")
+            .field("count", &snapshot.count())
+            .field("p50", &snapshot.percentile(50.0))
+            .field("p99", &snapshot.percentile(99.0))
+            .field("max", &snapshot.max())
+            .finish()
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&self, latency: Duration) {
+        self.buckets[bucket_index(as_micros(latency))].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the recorded latencies.
+    pub fn snapshot(&self) -> LatencyHistogramSnapshot {
+        LatencyHistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+
+    /// Returns the recorded latencies and clears the histogram.
+    pub fn read_and_reset(&self) -> LatencyHistogramSnapshot {
+        LatencyHistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.swap(0, Ordering::Relaxed))
+                .collect(),
+        }
+    }
+}
+
+impl LatencyHistogramSnapshot {
+    /// Returns the number of recorded latencies.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Returns the latency below which `percentile` percent of the recorded
+    /// latencies fall, or `None` if nothing has been recorded. The latency is
+    /// rounded up to the upper bound of its bucket.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0 * count as f64).ceil() as u64).max(1);
+        let mut seen = 0u64;
+        self.buckets
+            .iter()
+            .position(|bucket| {
+                seen = seen.saturating_add(*bucket);
+                seen >= rank
+            })
+            .map(|index| Duration::from_micros(bucket_upper_bound(index)))
+    }
+
+    /// Returns the highest recorded latency, rounded up to the upper bound of
+    /// its bucket.
+    pub fn max(&self) -> Option<Duration> {
+        self.buckets
+            .iter()
+            .rposition(|bucket| *bucket > 0)
+            .map(|index| Duration::from_micros(bucket_upper_bound(index)))
+    }
+
+    /// Returns the latencies recorded since `older`.
+    pub fn diff(&self, older: &LatencyHistogramSnapshot) -> LatencyHistogramSnapshot {
+        LatencyHistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .enumerate()
+                .map(|(index, bucket)| {
+                    bucket.saturating_sub(older.buckets.get(index).copied().unwrap_or_default())
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds the latencies recorded in `other`.
+    pub fn merge(&mut self, other: &LatencyHistogramSnapshot) {
+        self.buckets.resize(NUM_BUCKETS, 0);
+        for (bucket, other) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket = bucket.saturating_add(*other);
+        }
+    }
+}
+
+impl LatencyHistogramSnapshot {
+    /// Returns the p50, p90 and p99 latencies.
+    pub fn percentiles(&self) -> LatencyPercentiles {
+        LatencyPercentiles {
+            p50: self.percentile(50.0),
+            p90: self.percentile(90.0),
+            p99: self.percentile(99.0),
+        }
+    }
+}
+
+impl serde::Serialize for LatencyHistogramSnapshot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let percentiles = self.percentiles();
+        let micros = |latency: Option<Duration>| latency.map(as_micros);
+        let mut state = serializer.serialize_struct("LatencyHistogramSnapshot", 5)?;
+        state.serialize_field("count", &self.count())?;
+        state.serialize_field("p50_us", &micros(percentiles.p50))?;
+        state.serialize_field("p90_us", &micros(percentiles.p90))?;
+        state.serialize_field("p99_us", &micros(percentiles.p99))?;
+        state.serialize_field("max_us", &micros(self.max()))?;
+        state.end()
+    }
+}
+
+fn as_micros(latency: Duration) -> u64 {
+    u64::try_from(latency.as_micros()).unwrap_or(u64::MAX)
+}
+
+fn serialize_micros<S: Serializer>(
+    latency: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&latency.map(as_micros), serializer)
+}
+
+impl Default for SlidingLatencyHistogram {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            slots: (0..NUM_SLOTS).map(|_| SlidingSlot::default()).collect(),
+        }
+    }
+}
+
+impl fmt::Debug for SlidingLatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlidingLatencyHistogram")
+            .field("percentiles", &self.percentiles())
+            .finish()
+    }
+}
+
+impl SlidingLatencyHistogram {
+    pub fn record(&self, latency: Duration) {
+        self.slot(self.now()).histogram.record(latency);
+    }
+
+    /// Returns the latencies recorded over the last [`LATENCY_WINDOW`].
+    pub fn snapshot(&self) -> LatencyHistogramSnapshot {
+        self.snapshot_at(self.now())
+    }
+
+    /// Returns the p50, p90 and p99 latencies over the last
+    /// [`LATENCY_WINDOW`].
+    pub fn percentiles(&self) -> LatencyPercentiles {
+        self.snapshot().percentiles()
+    }
+
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_secs() / SLOT_WIDTH.as_secs()
+    }
+
+    fn slot(&self, period: u64) -> &SlidingSlot {
+        let slot = &self.slots[period as usize % NUM_SLOTS];
+        if slot.period.load(Ordering::Acquire) != period
+            && slot.period.swap(period, Ordering::AcqRel) != period
+        {
+            slot.histogram.read_and_reset();
+        }
+        slot
+    }
+
+    fn snapshot_at(&self, now: u64) -> LatencyHistogramSnapshot {
+        let oldest = now.saturating_sub(NUM_SLOTS as u64 - 1);
+        let mut snapshot = LatencyHistogramSnapshot::default();
+        for slot in self
+            .slots
+            .iter()
+            .filter(|slot| (oldest..=now).contains(&slot.period.load(Ordering::Acquire)))
+        {
+            snapshot.merge(&slot.histogram.snapshot());
+        }
+        snapshot
+    }
+}
+
+fn bucket_index(micros: u64) -> usize {
+    if micros < NUM_SUB_BUCKETS as u64 {
+        return micros as usize;
+    }
+    let magnitude = u64::BITS - 1 - micros.leading_zeros();
+    let shift = magnitude - SUB_BUCKET_BITS;
+    let sub_bucket = (micros >> shift) as usize - NUM_SUB_BUCKETS;
+    (shift as usize + 1) * NUM_SUB_BUCKETS + sub_bucket
+}
+
+fn bucket_upper_bound(index: usize) -> u64 {
+    if index < NUM_SUB_BUCKETS {
+        return index as u64;
+    }
+    let shift = (index / NUM_SUB_BUCKETS - 1) as u32;
+    let sub_bucket = (index % NUM_SUB_BUCKETS) as u64;
+    let lower_bound = (NUM_SUB_BUCKETS as u64 + sub_bucket) << shift;
+    lower_bound.saturating_add((1 << shift) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_bounds() {
+        for micros in [0, 1, 7, 8, 9, 15, 16, 17, 1000, 123_456, u64::MAX] {
+            let index = bucket_index(micros);
+            assert!(index < NUM_BUCKETS);
+            let upper_bound = bucket_upper_bound(index);
+            assert!(micros <= upper_bound);
+            // The relative error is bounded by the sub-bucket width.
+            assert!(upper_bound - micros <= micros / NUM_SUB_BUCKETS as u64);
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.snapshot().percentile(50.0), None);
+        for micros in 1..=100 {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        let snapshot = histogram.read_and_reset();
+        assert_eq!(snapshot.count(), 100);
+        assert_eq!(snapshot.percentile(0.0), Some(Duration::from_micros(1)));
+        assert_eq!(snapshot.percentile(50.0), Some(Duration::from_micros(51)));
+        assert_eq!(snapshot.percentile(99.0), Some(Duration::from_micros(103)));
+        assert_eq!(snapshot.max(), Some(Duration::from_micros(103)));
+        assert_eq!(histogram.snapshot().count(), 0);
+
+        let mut merged = LatencyHistogramSnapshot::default();
+        merged.merge(&snapshot);
+        merged.merge(&snapshot);
+        assert_eq!(merged.count(), 200);
+        assert_eq!(merged.percentile(50.0), snapshot.percentile(50.0));
+    }
+
+    #[test]
+    fn test_sliding_latency_histogram() {
+        let histogram = SlidingLatencyHistogram::default();
+        assert_eq!(histogram.percentiles(), LatencyPercentiles::default());
+        histogram
+            .slot(0)
+            .histogram
+            .record(Duration::from_micros(100));
+        for _ in 0..9 {
+            histogram.slot(3).histogram.record(Duration::from_micros(1));
+        }
+
+        let percentiles = histogram.snapshot_at(5).percentiles();
+        assert_eq!(percentiles.p50, Some(Duration::from_micros(1)));
+        assert_eq!(percentiles.p99, Some(Duration::from_micros(103)));
+
+        // The first period has slid out of the window.
+        assert_eq!(histogram.snapshot_at(6).count(), 9);
+        histogram.slot(6).histogram.record(Duration::from_micros(2));
+        let snapshot = histogram.snapshot_at(6);
+        assert_eq!(snapshot.count(), 10);
+        assert_eq!(snapshot.max(), Some(Duration::from_micros(2)));
+    }
+}
@@ -0,0 +1,210 @@
+//! This module defines [`QuicErrorCodeStats`] which breaks the connection and
+//! write errors down by the underlying QUIC error.
+
+#[cfg(feature = "agave-unstable-api")]
+use qualifier_attr::qualifiers;
+use {
+    crate::QuicError,
+    quinn::{ConnectionError, TransportErrorCode, WriteError},
+    serde::{Serialize, Serializer},
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt,
+        sync::Mutex,
+    },
+};
+
+/// Maximum number of distinct error codes tracked by [`QuicErrorCodeStats`].
+/// Application codes are chosen by the peers, so a misbehaving peer must not be
+/// able to grow the stats without bound.
+pub const MAX_TRACKED_ERROR_CODES: usize = 256;
+
+/// QUIC transport error codes in this range carry a TLS alert.
+const CRYPTO_ERROR_RANGE: std::ops::RangeInclusive<u64> = 0x100..=0x1ff;
+
+/// The QUIC error behind a connection or a write error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum QuicErrorCode {
+    /// The peer closed the connection with this application error code.
+    ApplicationClose(u64),
+    /// The peer stopped the stream with this application error code.
+    StreamStopped(u64),
+    /// The connection was closed with this transport error code.
+    Transport(u64),
+    /// The TLS handshake failed with this alert, e.g. 42 (`bad_certificate`)
+    /// or 48 (`unknown_ca`) when a certificate has been rejected.
+    TlsAlert(u8),
+    VersionMismatch,
+    Reset,
+    TimedOut,
+    HandshakeTimeout,
+}
+
+impl QuicErrorCode {
+    /// Returns the QUIC error behind `err`, if any.
+    pub(crate) fn from_quic_error(err: &QuicError) -> Option<Self> {
+        match err {
+            QuicError::Connection(err) => Self::from_connection_error(err),
+            QuicError::StreamWrite(WriteError::Stopped(code)) => {
+                Some(Self::StreamStopped(code.into_inner()))
+            }
+            QuicError::StreamWrite(WriteError::ConnectionLost(err)) => {
+                Self::from_connection_error(err)
+            }
+            QuicError::HandshakeTimeout => Some(Self::HandshakeTimeout),
+            QuicError::StreamWrite(_) | QuicError::Connect(_) | QuicError::Endpoint(_) => None,
+        }
+    }
+
+    /// Returns the QUIC error behind `err`, if any. The codes are chosen by the
+    /// peers, so any value must be handled.
+    #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+    fn from_connection_error(err: &ConnectionError) -> Option<Self> {
+        match err {
+            ConnectionError::ApplicationClosed(close) => {
+                Some(Self::ApplicationClose(close.error_code.into_inner()))
+            }
+            ConnectionError::ConnectionClosed(close) => {
+                Some(Self::from_transport(close.error_code))
+            }
+            ConnectionError::TransportError(err) => Some(Self::from_transport(err.code)),
+            ConnectionError::VersionMismatch => Some(Self::VersionMismatch),
+            ConnectionError::Reset => Some(Self::Reset),
+            ConnectionError::TimedOut => Some(Self::TimedOut),
+            ConnectionError::LocallyClosed | ConnectionError::CidsExhausted => None,
+        }
+    }
+
+    fn from_transport(code: TransportErrorCode) -> Self {
+        let code = u64::from(code);
+        if CRYPTO_ERROR_RANGE.contains(&code) {
+            Self::TlsAlert(code as u8)
+        } else {
+            Self::Transport(code)
+        }
+    }
+}
+
+impl fmt::Display for QuicErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ApplicationClose(code) => write!(f, "application_close_{code}"),
+            Self::StreamStopped(code) => write!(f, "stream_stopped_{code}"),
+            Self::Transport(code) => write!(f, "transport_{code:#x}"),
+            Self::TlsAlert(alert) => write!(f, "tls_alert_{alert}"),
+            Self::VersionMismatch => write!(f, "version_mismatch"),
+            Self::Reset => write!(f, "reset"),
+            Self::TimedOut => write!(f, "timed_out"),
+            Self::HandshakeTimeout => write!(f, "handshake_timeout"),
+        }
+    }
+}
+
+// Serialized as in `Display`, so that the codes can be keys of JSON objects.
+impl Serialize for QuicErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// [`QuicErrorCodeStats`] counts the errors by [`QuicErrorCode`], so that a
+/// high number of connection errors can be told apart between, for instance,
+/// peers rejecting the certificate and peers closing the connection because
+/// of their connection limits.
+///
+/// Once [`MAX_TRACKED_ERROR_CODES`] codes are tracked, new codes are not
+/// counted here, they are still counted by the aggregated counters of
+/// [`SendTransactionStats`].
+///
+/// [`SendTransactionStats`]: crate::SendTransactionStats
+#[derive(Debug, Default)]
+pub struct QuicErrorCodeStats {
+    codes: Mutex<HashMap<QuicErrorCode, u64>>,
+}
+
+impl QuicErrorCodeStats {
+    pub fn record(&self, code: QuicErrorCode) {
+        let mut codes = self.codes.lock().unwrap();
+        if codes.len() >= MAX_TRACKED_ERROR_CODES && !codes.contains_key(&code) {
+            return;
+        }
+        let count = codes.entry(code).or_default();
+        *count = count.saturating_add(1);
+    }
+
+    /// Returns the number of errors recorded with `code`.
+    pub fn get(&self, code: &QuicErrorCode) -> u64 {
+        self.codes
+            .lock()
+            .unwrap()
+            .get(code)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the counts sorted by code.
+    pub fn to_non_atomic(&self) -> BTreeMap<QuicErrorCode, u64> {
+        self.codes.lock().unwrap().clone().into_iter().collect()
+    }
+
+    /// Returns the counts sorted by code and clears them.
+    pub fn read_and_reset(&self) -> BTreeMap<QuicErrorCode, u64> {
+        std::mem::take(&mut *self.codes.lock().unwrap())
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        quinn::{ApplicationClose, ConnectionClose, VarInt},
+    };
+
+    #[test]
+    fn test_quic_error_codes() {
+        let application_closed =
+            QuicError::Connection(ConnectionError::ApplicationClosed(ApplicationClose {
+                error_code: VarInt::from_u32(1),
+                reason: Default::default(),
+            }));
+        let certificate_rejected =
+            QuicError::Connection(ConnectionError::ConnectionClosed(ConnectionClose {
+                error_code: TransportErrorCode::crypto(42),
+                frame_type: None,
+                reason: Default::default(),
+            }));
+        let connection_lost =
+            QuicError::StreamWrite(WriteError::ConnectionLost(ConnectionError::TimedOut));
+        assert_eq!(
+            QuicErrorCode::from_quic_error(&application_closed),
+            Some(QuicErrorCode::ApplicationClose(1))
+        );
+        assert_eq!(
+            QuicErrorCode::from_quic_error(&certificate_rejected),
+            Some(QuicErrorCode::TlsAlert(42))
+        );
+        assert_eq!(
+            QuicErrorCode::from_quic_error(&connection_lost),
+            Some(QuicErrorCode::TimedOut)
+        );
+        assert_eq!(
+            QuicErrorCode::from_quic_error(&QuicError::StreamWrite(WriteError::ClosedStream)),
+            None
+        );
+        assert_eq!(QuicErrorCode::TlsAlert(42).to_string(), "tls_alert_42");
+
+        let stats = QuicErrorCodeStats::default();
+        for code in 0..MAX_TRACKED_ERROR_CODES as u64 + 1 {
+            stats.record(QuicErrorCode::ApplicationClose(code));
+        }
+        stats.record(QuicErrorCode::ApplicationClose(0));
+        assert_eq!(stats.get(&QuicErrorCode::ApplicationClose(0)), 2);
+        let codes = stats.read_and_reset();
+        assert_eq!(codes.len(), MAX_TRACKED_ERROR_CODES);
+        assert_eq!(codes[&QuicErrorCode::ApplicationClose(0)], 2);
+        assert!(stats.to_non_atomic().is_empty());
+    }
+}
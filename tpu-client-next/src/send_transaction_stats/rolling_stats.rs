@@ -0,0 +1,176 @@
+//! This module defines [`RollingStats`] which counts the transactions sent
+//! and the errors over the last few minutes.
+
+use {
+    serde::Serializer,
+    serde_derive::Serialize,
+    std::{
+        fmt,
+        sync::atomic::{AtomicU64, Ordering},
+        time::{Duration, Instant},
+    },
+};
+
+/// The longest window covered by [`RollingStats`].
+pub const MAX_ROLLING_WINDOW: Duration = Duration::from_secs(300);
+
+const NUM_SLOTS: usize = MAX_ROLLING_WINDOW.as_secs() as usize;
+
+/// [`RollingStats`] counts the transactions sent and the errors in one second
+/// slots covering [`MAX_ROLLING_WINDOW`], so that the recent rates are
+/// available without diffing the totals of [`SendTransactionStats`].
+///
+/// Slots are reused without locking, so an event recorded at the very moment
+/// its slot is recycled might be lost. The counts are meant for dashboards,
+/// not accounting.
+///
+/// [`SendTransactionStats`]: crate::SendTransactionStats
+pub struct RollingStats {
+    start: Instant,
+    slots: Box<[Slot]>,
+}
+
+#[derive(Default)]
+struct Slot {
+    /// The second since `start` counted in this slot.
+    second: AtomicU64,
+    sent: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// Counts over a window of [`RollingStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct RollingWindow {
+    #[serde(rename = "window_secs", serialize_with = "serialize_secs")]
+    pub window: Duration,
+    pub sent: u64,
+    pub errors: u64,
+}
+
+impl Default for RollingStats {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            slots: (0..NUM_SLOTS).map(|_| Slot::default()).collect(),
+        }
+    }
+}
+
+impl fmt::Debug for RollingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RollingStats")
+            .field("last_10s", &self.last_10s())
+            .field("last_1m", &self.last_1m())
+            .field("last_5m", &self.last_5m())
+            .finish()
+    }
+}
+
+impl RollingStats {
+    pub fn record_sent(&self) {
+        self.slot(self.now()).sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self) {
+        self.slot(self.now()).errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the counts of the last `window`, which is capped by
+    /// [`MAX_ROLLING_WINDOW`]. The current second is included.
+    pub fn window(&self, window: Duration) -> RollingWindow {
+        self.window_at(self.now(), window)
+    }
+
+    pub fn last_10s(&self) -> RollingWindow {
+        self.window(Duration::from_secs(10))
+    }
+
+    pub fn last_1m(&self) -> RollingWindow {
+        self.window(Duration::from_secs(60))
+    }
+
+    pub fn last_5m(&self) -> RollingWindow {
+        self.window(Duration::from_secs(300))
+    }
+
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_secs()
+    }
+
+    fn slot(&self, second: u64) -> &Slot {
+        let slot = &self.slots[second as usize % NUM_SLOTS];
+        if slot.second.load(Ordering::Acquire) != second
+            && slot.second.swap(second, Ordering::AcqRel) != second
+        {
+            slot.sent.store(0, Ordering::Relaxed);
+            slot.errors.store(0, Ordering::Relaxed);
+        }
+        slot
+    }
+
+    fn window_at(&self, now: u64, window: Duration) -> RollingWindow {
+        let window = window.min(MAX_ROLLING_WINDOW);
+        let num_seconds = window.as_secs().max(1);
+        let oldest = now.saturating_sub(num_seconds.saturating_sub(1));
+        self.slots
+            .iter()
+            .filter(|slot| (oldest..=now).contains(&slot.second.load(Ordering::Acquire)))
+            .fold(
+                RollingWindow {
+                    window,
+                    ..RollingWindow::default()
+                },
+                |counts, slot| RollingWindow {
+                    sent: counts
+                        .sent
+                        .saturating_add(slot.sent.load(Ordering::Relaxed)),
+                    errors: counts
+                        .errors
+                        .saturating_add(slot.errors.load(Ordering::Relaxed)),
+                    ..counts
+                },
+            )
+    }
+}
+
+fn serialize_secs<S: Serializer>(window: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(window.as_secs())
+}
+
+impl RollingWindow {
+    /// Returns the number of transactions sent per second.
+    pub fn sent_per_second(&self) -> f64 {
+        self.sent as f64 / self.window.as_secs_f64().max(1.0)
+    }
+
+    /// Returns the number of errors per second.
+    pub fn errors_per_second(&self) -> f64 {
+        self.errors as f64 / self.window.as_secs_f64().max(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_windows() {
+        let stats = RollingStats::default();
+        for second in [0, 1, 1, 30, 200] {
+            stats.slot(second).sent.fetch_add(1, Ordering::Relaxed);
+        }
+        stats.slot(200).errors.fetch_add(2, Ordering::Relaxed);
+
+        let window = stats.window_at(200, Duration::from_secs(10));
+        assert_eq!((window.sent, window.errors), (1, 2));
+        assert_eq!(window.errors_per_second(), 0.2);
+        assert_eq!(stats.window_at(200, Duration::from_secs(300)).sent, 5);
+        assert_eq!(stats.window_at(200, Duration::from_secs(3600)).sent, 5);
+
+        // The slot of the second 1 is recycled for the second 301.
+        stats.slot(301).sent.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(stats.window_at(301, Duration::from_secs(300)).sent, 3);
+        assert_eq!(stats.window_at(301, Duration::from_secs(1)).sent, 1);
+    }
+}
@@ -0,0 +1,78 @@
+//! This module defines [`StatsObserver`] which lets the application follow
+//! the events accounted in [`SendTransactionStats`] as they happen.
+//!
+//! [`SendTransactionStats`]: crate::SendTransactionStats
+
+use {
+    super::DropReason,
+    crate::transaction_batch::TransactionMetadata,
+    std::{
+        error::Error,
+        fmt,
+        net::SocketAddr,
+        sync::{Arc, RwLock},
+    },
+};
+
+/// [`StatsObserver`] is notified of the significant events of sending
+/// transactions, so that they can be bridged into any telemetry system. All
+/// the methods do nothing by default.
+///
+/// The methods are called from the workers and the scheduler, so they must
+/// return quickly and never block.
+pub trait StatsObserver: Send + Sync {
+    /// Called when establishing or using the connection to `peer` fails.
+    fn on_connection_error(&self, _peer: SocketAddr, _error: &(dyn Error + 'static)) {}
+
+    /// Called when `num_transactions` for `peer` are dropped without being
+    /// sent for `reason`.
+    fn on_batch_dropped(&self, _peer: SocketAddr, _reason: DropReason, _num_transactions: usize) {}
+
+    /// Called when a batch is not handed to the worker of `peer` because the
+    /// worker can't keep up and its queue is full. The batch is also reported
+    /// as dropped with [`DropReason::Overflow`].
+    fn on_throttled(&self, _peer: SocketAddr, _num_transactions: usize) {}
+
+    /// Called when a transaction which carries `metadata` has been sent to
+    /// `peer`. Only the transactions of batches with metadata attached are
+    /// reported, see [`TransactionBatch::with_metadata`].
+    ///
+    /// [`TransactionBatch::with_metadata`]: crate::transaction_batch::TransactionBatch::with_metadata
+    fn on_transaction_sent(&self, _peer: SocketAddr, _metadata: TransactionMetadata) {}
+
+    /// Called for each transaction which carries `metadata` when it is
+    /// dropped for `reason` without being sent to `peer`, in addition to
+    /// [`StatsObserver::on_batch_dropped`].
+    fn on_transaction_dropped(
+        &self,
+        _peer: SocketAddr,
+        _reason: DropReason,
+        _metadata: TransactionMetadata,
+    ) {
+    }
+}
+
+/// Holds the observer installed with
+/// [`SendTransactionStats::set_observer`].
+///
+/// [`SendTransactionStats::set_observer`]: crate::SendTransactionStats::set_observer
+#[derive(Default)]
+pub(crate) struct ObserverSlot(RwLock<Option<Arc<dyn StatsObserver>>>);
+
+impl ObserverSlot {
+    pub(crate) fn set(&self, observer: Arc<dyn StatsObserver>) {
+        *self.0.write().unwrap() = Some(observer);
+    }
+
+    pub(crate) fn get(&self) -> Option<Arc<dyn StatsObserver>> {
+        self.0.read().unwrap().clone()
+    }
+}
+
+impl fmt::Debug for ObserverSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObserverSlot")
+            .field("is_set", &self.0.read().unwrap().is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,128 @@
+//! This module defines [`ThroughputStats`] which keeps exponentially weighted
+//! rates of the transactions and the batches sent.
+
+use {
+    serde_derive::Serialize,
+    std::{
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+
+/// Time constant of the exponentially weighted rates: a change of throughput
+/// is reflected at about 63% after this duration.
+pub const THROUGHPUT_TIME_CONSTANT: Duration = Duration::from_secs(10);
+
+/// The rates are updated at most this often, the events in between are
+/// accumulated.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// [`ThroughputStats`] maintains the exponentially weighted number of
+/// transactions and batches sent per second. A batch sent to several leaders
+/// is counted once per leader.
+#[derive(Debug)]
+pub struct ThroughputStats {
+    state: Mutex<ThroughputState>,
+}
+
+#[derive(Debug)]
+struct ThroughputState {
+    last_update: Instant,
+    /// Events since `last_update`.
+    transactions: u64,
+    batches: u64,
+    transactions_per_second: f64,
+    batches_per_second: f64,
+}
+
+/// Rates reported by [`ThroughputStats::throughput`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct Throughput {
+    pub transactions_per_second: f64,
+    pub batches_per_second: f64,
+}
+
+impl Default for ThroughputStats {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(ThroughputState::new(Instant::now())),
+        }
+    }
+}
+
+impl ThroughputStats {
+    /// Records a batch of which `num_transactions` have been sent.
+    pub fn record_batch(&self, num_transactions: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.update(Instant::now());
+        state.transactions = state.transactions.saturating_add(num_transactions as u64);
+        state.batches = state.batches.saturating_add(1);
+    }
+
+    /// Returns the current rates, which decay when nothing is sent.
+    pub fn throughput(&self) -> Throughput {
+        let mut state = self.state.lock().unwrap();
+        state.update(Instant::now());
+        Throughput {
+            transactions_per_second: state.transactions_per_second,
+            batches_per_second: state.batches_per_second,
+        }
+    }
+}
+
+impl ThroughputState {
+    fn new(now: Instant) -> Self {
+        Self {
+            last_update: now,
+            transactions: 0,
+            batches: 0,
+            transactions_per_second: 0.0,
+            batches_per_second: 0.0,
+        }
+    }
+
+    /// Folds the events since the last update into the rates.
+    fn update(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_update);
+        if elapsed < UPDATE_INTERVAL {
+            return;
+        }
+        let elapsed = elapsed.as_secs_f64();
+        let alpha = 1.0 - (-elapsed / THROUGHPUT_TIME_CONSTANT.as_secs_f64()).exp();
+        let ewma = |rate: f64, events: u64| rate + alpha * (events as f64 / elapsed - rate);
+        self.transactions_per_second = ewma(self.transactions_per_second, self.transactions);
+        self.batches_per_second = ewma(self.batches_per_second, self.batches);
+        self.transactions = 0;
+        self.batches = 0;
+        self.last_update = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput() {
+        let start = Instant::now();
+        let mut state = ThroughputState::new(start);
+        for second in 1..=100 {
+            state.transactions += 64;
+            state.batches += 1;
+            state.update(start + Duration::from_secs(second));
+        }
+        assert!((state.transactions_per_second - 64.0).abs() < 0.01);
+        assert!((state.batches_per_second - 1.0).abs() < 0.01);
+
+        // Events are accumulated until the next update.
+        state.transactions += 64;
+        state.update(start + Duration::from_millis(100_500));
+        assert_eq!(state.transactions, 64);
+
+        // The rates decay when nothing is sent.
+        state.transactions = 0;
+        state.update(start + Duration::from_secs(110));
+        assert!((state.transactions_per_second - 64.0 / std::f64::consts::E).abs() < 0.01);
+    }
+}
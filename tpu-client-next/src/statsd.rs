@@ -0,0 +1,175 @@
+//! If `statsd` feature is activated, this module provides
+//! `report_to_statsd` method for [`SendTransactionStats`] which periodically
+//! pushes the main counters and gauges to a StatsD agent over UDP. Tags use
+//! the DogStatsD extension of the protocol.
+use {
+    crate::{send_transaction_stats::SendTransactionStatsNonAtomic, SendTransactionStats},
+    std::{io, net::SocketAddr, sync::Arc, time::Duration},
+    tokio::{net::UdpSocket, select, time::interval},
+    tokio_util::sync::CancellationToken,
+};
+
+/// Maximum size of a datagram, it fits in the MTU of common networks.
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+/// Percentiles of the latency histograms reported as gauges.
+const LATENCY_PERCENTILES: [(&str, f64); 3] = [("p50", 50.0), ("p90", 90.0), ("p99", 99.0)];
+
+/// Configuration of the StatsD exporter.
+#[derive(Debug, Clone)]
+pub struct StatsdConfig {
+    /// Address of the StatsD agent.
+    pub agent_address: SocketAddr,
+    /// Prefix of the metric names, e.g. `tpu_client_next`.
+    pub prefix: String,
+    /// Tags attached to every metric.
+    pub tags: Vec<(String, String)>,
+    pub reporting_interval: Duration,
+}
+
+impl SendTransactionStats {
+    /// Pushes the increments of the counters and the latency percentiles to
+    /// the StatsD agent every `reporting_interval` from `socket`.
+    ///
+    /// Unlike `report_to_influxdb`, the stats are not reset, so that both can
+    /// be used at the same time. Returns an error if the socket cannot be
+    /// registered with the runtime, sending failures are ignored like StatsD
+    /// clients usually do.
+    pub async fn report_to_statsd(
+        self: Arc<Self>,
+        socket: std::net::UdpSocket,
+        config: StatsdConfig,
+        cancel: CancellationToken,
+    ) -> io::Result<()> {
+        socket.set_nonblocking(true)?;
+        let socket = UdpSocket::from_std(socket)?;
+        let mut interval = interval(config.reporting_interval);
+        let mut last = self.to_non_atomic();
+        loop {
+            select! {
+                _ = interval.tick() => {
+                    let view = self.to_non_atomic();
+                    for datagram in encode_statsd(&self, &view, &last, &config) {
+                        let _ = socket.send_to(datagram.as_bytes(), config.agent_address).await;
+                    }
+                    last = view;
+                }
+                _ = cancel.cancelled() => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Encodes the metrics into datagrams of at most [`MAX_DATAGRAM_SIZE`] bytes.
+fn encode_statsd(
+    stats: &SendTransactionStats,
+    view: &SendTransactionStatsNonAtomic,
+    last: &SendTransactionStatsNonAtomic,
+    config: &StatsdConfig,
+) -> Vec<String> {
+    let tags = if config.tags.is_empty() {
+        String::new()
+    } else {
+        let tags: Vec<_> = config
+            .tags
+            .iter()
+            .map(|(key, value)| format!("{key}:{value}"))
+            .collect();
+        format!("|#{}", tags.join(","))
+    };
+    let prefix = &config.prefix;
+
+    let mut lines = Vec::new();
+    for ((field, value), (_, last)) in view.fields().into_iter().zip(last.fields()) {
+        // The counters only decrease if the stats are reset elsewhere, in that
+        // case the whole value is reported.
+        let delta = value.checked_sub(last).unwrap_or(value);
+        if delta > 0 {
+            lines.push(format!("{prefix}.{field}:{delta}|c{tags}"));
+        }
+    }
+    let latency = &stats.latency;
+    for (stage, histogram) in [
+        ("queueing", &latency.queueing),
+        ("sending", &latency.sending),
+        ("end_to_end", &latency.end_to_end),
+    ] {
+        let snapshot = histogram.snapshot();
+        for (name, percentile) in LATENCY_PERCENTILES {
+            if let Some(latency) = snapshot.percentile(percentile) {
+                let latency_ms = latency.as_micros() as f64 / 1000.0;
+                lines.push(format!(
+                    "{prefix}.latency.{stage}.{name}:{latency_ms}|g{tags}"
+                ));
+            }
+        }
+    }
+    lines.push(format!(
+        "{prefix}.tracked_leaders:{}|g{tags}",
+        stats.leaders.len()
+    ));
+
+    let mut datagrams = Vec::new();
+    let mut datagram = String::new();
+    for line in lines {
+        if !datagram.is_empty() && datagram.len() + line.len() + 1 > MAX_DATAGRAM_SIZE {
+            datagrams.push(std::mem::take(&mut datagram));
+        }
+        if !datagram.is_empty() {
+            datagram.push('\n');
+        }
+        datagram.push_str(&line);
+    }
+    if !datagram.is_empty() {
+        datagrams.push(datagram);
+    }
+    datagrams
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{net::Ipv4Addr, sync::atomic::Ordering},
+    };
+
+    fn test_config(tags: Vec<(String, String)>) -> StatsdConfig {
+        StatsdConfig {
+            agent_address: SocketAddr::from((Ipv4Addr::LOCALHOST, 8125)),
+            prefix: "tpu".to_string(),
+            tags,
+            reporting_interval: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn test_encode_statsd() {
+        let stats = SendTransactionStats::default();
+        let last = stats.to_non_atomic();
+        stats.successfully_sent.fetch_add(5, Ordering::Relaxed);
+        stats.workers_evicted.fetch_add(1, Ordering::Relaxed);
+        stats.latency.sending.record(Duration::from_millis(2));
+
+        let config = test_config(vec![("scheduler".to_string(), "forwarding".to_string())]);
+        let datagrams = encode_statsd(&stats, &stats.to_non_atomic(), &last, &config);
+        assert_eq!(
+            datagrams,
+            vec![[
+                "tpu.successfully_sent:5|c|#scheduler:forwarding",
+                "tpu.workers_evicted:1|c|#scheduler:forwarding",
+                "tpu.latency.sending.p50:2.047|g|#scheduler:forwarding",
+                "tpu.latency.sending.p90:2.047|g|#scheduler:forwarding",
+                "tpu.latency.sending.p99:2.047|g|#scheduler:forwarding",
+                "tpu.tracked_leaders:0|g|#scheduler:forwarding",
+            ]
+            .join("\n")]
+        );
+
+        // Only the increments are reported.
+        let view = stats.to_non_atomic();
+        let datagrams = encode_statsd(&stats, &view, &view, &test_config(vec![]));
+        assert!(!datagrams[0].contains("successfully_sent"));
+        assert!(datagrams[0].ends_with("tpu.tracked_leaders:0|g"));
+    }
+}
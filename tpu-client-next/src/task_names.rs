@@ -0,0 +1,33 @@
+//! This module spawns the tasks of the crate with names, so that a runtime
+//! stall can be attributed to a specific worker in tokio-console.
+//!
+//! Tokio only names the tasks when the application is built with
+//! `--cfg tokio_unstable` and the `tokio-console` feature is activated, the
+//! tasks are spawned as usual otherwise.
+
+use {std::future::Future, tokio::task::JoinHandle};
+
+/// Spawns `future` on a task named by `name`, which is only called if the
+/// name is used.
+#[cfg(all(tokio_unstable, feature = "tokio-console"))]
+pub(crate) fn spawn_named<F>(name: impl FnOnce() -> String, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::task::Builder::new()
+        .name(&name())
+        .spawn(future)
+        .expect("spawning a task on the current runtime never fails")
+}
+
+/// Spawns `future` on a task named by `name`, which is only called if the
+/// name is used.
+#[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+pub(crate) fn spawn_named<F>(_name: impl FnOnce() -> String, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(future)
+}
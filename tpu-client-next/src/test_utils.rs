@@ -0,0 +1,308 @@
+//! This module provides [`TestTpuServer`], an in-process QUIC server which
+//! accepts the connections of the client like the TPU of a validator, so that
+//! the users of this crate can test against it without running a validator,
+//! and [`MockLeaderUpdater`] which returns scripted leaders. The faults of the
+//! transport of the client can be injected with [`set_chaos`], and the time
+//! of the scheduler can be driven by [`MockClock`].
+
+pub mod chaos;
+pub mod clock;
+pub mod mock_leader_updater;
+
+pub use {
+    chaos::{set_chaos, ChaosConfig, CHAOS_THROTTLE_CODE},
+    clock::MockClock,
+    mock_leader_updater::{MockLeaderSchedule, MockLeaderUpdater},
+};
+use {
+    quinn::{crypto::rustls::QuicServerConfig, Connection, Endpoint, Incoming, ServerConfig},
+    solana_keypair::Keypair,
+    solana_streamer::{nonblocking::quic::ALPN_TPU_PROTOCOL_ID, packet::PACKET_DATA_SIZE},
+    solana_tls_utils::{new_dummy_x509_certificate, tls_server_config_builder},
+    std::{
+        io,
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex, RwLock,
+        },
+        time::Duration,
+    },
+    tokio::{sync::Notify, task::JoinHandle, time::timeout},
+    tokio_util::sync::CancellationToken,
+};
+
+/// A fault injected by [`TestTpuServer`], see [`TestTpuServer::set_fault`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fault {
+    /// The incoming connections are ignored, so that the handshakes time out.
+    IgnoreHandshakes,
+    /// The connections are closed with `code` right after the handshake.
+    CloseConnections { code: u32 },
+    /// The streams are stopped with `code` instead of being read, so that the
+    /// transactions are not received.
+    StopStreams { code: u32 },
+}
+
+/// State shared by the server and its connections.
+#[derive(Default)]
+struct ServerState {
+    transactions: Mutex<Vec<Vec<u8>>>,
+    received: Notify,
+    num_connections: AtomicUsize,
+    fault: RwLock<Option<Fault>>,
+}
+
+impl ServerState {
+    fn fault(&self) -> Option<Fault> {
+        *self.fault.read().unwrap()
+    }
+}
+
+/// [`TestTpuServer`] accepts the QUIC connections with the TPU protocol and
+/// records the transactions received over their unidirectional streams, one
+/// transaction per stream. Unlike the TPU of a validator, it neither limits
+/// the connections nor the streams by stake.
+///
+/// Faults can be injected with [`TestTpuServer::set_fault`] to test how the
+/// client handles an unreachable or misbehaving leader.
+///
+/// The server runs until it is dropped or [`TestTpuServer::shutdown`] is
+/// called.
+pub struct TestTpuServer {
+    endpoint: Endpoint,
+    state: Arc<ServerState>,
+    cancel: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+impl TestTpuServer {
+    /// Starts a server on an unused port of localhost. It must be called from
+    /// a tokio runtime.
+    pub fn start() -> io::Result<Self> {
+        Self::start_on(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0))
+    }
+
+    /// Starts a server bound to `address`. It must be called from a tokio
+    /// runtime.
+    pub fn start_on(address: SocketAddr) -> io::Result<Self> {
+        let (cert, key) = new_dummy_x509_certificate(&Keypair::new());
+        let mut crypto = tls_server_config_builder()
+            .with_single_cert(vec![cert], key)
+            .expect("Failed to set QUIC server certificates");
+        crypto.alpn_protocols = vec![ALPN_TPU_PROTOCOL_ID.to_vec()];
+        let server_config = ServerConfig::with_crypto(Arc::new(
+            QuicServerConfig::try_from(crypto).expect("Failed to create QUIC server config"),
+        ));
+        let endpoint = Endpoint::server(server_config, address)?;
+
+        let state = Arc::new(ServerState::default());
+        let cancel = CancellationToken::new();
+        let handle = tokio::spawn(run_server(endpoint.clone(), state.clone(), cancel.clone()));
+        Ok(Self {
+            endpoint,
+            state,
+            cancel,
+            handle,
+        })
+    }
+
+    /// Returns the address the server is bound to, to be used as the TPU
+    /// address of the leader.
+    pub fn address(&self) -> SocketAddr {
+        self.endpoint
+            .local_addr()
+            .expect("Failed to get the local address")
+    }
+
+    /// Returns the transactions received so far, in the order they were
+    /// received.
+    pub fn transactions(&self) -> Vec<Vec<u8>> {
+        self.state.transactions.lock().unwrap().clone()
+    }
+
+    /// Returns the transactions received so far and clears them.
+    pub fn take_transactions(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut *self.state.transactions.lock().unwrap())
+    }
+
+    /// Waits until at least `count` transactions have been received or
+    /// `wait_timeout` has elapsed, and returns whether they were received.
+    pub async fn wait_for_transactions(&self, count: usize, wait_timeout: Duration) -> bool {
+        timeout(wait_timeout, async {
+            loop {
+                let received = self.state.received.notified();
+                if self.state.transactions.lock().unwrap().len() >= count {
+                    return;
+                }
+                received.await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+
+    /// Returns the number of connections which completed the handshake.
+    pub fn num_connections(&self) -> usize {
+        self.state.num_connections.load(Ordering::Relaxed)
+    }
+
+    /// Injects `fault` in the handling of the connections and the streams
+    /// accepted from now on, `None` restores the normal behavior.
+    pub fn set_fault(&self, fault: Option<Fault>) {
+        *self.state.fault.write().unwrap() = fault;
+    }
+
+    /// Closes the connections and stops the server.
+    pub async fn shutdown(mut self) {
+        self.cancel.cancel();
+        self.endpoint.close(0u32.into(), b"shutdown");
+        let _ = (&mut self.handle).await;
+        self.endpoint.wait_idle().await;
+    }
+}
+
+impl Drop for TestTpuServer {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+        self.endpoint.close(0u32.into(), b"shutdown");
+    }
+}
+
+async fn run_server(endpoint: Endpoint, state: Arc<ServerState>, cancel: CancellationToken) {
+    loop {
+        let incoming = tokio::select! {
+            incoming = endpoint.accept() => incoming,
+            () = cancel.cancelled() => return,
+        };
+        let Some(incoming) = incoming else {
+            return;
+        };
+        tokio::spawn(handle_incoming(incoming, state.clone(), cancel.clone()));
+    }
+}
+
+async fn handle_incoming(incoming: Incoming, state: Arc<ServerState>, cancel: CancellationToken) {
+    if state.fault() == Some(Fault::IgnoreHandshakes) {
+        incoming.ignore();
+        return;
+    }
+    let Ok(connecting) = incoming.accept() else {
+        return;
+    };
+    let Ok(connection) = connecting.await else {
+        return;
+    };
+    state.num_connections.fetch_add(1, Ordering::Relaxed);
+    if let Some(Fault::CloseConnections { code }) = state.fault() {
+        connection.close(code.into(), b"fault");
+        return;
+    }
+    tokio::select! {
+        () = handle_connection(&connection, &state) => {},
+        () = cancel.cancelled() => {},
+    }
+}
+
+async fn handle_connection(connection: &Connection, state: &Arc<ServerState>) {
+    while let Ok(mut stream) = connection.accept_uni().await {
+        if let Some(Fault::StopStreams { code }) = state.fault() {
+            let _ = stream.stop(code.into());
+            continue;
+        }
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Ok(transaction) = stream.read_to_end(PACKET_DATA_SIZE).await {
+                state.transactions.lock().unwrap().push(transaction);
+                state.received.notify_waiters();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::{
+            connection_workers_scheduler::BindTarget,
+            quic_networking::{
+                create_client_config, create_client_endpoint, send_data_over_stream,
+                QuicClientCertificate,
+            },
+        },
+        quinn::ConnectionError,
+        solana_tls_utils::socket_addr_to_quic_server_name,
+    };
+
+    pub(crate) fn client_endpoint() -> Endpoint {
+        let client_config = create_client_config(&QuicClientCertificate::new(None));
+        let bind = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+        create_client_endpoint(BindTarget::Address(bind), client_config).unwrap()
+    }
+
+    pub(crate) async fn connect(endpoint: &Endpoint, server: &TestTpuServer) -> Connection {
+        let address = server.address();
+        endpoint
+            .connect(address, &socket_addr_to_quic_server_name(address))
+            .unwrap()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_server_records_transactions() {
+        let server = TestTpuServer::start().unwrap();
+        let endpoint = client_endpoint();
+        let connection = connect(&endpoint, &server).await;
+        for transaction in [vec![1u8; 10], vec![2u8; 20]] {
+            send_data_over_stream(&connection, transaction.into())
+                .await
+                .unwrap();
+        }
+        assert!(
+            server
+                .wait_for_transactions(2, Duration::from_secs(5))
+                .await
+        );
+        let mut transactions = server.take_transactions();
+        transactions.sort();
+        assert_eq!(transactions, vec![vec![1u8; 10], vec![2u8; 20]]);
+        assert!(server.transactions().is_empty());
+        assert_eq!(server.num_connections(), 1);
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_server_faults() {
+        let server = TestTpuServer::start().unwrap();
+        let endpoint = client_endpoint();
+
+        server.set_fault(Some(Fault::CloseConnections { code: 7 }));
+        let connection = connect(&endpoint, &server).await;
+        let err = connection.closed().await;
+        assert!(
+            matches!(err, ConnectionError::ApplicationClosed(close) if close.error_code == 7u32.into())
+        );
+
+        server.set_fault(Some(Fault::StopStreams { code: 9 }));
+        let connection = connect(&endpoint, &server).await;
+        let mut stream = connection.open_uni().await.unwrap();
+        stream.write_all(&[1u8; 10]).await.unwrap();
+        stream.finish().unwrap();
+        assert_eq!(stream.stopped().await.unwrap(), Some(9u32.into()));
+        assert!(server.transactions().is_empty());
+
+        server.set_fault(Some(Fault::IgnoreHandshakes));
+        let address = server.address();
+        let connecting = endpoint
+            .connect(address, &socket_addr_to_quic_server_name(address))
+            .unwrap();
+        assert!(timeout(Duration::from_millis(200), connecting)
+            .await
+            .is_err());
+        assert_eq!(server.num_connections(), 2);
+        server.shutdown().await;
+    }
+}
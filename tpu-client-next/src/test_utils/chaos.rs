@@ -0,0 +1,198 @@
+//! This module provides the injection of faults in the transport, configured
+//! per peer with [`set_chaos`], to exercise the retry logic of the workers.
+
+use {
+    crate::QuicError,
+    quinn::{Connection, ConnectionError, VarInt, WriteError},
+    std::{
+        collections::HashMap,
+        net::SocketAddr,
+        sync::{LazyLock, Mutex},
+        time::Duration,
+    },
+    tokio::time::sleep,
+};
+
+/// Application error code of the streams failed by the injected throttling,
+/// the same as the one used by the streamer for the connections exceeding
+/// its limits.
+pub const CHAOS_THROTTLE_CODE: u32 = 4;
+
+/// Faults injected in the writes to a peer, see [`set_chaos`]. The
+/// probabilities are in `[0, 1]`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChaosConfig {
+    /// Probability that the connection is closed instead of writing.
+    pub drop_connection_probability: f64,
+
+    /// Probability that the write fails as if the stream had been stopped by
+    /// the peer with [`CHAOS_THROTTLE_CODE`].
+    pub throttle_probability: f64,
+
+    /// Probability that the write is delayed by up to [`Self::max_delay`], so
+    /// that concurrent writes may be reordered.
+    pub delay_probability: f64,
+
+    /// Upper bound of the delays, which are uniformly distributed.
+    pub max_delay: Duration,
+
+    /// Seed of the random faults, so that a run can be reproduced.
+    pub seed: u64,
+}
+
+struct ChaosState {
+    config: ChaosConfig,
+    rng: u64,
+}
+
+impl ChaosState {
+    /// Returns a uniformly distributed number in `[0, 1)`, using xorshift64*.
+    fn next_f64(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let value = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (value >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+static CHAOS: LazyLock<Mutex<HashMap<SocketAddr, ChaosState>>> = LazyLock::new(Mutex::default);
+
+/// Injects the faults of `config` in the writes to `peer`, `None` stops the
+/// injection. As the faults are set per peer, the tests using distinct
+/// servers don't interfere with each other.
+pub fn set_chaos(peer: SocketAddr, config: Option<ChaosConfig>) {
+    let mut chaos = CHAOS.lock().unwrap();
+    match config {
+        Some(config) => {
+            // xorshift gets stuck at 0.
+            let rng = config.seed.max(1);
+            chaos.insert(peer, ChaosState { config, rng });
+        }
+        None => {
+            chaos.remove(&peer);
+        }
+    }
+}
+
+enum Fault {
+    DropConnection,
+    Throttle,
+    Delay(Duration),
+}
+
+fn next_fault(peer: &SocketAddr) -> Option<Fault> {
+    let mut chaos = CHAOS.lock().unwrap();
+    let state = chaos.get_mut(peer)?;
+    let draw = state.next_f64();
+    let config = &state.config;
+    if draw < config.drop_connection_probability {
+        return Some(Fault::DropConnection);
+    }
+    if draw < config.drop_connection_probability + config.throttle_probability {
+        return Some(Fault::Throttle);
+    }
+    if state.next_f64() < state.config.delay_probability {
+        let delay = state.config.max_delay.mul_f64(state.next_f64());
+        return Some(Fault::Delay(delay));
+    }
+    None
+}
+
+/// Applies the next fault configured for the peer of `connection`, if any,
+/// before a write over it.
+pub(crate) async fn inject_write_fault(connection: &Connection) -> Result<(), QuicError> {
+    match next_fault(&connection.remote_address()) {
+        None => Ok(()),
+        Some(Fault::DropConnection) => {
+            connection.close(0u32.into(), b"chaos");
+            Err(QuicError::Connection(ConnectionError::LocallyClosed))
+        }
+        Some(Fault::Throttle) => Err(QuicError::StreamWrite(WriteError::Stopped(
+            VarInt::from_u32(CHAOS_THROTTLE_CODE),
+        ))),
+        Some(Fault::Delay(delay)) => {
+            sleep(delay).await;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            quic_networking::send_data_over_stream,
+            test_utils::{
+                tests::{client_endpoint, connect},
+                TestTpuServer,
+            },
+        },
+    };
+
+    #[tokio::test]
+    async fn test_chaos() {
+        let server = TestTpuServer::start().unwrap();
+        let endpoint = client_endpoint();
+        let connection = connect(&endpoint, &server).await;
+        let send = || send_data_over_stream(&connection, vec![1u8; 10].into());
+
+        set_chaos(
+            server.address(),
+            Some(ChaosConfig {
+                delay_probability: 1.0,
+                max_delay: Duration::from_millis(10),
+                ..ChaosConfig::default()
+            }),
+        );
+        send().await.unwrap();
+        assert!(
+            server
+                .wait_for_transactions(1, Duration::from_secs(5))
+                .await
+        );
+
+        set_chaos(
+            server.address(),
+            Some(ChaosConfig {
+                throttle_probability: 1.0,
+                ..ChaosConfig::default()
+            }),
+        );
+        assert!(matches!(
+            send().await,
+            Err(QuicError::StreamWrite(WriteError::Stopped(code)))
+                if code == VarInt::from_u32(CHAOS_THROTTLE_CODE)
+        ));
+
+        set_chaos(
+            server.address(),
+            Some(ChaosConfig {
+                drop_connection_probability: 1.0,
+                ..ChaosConfig::default()
+            }),
+        );
+        assert!(matches!(
+            send().await,
+            Err(QuicError::Connection(ConnectionError::LocallyClosed))
+        ));
+        assert!(connection.close_reason().is_some());
+        assert_eq!(server.transactions().len(), 1);
+        set_chaos(server.address(), None);
+    }
+
+    #[test]
+    fn test_chaos_is_reproducible() {
+        let draws = |seed| {
+            let mut state = ChaosState {
+                config: ChaosConfig::default(),
+                rng: seed,
+            };
+            (0..8).map(|_| state.next_f64()).collect::<Vec<_>>()
+        };
+        assert_eq!(draws(42), draws(42));
+        assert_ne!(draws(42), draws(43));
+        assert!(draws(42).iter().all(|draw| (0.0..1.0).contains(draw)));
+    }
+}
@@ -0,0 +1,251 @@
+//! This module provides [`MockClock`], which drives the time of the scheduler
+//! in the tests, so that the logic depending on it runs without real sleeps.
+
+use {
+    solana_clock::DEFAULT_MS_PER_SLOT,
+    std::time::Duration,
+    tokio::time::{self, Instant},
+};
+
+/// [`MockClock`] controls the paused clock of the current tokio runtime. Once
+/// paused, the time only moves when all the tasks of the runtime wait, in
+/// which case it jumps to the next timer. Everything the scheduler times
+/// follows it: the slot ticks of the cache warming and of the health checks,
+/// the handshake timeouts, the backoff between the reconnection attempts and
+/// the lifetime of the workers. The age of the transaction batches follows the
+/// wall clock, so the batches don't expire while the time is advanced.
+///
+/// As the time also jumps while the tasks wait for the network, the tests
+/// suit the peers which never answer, whose connections only depend on the
+/// timers, better than the ones which answer within a deadline.
+///
+/// Pausing the time requires a `current_thread` runtime, which is the one
+/// created by `#[tokio::test]`.
+///
+/// ```ignore
+/// #[tokio::test]
+/// async fn test_cache_warming() {
+///     let clock = MockClock::pause();
+///     // Spawn the scheduler with `with_cache_warming`.
+///     clock.advance_slots(1).await;
+///     // The scheduler has checked the upcoming leaders once more.
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MockClock {
+    start: Instant,
+}
+
+impl MockClock {
+    /// Pauses the time of the current runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the time is already paused, for instance by
+    /// `#[tokio::test(start_paused = true)]`, in which case
+    /// [`MockClock::new`] is used instead, or if the runtime is not a
+    /// `current_thread` one.
+    pub fn pause() -> Self {
+        time::pause();
+        Self::new()
+    }
+
+    /// Creates a [`MockClock`] for a runtime whose time is already paused.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns the current time of the runtime.
+    pub fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Returns the time elapsed since the creation of the clock.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Moves the time forward by `duration`. The timers expiring meanwhile
+    /// fire in order, each once the tasks woken by the previous ones wait
+    /// again, as they would in real time.
+    ///
+    /// The timers have a resolution of a millisecond, by which the time may
+    /// overshoot `duration`. The tasks woken at the very end of `duration` may
+    /// not have run yet when it returns, so the tests check the state of the
+    /// scheduler in between its ticks.
+    pub async fn advance(&self, duration: Duration) {
+        time::sleep(duration).await;
+    }
+
+    /// Moves the time forward by `num_slots` slots of [`DEFAULT_MS_PER_SLOT`].
+    pub async fn advance_slots(&self, num_slots: u64) {
+        self.advance(Duration::from_millis(
+            num_slots.saturating_mul(DEFAULT_MS_PER_SLOT),
+        ))
+        .await;
+    }
+
+    /// Resumes the time of the runtime, which then follows the system clock
+    /// again.
+    pub fn resume(self) {
+        time::resume();
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            clock::timestamp,
+            connection_worker::DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
+            connection_workers_scheduler::{
+                BindTarget, CacheWarmingConfig, ConnectionWorkersSchedulerConfig, Fanout,
+            },
+            quic_networking::{create_client_config, create_client_endpoint},
+            test_utils::MockLeaderUpdater,
+            transaction_batch::TransactionBatch,
+            workers_cache::spawn_worker,
+            ConnectionWorkersScheduler, SendTransactionStats,
+        },
+        solana_clock::NUM_CONSECUTIVE_LEADER_SLOTS,
+        solana_net_utils::sockets::{bind_to_localhost_unique, unique_port_range_for_tests},
+        solana_tls_utils::QuicClientCertificate,
+        std::{
+            net::{IpAddr, Ipv4Addr, SocketAddr},
+            sync::{atomic::Ordering, Arc},
+        },
+        tokio::sync::{mpsc, watch},
+        tokio_util::sync::CancellationToken,
+    };
+
+    #[tokio::test]
+    async fn test_mock_clock() {
+        let clock = MockClock::pause();
+        let batch = TransactionBatch::new(vec![vec![0u8; 1]]);
+
+        clock.advance(Duration::from_secs(1)).await;
+        clock.advance_slots(NUM_CONSECUTIVE_LEADER_SLOTS).await;
+        let expected = Duration::from_secs(1)
+            + Duration::from_millis(NUM_CONSECUTIVE_LEADER_SLOTS * DEFAULT_MS_PER_SLOT);
+        let elapsed = clock.elapsed();
+        assert!(
+            elapsed >= expected && elapsed < expected + Duration::from_millis(10),
+            "{elapsed:?}"
+        );
+        // In the tests of this crate, the batches age with the time of the
+        // runtime.
+        let age = timestamp() - batch.timestamp();
+        assert_eq!(age, elapsed.as_millis() as u64);
+        clock.resume();
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_backoff() {
+        let clock = MockClock::pause();
+        let socket = bind_to_localhost_unique().unwrap();
+        let client_config = create_client_config(&QuicClientCertificate::new(None));
+        let endpoint = create_client_endpoint(BindTarget::Socket(socket), client_config).unwrap();
+        // Nothing listens on this port, so every handshake times out.
+        let peer = SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            unique_port_range_for_tests(1).start,
+        );
+
+        let max_reconnect_attempts = 2;
+        let stats = Arc::new(SendTransactionStats::default());
+        let worker_info = spawn_worker(
+            &endpoint,
+            &peer,
+            1,
+            true,
+            max_reconnect_attempts,
+            DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
+            stats.clone(),
+            Arc::default(),
+            None,
+            None,
+            None,
+        );
+        // The receiver of the transactions is dropped once the worker stops.
+        worker_info.transactions_sender().closed().await;
+
+        // The first connection and each reconnection time out, with a slot
+        // group of backoff before each reconnection.
+        let num_attempts = max_reconnect_attempts as u32 + 1;
+        let backoff = Duration::from_millis(NUM_CONSECUTIVE_LEADER_SLOTS * DEFAULT_MS_PER_SLOT);
+        let expected =
+            DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT * num_attempts + backoff * (num_attempts - 1);
+        let elapsed = clock.elapsed();
+        assert!(
+            elapsed >= expected && elapsed < expected + Duration::from_millis(100),
+            "{elapsed:?}"
+        );
+        assert_eq!(
+            stats.connection_error_timed_out.load(Ordering::Relaxed),
+            num_attempts as u64
+        );
+        assert_eq!(
+            stats.retries.reconnect_attempts.load(Ordering::Relaxed),
+            max_reconnect_attempts as u64
+        );
+        assert_eq!(stats.retries.gave_up.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_warming_ticks() {
+        let clock = MockClock::pause();
+        let peer = SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            unique_port_range_for_tests(1).start,
+        );
+        let (leader_updater, schedule) = MockLeaderUpdater::new(vec![peer]);
+        let (_transaction_sender, transaction_receiver) = mpsc::channel(1);
+        let (_identity_sender, identity_receiver) = watch::channel(None);
+        let cancel = CancellationToken::new();
+        let scheduler = ConnectionWorkersScheduler::new(
+            Box::new(leader_updater),
+            transaction_receiver,
+            identity_receiver,
+            cancel.clone(),
+        )
+        .with_cache_warming(CacheWarmingConfig {
+            lookahead_slots: NUM_CONSECUTIVE_LEADER_SLOTS,
+            interval: Duration::from_millis(DEFAULT_MS_PER_SLOT),
+        });
+        let config = ConnectionWorkersSchedulerConfig {
+            bind: BindTarget::Socket(bind_to_localhost_unique().unwrap()),
+            stake_identity: None,
+            num_connections: 1,
+            skip_check_transaction_age: true,
+            worker_channel_size: 1,
+            max_reconnect_attempts: 0,
+            leaders_fanout: Fanout {
+                send: 1,
+                connect: 1,
+            },
+        };
+        let scheduler = tokio::spawn(scheduler.run(config));
+
+        // The upcoming leaders are checked as soon as the scheduler starts,
+        // then once per interval, which is verified in between the ticks.
+        let half_slot = Duration::from_millis(DEFAULT_MS_PER_SLOT / 2);
+        clock.advance(half_slot).await;
+        assert_eq!(schedule.num_calls(), 1);
+        clock.advance_slots(10).await;
+        assert_eq!(schedule.num_calls(), 11);
+
+        cancel.cancel();
+        let stats = scheduler.await.unwrap().unwrap();
+        // The handshakes of the warmed workers have timed out meanwhile.
+        assert!(stats.connection_error_timed_out.load(Ordering::Relaxed) >= 1);
+    }
+}
@@ -0,0 +1,160 @@
+//! This module provides [`MockLeaderUpdater`], a [`LeaderUpdater`] whose
+//! leaders are scripted by the test.
+
+use {
+    crate::leader_updater::LeaderUpdater,
+    async_trait::async_trait,
+    std::{
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+    },
+};
+
+#[derive(Debug, Default)]
+struct MockLeaderState {
+    schedule: Vec<SocketAddr>,
+    tick: usize,
+    num_calls: usize,
+    invalidated: Vec<SocketAddr>,
+    stopped: bool,
+}
+
+/// [`MockLeaderUpdater`] returns the leaders of a scripted schedule, in which
+/// each entry is the TPU address of the leader of a group of
+/// [`NUM_CONSECUTIVE_LEADER_SLOTS`] slots. The schedule only advances when
+/// the test calls [`MockLeaderSchedule::tick`], so that the behavior of the
+/// scheduler around leader rotation can be tested deterministically.
+///
+/// Like the other implementations, consecutive entries of the same leader
+/// are merged, so that `lookahead_leaders` covers that many distinct
+/// leaders. Once the schedule is exhausted, no leader is returned.
+///
+/// [`NUM_CONSECUTIVE_LEADER_SLOTS`]: solana_clock::NUM_CONSECUTIVE_LEADER_SLOTS
+#[derive(Debug)]
+pub struct MockLeaderUpdater {
+    state: Arc<Mutex<MockLeaderState>>,
+}
+
+/// The handle used by the test to drive a [`MockLeaderUpdater`] and to
+/// inspect how the scheduler used it.
+#[derive(Clone, Debug)]
+pub struct MockLeaderSchedule {
+    state: Arc<Mutex<MockLeaderState>>,
+}
+
+impl MockLeaderUpdater {
+    /// Creates a [`MockLeaderUpdater`] starting at the first entry of
+    /// `schedule`, along with the handle which drives it.
+    pub fn new(schedule: Vec<SocketAddr>) -> (Self, MockLeaderSchedule) {
+        let state = Arc::new(Mutex::new(MockLeaderState {
+            schedule,
+            ..MockLeaderState::default()
+        }));
+        (
+            Self {
+                state: state.clone(),
+            },
+            MockLeaderSchedule { state },
+        )
+    }
+}
+
+impl MockLeaderSchedule {
+    /// Moves to the next entry of the schedule.
+    pub fn tick(&self) {
+        self.advance(1);
+    }
+
+    /// Moves `num_ticks` entries forward in the schedule.
+    pub fn advance(&self, num_ticks: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.tick = state.tick.saturating_add(num_ticks);
+    }
+
+    /// Returns the index of the current entry of the schedule.
+    pub fn current_tick(&self) -> usize {
+        self.state.lock().unwrap().tick
+    }
+
+    /// Appends `leaders` to the schedule.
+    pub fn extend(&self, leaders: impl IntoIterator<Item = SocketAddr>) {
+        self.state.lock().unwrap().schedule.extend(leaders);
+    }
+
+    /// Returns the number of times the leaders were requested, so that the
+    /// test can wait for the scheduler to observe a tick.
+    pub fn num_calls(&self) -> usize {
+        self.state.lock().unwrap().num_calls
+    }
+
+    /// Returns the addresses passed to
+    /// [`LeaderUpdater::invalidate_leader_address`], oldest first.
+    pub fn invalidated_addresses(&self) -> Vec<SocketAddr> {
+        self.state.lock().unwrap().invalidated.clone()
+    }
+
+    /// Returns whether [`LeaderUpdater::stop`] was called.
+    pub fn is_stopped(&self) -> bool {
+        self.state.lock().unwrap().stopped
+    }
+}
+
+#[async_trait]
+impl LeaderUpdater for MockLeaderUpdater {
+    fn next_leaders(&mut self, lookahead_leaders: usize) -> Vec<SocketAddr> {
+        let mut state = self.state.lock().unwrap();
+        state.num_calls = state.num_calls.saturating_add(1);
+        let mut leaders: Vec<SocketAddr> = Vec::with_capacity(lookahead_leaders);
+        for leader in state.schedule.iter().skip(state.tick) {
+            if leaders.last() == Some(leader) {
+                continue;
+            }
+            if leaders.len() == lookahead_leaders {
+                break;
+            }
+            leaders.push(*leader);
+        }
+        leaders
+    }
+
+    fn invalidate_leader_address(&mut self, address: &SocketAddr) {
+        self.state.lock().unwrap().invalidated.push(*address);
+    }
+
+    async fn stop(&mut self) {
+        self.state.lock().unwrap().stopped = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::net::{IpAddr, Ipv4Addr},
+    };
+
+    #[tokio::test]
+    async fn test_mock_leader_updater() {
+        let leader = |port| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+        let (mut leader_updater, schedule) =
+            MockLeaderUpdater::new(vec![leader(1), leader(1), leader(2), leader(3)]);
+
+        assert_eq!(leader_updater.next_leaders(2), vec![leader(1), leader(2)]);
+        schedule.tick();
+        assert_eq!(leader_updater.next_leaders(2), vec![leader(1), leader(2)]);
+        schedule.tick();
+        assert_eq!(leader_updater.next_leaders(2), vec![leader(2), leader(3)]);
+        schedule.extend([leader(4)]);
+        schedule.advance(2);
+        assert_eq!(schedule.current_tick(), 4);
+        assert_eq!(leader_updater.next_leaders(2), vec![leader(4)]);
+        schedule.tick();
+        assert!(leader_updater.next_leaders(2).is_empty());
+        assert_eq!(schedule.num_calls(), 5);
+
+        leader_updater.invalidate_leader_address(&leader(4));
+        assert_eq!(schedule.invalidated_addresses(), vec![leader(4)]);
+        leader_updater.stop().await;
+        assert!(schedule.is_stopped());
+    }
+}
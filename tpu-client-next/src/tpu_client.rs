@@ -0,0 +1,139 @@
+//! This module provides the blocking [`TpuClientNext`], which runs the async
+//! [`nonblocking::tpu_client::TpuClientNext`] on a runtime of its own, for
+//! the clients which are not async.
+//!
+//! [`nonblocking::tpu_client::TpuClientNext`]: crate::nonblocking::tpu_client::TpuClientNext
+
+pub use crate::nonblocking::tpu_client::{
+    TpuClientNextError, TransactionOutcome, DEFAULT_RESEND_INTERVAL,
+};
+use {
+    crate::{
+        connection_workers_scheduler::{
+            ConnectionWorkersSchedulerConfig, RpcScheduler, StakeIdentity,
+        },
+        nonblocking::tpu_client::TpuClientNext as NonblockingTpuClientNext,
+        SendTransactionStats,
+    },
+    solana_rpc_client::rpc_client::RpcClient,
+    solana_transaction::versioned::VersionedTransaction,
+    std::{sync::Arc, time::Duration},
+    tokio::runtime::{Builder, Runtime},
+};
+
+/// Blocking version of [`NonblockingTpuClientNext`], the methods block the
+/// calling thread until the corresponding async method completes. They must
+/// not be called from an async context.
+pub struct TpuClientNext {
+    // Dropped before the runtime which runs its scheduler.
+    client: NonblockingTpuClientNext,
+    runtime: Runtime,
+}
+
+impl TpuClientNext {
+    /// Creates a client sending to the leaders tracked with `rpc_client`, and
+    /// starts its scheduler with `config` on a new runtime.
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        config: ConnectionWorkersSchedulerConfig,
+    ) -> Result<Self, TpuClientNextError> {
+        let runtime = Self::build_runtime()?;
+        let client = runtime.block_on(NonblockingTpuClientNext::new(
+            rpc_client.get_inner_client().clone(),
+            config,
+        ))?;
+        Ok(Self { client, runtime })
+    }
+
+    /// Creates a client which starts `rpc_scheduler` on `runtime` and checks
+    /// the status of the transactions with `rpc_client`.
+    pub fn from_scheduler(
+        runtime: Runtime,
+        rpc_client: Arc<RpcClient>,
+        rpc_scheduler: RpcScheduler,
+    ) -> Self {
+        let client = {
+            let _guard = runtime.enter();
+            NonblockingTpuClientNext::from_scheduler(
+                rpc_client.get_inner_client().clone(),
+                rpc_scheduler,
+            )
+        };
+        Self { client, runtime }
+    }
+
+    fn build_runtime() -> Result<Runtime, TpuClientNextError> {
+        Ok(Builder::new_multi_thread()
+            .thread_name("solTpuClientNxt")
+            .enable_all()
+            .build()?)
+    }
+
+    /// Sets how often the unconfirmed transactions are submitted again by
+    /// [`TpuClientNext::send_and_confirm_transactions`].
+    pub fn with_resend_interval(mut self, resend_interval: Duration) -> Self {
+        self.client = self.client.with_resend_interval(resend_interval);
+        self
+    }
+
+    /// Returns the statistics of the scheduler.
+    pub fn stats(&self) -> &Arc<SendTransactionStats> {
+        self.client.stats()
+    }
+
+    /// Replaces the identity used to connect to the leaders, see
+    /// [`StakeIdentity`].
+    pub fn update_identity(&self, identity: Option<StakeIdentity>) {
+        self.client.update_identity(identity);
+    }
+
+    /// See [`NonblockingTpuClientNext::send_transactions`].
+    pub fn send_transactions(
+        &self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<(), TpuClientNextError> {
+        self.runtime
+            .block_on(self.client.send_transactions(transactions))
+    }
+
+    /// See [`NonblockingTpuClientNext::send_and_confirm_transactions`].
+    pub fn send_and_confirm_transactions(
+        &self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<Vec<TransactionOutcome>, TpuClientNextError> {
+        self.runtime
+            .block_on(self.client.send_and_confirm_transactions(transactions))
+    }
+
+    /// Stops the scheduler and returns its statistics.
+    pub fn shutdown(self) -> Result<Arc<SendTransactionStats>, TpuClientNextError> {
+        let Self { client, runtime } = self;
+        runtime.block_on(client.shutdown())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::nonblocking::tpu_client::tests::{test_scheduler, transactions},
+    };
+
+    #[test]
+    fn test_send_and_confirm_transactions() {
+        let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+        let runtime = TpuClientNext::build_runtime().unwrap();
+        let rpc_scheduler = runtime.block_on(test_scheduler(rpc_client.get_inner_client().clone()));
+        let client = TpuClientNext::from_scheduler(runtime, rpc_client, rpc_scheduler)
+            .with_resend_interval(Duration::from_millis(10));
+
+        client.send_transactions(&transactions()).unwrap();
+        assert_eq!(
+            client
+                .send_and_confirm_transactions(&transactions())
+                .unwrap(),
+            vec![TransactionOutcome::Confirmed; 3]
+        );
+        client.shutdown().unwrap();
+    }
+}
@@ -1,23 +1,249 @@
 //! This module holds [`TransactionBatch`] structure.
 
-use {solana_time_utils::timestamp, tokio_util::bytes::Bytes};
+pub mod batch_ref;
+pub mod batch_stream;
+pub mod coalescer;
+
+#[cfg(feature = "otel")]
+use crate::otel::BatchSpan;
+use {
+    crate::{clock::timestamp, connection_workers_scheduler::Fanout},
+    serde::{de, Deserializer, Serializer},
+    serde_derive::{Deserialize, Serialize},
+    solana_streamer::packet::PACKET_DATA_SIZE,
+    std::{
+        collections::HashSet,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    },
+    thiserror::Error,
+    tokio_util::bytes::Bytes,
+};
+pub use {
+    batch_ref::{send_transaction_batch_ref, TransactionBatchRef},
+    batch_stream::batch_stream,
+    coalescer::{coalesce_batches, CoalescingConfig},
+};
+#[cfg(feature = "transaction")]
+use {
+    solana_sdk_ids::compute_budget, solana_transaction::versioned::VersionedTransaction,
+    std::cmp::Reverse,
+};
 
 /// Batch of generated transactions timestamp is used to discard batches which
 /// are too old to have valid blockhash.
+///
+/// The transactions are shared between the clones of a batch, so that handing
+/// a batch to the workers of several leaders does not copy them.
 #[derive(Clone, PartialEq)]
 pub struct TransactionBatch {
-    wired_transactions: Vec<WiredTransaction>,
+    wired_transactions: Arc<Vec<WiredTransaction>>,
+    // Identifier of this batch within the process, see `TransactionBatch::id`
+    id: u64,
     // Time of creation of this batch, used for batch timeouts
     timestamp: u64,
+    // Monotonic time of creation of this batch, used to measure latencies
+    created_at: Instant,
+    // Fanout requested for this batch in addition to the scheduler's one
+    leaders_fanout: Option<Fanout>,
+    // Metadata of each of the transactions, empty if none has been attached
+    metadata: Arc<Vec<TransactionMetadata>>,
+    // Last block height at which the blockhash of the transactions is valid
+    last_valid_block_height: Option<u64>,
+    // Span in which this batch was created
+    #[cfg(feature = "otel")]
+    span: BatchSpan,
 }
 
 type WiredTransaction = Bytes;
 
+static NEXT_BATCH_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Size of an ed25519 signature on the wire.
+pub const SIGNATURE_BYTES: usize = 64;
+
+/// Discriminant of the `SetComputeUnitPrice` compute budget instruction, whose
+/// data is followed by the price as a little-endian `u64`.
+#[cfg(feature = "transaction")]
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+/// Caller-supplied data attached to a transaction of a [`TransactionBatch`],
+/// see [`TransactionBatch::with_metadata`]. It is handed back in the events of
+/// the [`StatsObserver`], so that the outcome of each transaction of a batch
+/// can be attributed.
+///
+/// [`StatsObserver`]: crate::send_transaction_stats::StatsObserver
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TransactionMetadata {
+    pub id: Option<u64>,
+    pub priority: Option<u64>,
+}
+
+/// Errors that arise from building a [`TransactionBatch`] out of
+/// transactions, see [`TransactionBatch::try_new`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TransactionBatchError {
+    #[cfg(feature = "transaction")]
+    #[error(transparent)]
+    Serialize(#[from] bincode::Error),
+    #[error("Transaction {index} is {size} bytes, more than {PACKET_DATA_SIZE} bytes.")]
+    Oversize { index: usize, size: usize },
+}
+
+/// Fails with the index of the first of the transactions `sizes` above
+/// [`PACKET_DATA_SIZE`].
+fn check_sizes(sizes: impl Iterator<Item = usize>) -> Result<(), TransactionBatchError> {
+    for (index, size) in sizes.enumerate() {
+        if size > PACKET_DATA_SIZE {
+            return Err(TransactionBatchError::Oversize { index, size });
+        }
+    }
+    Ok(())
+}
+
+/// Returns the compute unit price set by the compute budget instructions of
+/// the serialized `transaction`, 0 if there is none.
+#[cfg(feature = "transaction")]
+fn compute_unit_price(wired_transaction: &[u8]) -> u64 {
+    let Ok(transaction) = bincode::deserialize::<VersionedTransaction>(wired_transaction) else {
+        return 0;
+    };
+    let account_keys = transaction.message.static_account_keys();
+    transaction
+        .message
+        .instructions()
+        .iter()
+        .filter(|instruction| {
+            account_keys.get(usize::from(instruction.program_id_index)) == Some(&compute_budget::ID)
+        })
+        .find_map(|instruction| match instruction.data.as_slice() {
+            [SET_COMPUTE_UNIT_PRICE, price @ ..] => price.try_into().ok().map(u64::from_le_bytes),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the first signature of the serialized `transaction`, which starts
+/// with the number of signatures encoded as a compact-u16.
+fn first_signature(transaction: &[u8]) -> Option<&[u8; SIGNATURE_BYTES]> {
+    let mut num_signatures = 0usize;
+    let mut offset = 0usize;
+    for shift in [0, 7, 14] {
+        let byte = *transaction.get(offset)?;
+        offset += 1;
+        num_signatures |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    if num_signatures == 0 {
+        return None;
+    }
+    transaction
+        .get(offset..offset.checked_add(SIGNATURE_BYTES)?)?
+        .try_into()
+        .ok()
+}
+
 impl IntoIterator for TransactionBatch {
     type Item = Bytes;
     type IntoIter = std::vec::IntoIter<Self::Item>;
     fn into_iter(self) -> Self::IntoIter {
-        self.wired_transactions.into_iter()
+        Arc::unwrap_or_clone(self.wired_transactions).into_iter()
+    }
+}
+
+/// Serialized form of [`TransactionBatch`].
+#[derive(Serialize)]
+struct SerializedBatch<'a> {
+    wired_transactions: Vec<&'a [u8]>,
+    timestamp: u64,
+    leaders_fanout: Option<&'a Fanout>,
+    metadata: &'a [TransactionMetadata],
+    last_valid_block_height: Option<u64>,
+}
+
+/// Deserialized form of [`TransactionBatch`], see [`SerializedBatch`].
+#[derive(Deserialize)]
+struct DeserializedBatch {
+    wired_transactions: Vec<Vec<u8>>,
+    timestamp: u64,
+    leaders_fanout: Option<Fanout>,
+    metadata: Vec<TransactionMetadata>,
+    last_valid_block_height: Option<u64>,
+}
+
+// Serialized along with its requested fanout, metadata and last valid block
+// height, so that it can be persisted or handed to another process, with any
+// serde format, e.g. bincode or JSON. The monotonic creation time is not
+// serialized, it is derived from the timestamp of the batch when it is
+// deserialized, so that the batch keeps its age. Like `try_new`, the
+// deserialization fails on transactions larger than what the leaders accept.
+impl serde::Serialize for TransactionBatch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedBatch {
+            wired_transactions: self.wired_transactions.iter().map(Bytes::as_ref).collect(),
+            timestamp: self.timestamp,
+            leaders_fanout: self.leaders_fanout.as_ref(),
+            metadata: &self.metadata,
+            last_valid_block_height: self.last_valid_block_height,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for TransactionBatch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let DeserializedBatch {
+            wired_transactions,
+            timestamp: batch_timestamp,
+            leaders_fanout,
+            metadata,
+            last_valid_block_height,
+        } = DeserializedBatch::deserialize(deserializer)?;
+        if !metadata.is_empty() && metadata.len() != wired_transactions.len() {
+            return Err(de::Error::custom(format!(
+                "{} metadata for {} transactions",
+                metadata.len(),
+                wired_transactions.len()
+            )));
+        }
+        let now = Instant::now();
+        let age = Duration::from_millis(timestamp().saturating_sub(batch_timestamp));
+        let mut batch = Self::try_new(wired_transactions).map_err(de::Error::custom)?;
+        batch.timestamp = batch_timestamp;
+        batch.created_at = now.checked_sub(age).unwrap_or(now);
+        batch.leaders_fanout = leaders_fanout;
+        batch.metadata = Arc::new(metadata);
+        batch.last_valid_block_height = last_valid_block_height;
+        Ok(batch)
+    }
+}
+
+/// Builds a batch out of the wire transactions taken by
+/// `TpuClient::try_send_wire_transaction_batch` of `solana-tpu-client`, see
+/// [`TransactionBatch::try_new`].
+impl TryFrom<Vec<Vec<u8>>> for TransactionBatch {
+    type Error = TransactionBatchError;
+
+    fn try_from(wired_transactions: Vec<Vec<u8>>) -> Result<Self, Self::Error> {
+        Self::try_new(wired_transactions)
+    }
+}
+
+/// Returns the wire transactions in the shape taken by `solana-tpu-client`.
+/// The payloads are copied.
+impl From<TransactionBatch> for Vec<Vec<u8>> {
+    fn from(batch: TransactionBatch) -> Self {
+        batch
+            .wired_transactions
+            .iter()
+            .map(|wired_transaction| wired_transaction.to_vec())
+            .collect()
     }
 }
 
@@ -30,13 +256,589 @@ impl TransactionBatch {
             .into_iter()
             .map(|v| Bytes::from_owner(v))
             .collect();
+        Self::from_bytes(wired_transactions)
+    }
 
+    /// Same as [`TransactionBatch::new`], but fails with the index of the
+    /// first transaction larger than what the leaders accept, rather than
+    /// having the worker drop it.
+    pub fn try_new<T>(wired_transactions: Vec<T>) -> Result<Self, TransactionBatchError>
+    where
+        T: AsRef<[u8]> + Send + 'static,
+    {
+        check_sizes(wired_transactions.iter().map(|v| v.as_ref().len()))?;
+        Ok(Self::new(wired_transactions))
+    }
+
+    /// Same as [`TransactionBatch::from_bytes`], but fails with the index of
+    /// the first transaction larger than what the leaders accept.
+    pub fn try_from_bytes(wired_transactions: Vec<Bytes>) -> Result<Self, TransactionBatchError> {
+        check_sizes(wired_transactions.iter().map(Bytes::len))?;
+        Ok(Self::from_bytes(wired_transactions))
+    }
+
+    /// Creates a batch out of transactions which are already serialized into
+    /// `Bytes`, e.g. received from the network. The payloads are neither
+    /// copied nor wrapped, they are handed as they are to the connections of
+    /// the leaders, which share them.
+    pub fn from_bytes(wired_transactions: Vec<Bytes>) -> Self {
         Self {
-            wired_transactions,
+            wired_transactions: Arc::new(wired_transactions),
+            id: NEXT_BATCH_ID.fetch_add(1, Ordering::Relaxed),
             timestamp: timestamp(),
+            created_at: Instant::now(),
+            leaders_fanout: None,
+            metadata: Arc::default(),
+            last_valid_block_height: None,
+            #[cfg(feature = "otel")]
+            span: BatchSpan::current(),
+        }
+    }
+
+    /// Serializes the `transactions` with bincode into a batch. Fails if any of
+    /// them is larger than what the leaders accept, rather than having the
+    /// worker drop it.
+    #[cfg(feature = "transaction")]
+    pub fn try_from_transactions(
+        transactions: &[VersionedTransaction],
+    ) -> Result<Self, TransactionBatchError> {
+        let wired_transactions = transactions
+            .iter()
+            .map(bincode::serialize)
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::try_new(wired_transactions)
+    }
+
+    /// Splits the batch into batches of at most `max_count` transactions and
+    /// `max_bytes` bytes, keeping the order of the transactions. A transaction
+    /// larger than `max_bytes` is put in a batch of its own.
+    ///
+    /// The batches keep the creation time and the requested fanout of this
+    /// batch, so that they are not considered newer than they are.
+    pub fn split_by_size(mut self, max_bytes: usize, max_count: usize) -> Vec<TransactionBatch> {
+        let max_count = max_count.max(1);
+        let metadata = std::mem::take(&mut self.metadata);
+        let mut batches = Vec::new();
+        let mut chunk = Vec::new();
+        let mut chunk_start = 0usize;
+        let mut chunk_bytes = 0usize;
+        for (index, wired_transaction) in
+            Arc::unwrap_or_clone(std::mem::take(&mut self.wired_transactions))
+                .into_iter()
+                .enumerate()
+        {
+            if !chunk.is_empty()
+                && (chunk.len() >= max_count
+                    || chunk_bytes.saturating_add(wired_transaction.len()) > max_bytes)
+            {
+                batches.push(self.with_wired_transactions(
+                    std::mem::take(&mut chunk),
+                    metadata.get(chunk_start..index).unwrap_or_default(),
+                ));
+                chunk_start = index;
+                chunk_bytes = 0;
+            }
+            chunk_bytes = chunk_bytes.saturating_add(wired_transaction.len());
+            chunk.push(wired_transaction);
         }
+        if !chunk.is_empty() {
+            batches.push(
+                self.with_wired_transactions(
+                    chunk,
+                    metadata.get(chunk_start..).unwrap_or_default(),
+                ),
+            );
+        }
+        batches
+    }
+
+    /// Moves the transactions of `other` to the end of this batch, which takes
+    /// the creation time of the older of the two. The batches are expected to
+    /// have the same last valid block height.
+    fn append(&mut self, other: TransactionBatch) {
+        if !self.metadata.is_empty() || !other.metadata.is_empty() {
+            let metadata = Arc::make_mut(&mut self.metadata);
+            metadata.resize(
+                self.wired_transactions.len(),
+                TransactionMetadata::default(),
+            );
+            metadata.extend_from_slice(&other.metadata);
+            metadata.resize(
+                self.wired_transactions.len() + other.len(),
+                TransactionMetadata::default(),
+            );
+        }
+        Arc::make_mut(&mut self.wired_transactions).extend_from_slice(&other.wired_transactions);
+        self.timestamp = self.timestamp.min(other.timestamp);
+        self.created_at = self.created_at.min(other.created_at);
+    }
+
+    /// Returns a batch holding `wired_transactions` and their `metadata` with
+    /// the identifier, the creation time, the fanout and the span of this batch.
+    fn with_wired_transactions(
+        &self,
+        wired_transactions: Vec<WiredTransaction>,
+        metadata: &[TransactionMetadata],
+    ) -> Self {
+        Self {
+            wired_transactions: Arc::new(wired_transactions),
+            id: self.id,
+            timestamp: self.timestamp,
+            created_at: self.created_at,
+            leaders_fanout: self.leaders_fanout.clone(),
+            metadata: Arc::new(metadata.to_vec()),
+            last_valid_block_height: self.last_valid_block_height,
+            #[cfg(feature = "otel")]
+            span: self.span.clone(),
+        }
+    }
+
+    /// Attaches `metadata` to the transactions of the batch, in the same
+    /// order. Transactions without metadata get the default one, and the
+    /// metadata beyond the transactions is ignored.
+    pub fn with_metadata(mut self, mut metadata: Vec<TransactionMetadata>) -> Self {
+        metadata.resize(
+            self.wired_transactions.len(),
+            TransactionMetadata::default(),
+        );
+        self.metadata = Arc::new(metadata);
+        self
+    }
+
+    /// Returns the metadata of the transactions, which is empty if none has
+    /// been attached with [`TransactionBatch::with_metadata`].
+    pub fn metadata(&self) -> &[TransactionMetadata] {
+        &self.metadata
+    }
+
+    /// Returns the transactions along with their metadata, which is empty if
+    /// none has been attached. They are still shared with the clones of the
+    /// batch.
+    pub(crate) fn into_parts(self) -> (Arc<Vec<WiredTransaction>>, Arc<Vec<TransactionMetadata>>) {
+        (self.wired_transactions, self.metadata)
     }
+
+    /// Requests to send this batch to at least `leaders_fanout` leaders. The
+    /// fanout configured for the scheduler is used when it is deeper.
+    pub fn with_leaders_fanout(mut self, leaders_fanout: Fanout) -> Self {
+        self.leaders_fanout = Some(leaders_fanout);
+        self
+    }
+
+    /// Tags the batch with the last block height at which the blockhash of its
+    /// transactions is valid, so that the scheduler drops the batch once it
+    /// can't land anymore, see
+    /// [`ConnectionWorkersScheduler::with_block_height_receiver`].
+    ///
+    /// [`ConnectionWorkersScheduler::with_block_height_receiver`]: crate::ConnectionWorkersScheduler::with_block_height_receiver
+    pub fn with_last_valid_block_height(mut self, last_valid_block_height: u64) -> Self {
+        self.last_valid_block_height = Some(last_valid_block_height);
+        self
+    }
+
+    /// Returns the number of transactions in the batch.
+    pub fn len(&self) -> usize {
+        self.wired_transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wired_transactions.is_empty()
+    }
+
+    /// Returns the number of bytes of the transactions in the batch.
+    pub fn num_bytes(&self) -> usize {
+        self.wired_transactions.iter().map(Bytes::len).sum()
+    }
+
+    /// Orders the transactions by decreasing compute unit price, so that the
+    /// leaders ingest the higher paying ones first. The transactions without
+    /// a price, or which can't be deserialized, come last, and the order of
+    /// the transactions with the same price is kept.
+    ///
+    /// Each transaction is deserialized, which is why the batches are not
+    /// sorted unless asked to.
+    #[cfg(feature = "transaction")]
+    pub fn sort_by_compute_unit_price(&mut self) {
+        let mut order: Vec<(u64, usize)> = self
+            .wired_transactions
+            .iter()
+            .map(|wired_transaction| compute_unit_price(wired_transaction))
+            .zip(0..)
+            .collect();
+        order.sort_by_key(|(price, _)| Reverse(*price));
+        self.wired_transactions = Arc::new(
+            order
+                .iter()
+                .map(|(_, index)| self.wired_transactions[*index].clone())
+                .collect(),
+        );
+        if !self.metadata.is_empty() {
+            self.metadata = Arc::new(
+                order
+                    .iter()
+                    .map(|(_, index)| self.metadata[*index])
+                    .collect(),
+            );
+        }
+    }
+
+    /// Returns the first signature of each of the transactions, read at its
+    /// fixed offset without deserializing the transaction, or `None` if the
+    /// transaction has no signature or is truncated.
+    pub fn signatures(&self) -> impl Iterator<Item = Option<&[u8; SIGNATURE_BYTES]>> + '_ {
+        self.wired_transactions
+            .iter()
+            .map(|wired_transaction| first_signature(wired_transaction))
+    }
+
+    /// Removes the transactions whose first signature is the same as the one
+    /// of an earlier transaction of the batch, along with their metadata.
+    /// Only the signature is parsed, and the transactions without any are
+    /// kept.
+    pub fn dedup_by_signature(&mut self) {
+        let mut signatures = HashSet::with_capacity(self.wired_transactions.len());
+        let is_unique: Vec<bool> = self
+            .signatures()
+            .map(|signature| signature.is_none_or(|signature| signatures.insert(signature)))
+            .collect();
+        if is_unique.iter().all(|is_unique| *is_unique) {
+            return;
+        }
+        let mut is_unique_iter = is_unique.iter();
+        Arc::make_mut(&mut self.wired_transactions).retain(|_| *is_unique_iter.next().unwrap());
+        if !self.metadata.is_empty() {
+            let mut is_unique_iter = is_unique.iter();
+            Arc::make_mut(&mut self.metadata).retain(|_| *is_unique_iter.next().unwrap());
+        }
+    }
+
+    /// Returns the identifier of the batch, which is unique within the
+    /// process and recorded in the spans of the batch with `tracing`. The
+    /// batches split from or merged into a batch keep its identifier.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     pub fn timestamp(&self) -> u64 {
         self.timestamp
     }
+
+    pub fn created_at(&self) -> Instant {
+        self.created_at
+    }
+
+    pub fn leaders_fanout(&self) -> Option<&Fanout> {
+        self.leaders_fanout.as_ref()
+    }
+
+    pub fn last_valid_block_height(&self) -> Option<u64> {
+        self.last_valid_block_height
+    }
+
+    #[cfg(feature = "otel")]
+    pub(crate) fn span(&self) -> &BatchSpan {
+        &self.span
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let wired_transaction = Bytes::from(vec![1u8; 16]);
+        let batch = TransactionBatch::from_bytes(vec![wired_transaction.clone()]);
+        let sent_transaction = batch.clone().into_iter().next().unwrap();
+        assert_eq!(sent_transaction.as_ptr(), wired_transaction.as_ptr());
+    }
+
+    #[test]
+    fn test_split_by_size() {
+        let sizes = [4, 4, 4, 10, 1, 1, 1];
+        let batch = TransactionBatch::new(sizes.iter().map(|size| vec![0u8; *size]).collect())
+            .with_leaders_fanout(Fanout {
+                send: 2,
+                connect: 3,
+            });
+        let created_at = batch.created_at();
+        let id = batch.id();
+        assert_ne!(TransactionBatch::new(vec![vec![0u8; 4]]).id(), id);
+
+        let batches = batch.split_by_size(8, 2);
+        let split_sizes: Vec<Vec<usize>> = batches
+            .iter()
+            .map(|batch| batch.wired_transactions.iter().map(Bytes::len).collect())
+            .collect();
+        // The transaction of 10 bytes doesn't fit any batch, so it goes alone.
+        assert_eq!(
+            split_sizes,
+            vec![vec![4, 4], vec![4], vec![10], vec![1, 1], vec![1]]
+        );
+        for batch in &batches {
+            assert_eq!(batch.id(), id);
+            assert_eq!(batch.created_at(), created_at);
+            assert_eq!(batch.leaders_fanout().unwrap().connect, 3);
+        }
+
+        assert!(TransactionBatch::new(Vec::<Vec<u8>>::new())
+            .split_by_size(8, 2)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_clone_shares_transactions() {
+        let batch = TransactionBatch::new(vec![vec![0u8; 8]; 4])
+            .with_metadata(vec![TransactionMetadata::default(); 4]);
+        let clone = batch.clone();
+        assert!(Arc::ptr_eq(
+            &batch.wired_transactions,
+            &clone.wired_transactions
+        ));
+        assert!(Arc::ptr_eq(&batch.metadata, &clone.metadata));
+
+        // Modifying a clone leaves the others as they are.
+        let mut merged = clone.clone();
+        merged.append(TransactionBatch::new(vec![vec![1u8; 8]]));
+        assert_eq!(merged.len(), 5);
+        assert_eq!(merged.metadata().len(), 5);
+        assert_eq!(clone.len(), 4);
+        assert_eq!(clone.metadata().len(), 4);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata = |id| TransactionMetadata {
+            id: Some(id),
+            priority: None,
+        };
+        let batch =
+            TransactionBatch::new(vec![vec![0u8]; 3]).with_metadata(vec![metadata(0), metadata(1)]);
+        assert_eq!(
+            batch.metadata(),
+            &[metadata(0), metadata(1), TransactionMetadata::default()]
+        );
+
+        let batches = batch.split_by_size(usize::MAX, 2);
+        assert_eq!(batches[0].metadata(), &[metadata(0), metadata(1)]);
+        assert_eq!(batches[1].metadata(), &[TransactionMetadata::default()]);
+
+        let mut merged = TransactionBatch::new(vec![vec![0u8]]);
+        merged.append(batches[0].clone());
+        assert_eq!(
+            merged.metadata(),
+            &[TransactionMetadata::default(), metadata(0), metadata(1)]
+        );
+        let mut merged = TransactionBatch::new(vec![vec![0u8]]);
+        merged.append(TransactionBatch::new(vec![vec![0u8]]));
+        assert!(merged.metadata().is_empty());
+    }
+
+    #[test]
+    fn test_dedup_by_signature() {
+        let transaction = |signature: u8, payload: u8| {
+            let mut transaction = vec![1u8];
+            transaction.extend_from_slice(&[signature; SIGNATURE_BYTES]);
+            transaction.push(payload);
+            transaction
+        };
+        let metadata = |id| TransactionMetadata {
+            id: Some(id),
+            priority: None,
+        };
+        let mut batch = TransactionBatch::new(vec![
+            transaction(1, 0),
+            transaction(2, 0),
+            transaction(1, 1),
+            // Transactions without a signature are kept.
+            vec![0u8],
+            vec![0u8],
+            // So are truncated ones.
+            vec![1u8; 4],
+            vec![1u8; 4],
+        ])
+        .with_metadata((0..7).map(metadata).collect());
+        batch.dedup_by_signature();
+        assert_eq!(batch.len(), 6);
+        assert_eq!(batch.wired_transactions[0], transaction(1, 0));
+        assert_eq!(batch.wired_transactions[1], transaction(2, 0));
+        assert_eq!(
+            batch
+                .metadata()
+                .iter()
+                .map(|m| m.id.unwrap())
+                .collect::<Vec<_>>(),
+            vec![0, 1, 3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn test_serde() {
+        let batch = TransactionBatch::new(vec![vec![1u8; 4], vec![2u8; 8]])
+            .with_leaders_fanout(Fanout {
+                send: 2,
+                connect: 4,
+            })
+            .with_metadata(vec![TransactionMetadata {
+                id: Some(7),
+                priority: Some(1),
+            }])
+            .with_last_valid_block_height(100);
+        let check = |deserialized: TransactionBatch| {
+            assert_eq!(deserialized.wired_transactions, batch.wired_transactions);
+            assert_eq!(deserialized.timestamp(), batch.timestamp());
+            assert!(deserialized.created_at() <= Instant::now());
+            assert_eq!(deserialized.leaders_fanout(), batch.leaders_fanout());
+            assert_eq!(deserialized.metadata(), batch.metadata());
+            assert_eq!(deserialized.last_valid_block_height(), Some(100));
+        };
+
+        let serialized = bincode::serialize(&batch).unwrap();
+        // The payloads are written as they are.
+        assert!(serialized.windows(8).any(|window| window == [2u8; 8]));
+        check(bincode::deserialize(&serialized).unwrap());
+        let json = serde_json::to_string(&batch).unwrap();
+        check(serde_json::from_str(&json).unwrap());
+
+        let json = r#"{"wired_transactions": [[0]], "timestamp": 0, "leaders_fanout": null,
+            "metadata": [{"id": null, "priority": null}, {"id": null, "priority": null}],
+            "last_valid_block_height": null}"#;
+        assert!(serde_json::from_str::<TransactionBatch>(json).is_err());
+        let oversized = SerializedBatch {
+            wired_transactions: vec![&[0u8; PACKET_DATA_SIZE + 1]],
+            timestamp: 0,
+            leaders_fanout: None,
+            metadata: &[],
+            last_valid_block_height: None,
+        };
+        let serialized = bincode::serialize(&oversized).unwrap();
+        assert!(bincode::deserialize::<TransactionBatch>(&serialized).is_err());
+    }
+
+    #[test]
+    fn test_legacy_conversions() {
+        let wired_transactions = vec![vec![1u8; 4], vec![2u8; 8]];
+        let batch = TransactionBatch::try_from(wired_transactions.clone()).unwrap();
+        assert_eq!(Vec::<Vec<u8>>::from(batch), wired_transactions);
+        assert!(matches!(
+            TransactionBatch::try_from(vec![vec![0u8; PACKET_DATA_SIZE + 1]]),
+            Err(TransactionBatchError::Oversize { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert_eq!(
+            TransactionBatch::try_new(vec![vec![0u8; PACKET_DATA_SIZE]])
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(matches!(
+            TransactionBatch::try_new(vec![vec![0u8; 1], vec![0u8; PACKET_DATA_SIZE + 1]]),
+            Err(TransactionBatchError::Oversize {
+                index: 1,
+                size
+            }) if size == PACKET_DATA_SIZE + 1
+        ));
+        assert!(matches!(
+            TransactionBatch::try_from_bytes(vec![Bytes::from(vec![0u8; PACKET_DATA_SIZE + 1])]),
+            Err(TransactionBatchError::Oversize { index: 0, .. })
+        ));
+    }
+
+    #[cfg(feature = "transaction")]
+    #[test]
+    fn test_sort_by_compute_unit_price() {
+        use solana_transaction::{Instruction, Message, VersionedMessage};
+
+        let transaction = |price: Option<u64>| {
+            let instructions: Vec<_> = price
+                .map(|price| {
+                    let mut data = vec![SET_COMPUTE_UNIT_PRICE];
+                    data.extend_from_slice(&price.to_le_bytes());
+                    Instruction::new_with_bytes(compute_budget::ID, &data, vec![])
+                })
+                .into_iter()
+                .collect();
+            bincode::serialize(&VersionedTransaction {
+                signatures: vec![],
+                message: VersionedMessage::Legacy(Message::new(&instructions, None)),
+            })
+            .unwrap()
+        };
+        let prices = [None, Some(5), Some(1), Some(5), Some(10)];
+        let mut batch = TransactionBatch::new(prices.map(transaction).to_vec()).with_metadata(
+            (0..5)
+                .map(|id| TransactionMetadata {
+                    id: Some(id),
+                    priority: None,
+                })
+                .collect(),
+        );
+        batch.sort_by_compute_unit_price();
+        assert_eq!(
+            batch
+                .metadata()
+                .iter()
+                .map(|m| m.id.unwrap())
+                .collect::<Vec<_>>(),
+            vec![4, 1, 3, 2, 0]
+        );
+        assert_eq!(batch.wired_transactions[0], transaction(Some(10)));
+        assert_eq!(compute_unit_price(&[0u8; 3]), 0);
+    }
+
+    #[cfg(feature = "transaction")]
+    #[test]
+    fn test_signatures() {
+        use solana_transaction::Signature;
+
+        // The number of signatures takes two bytes from 128 on.
+        let transactions: Vec<_> = [1, 200, 0]
+            .map(|num_signatures| VersionedTransaction {
+                signatures: (0..num_signatures)
+                    .map(|i: u8| Signature::from([num_signatures.wrapping_add(i); 64]))
+                    .collect(),
+                ..VersionedTransaction::default()
+            })
+            .into();
+        let batch = TransactionBatch::new(
+            transactions
+                .iter()
+                .map(|transaction| bincode::serialize(transaction).unwrap())
+                .collect(),
+        );
+        let signatures: Vec<_> = batch.signatures().collect();
+        assert_eq!(
+            signatures[0],
+            Some(transactions[0].signatures[0].as_array())
+        );
+        assert_eq!(
+            signatures[1],
+            Some(transactions[1].signatures[0].as_array())
+        );
+        assert_eq!(signatures[2], None);
+    }
+
+    #[cfg(feature = "transaction")]
+    #[test]
+    fn test_try_from_transactions() {
+        let transaction = VersionedTransaction::default();
+        let batch = TransactionBatch::try_from_transactions(&[transaction.clone()]).unwrap();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(
+            batch.into_iter().next().unwrap(),
+            bincode::serialize(&transaction).unwrap()
+        );
+
+        let oversize_transaction = VersionedTransaction {
+            signatures: vec![Default::default(); 100],
+            ..VersionedTransaction::default()
+        };
+        assert!(matches!(
+            TransactionBatch::try_from_transactions(&[transaction, oversize_transaction]),
+            Err(TransactionBatchError::Oversize { index: 1, .. })
+        ));
+    }
 }
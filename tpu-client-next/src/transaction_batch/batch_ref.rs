@@ -0,0 +1,108 @@
+//! This module defines [`TransactionBatchRef`], a view of wire transactions
+//! owned by the caller, and [`send_transaction_batch_ref`] which sends them
+//! over a connection without building a [`TransactionBatch`].
+
+use {
+    super::{check_sizes, TransactionBatch, TransactionBatchError},
+    crate::{quic_networking::send_slice_over_stream, QuicError},
+    quinn::Connection,
+};
+
+/// [`TransactionBatchRef`] borrows the wire transactions of a batch, e.g. from
+/// the preallocated ring buffer of a load generator, so that sending them
+/// requires no allocation per batch.
+///
+/// Borrowed batches can't be handed to the scheduler, whose workers outlive
+/// the caller's buffers, see [`send_transaction_batch_ref`] instead.
+#[derive(Debug)]
+pub struct TransactionBatchRef<'a, T> {
+    wired_transactions: &'a [T],
+}
+
+// Derived only if `T` is `Clone`, while the slice always is.
+impl<T> Clone for TransactionBatchRef<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TransactionBatchRef<'_, T> {}
+
+impl<'a, T: AsRef<[u8]>> TransactionBatchRef<'a, T> {
+    pub fn new(wired_transactions: &'a [T]) -> Self {
+        Self { wired_transactions }
+    }
+
+    /// Same as [`TransactionBatchRef::new`], but fails with the index of the
+    /// first transaction larger than what the leaders accept.
+    pub fn try_new(wired_transactions: &'a [T]) -> Result<Self, TransactionBatchError> {
+        check_sizes(wired_transactions.iter().map(|v| v.as_ref().len()))?;
+        Ok(Self::new(wired_transactions))
+    }
+
+    /// Returns the number of transactions in the batch.
+    pub fn len(&self) -> usize {
+        self.wired_transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wired_transactions.is_empty()
+    }
+
+    /// Returns the number of bytes of the transactions in the batch.
+    pub fn num_bytes(&self) -> usize {
+        self.iter().map(<[u8]>::len).sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.wired_transactions.iter().map(AsRef::as_ref)
+    }
+
+    /// Copies the transactions into a [`TransactionBatch`], which can be handed
+    /// to the scheduler.
+    pub fn to_batch(&self) -> TransactionBatch {
+        TransactionBatch::new(self.iter().map(<[u8]>::to_vec).collect())
+    }
+}
+
+/// Sends the transactions of `batch` over `connection`, each over a
+/// unidirectional stream of its own like the workers do. It stops at the first
+/// failure, the transactions before it have been sent.
+///
+/// It is meant for the callers managing their connections themselves, it
+/// neither retries nor accounts anything in [`SendTransactionStats`].
+///
+/// [`SendTransactionStats`]: crate::SendTransactionStats
+pub async fn send_transaction_batch_ref<T: AsRef<[u8]>>(
+    connection: &Connection,
+    batch: TransactionBatchRef<'_, T>,
+) -> Result<(), QuicError> {
+    for data in batch.iter() {
+        send_slice_over_stream(connection, data).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_streamer::packet::PACKET_DATA_SIZE};
+
+    #[test]
+    fn test_transaction_batch_ref() {
+        let buffer = [[1u8; 4], [2u8; 4], [3u8; 4]];
+        let batch = TransactionBatchRef::new(&buffer[..2]);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.num_bytes(), 8);
+        assert_eq!(batch.iter().collect::<Vec<_>>(), vec![&[1u8; 4], &[2u8; 4]]);
+        assert_eq!(
+            batch.to_batch().into_iter().collect::<Vec<_>>(),
+            vec![vec![1u8; 4], vec![2u8; 4]]
+        );
+
+        let buffer = [vec![0u8; 1], vec![0u8; PACKET_DATA_SIZE + 1]];
+        assert!(matches!(
+            TransactionBatchRef::try_new(&buffer),
+            Err(TransactionBatchError::Oversize { index: 1, .. })
+        ));
+    }
+}
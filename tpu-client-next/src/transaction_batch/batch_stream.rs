@@ -0,0 +1,162 @@
+//! This module provides [`batch_stream`] which groups a stream of wire
+//! transactions into [`TransactionBatch`]es.
+
+use {
+    super::{CoalescingConfig, TransactionBatch},
+    crate::clock::timestamp,
+    futures::stream::{self, Stream, StreamExt},
+    std::pin::Pin,
+    tokio::time::{sleep_until, Instant},
+    tokio_util::bytes::Bytes,
+};
+
+/// Returns a stream of the batches built out of the wire `transactions`. A
+/// batch is yielded once it holds [`CoalescingConfig::max_transactions`]
+/// transactions or [`CoalescingConfig::max_bytes`] bytes, or once its first
+/// transaction has waited for [`CoalescingConfig::max_delay`]. The pending
+/// batch is yielded when `transactions` ends, and so does the returned stream.
+///
+/// A transaction which would take the batch over
+/// [`CoalescingConfig::max_bytes`] goes to the next batch. A batch has the
+/// creation time of its first transaction, so that the time spent waiting for
+/// the others counts towards its age.
+///
+/// Iterators can be turned into a stream with [`futures::stream::iter`].
+pub fn batch_stream<S, T>(
+    transactions: S,
+    config: CoalescingConfig,
+) -> impl Stream<Item = TransactionBatch> + Send
+where
+    S: Stream<Item = T> + Send + 'static,
+    T: AsRef<[u8]> + Send + 'static,
+{
+    let builder = BatchBuilder {
+        transactions: Box::pin(transactions.fuse()),
+        config,
+        pending: Vec::new(),
+        pending_bytes: 0,
+        timestamp: 0,
+        created_at: Instant::now(),
+    };
+    stream::unfold(builder, |mut builder| async move {
+        let batch = builder.next_batch().await?;
+        Some((batch, builder))
+    })
+}
+
+/// Accumulates the transactions of the batch being built by [`batch_stream`].
+struct BatchBuilder<S> {
+    transactions: Pin<Box<stream::Fuse<S>>>,
+    config: CoalescingConfig,
+    pending: Vec<Bytes>,
+    pending_bytes: usize,
+    // Creation time of the first pending transaction
+    timestamp: u64,
+    created_at: Instant,
+}
+
+impl<S, T> BatchBuilder<S>
+where
+    S: Stream<Item = T>,
+    T: AsRef<[u8]> + Send + 'static,
+{
+    async fn next_batch(&mut self) -> Option<TransactionBatch> {
+        loop {
+            let transaction = if self.pending.is_empty() {
+                self.transactions.next().await
+            } else {
+                let deadline = self.created_at + self.config.max_delay;
+                tokio::select! {
+                    transaction = self.transactions.next() => transaction,
+                    () = sleep_until(deadline) => return self.take(),
+                }
+            };
+            let Some(transaction) = transaction else {
+                return self.take();
+            };
+            let transaction = Bytes::from_owner(transaction);
+            let num_bytes = transaction.len();
+            let flushed = if !self.pending.is_empty()
+                && self.pending_bytes.saturating_add(num_bytes) > self.config.max_bytes
+            {
+                self.take()
+            } else {
+                None
+            };
+            self.push(transaction);
+            if flushed.is_some() {
+                return flushed;
+            }
+            if self.pending.len() >= self.config.max_transactions
+                || self.pending_bytes >= self.config.max_bytes
+            {
+                return self.take();
+            }
+        }
+    }
+
+    fn push(&mut self, transaction: Bytes) {
+        if self.pending.is_empty() {
+            self.timestamp = timestamp();
+            self.created_at = Instant::now();
+        }
+        self.pending_bytes = self.pending_bytes.saturating_add(transaction.len());
+        self.pending.push(transaction);
+    }
+
+    /// Returns the pending transactions as a batch, if any.
+    fn take(&mut self) -> Option<TransactionBatch> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.pending_bytes = 0;
+        let mut batch = TransactionBatch::from_bytes(std::mem::take(&mut self.pending));
+        batch.timestamp = self.timestamp;
+        batch.created_at = self.created_at.into_std();
+        Some(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration};
+
+    #[tokio::test]
+    async fn test_batch_stream() {
+        let config = CoalescingConfig {
+            max_transactions: 3,
+            max_bytes: 25,
+            max_delay: Duration::from_millis(50),
+        };
+
+        // The batches are cut by count and by size, and the last one is
+        // yielded once the input ends.
+        let sizes: Vec<_> = batch_stream(stream::iter(vec![vec![0u8; 5]; 4]), config.clone())
+            .map(|batch| batch.len())
+            .collect()
+            .await;
+        assert_eq!(sizes, vec![3, 1]);
+        let sizes: Vec<_> = batch_stream(
+            stream::iter(vec![vec![0u8; 10], vec![0u8; 10], vec![0u8; 10]]),
+            config.clone(),
+        )
+        .map(|batch| batch.num_bytes())
+        .collect()
+        .await;
+        assert_eq!(sizes, vec![20, 10]);
+
+        // Otherwise the batch is yielded once the delay has passed.
+        let (sender, receiver) = tokio::sync::mpsc::channel(4);
+        let receiver = stream::unfold(receiver, |mut receiver| async move {
+            let transaction = receiver.recv().await?;
+            Some((transaction, receiver))
+        });
+        let mut batches = Box::pin(batch_stream(receiver, config));
+        let start = Instant::now();
+        sender.send(vec![0u8; 5]).await.unwrap();
+        assert_eq!(batches.next().await.unwrap().len(), 1);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        drop(sender);
+        assert!(batches.next().await.is_none());
+    }
+}
@@ -0,0 +1,176 @@
+//! This module provides [`coalesce_batches`] which merges small
+//! [`TransactionBatch`]es into larger ones before they reach the scheduler.
+
+use {
+    super::TransactionBatch,
+    solana_streamer::packet::PACKET_DATA_SIZE,
+    std::time::Duration,
+    tokio::{
+        select,
+        sync::mpsc,
+        time::{sleep_until, Instant},
+    },
+    tokio_util::sync::CancellationToken,
+};
+
+/// Bounds of the batches built by [`coalesce_batches`] and [`batch_stream`].
+///
+/// [`batch_stream`]: super::batch_stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoalescingConfig {
+    /// The number of transactions above which a batch is sent.
+    pub max_transactions: usize,
+
+    /// The number of bytes above which a batch is sent.
+    pub max_bytes: usize,
+
+    /// How long the first transaction of a batch waits for others before the
+    /// batch is sent anyway.
+    pub max_delay: Duration,
+}
+
+impl Default for CoalescingConfig {
+    fn default() -> Self {
+        Self {
+            max_transactions: 64,
+            max_bytes: 64 * PACKET_DATA_SIZE,
+            max_delay: Duration::from_millis(5),
+        }
+    }
+}
+
+/// Merges the batches received over `receiver` and sends them over `sender`
+/// once they hold [`CoalescingConfig::max_transactions`] transactions or
+/// [`CoalescingConfig::max_bytes`] bytes, or once their first transaction has
+/// waited for [`CoalescingConfig::max_delay`]. It is meant for producers of
+/// single transactions, e.g. an API server, as the scheduler handles a batch
+/// at a time.
+///
+/// Batches requesting different fanouts or tagged with different last valid
+/// block heights are not merged. A merged batch keeps
+/// the creation time of its oldest transactions, so that they are not
+/// considered newer than they are. Batches which exceed the bounds on their
+/// own are passed through as they are.
+///
+/// Returns once `receiver` is closed, after sending the pending batch, or once
+/// `cancel` is cancelled or `sender` is closed, in which case the pending
+/// batch is dropped.
+pub async fn coalesce_batches(
+    mut receiver: mpsc::Receiver<TransactionBatch>,
+    sender: mpsc::Sender<TransactionBatch>,
+    config: CoalescingConfig,
+    cancel: CancellationToken,
+) {
+    let mut pending: Option<(TransactionBatch, usize)> = None;
+    let mut deadline = Instant::now();
+    loop {
+        let to_send = select! {
+            batch = receiver.recv() => {
+                let Some(batch) = batch else {
+                    if let Some((pending, _)) = pending {
+                        let _ = sender.send(pending).await;
+                    }
+                    return;
+                };
+                let num_bytes = batch.num_bytes();
+                match pending.take() {
+                    Some((mut merged, merged_bytes))
+                        if merged.leaders_fanout == batch.leaders_fanout
+                            && merged.last_valid_block_height == batch.last_valid_block_height
+                            && merged.len().saturating_add(batch.len()) <= config.max_transactions
+                            && merged_bytes.saturating_add(num_bytes) <= config.max_bytes =>
+                    {
+                        merged.append(batch);
+                        pending = Some((merged, merged_bytes.saturating_add(num_bytes)));
+                        None
+                    }
+                    flushed => {
+                        deadline = Instant::now() + config.max_delay;
+                        pending = Some((batch, num_bytes));
+                        flushed.map(|(flushed, _)| flushed)
+                    }
+                }
+            }
+            () = sleep_until(deadline), if pending.is_some() => {
+                pending.take().map(|(pending, _)| pending)
+            }
+            () = cancel.cancelled() => return,
+        };
+        // Send the pending batch right away if it can't take any other
+        // transaction.
+        let is_full = pending.as_ref().is_some_and(|(pending, num_bytes)| {
+            pending.len() >= config.max_transactions || *num_bytes >= config.max_bytes
+        });
+        let full = if is_full { pending.take() } else { None };
+        for batch in to_send.into_iter().chain(full.map(|(batch, _)| batch)) {
+            let sent = select! {
+                res = sender.send(batch) => res.is_ok(),
+                () = cancel.cancelled() => false,
+            };
+            if !sent {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::connection_workers_scheduler::Fanout};
+
+    #[tokio::test]
+    async fn test_coalesce_batches() {
+        let (sender, receiver) = mpsc::channel(16);
+        let (coalesced_sender, mut coalesced_receiver) = mpsc::channel(16);
+        let config = CoalescingConfig {
+            max_transactions: 3,
+            max_bytes: 100,
+            max_delay: Duration::from_millis(50),
+        };
+        let coalescer = tokio::spawn(coalesce_batches(
+            receiver,
+            coalesced_sender,
+            config,
+            CancellationToken::new(),
+        ));
+        let single = || TransactionBatch::new(vec![vec![0u8; 10]]);
+
+        // The batch is sent as soon as it is full.
+        for _ in 0..3 {
+            sender.send(single()).await.unwrap();
+        }
+        assert_eq!(coalesced_receiver.recv().await.unwrap().len(), 3);
+
+        // Otherwise it is sent after the delay.
+        let start = Instant::now();
+        sender.send(single()).await.unwrap();
+        assert_eq!(coalesced_receiver.recv().await.unwrap().len(), 1);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // Batches requesting different fanouts are not merged, and the
+        // pending batch is sent once the input is closed.
+        sender.send(single()).await.unwrap();
+        sender
+            .send(single().with_leaders_fanout(Fanout {
+                send: 2,
+                connect: 2,
+            }))
+            .await
+            .unwrap();
+        drop(sender);
+        assert!(coalesced_receiver
+            .recv()
+            .await
+            .unwrap()
+            .leaders_fanout()
+            .is_none());
+        assert!(coalesced_receiver
+            .recv()
+            .await
+            .unwrap()
+            .leaders_fanout()
+            .is_some());
+        coalescer.await.unwrap();
+        assert!(coalesced_receiver.recv().await.is_none());
+    }
+}
@@ -6,26 +6,169 @@
 use qualifier_attr::qualifiers;
 use {
     crate::{
-        connection_worker::ConnectionWorker, logging::debug, transaction_batch::TransactionBatch,
+        connection_worker::ConnectionWorker,
+        logging::{debug, log_event, DebugValue},
+        send_transaction_stats::{DropReason, ErrorRecord, LeaderSendStats},
+        task_names::spawn_named,
+        transaction_batch::TransactionBatch,
+        workers_cache::worker_task_pool::PooledTaskHandle,
         SendTransactionStats,
     },
-    lru::LruCache,
     quinn::Endpoint,
-    std::{net::SocketAddr, sync::Arc, time::Duration},
+    std::{
+        collections::{HashMap, HashSet},
+        net::SocketAddr,
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    },
     thiserror::Error,
     tokio::{
-        sync::mpsc::{self, error::TrySendError},
+        sync::{
+            broadcast,
+            mpsc::{self, error::TrySendError},
+            watch, Semaphore,
+        },
         task::{JoinHandle, JoinSet},
+        time::{timeout, Instant},
     },
     tokio_util::sync::CancellationToken,
 };
 
+pub mod eviction_policy;
+pub mod peer_capabilities;
+pub mod worker_task_pool;
+
+pub use {
+    eviction_policy::{
+        EvictionPolicy, LfuEvictionPolicy, LruEvictionPolicy, StakeWeightedEvictionPolicy,
+        TtlEvictionPolicy,
+    },
+    peer_capabilities::PeerCapabilities,
+    worker_task_pool::WorkerTaskPool,
+};
+
+/// Default maximal number of workers removed from the cache which are shut
+/// down concurrently.
+pub const DEFAULT_MAX_CONCURRENT_SHUTDOWNS: usize = 16;
+
+/// Default time given to a removed worker to shut down gracefully before its
+/// task is aborted.
+pub const DEFAULT_SHUTDOWN_DEADLINE: Duration = Duration::from_secs(5);
+
 /// [`WorkerInfo`] holds information about a worker responsible for sending
 /// transaction batches.
 pub struct WorkerInfo {
     sender: mpsc::Sender<TransactionBatch>,
-    handle: JoinHandle<()>,
+    handle: WorkerTask,
     cancel: CancellationToken,
+    created_at: Instant,
+    last_send: Option<Instant>,
+    leader_stats: Arc<LeaderSendStats>,
+    capabilities: watch::Receiver<Option<PeerCapabilities>>,
+}
+
+/// The task running the worker, either spawned for this worker or taken from
+/// the [`WorkerTaskPool`].
+enum WorkerTask {
+    Spawned(JoinHandle<()>),
+    Pooled(PooledTaskHandle),
+}
+
+impl WorkerTask {
+    fn is_finished(&self) -> bool {
+        match self {
+            WorkerTask::Spawned(handle) => handle.is_finished(),
+            WorkerTask::Pooled(handle) => handle.is_finished(),
+        }
+    }
+
+    fn abort(&self) {
+        match self {
+            WorkerTask::Spawned(handle) => handle.abort(),
+            WorkerTask::Pooled(handle) => handle.abort(),
+        }
+    }
+
+    async fn join(&mut self) -> Result<(), WorkersCacheError> {
+        match self {
+            WorkerTask::Spawned(handle) => {
+                handle.await.map_err(|_| WorkersCacheError::TaskJoinFailure)
+            }
+            WorkerTask::Pooled(handle) => {
+                handle.join().await;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// [`WorkersCacheEvent`] notifies about the changes of the [`WorkersCache`]
+/// and the failures of its workers, see [`WorkersCache::with_events_sender`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkersCacheEvent {
+    /// The worker for the `peer` has been inserted into the cache.
+    Added { peer: SocketAddr },
+    /// The worker for the `peer` has been removed from the cache, its
+    /// shutdown follows.
+    Removed {
+        peer: SocketAddr,
+        reason: RemovalReason,
+    },
+    /// The removed worker for the `peer` has shut down. It is not `graceful`
+    /// if the worker has been aborted after the shutdown deadline or has
+    /// failed. Not emitted when the whole cache shuts down.
+    ShutDown { peer: SocketAddr, graceful: bool },
+    /// Connecting to or sending over the connection to `record.peer` has
+    /// failed. The transactions which were being sent are dropped, see
+    /// [`DropReason::ConnectionLost`].
+    Failed { record: ErrorRecord },
+}
+
+/// The reason why a worker has been removed from the [`WorkersCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// Evicted to make room for another worker.
+    Evicted,
+    /// Replaced with a new worker for the same peer.
+    Replaced,
+    /// The worker has stopped, e.g. it has failed to connect.
+    Stopped,
+    /// Removed on request of the cache user.
+    Requested,
+    /// All the workers have been flushed, e.g. after identity update.
+    Flushed,
+    /// The cache is shutting down.
+    CacheShutdown,
+}
+
+/// [`WorkerSnapshot`] describes the state of a cached worker at the moment
+/// [`WorkersCache::snapshot`] is called.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkerSnapshot {
+    pub peer: SocketAddr,
+    /// Time since the worker has been created, the worker starts connecting
+    /// right away.
+    pub age: Duration,
+    /// The number of batches waiting in the worker's channel.
+    pub queued_batches: usize,
+    /// Time since the last batch has been handed to the worker, `None` if
+    /// nothing has been sent to it yet.
+    pub since_last_send: Option<Duration>,
+    /// The number of transactions successfully sent to the peer, accumulated
+    /// over all the workers for this peer.
+    pub successfully_sent: u64,
+    /// The number of transactions which failed to be sent to the peer,
+    /// accumulated over all the workers for this peer.
+    pub failed: u64,
+    /// The number of times the peer was targeted while its worker was cached,
+    /// missing and stopped respectively, see [`LeaderSendStats`].
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub cache_expired: u64,
+    pub is_pinned: bool,
+    pub is_stopped: bool,
+    /// What the peer supports, `None` until the worker has connected.
+    pub capabilities: Option<PeerCapabilities>,
 }
 
 impl WorkerInfo {
@@ -33,47 +176,116 @@ impl WorkerInfo {
         sender: mpsc::Sender<TransactionBatch>,
         handle: JoinHandle<()>,
         cancel: CancellationToken,
+    ) -> Self {
+        Self::with_task(sender, WorkerTask::Spawned(handle), cancel)
+    }
+
+    fn with_task(
+        sender: mpsc::Sender<TransactionBatch>,
+        handle: WorkerTask,
+        cancel: CancellationToken,
     ) -> Self {
         Self {
             sender,
             handle,
             cancel,
+            created_at: Instant::now(),
+            last_send: None,
+            leader_stats: Arc::default(),
+            capabilities: watch::channel(None).1,
         }
     }
 
-    fn try_send_transactions(&self, txs_batch: TransactionBatch) -> Result<(), WorkersCacheError> {
+    /// Sets the `leader_stats` updated by the worker, they are reported in
+    /// [`WorkerSnapshot`].
+    #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+    pub(crate) fn with_leader_stats(mut self, leader_stats: Arc<LeaderSendStats>) -> Self {
+        self.leader_stats = leader_stats;
+        self
+    }
+
+    /// Returns the sender of the channel the worker takes the batches from.
+    #[cfg(any(feature = "connection-cache", all(test, feature = "test-utils")))]
+    pub(crate) fn transactions_sender(&self) -> &mpsc::Sender<TransactionBatch> {
+        &self.sender
+    }
+
+    /// Publishes the [`PeerCapabilities`] detected by the worker, they are
+    /// reported in [`WorkerSnapshot`].
+    fn with_capabilities(
+        mut self,
+        capabilities: watch::Receiver<Option<PeerCapabilities>>,
+    ) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Returns the [`PeerCapabilities`] detected on the last connection of the
+    /// worker, `None` until the worker has connected.
+    pub fn capabilities(&self) -> Option<PeerCapabilities> {
+        self.capabilities.borrow().clone()
+    }
+
+    fn try_send_transactions(
+        &mut self,
+        txs_batch: TransactionBatch,
+    ) -> Result<(), WorkersCacheError> {
         self.sender.try_send(txs_batch).map_err(|err| match err {
             TrySendError::Full(_) => WorkersCacheError::FullChannel,
             TrySendError::Closed(_) => WorkersCacheError::ReceiverDropped,
         })?;
+        self.last_send = Some(Instant::now());
         Ok(())
     }
 
     async fn send_transactions(
-        &self,
+        &mut self,
         txs_batch: TransactionBatch,
     ) -> Result<(), WorkersCacheError> {
         self.sender
             .send(txs_batch)
             .await
             .map_err(|_| WorkersCacheError::ReceiverDropped)?;
+        self.last_send = Some(Instant::now());
         Ok(())
     }
 
+    /// Returns the number of batches waiting in the worker's channel.
+    fn queued_batches(&self) -> usize {
+        self.sender
+            .max_capacity()
+            .saturating_sub(self.sender.capacity())
+    }
+
     /// Closes the worker by dropping the sender and awaiting the worker's
     /// statistics.
-    async fn shutdown(self) -> Result<(), WorkersCacheError> {
+    async fn shutdown(mut self) -> Result<(), WorkersCacheError> {
         self.cancel.cancel();
         drop(self.sender);
-        self.handle
-            .await
-            .map_err(|_| WorkersCacheError::TaskJoinFailure)?;
-        Ok(())
+        self.handle.join().await
+    }
+
+    /// Closes the worker like [`WorkerInfo::shutdown`] but aborts the worker
+    /// task if it doesn't finish within `deadline`.
+    async fn shutdown_with_deadline(mut self, deadline: Duration) -> Result<(), WorkersCacheError> {
+        self.cancel.cancel();
+        drop(self.sender);
+        match timeout(deadline, self.handle.join()).await {
+            Ok(res) => res,
+            Err(_) => {
+                self.handle.abort();
+                Err(WorkersCacheError::ShutdownTimeout)
+            }
+        }
     }
 }
 
-/// Spawns a worker to handle communication with a given peer.
+/// Spawns a worker to handle communication with a given peer. The worker runs
+/// on a task from `task_pool` if provided, otherwise on a new task. If
+/// `connect_permits` are provided, the worker establishes the connection only
+/// while holding a permit.
 #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn spawn_worker(
     endpoint: &Endpoint,
     peer: &SocketAddr,
@@ -82,11 +294,20 @@ pub(crate) fn spawn_worker(
     max_reconnect_attempts: usize,
     handshake_timeout: Duration,
     stats: Arc<SendTransactionStats>,
+    leader_stats: Arc<LeaderSendStats>,
+    task_pool: Option<&WorkerTaskPool>,
+    connect_permits: Option<Arc<Semaphore>>,
+    events_sender: Option<broadcast::Sender<WorkersCacheEvent>>,
 ) -> WorkerInfo {
     let (txs_sender, txs_receiver) = mpsc::channel(worker_channel_size);
     let endpoint = endpoint.clone();
     let peer = *peer;
 
+    #[cfg(feature = "tracing")]
+    let leader = stats
+        .error_records
+        .leader(&peer)
+        .map(|leader| crate::logging::Redacted::new("leader", &leader).to_string());
     let (mut worker, cancel) = ConnectionWorker::new(
         endpoint,
         peer,
@@ -94,19 +315,68 @@ pub(crate) fn spawn_worker(
         skip_check_transaction_age,
         max_reconnect_attempts,
         stats,
+        leader_stats.clone(),
         handshake_timeout,
     );
-    let handle = tokio::spawn(async move {
+    if let Some(connect_permits) = connect_permits {
+        worker = worker.with_connect_permits(connect_permits);
+    }
+    if let Some(events_sender) = events_sender {
+        worker = worker.with_events_sender(events_sender);
+    }
+    let capabilities = worker.subscribe_capabilities();
+    let run_worker = async move {
         worker.run().await;
-    });
+    };
+    #[cfg(feature = "tracing")]
+    let run_worker = tracing::Instrument::instrument(
+        run_worker,
+        tracing::info_span!(
+            "connection_worker",
+            peer = %crate::logging::Redacted::new("peer", &peer),
+            leader = leader.map(tracing::field::display),
+        ),
+    );
+    let handle = match task_pool {
+        Some(task_pool) => WorkerTask::Pooled(task_pool.spawn(run_worker)),
+        None => WorkerTask::Spawned(spawn_named(|| format!("tpu-worker-{peer}"), run_worker)),
+    };
 
-    WorkerInfo::new(txs_sender, handle, cancel)
+    WorkerInfo::with_task(txs_sender, handle, cancel)
+        .with_leader_stats(leader_stats)
+        .with_capabilities(capabilities)
 }
 
-/// [`WorkersCache`] manages and caches workers. It uses an LRU cache to store and
-/// manage workers. It also tracks transaction statistics for each peer.
+/// [`WorkersCache`] manages and caches workers. When the cache is full, the
+/// worker to evict is selected by the [`EvictionPolicy`], which is
+/// [`LruEvictionPolicy`] by default. It also tracks transaction statistics for
+/// each peer.
 pub struct WorkersCache {
-    workers: LruCache<SocketAddr, WorkerInfo>,
+    workers: HashMap<SocketAddr, WorkerInfo>,
+    capacity: usize,
+    /// The number of workers above which [`WorkersCache::push`] evicts
+    /// immediately. Between `capacity` and `soft_capacity`, eviction is
+    /// deferred to [`WorkersCache::evict_excess`].
+    soft_capacity: usize,
+    /// The age after which a worker is not reused anymore, see
+    /// [`WorkersCache::reuse`].
+    max_worker_lifetime: Option<Duration>,
+    eviction_policy: Box<dyn EvictionPolicy>,
+
+    /// Peers whose workers have been evicted, used to count the workers which
+    /// are inserted again after eviction.
+    evicted_peers: HashSet<SocketAddr>,
+    /// Peers whose workers are never evicted. They are not tracked by the
+    /// eviction policy.
+    pinned_peers: HashSet<SocketAddr>,
+    stats: Arc<SendTransactionStats>,
+
+    /// Optional channel to notify about added and removed workers.
+    events_sender: Option<broadcast::Sender<WorkersCacheEvent>>,
+
+    /// Bounds the number of removed workers being shut down concurrently.
+    shutdown_permits: Arc<Semaphore>,
+    shutdown_deadline: Duration,
 
     /// Indicates that the `WorkersCache` is been `shutdown()`, interrupting any outstanding
     /// `send_transactions_to_address()` invocations.
@@ -127,21 +397,295 @@ pub enum WorkersCacheError {
 
     #[error("The WorkersCache is being shutdown.")]
     ShutdownError,
+
+    #[error("Worker failed to shut down before the deadline.")]
+    ShutdownTimeout,
 }
 
 impl WorkersCache {
     #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
     pub(crate) fn new(capacity: usize, cancel: CancellationToken) -> Self {
+        Self::with_eviction_policy(capacity, cancel, Box::<LruEvictionPolicy>::default())
+    }
+
+    #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+    pub(crate) fn with_eviction_policy(
+        capacity: usize,
+        cancel: CancellationToken,
+        eviction_policy: Box<dyn EvictionPolicy>,
+    ) -> Self {
         Self {
-            workers: LruCache::new(capacity),
+            workers: HashMap::with_capacity(capacity),
+            capacity,
+            soft_capacity: capacity,
+            max_worker_lifetime: None,
+            eviction_policy,
+            evicted_peers: HashSet::new(),
+            pinned_peers: HashSet::new(),
+            stats: Arc::default(),
+            events_sender: None,
+            shutdown_permits: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_SHUTDOWNS)),
+            shutdown_deadline: DEFAULT_SHUTDOWN_DEADLINE,
             cancel,
         }
     }
 
+    /// Sets the `stats` used to account evictions and reinsertions of workers.
+    #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+    pub(crate) fn with_stats(mut self, stats: Arc<SendTransactionStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Sets the channel used to notify about the workers added to and removed
+    /// from the cache, see [`WorkersCacheEvent`]. It allows to mirror the
+    /// content of the cache without polling. The failures are only notified
+    /// by the workers spawned with the sender, see [`spawn_worker`].
+    #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+    pub(crate) fn with_events_sender(
+        mut self,
+        events_sender: broadcast::Sender<WorkersCacheEvent>,
+    ) -> Self {
+        self.events_sender = Some(events_sender);
+        self
+    }
+
+    /// Returns the sender of the events, see
+    /// [`WorkersCache::with_events_sender`].
+    pub(crate) fn events_sender(&self) -> Option<broadcast::Sender<WorkersCacheEvent>> {
+        self.events_sender.clone()
+    }
+
+    fn notify(&self, event: WorkersCacheEvent) {
+        if let Some(events_sender) = &self.events_sender {
+            // It is fine to have no subscribers at the moment.
+            let _ = events_sender.send(event);
+        }
+    }
+
+    /// Allows the cache to hold up to `soft_capacity` workers before evicting
+    /// on insertion. The workers above the capacity are evicted later by
+    /// [`WorkersCache::evict_excess`], so that inserting the worker for a new
+    /// leader doesn't wait for tearing down an old one. The `soft_capacity`
+    /// smaller than the capacity is ignored.
+    #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+    pub(crate) fn with_soft_capacity(mut self, soft_capacity: usize) -> Self {
+        self.soft_capacity = soft_capacity.max(self.capacity);
+        self
+    }
+
+    /// Limits the age of the workers which can be reused. Older workers are
+    /// meant to be replaced with new ones, so that a long-lived connection
+    /// which performs poorly without failing doesn't stay forever.
+    #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+    pub(crate) fn with_max_worker_lifetime(mut self, max_worker_lifetime: Duration) -> Self {
+        self.max_worker_lifetime = Some(max_worker_lifetime);
+        self
+    }
+
+    /// Sets how many of the removed workers can be shut down concurrently and
+    /// the time each of them is given to finish, see
+    /// [`WorkersCache::spawn_shutdown`].
+    #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+    pub(crate) fn with_shutdown_limits(
+        mut self,
+        max_concurrent_shutdowns: usize,
+        shutdown_deadline: Duration,
+    ) -> Self {
+        self.shutdown_permits = Arc::new(Semaphore::new(max_concurrent_shutdowns.max(1)));
+        self.shutdown_deadline = shutdown_deadline;
+        self
+    }
+
+    /// Shuts down the `worker` removed from the cache in a separate task, so
+    /// that the caller is not blocked.
+    ///
+    /// At most `max_concurrent_shutdowns` workers are shut down at the same
+    /// time, the others wait for their turn. The workers which don't finish
+    /// within the shutdown deadline are aborted and accounted in
+    /// [`SendTransactionStats::worker_shutdown_stragglers`].
+    pub fn spawn_shutdown(&self, worker: ShutdownWorker) {
+        let shutdown_permits = self.shutdown_permits.clone();
+        let shutdown_deadline = self.shutdown_deadline;
+        let stats = self.stats.clone();
+        let events_sender = self.events_sender.clone();
+        let leader = worker.leader();
+        spawn_named(|| format!("tpu-worker-shutdown-{leader}"), async move {
+            // The semaphore is never closed.
+            let Ok(_permit) = shutdown_permits.acquire_owned().await else {
+                return;
+            };
+            let res = worker
+                .worker
+                .shutdown_with_deadline(shutdown_deadline)
+                .await;
+            if let Some(events_sender) = events_sender {
+                let _ = events_sender.send(WorkersCacheEvent::ShutDown {
+                    peer: leader,
+                    graceful: res.is_ok(),
+                });
+            }
+            match res {
+                Ok(()) => (),
+                Err(WorkersCacheError::ShutdownTimeout) => {
+                    log_event!(
+                        debug,
+                        "Worker did not shut down in time",
+                        peer = leader,
+                        deadline = DebugValue(shutdown_deadline),
+                    );
+                    stats
+                        .worker_shutdown_stragglers
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+                Err(err) => {
+                    log_event!(
+                        debug,
+                        "Error while shutting down worker",
+                        peer = leader,
+                        error = err,
+                    );
+                }
+            }
+        });
+    }
+
+    /// Pins the `peer`, so that its worker is never evicted when the cache is
+    /// full. The peer might not have a worker yet, in which case the worker
+    /// inserted later is pinned.
+    ///
+    /// Pinned workers count towards the capacity, but if all the workers are
+    /// pinned, the cache grows beyond its capacity instead of evicting.
+    pub fn pin(&mut self, peer: SocketAddr) {
+        if self.pinned_peers.insert(peer) && self.workers.contains_key(&peer) {
+            self.eviction_policy.on_remove(&peer);
+        }
+    }
+
+    /// Unpins the `peer`, so that its worker can be evicted again.
+    pub fn unpin(&mut self, peer: &SocketAddr) {
+        if self.pinned_peers.remove(peer) && self.workers.contains_key(peer) {
+            self.eviction_policy.on_insert(*peer);
+        }
+    }
+
+    /// Checks if the `peer` is pinned, see [`WorkersCache::pin`].
+    pub fn is_pinned(&self, peer: &SocketAddr) -> bool {
+        self.pinned_peers.contains(peer)
+    }
+
+    /// Removes the workers which have stopped, either because their connection
+    /// died and they failed to reconnect or because their task has finished.
+    /// Returns the peers of the removed workers.
+    ///
+    /// It allows to discover such workers proactively instead of when the next
+    /// batch of transactions fails to be sent to them.
+    pub fn remove_stopped(&mut self) -> Vec<SocketAddr> {
+        let stopped_peers: Vec<_> = self
+            .workers
+            .iter()
+            .filter(|(_, worker)| worker.sender.is_closed() || worker.handle.is_finished())
+            .map(|(peer, _)| *peer)
+            .collect();
+        for peer in &stopped_peers {
+            if let Some(worker) = self.pop(*peer) {
+                self.spawn_shutdown(worker);
+            }
+        }
+        self.stats
+            .unhealthy_workers_removed
+            .fetch_add(stopped_peers.len() as u64, Ordering::Relaxed);
+        stopped_peers
+    }
+
+    /// Returns the number of batches waiting in the channels of all the cached
+    /// workers.
+    pub fn queued_batches(&self) -> usize {
+        self.workers.values().map(WorkerInfo::queued_batches).sum()
+    }
+
+    /// Returns the state of all the cached workers ordered by peer address,
+    /// see [`WorkerSnapshot`].
+    pub fn snapshot(&self) -> Vec<WorkerSnapshot> {
+        let now = Instant::now();
+        let mut snapshot: Vec<_> = self
+            .workers
+            .iter()
+            .map(|(peer, worker)| WorkerSnapshot {
+                peer: *peer,
+                age: now.saturating_duration_since(worker.created_at),
+                queued_batches: worker.queued_batches(),
+                since_last_send: worker
+                    .last_send
+                    .map(|last_send| now.saturating_duration_since(last_send)),
+                successfully_sent: worker
+                    .leader_stats
+                    .successfully_sent
+                    .load(Ordering::Relaxed),
+                failed: worker.leader_stats.failed.load(Ordering::Relaxed),
+                cache_hits: worker.leader_stats.cache_hits.load(Ordering::Relaxed),
+                cache_misses: worker.leader_stats.cache_misses.load(Ordering::Relaxed),
+                cache_expired: worker.leader_stats.cache_expired.load(Ordering::Relaxed),
+                is_pinned: self.pinned_peers.contains(peer),
+                is_stopped: worker.sender.is_closed(),
+                capabilities: worker.capabilities(),
+            })
+            .collect();
+        snapshot.sort_unstable_by_key(|worker| worker.peer);
+        snapshot
+    }
+
+    /// Returns the maximal number of workers in the cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the [`PeerCapabilities`] detected by the worker for `peer`,
+    /// `None` if there is no such worker or it hasn't connected yet.
+    pub fn capabilities(&self, peer: &SocketAddr) -> Option<PeerCapabilities> {
+        self.workers.get(peer).and_then(WorkerInfo::capabilities)
+    }
+
     /// Checks if the worker for a given peer exists and it hasn't been
     /// cancelled.
     pub fn contains(&self, peer: &SocketAddr) -> bool {
-        self.workers.contains(peer)
+        self.workers.contains_key(peer)
+    }
+
+    /// Marks the running worker for `peer` as used, so that it is reused
+    /// instead of being evicted while the leader is still upcoming. Returns
+    /// `false` if there is no running worker for the `peer` or if it has
+    /// outlived the maximal worker lifetime, see [`WorkersCache::is_outlived`].
+    pub fn reuse(&mut self, peer: &SocketAddr) -> bool {
+        let is_running = self
+            .workers
+            .get(peer)
+            .is_some_and(|worker| !worker.sender.is_closed() && !self.has_outlived(worker));
+        if is_running && !self.pinned_peers.contains(peer) {
+            self.eviction_policy.on_access(peer);
+        }
+        is_running
+    }
+
+    /// Checks if the worker for a given peer exists and is older than the
+    /// maximal worker lifetime, see [`WorkersCache::with_max_worker_lifetime`].
+    pub fn is_outlived(&self, peer: &SocketAddr) -> bool {
+        self.workers
+            .get(peer)
+            .is_some_and(|worker| self.has_outlived(worker))
+    }
+
+    fn has_outlived(&self, worker: &WorkerInfo) -> bool {
+        self.max_worker_lifetime
+            .is_some_and(|max_worker_lifetime| worker.created_at.elapsed() > max_worker_lifetime)
+    }
+
+    /// Checks if the worker for a given peer exists but has already stopped,
+    /// which happens when it fails to connect after `max_reconnect_attempts`.
+    pub fn is_stopped(&self, peer: &SocketAddr) -> bool {
+        self.workers
+            .get(peer)
+            .is_some_and(|worker| worker.sender.is_closed())
     }
 
     #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
@@ -150,17 +694,94 @@ impl WorkersCache {
         leader: SocketAddr,
         peer_worker: WorkerInfo,
     ) -> Option<ShutdownWorker> {
-        if let Some((leader, popped_worker)) = self.workers.push(leader, peer_worker) {
+        if self.capacity == 0 {
+            return Some(ShutdownWorker {
+                leader,
+                worker: peer_worker,
+            });
+        }
+        let is_pinned = self.pinned_peers.contains(&leader);
+        if let Some(popped_worker) = self.workers.insert(leader, peer_worker) {
+            if !is_pinned {
+                self.eviction_policy.on_insert(leader);
+            }
+            self.notify(WorkersCacheEvent::Removed {
+                peer: leader,
+                reason: RemovalReason::Replaced,
+            });
+            self.notify(WorkersCacheEvent::Added { peer: leader });
             return Some(ShutdownWorker {
                 leader,
                 worker: popped_worker,
             });
         }
-        None
+        if self.evicted_peers.remove(&leader) {
+            self.stats
+                .workers_reinserted
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        let evicted = if self.workers.len() > self.soft_capacity {
+            self.evict(Some(&leader))
+        } else {
+            None
+        };
+        if !is_pinned {
+            self.eviction_policy.on_insert(leader);
+        }
+        self.notify(WorkersCacheEvent::Added { peer: leader });
+        evicted
+    }
+
+    /// Evicts the workers above the capacity, which have been kept because of
+    /// the soft capacity, see [`WorkersCache::with_soft_capacity`]. Their
+    /// shutdown happens in the background, see
+    /// [`WorkersCache::spawn_shutdown`].
+    pub fn evict_excess(&mut self) {
+        while self.workers.len() > self.capacity {
+            let Some(evicted) = self.evict(None) else {
+                // Only pinned workers are left.
+                break;
+            };
+            self.spawn_shutdown(evicted);
+        }
+    }
+
+    /// Removes the worker selected by the eviction policy, never a pinned one
+    /// nor the `protected` one, e.g. the just inserted.
+    fn evict(&mut self, protected: Option<&SocketAddr>) -> Option<ShutdownWorker> {
+        let is_evictable =
+            |peer: &SocketAddr| Some(peer) != protected && !self.pinned_peers.contains(peer);
+        let victim = self
+            .eviction_policy
+            .select_victim()
+            .filter(|victim| is_evictable(victim) && self.workers.contains_key(victim))
+            .or_else(|| self.workers.keys().find(|peer| is_evictable(peer)).copied())?;
+        let evicted = self.remove(victim, RemovalReason::Evicted)?;
+        let queued_batches = evicted.worker.queued_batches();
+        self.stats.workers_evicted.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .evicted_worker_queued_batches
+            .fetch_add(queued_batches as u64, Ordering::Relaxed);
+        self.evicted_peers.insert(victim);
+        Some(evicted)
     }
 
     pub fn pop(&mut self, leader: SocketAddr) -> Option<ShutdownWorker> {
-        if let Some(popped_worker) = self.workers.pop(&leader) {
+        let reason = if self.is_stopped(&leader) {
+            RemovalReason::Stopped
+        } else {
+            RemovalReason::Requested
+        };
+        self.remove(leader, reason)
+    }
+
+    fn remove(&mut self, leader: SocketAddr, reason: RemovalReason) -> Option<ShutdownWorker> {
+        if let Some(popped_worker) = self.workers.remove(&leader) {
+            self.eviction_policy.on_remove(&leader);
+            self.notify(WorkersCacheEvent::Removed {
+                peer: leader,
+                reason,
+            });
             return Some(ShutdownWorker {
                 leader,
                 worker: popped_worker,
@@ -183,35 +804,76 @@ impl WorkersCache {
         peer: &SocketAddr,
         txs_batch: TransactionBatch,
     ) -> Result<(), WorkersCacheError> {
-        let Self {
-            workers, cancel, ..
-        } = self;
-        if cancel.is_cancelled() {
+        if self.cancel.is_cancelled() {
+            self.record_batch_dropped(*peer, DropReason::Cancelled, &txs_batch);
             return Err(WorkersCacheError::ShutdownError);
         }
 
-        let current_worker = workers.get(peer).expect(
+        let current_worker = self.workers.get_mut(peer).expect(
             "Failed to fetch worker for peer {peer}. Peer existence must be checked before this \
              call using `contains` method.",
         );
+        // Kept to report the transactions if the worker can't take the batch.
+        let num_transactions = txs_batch.len();
+        let metadata = txs_batch.metadata().to_vec();
         let send_res = current_worker.try_send_transactions(txs_batch);
+        if !self.pinned_peers.contains(peer) {
+            self.eviction_policy.on_access(peer);
+        }
 
-        if let Err(WorkersCacheError::ReceiverDropped) = send_res {
-            debug!(
-                "Failed to deliver transaction batch for leader {}, drop batch.",
-                peer.ip()
-            );
-            if let Some(current_worker) = workers.pop(peer) {
-                shutdown_worker(ShutdownWorker {
-                    leader: *peer,
-                    worker: current_worker,
-                })
+        match send_res {
+            Err(WorkersCacheError::ReceiverDropped) => {
+                log_event!(
+                    debug,
+                    "Failed to deliver transaction batch, drop batch",
+                    peer = peer,
+                    batch_size = num_transactions,
+                );
+                self.record_dropped(*peer, DropReason::ConnectionLost, num_transactions);
+                self.stats.notify_transactions_dropped(
+                    *peer,
+                    DropReason::ConnectionLost,
+                    &metadata,
+                );
+                if let Some(current_worker) = self.pop(*peer) {
+                    self.spawn_shutdown(current_worker)
+                }
             }
+            Err(WorkersCacheError::FullChannel) => {
+                if let Some(observer) = self.stats.observer() {
+                    observer.on_throttled(*peer, num_transactions);
+                }
+                self.record_dropped(*peer, DropReason::Overflow, num_transactions);
+                self.stats
+                    .notify_transactions_dropped(*peer, DropReason::Overflow, &metadata);
+            }
+            _ => (),
         }
 
         send_res
     }
 
+    /// Accounts `num_transactions` for `peer` dropped for `reason` in
+    /// [`SendTransactionStats::dropped`], for the [`WorkersBroadcaster`]
+    /// implementations dropping batches themselves.
+    ///
+    /// [`WorkersBroadcaster`]: crate::connection_workers_scheduler::WorkersBroadcaster
+    pub fn record_dropped(&self, peer: SocketAddr, reason: DropReason, num_transactions: usize) {
+        self.stats
+            .record_dropped(Some(peer), reason, num_transactions);
+    }
+
+    /// Same as [`WorkersCache::record_dropped`] for the transactions of
+    /// `batch`, the observer is also notified of each of them.
+    pub fn record_batch_dropped(
+        &self,
+        peer: SocketAddr,
+        reason: DropReason,
+        batch: &TransactionBatch,
+    ) {
+        self.stats.record_batch_dropped(Some(peer), reason, batch);
+    }
+
     /// Sends a batch of transactions to the worker for a given peer.
     ///
     /// If the worker for the peer is disconnected or fails, it
@@ -226,23 +888,20 @@ impl WorkersCache {
         peer: &SocketAddr,
         txs_batch: TransactionBatch,
     ) -> Result<(), WorkersCacheError> {
-        let Self {
-            workers, cancel, ..
-        } = self;
-
-        let body = async move {
-            let current_worker = workers.get(peer).expect(
+        let cancel = self.cancel.clone();
+        let body = async {
+            let current_worker = self.workers.get_mut(peer).expect(
                 "Failed to fetch worker for peer {peer}. Peer existence must be checked before \
                  this call using `contains` method.",
             );
             let send_res = current_worker.send_transactions(txs_batch).await;
+            if !self.pinned_peers.contains(peer) {
+                self.eviction_policy.on_access(peer);
+            }
             if let Err(WorkersCacheError::ReceiverDropped) = send_res {
                 // Remove the worker from the cache, if the peer has disconnected.
-                if let Some(current_worker) = workers.pop(peer) {
-                    shutdown_worker(ShutdownWorker {
-                        leader: *peer,
-                        worker: current_worker,
-                    })
+                if let Some(current_worker) = self.pop(*peer) {
+                    self.spawn_shutdown(current_worker)
                 }
             }
 
@@ -258,8 +917,14 @@ impl WorkersCache {
     /// Flushes the cache and asynchronously shuts down all workers. This method
     /// doesn't wait for the completion of all the shutdown tasks.
     pub(crate) fn flush(&mut self) {
-        while let Some((peer, current_worker)) = self.workers.pop_lru() {
-            shutdown_worker(ShutdownWorker {
+        let workers: Vec<_> = self.workers.drain().collect();
+        for (peer, current_worker) in workers {
+            self.eviction_policy.on_remove(&peer);
+            self.notify(WorkersCacheEvent::Removed {
+                peer,
+                reason: RemovalReason::Flushed,
+            });
+            self.spawn_shutdown(ShutdownWorker {
                 leader: peer,
                 worker: current_worker,
             });
@@ -277,7 +942,13 @@ impl WorkersCache {
         self.cancel.cancel();
 
         let mut tasks = JoinSet::new();
-        while let Some((peer, current_worker)) = self.workers.pop_lru() {
+        let workers: Vec<_> = self.workers.drain().collect();
+        for (peer, current_worker) in workers {
+            self.eviction_policy.on_remove(&peer);
+            self.notify(WorkersCacheEvent::Removed {
+                peer,
+                reason: RemovalReason::CacheShutdown,
+            });
             let shutdown_worker = ShutdownWorker {
                 leader: peer,
                 worker: current_worker,
@@ -311,11 +982,16 @@ impl ShutdownWorker {
 }
 
 pub fn shutdown_worker(worker: ShutdownWorker) {
-    tokio::spawn(async move {
-        let leader = worker.leader();
+    let leader = worker.leader();
+    spawn_named(|| format!("tpu-worker-shutdown-{leader}"), async move {
         let res = worker.shutdown().await;
         if let Err(err) = res {
-            debug!("Error while shutting down worker for {leader}: {err}");
+            log_event!(
+                debug,
+                "Error while shutting down worker",
+                peer = leader,
+                error = err,
+            );
         }
     });
 }
@@ -327,9 +1003,15 @@ mod tests {
             connection_worker::DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
             connection_workers_scheduler::BindTarget,
             quic_networking::{create_client_config, create_client_endpoint},
-            send_transaction_stats::SendTransactionStatsNonAtomic,
+            send_transaction_stats::{
+                DropReason, ErrorKind, LeaderSendStats, SendTransactionStatsNonAtomic,
+                StatsObserver,
+            },
             transaction_batch::TransactionBatch,
-            workers_cache::{spawn_worker, WorkersCache, WorkersCacheError},
+            workers_cache::{
+                spawn_worker, LfuEvictionPolicy, RemovalReason, ShutdownWorker, WorkerInfo,
+                WorkersCache, WorkersCacheError, WorkersCacheEvent,
+            },
             SendTransactionStats,
         },
         quinn::Endpoint,
@@ -337,10 +1019,16 @@ mod tests {
         solana_tls_utils::QuicClientCertificate,
         std::{
             net::{Ipv4Addr, SocketAddr},
-            sync::Arc,
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
             time::Duration,
         },
-        tokio::time::{sleep, timeout, Instant},
+        tokio::{
+            sync::{broadcast, mpsc, Semaphore},
+            time::{sleep, timeout, Instant},
+        },
         tokio_util::sync::CancellationToken,
     };
 
@@ -372,9 +1060,14 @@ mod tests {
             max_reconnect_attempts,
             DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
             stats.clone(),
+            Arc::default(),
+            None,
+            None,
+            None,
         );
 
-        timeout(TEST_MAX_TIME, worker_info.handle)
+        let mut worker_info = worker_info;
+        timeout(TEST_MAX_TIME, worker_info.handle.join())
             .await
             .unwrap_or_else(|_| panic!("Should stop in less than {TEST_MAX_TIME:?}."))
             .expect("Worker task should finish successfully.");
@@ -387,6 +1080,54 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_worker_waits_for_connect_permit() {
+        let endpoint = create_test_endpoint();
+
+        let port_range = unique_port_range_for_tests(2);
+        let peer: SocketAddr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port_range.start);
+
+        let connect_permits = Arc::new(Semaphore::new(0));
+        let handshake_timeout = Duration::from_millis(100);
+        let stats = Arc::new(SendTransactionStats::default());
+        let mut worker_info = spawn_worker(
+            &endpoint,
+            &peer,
+            1,
+            true,
+            0,
+            handshake_timeout,
+            stats.clone(),
+            Arc::default(),
+            None,
+            Some(connect_permits.clone()),
+            None,
+        );
+
+        // Without a permit, the worker doesn't even try to connect.
+        sleep(handshake_timeout * 5).await;
+        assert!(!worker_info.handle.is_finished());
+        assert_eq!(
+            stats.to_non_atomic(),
+            SendTransactionStatsNonAtomic::default()
+        );
+
+        connect_permits.add_permits(1);
+        timeout(TEST_MAX_TIME, worker_info.handle.join())
+            .await
+            .unwrap_or_else(|_| panic!("Should stop in less than {TEST_MAX_TIME:?}."))
+            .expect("Worker task should finish successfully.");
+        assert_eq!(
+            stats.read_and_reset(),
+            SendTransactionStatsNonAtomic {
+                connection_error_timed_out: 1,
+                ..Default::default()
+            }
+        );
+        // The permit is released once the handshake is over.
+        assert_eq!(connect_permits.available_permits(), 1);
+    }
+
     #[tokio::test]
     async fn test_worker_shutdown() {
         let endpoint = create_test_endpoint();
@@ -406,6 +1147,10 @@ mod tests {
             max_reconnect_attempts,
             DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
             stats.clone(),
+            Arc::default(),
+            None,
+            None,
+            None,
         );
 
         timeout(TEST_MAX_TIME, worker_info.shutdown())
@@ -414,6 +1159,45 @@ mod tests {
             .expect("Worker task should finish successfully.");
     }
 
+    #[tokio::test]
+    async fn test_worker_failure_event() {
+        let endpoint = create_test_endpoint();
+        // Nothing listens on the port, so the handshake times out.
+        let port_range = unique_port_range_for_tests(1);
+        let peer: SocketAddr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port_range.start);
+        let (events_sender, mut events_receiver) = broadcast::channel(16);
+        let stats = Arc::new(SendTransactionStats::default());
+        let worker_info = spawn_worker(
+            &endpoint,
+            &peer,
+            1,
+            true,
+            0,
+            Duration::from_millis(100),
+            stats.clone(),
+            Arc::default(),
+            None,
+            None,
+            Some(events_sender),
+        );
+
+        let event = timeout(TEST_MAX_TIME, events_receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let WorkersCacheEvent::Failed { record } = event else {
+            panic!("Unexpected event: {event:?}");
+        };
+        assert_eq!(record.peer, peer);
+        assert_eq!(record.kind, ErrorKind::HandshakeTimeout);
+        assert_eq!(stats.error_records.recent()[0], record);
+
+        timeout(TEST_MAX_TIME, worker_info.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
     // Verifies that a worker which terminates (e.g. due to connection failure)
     // is properly detected, its sender is closed, and it is removed from the
     // `WorkersCache`.
@@ -438,13 +1222,17 @@ mod tests {
             max_reconnect_attempts,
             DEFAULT_MAX_CONNECTION_HANDSHAKE_TIMEOUT,
             stats.clone(),
+            Arc::default(),
+            None,
+            None,
+            None,
         );
         assert!(cache.push(peer, worker).is_none());
+        assert!(!cache.is_stopped(&peer));
 
-        let worker_info = cache.workers.peek(&peer).unwrap();
         // wait until sender is closed which happens when task has finished.
         let start = Instant::now();
-        while !worker_info.sender.is_closed() {
+        while !cache.is_stopped(&peer) {
             if start.elapsed() > TEST_MAX_TIME {
                 panic!("Sender did not close in {TEST_MAX_TIME:?}");
             }
@@ -473,4 +1261,368 @@ mod tests {
             }
         );
     }
+
+    #[tokio::test]
+    async fn test_eviction_policy() {
+        let cancel = CancellationToken::new();
+        let stats = Arc::new(SendTransactionStats::default());
+        let mut cache =
+            WorkersCache::with_eviction_policy(2, cancel, Box::<LfuEvictionPolicy>::default())
+                .with_stats(stats.clone());
+        let peers: Vec<_> = (0..3)
+            .map(|i| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i))
+            .collect();
+        // Keep the receivers alive, so that sending to the workers succeeds.
+        let mut receivers = Vec::new();
+        let mut idle_worker = || {
+            let (sender, receiver) = mpsc::channel(10);
+            receivers.push(receiver);
+            WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new())
+        };
+
+        assert!(cache.push(peers[0], idle_worker()).is_none());
+        assert!(cache.push(peers[1], idle_worker()).is_none());
+        for _ in 0..2 {
+            cache
+                .try_send_transactions_to_address(&peers[0], TransactionBatch::new(vec![vec![0u8]]))
+                .unwrap();
+        }
+        cache
+            .try_send_transactions_to_address(&peers[1], TransactionBatch::new(vec![vec![0u8]]))
+            .unwrap();
+
+        // The least frequently used worker is evicted, even though the other
+        // one has been inserted before.
+        let evicted = cache.push(peers[2], idle_worker()).unwrap();
+        assert_eq!(evicted.leader(), peers[1]);
+        assert!(cache.contains(&peers[0]));
+        assert!(cache.contains(&peers[2]));
+
+        // The evicted worker is inserted again, evicting the unused one.
+        let evicted = cache.push(peers[1], idle_worker()).unwrap();
+        assert_eq!(evicted.leader(), peers[2]);
+        assert_eq!(
+            stats.read_and_reset(),
+            SendTransactionStatsNonAtomic {
+                workers_evicted: 2,
+                evicted_worker_queued_batches: 1,
+                workers_reinserted: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_stopped() {
+        let cancel = CancellationToken::new();
+        let stats = Arc::new(SendTransactionStats::default());
+        let mut cache = WorkersCache::new(10, cancel.clone()).with_stats(stats.clone());
+        let healthy_peer = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000);
+        let stopped_peer = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8001);
+
+        let (sender, _receiver) = mpsc::channel(10);
+        let worker = WorkerInfo::new(
+            sender,
+            tokio::spawn(std::future::pending()),
+            CancellationToken::new(),
+        );
+        assert!(cache.push(healthy_peer, worker).is_none());
+        // Dropping the receiver emulates the worker which has stopped.
+        let (sender, _) = mpsc::channel(10);
+        let worker = WorkerInfo::new(
+            sender,
+            tokio::spawn(std::future::pending()),
+            CancellationToken::new(),
+        );
+        assert!(cache.push(stopped_peer, worker).is_none());
+
+        assert_eq!(cache.remove_stopped(), vec![stopped_peer]);
+        assert!(cache.contains(&healthy_peer));
+        assert!(!cache.contains(&stopped_peer));
+        assert!(cache.remove_stopped().is_empty());
+        assert_eq!(
+            stats.read_and_reset(),
+            SendTransactionStatsNonAtomic {
+                unhealthy_workers_removed: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pinned_workers_not_evicted() {
+        let cancel = CancellationToken::new();
+        let mut cache = WorkersCache::new(2, cancel);
+        let peers: Vec<_> = (0..4)
+            .map(|i| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i))
+            .collect();
+        let mut receivers = Vec::new();
+        let mut idle_worker = || {
+            let (sender, receiver) = mpsc::channel(10);
+            receivers.push(receiver);
+            WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new())
+        };
+
+        // The pinned peer is the least recently used one, but it is not evicted.
+        cache.pin(peers[0]);
+        assert!(cache.push(peers[0], idle_worker()).is_none());
+        assert!(cache.push(peers[1], idle_worker()).is_none());
+        let evicted = cache.push(peers[2], idle_worker()).unwrap();
+        assert_eq!(evicted.leader(), peers[1]);
+        assert!(cache.contains(&peers[0]));
+
+        // When all the other workers are pinned, the cache grows beyond its
+        // capacity.
+        cache.pin(peers[2]);
+        assert!(cache.push(peers[3], idle_worker()).is_none());
+        assert!(cache.contains(&peers[0]));
+        assert!(cache.contains(&peers[2]));
+        assert!(cache.contains(&peers[3]));
+
+        // Once unpinned, the worker can be evicted again.
+        cache.unpin(&peers[0]);
+        assert!(!cache.is_pinned(&peers[0]));
+        let evicted = cache.push(peers[1], idle_worker()).unwrap();
+        assert_eq!(evicted.leader(), peers[3]);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let cancel = CancellationToken::new();
+        let mut cache = WorkersCache::new(2, cancel);
+        let peers: Vec<_> = (0..2)
+            .map(|i| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i))
+            .collect();
+        let (sender, _receiver) = mpsc::channel(10);
+        let leader_stats = Arc::new(LeaderSendStats {
+            successfully_sent: 3.into(),
+            failed: 1.into(),
+            cache_hits: 2.into(),
+            ..Default::default()
+        });
+        let worker = WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new())
+            .with_leader_stats(leader_stats);
+        assert!(cache.push(peers[1], worker).is_none());
+        let (sender, _) = mpsc::channel(10);
+        let worker = WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new());
+        assert!(cache.push(peers[0], worker).is_none());
+        cache.pin(peers[0]);
+        cache
+            .try_send_transactions_to_address(&peers[1], TransactionBatch::new(vec![vec![0u8]]))
+            .unwrap();
+
+        let snapshot = cache.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].peer, peers[0]);
+        assert!(snapshot[0].is_pinned);
+        assert!(snapshot[0].is_stopped);
+        assert_eq!(snapshot[0].since_last_send, None);
+        assert_eq!(snapshot[1].peer, peers[1]);
+        assert!(!snapshot[1].is_pinned);
+        assert!(!snapshot[1].is_stopped);
+        assert_eq!(snapshot[1].queued_batches, 1);
+        assert!(snapshot[1].since_last_send.is_some());
+        assert_eq!(snapshot[1].successfully_sent, 3);
+        assert_eq!(snapshot[1].failed, 1);
+        assert_eq!(snapshot[1].cache_hits, 2);
+        assert_eq!(snapshot[1].cache_misses, 0);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_shutdown_straggler() {
+        let cancel = CancellationToken::new();
+        let stats = Arc::new(SendTransactionStats::default());
+        let cache = WorkersCache::new(1, cancel)
+            .with_stats(stats.clone())
+            .with_shutdown_limits(1, Duration::from_millis(10));
+        let peer = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000);
+
+        // The worker ignores cancellation, so it never finishes on its own.
+        let (sender, _receiver) = mpsc::channel(10);
+        let handle = tokio::spawn(std::future::pending());
+        let abort_handle = handle.abort_handle();
+        cache.spawn_shutdown(ShutdownWorker {
+            leader: peer,
+            worker: WorkerInfo::new(sender, handle, CancellationToken::new()),
+        });
+
+        let start = Instant::now();
+        while !abort_handle.is_finished() {
+            if start.elapsed() > TEST_MAX_TIME {
+                panic!("Worker was not aborted in {TEST_MAX_TIME:?}");
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        // The stats are updated right after the task is aborted.
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(
+            stats.read_and_reset(),
+            SendTransactionStatsNonAtomic {
+                worker_shutdown_stragglers: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reused_worker_not_evicted() {
+        let cancel = CancellationToken::new();
+        let mut cache = WorkersCache::new(2, cancel);
+        let peers: Vec<_> = (0..3)
+            .map(|i| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i))
+            .collect();
+        let mut receivers = Vec::new();
+        let mut idle_worker = || {
+            let (sender, receiver) = mpsc::channel(10);
+            receivers.push(receiver);
+            WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new())
+        };
+
+        assert!(cache.push(peers[0], idle_worker()).is_none());
+        assert!(cache.push(peers[1], idle_worker()).is_none());
+        // The first leader reappears in the lookahead window.
+        assert!(cache.reuse(&peers[0]));
+        assert!(!cache.reuse(&peers[2]));
+        let evicted = cache.push(peers[2], idle_worker()).unwrap();
+        assert_eq!(evicted.leader(), peers[1]);
+    }
+
+    #[tokio::test]
+    async fn test_soft_capacity() {
+        let cancel = CancellationToken::new();
+        let stats = Arc::new(SendTransactionStats::default());
+        let mut cache = WorkersCache::new(1, cancel)
+            .with_stats(stats.clone())
+            .with_soft_capacity(2);
+        let peers: Vec<_> = (0..3)
+            .map(|i| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i))
+            .collect();
+        let mut receivers = Vec::new();
+        let mut idle_worker = || {
+            let (sender, receiver) = mpsc::channel(10);
+            receivers.push(receiver);
+            WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new())
+        };
+
+        // Above the capacity but within the soft capacity, nothing is evicted
+        // on insertion.
+        assert!(cache.push(peers[0], idle_worker()).is_none());
+        assert!(cache.push(peers[1], idle_worker()).is_none());
+        // Above the soft capacity, the eviction happens right away.
+        let evicted = cache.push(peers[2], idle_worker()).unwrap();
+        assert_eq!(evicted.leader(), peers[0]);
+
+        cache.evict_excess();
+        assert!(!cache.contains(&peers[1]));
+        assert!(cache.contains(&peers[2]));
+        assert_eq!(stats.workers_evicted.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_max_worker_lifetime() {
+        let cancel = CancellationToken::new();
+        let max_worker_lifetime = Duration::from_millis(100);
+        let mut cache = WorkersCache::new(2, cancel).with_max_worker_lifetime(max_worker_lifetime);
+        let peer = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000);
+        let (sender, _receiver) = mpsc::channel(10);
+        let worker = WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new());
+        assert!(cache.push(peer, worker).is_none());
+        assert!(cache.reuse(&peer));
+        assert!(!cache.is_outlived(&peer));
+
+        sleep(max_worker_lifetime * 2).await;
+        assert!(cache.is_outlived(&peer));
+        assert!(!cache.reuse(&peer));
+
+        // The outlived worker is replaced with a new one.
+        let (sender, _receiver) = mpsc::channel(10);
+        let worker = WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new());
+        assert!(cache.push(peer, worker).is_some());
+        assert!(cache.reuse(&peer));
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let cancel = CancellationToken::new();
+        let (events_sender, mut events_receiver) = broadcast::channel(16);
+        let mut cache = WorkersCache::new(1, cancel).with_events_sender(events_sender);
+        let peers: Vec<_> = (0..2)
+            .map(|i| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i))
+            .collect();
+        let mut receivers = Vec::new();
+        let mut idle_worker = || {
+            let (sender, receiver) = mpsc::channel(10);
+            receivers.push(receiver);
+            WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new())
+        };
+
+        assert!(cache.push(peers[0], idle_worker()).is_none());
+        let evicted = cache.push(peers[1], idle_worker()).unwrap();
+        cache.spawn_shutdown(evicted);
+        cache.shutdown().await;
+
+        let mut events = Vec::new();
+        for _ in 0..5 {
+            let event = timeout(TEST_MAX_TIME, events_receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            events.push(event);
+        }
+        assert_eq!(
+            events[..3],
+            [
+                WorkersCacheEvent::Added { peer: peers[0] },
+                WorkersCacheEvent::Removed {
+                    peer: peers[0],
+                    reason: RemovalReason::Evicted,
+                },
+                WorkersCacheEvent::Added { peer: peers[1] },
+            ]
+        );
+        // The evicted worker shuts down in the background, so it might finish
+        // after the cache has started shutting down.
+        assert!(events[3..].contains(&WorkersCacheEvent::ShutDown {
+            peer: peers[0],
+            graceful: true,
+        }));
+        assert!(events[3..].contains(&WorkersCacheEvent::Removed {
+            peer: peers[1],
+            reason: RemovalReason::CacheShutdown,
+        }));
+    }
+
+    #[derive(Default)]
+    struct ThrottledCounter(AtomicUsize);
+
+    impl StatsObserver for ThrottledCounter {
+        fn on_throttled(&self, _peer: SocketAddr, num_transactions: usize) {
+            self.0.fetch_add(num_transactions, Ordering::Relaxed);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observer_notified_when_throttled() {
+        let stats = Arc::new(SendTransactionStats::default());
+        let observer = Arc::new(ThrottledCounter::default());
+        stats.set_observer(observer.clone());
+        let mut cache = WorkersCache::new(1, CancellationToken::new()).with_stats(stats.clone());
+        let peer = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000);
+        let (sender, _receiver) = mpsc::channel(1);
+        cache.push(
+            peer,
+            WorkerInfo::new(sender, tokio::spawn(async {}), CancellationToken::new()),
+        );
+
+        let batch = TransactionBatch::new(vec![vec![0u8]; 2]);
+        cache
+            .try_send_transactions_to_address(&peer, batch.clone())
+            .unwrap();
+        assert_eq!(
+            cache.try_send_transactions_to_address(&peer, batch),
+            Err(WorkersCacheError::FullChannel)
+        );
+        assert_eq!(observer.0.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.dropped.get(DropReason::Overflow), 2);
+    }
 }
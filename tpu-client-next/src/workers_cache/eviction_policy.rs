@@ -0,0 +1,300 @@
+//! This module defines [`EvictionPolicy`] which is used by
+//! [`WorkersCache`](super::WorkersCache) to decide which worker to evict when
+//! the cache is full, along with the implementations of the common policies.
+
+use {
+    lru::LruCache,
+    std::{
+        collections::HashMap,
+        net::SocketAddr,
+        sync::{Arc, RwLock},
+        time::{Duration, Instant},
+    },
+};
+
+/// [`EvictionPolicy`] tracks how the workers in the cache are used and selects
+/// the one to evict when a worker for a new peer must be added to a full
+/// cache.
+pub trait EvictionPolicy: Send {
+    /// Records that a worker for `peer` has been added to the cache.
+    fn on_insert(&mut self, peer: SocketAddr);
+
+    /// Records that the worker for `peer` has been used to send transactions.
+    fn on_access(&mut self, peer: &SocketAddr);
+
+    /// Records that the worker for `peer` has been removed from the cache.
+    fn on_remove(&mut self, peer: &SocketAddr);
+
+    /// Returns the peer whose worker should be evicted, or `None` if the
+    /// policy doesn't track any worker.
+    fn select_victim(&self) -> Option<SocketAddr>;
+}
+
+/// Evicts the least recently used worker. This is the default policy.
+pub struct LruEvictionPolicy {
+    peers: LruCache<SocketAddr, ()>,
+}
+
+impl Default for LruEvictionPolicy {
+    fn default() -> Self {
+        Self {
+            peers: LruCache::unbounded(),
+        }
+    }
+}
+
+impl EvictionPolicy for LruEvictionPolicy {
+    fn on_insert(&mut self, peer: SocketAddr) {
+        self.peers.put(peer, ());
+    }
+
+    fn on_access(&mut self, peer: &SocketAddr) {
+        self.peers.get(peer);
+    }
+
+    fn on_remove(&mut self, peer: &SocketAddr) {
+        self.peers.pop(peer);
+    }
+
+    fn select_victim(&self) -> Option<SocketAddr> {
+        self.peers.peek_lru().map(|(peer, _)| *peer)
+    }
+}
+
+/// Usage of the worker for a single peer.
+struct PeerUsage {
+    inserted_at: Instant,
+    num_accesses: u64,
+    // Values of `UsageTracker::clock` at the insertion and at the last access,
+    // used to order peers by age and by recency.
+    insertion: u64,
+    last_access: u64,
+}
+
+/// Tracks the usage of workers for the policies which need more than the
+/// recency of access.
+#[derive(Default)]
+struct UsageTracker {
+    peers: HashMap<SocketAddr, PeerUsage>,
+    clock: u64,
+}
+
+impl UsageTracker {
+    fn on_insert(&mut self, peer: SocketAddr) {
+        self.clock = self.clock.wrapping_add(1);
+        self.peers.insert(
+            peer,
+            PeerUsage {
+                inserted_at: Instant::now(),
+                num_accesses: 0,
+                insertion: self.clock,
+                last_access: self.clock,
+            },
+        );
+    }
+
+    fn on_access(&mut self, peer: &SocketAddr) {
+        self.clock = self.clock.wrapping_add(1);
+        if let Some(usage) = self.peers.get_mut(peer) {
+            usage.num_accesses = usage.num_accesses.saturating_add(1);
+            usage.last_access = self.clock;
+        }
+    }
+
+    fn on_remove(&mut self, peer: &SocketAddr) {
+        self.peers.remove(peer);
+    }
+
+    fn min_by_key<K: Ord>(&self, f: impl Fn(&SocketAddr, &PeerUsage) -> K) -> Option<SocketAddr> {
+        self.peers
+            .iter()
+            .min_by_key(|(peer, usage)| f(peer, usage))
+            .map(|(peer, _)| *peer)
+    }
+}
+
+/// Evicts the least frequently used worker, so that connections to the leaders
+/// which are targeted often stay warm. Among the workers used equally often,
+/// the least recently used one is evicted.
+#[derive(Default)]
+pub struct LfuEvictionPolicy {
+    usage: UsageTracker,
+}
+
+impl EvictionPolicy for LfuEvictionPolicy {
+    fn on_insert(&mut self, peer: SocketAddr) {
+        self.usage.on_insert(peer);
+    }
+
+    fn on_access(&mut self, peer: &SocketAddr) {
+        self.usage.on_access(peer);
+    }
+
+    fn on_remove(&mut self, peer: &SocketAddr) {
+        self.usage.on_remove(peer);
+    }
+
+    fn select_victim(&self) -> Option<SocketAddr> {
+        self.usage
+            .min_by_key(|_, usage| (usage.num_accesses, usage.last_access))
+    }
+}
+
+/// Evicts the oldest worker once it has been in the cache for longer than
+/// `ttl`, which makes sure that the connections are renewed from time to time.
+/// If none of the workers has expired, the least recently used one is evicted.
+pub struct TtlEvictionPolicy {
+    ttl: Duration,
+    usage: UsageTracker,
+}
+
+impl TtlEvictionPolicy {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            usage: UsageTracker::default(),
+        }
+    }
+}
+
+impl EvictionPolicy for TtlEvictionPolicy {
+    fn on_insert(&mut self, peer: SocketAddr) {
+        self.usage.on_insert(peer);
+    }
+
+    fn on_access(&mut self, peer: &SocketAddr) {
+        self.usage.on_access(peer);
+    }
+
+    fn on_remove(&mut self, peer: &SocketAddr) {
+        self.usage.on_remove(peer);
+    }
+
+    fn select_victim(&self) -> Option<SocketAddr> {
+        self.usage
+            .peers
+            .iter()
+            .filter(|(_, usage)| usage.inserted_at.elapsed() >= self.ttl)
+            .min_by_key(|(_, usage)| usage.insertion)
+            .map(|(peer, _)| *peer)
+            .or_else(|| self.usage.min_by_key(|_, usage| usage.last_access))
+    }
+}
+
+/// Evicts the worker of the peer with the lowest stake, so that connections to
+/// the leaders which produce most of the blocks stay warm. Peers absent in
+/// `stakes` are considered unstaked. Among the peers with equal stake, the
+/// least recently used one is evicted.
+///
+/// The `stakes` are shared, so they can be updated by the caller, for example
+/// on epoch boundaries.
+pub struct StakeWeightedEvictionPolicy {
+    stakes: Arc<RwLock<HashMap<SocketAddr, u64>>>,
+    usage: UsageTracker,
+}
+
+impl StakeWeightedEvictionPolicy {
+    pub fn new(stakes: Arc<RwLock<HashMap<SocketAddr, u64>>>) -> Self {
+        Self {
+            stakes,
+            usage: UsageTracker::default(),
+        }
+    }
+}
+
+impl EvictionPolicy for StakeWeightedEvictionPolicy {
+    fn on_insert(&mut self, peer: SocketAddr) {
+        self.usage.on_insert(peer);
+    }
+
+    fn on_access(&mut self, peer: &SocketAddr) {
+        self.usage.on_access(peer);
+    }
+
+    fn on_remove(&mut self, peer: &SocketAddr) {
+        self.usage.on_remove(peer);
+    }
+
+    fn select_victim(&self) -> Option<SocketAddr> {
+        let stakes = self.stakes.read().unwrap();
+        self.usage.min_by_key(|peer, usage| {
+            (
+                stakes.get(peer).copied().unwrap_or_default(),
+                usage.last_access,
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::net::{IpAddr, Ipv4Addr},
+    };
+
+    fn peers(num: u16) -> Vec<SocketAddr> {
+        (0..num)
+            .map(|i| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8000 + i))
+            .collect()
+    }
+
+    #[test]
+    fn test_lru_eviction_policy() {
+        let peers = peers(3);
+        let mut policy = LruEvictionPolicy::default();
+        assert_eq!(policy.select_victim(), None);
+        peers.iter().for_each(|peer| policy.on_insert(*peer));
+        policy.on_access(&peers[0]);
+        assert_eq!(policy.select_victim(), Some(peers[1]));
+        policy.on_remove(&peers[1]);
+        assert_eq!(policy.select_victim(), Some(peers[2]));
+    }
+
+    #[test]
+    fn test_lfu_eviction_policy() {
+        let peers = peers(3);
+        let mut policy = LfuEvictionPolicy::default();
+        peers.iter().for_each(|peer| policy.on_insert(*peer));
+        policy.on_access(&peers[0]);
+        policy.on_access(&peers[0]);
+        policy.on_access(&peers[2]);
+        policy.on_access(&peers[1]);
+        // Peers 1 and 2 are used equally often, but 2 is less recent.
+        assert_eq!(policy.select_victim(), Some(peers[2]));
+        policy.on_remove(&peers[2]);
+        assert_eq!(policy.select_victim(), Some(peers[1]));
+    }
+
+    #[test]
+    fn test_ttl_eviction_policy() {
+        let peers = peers(2);
+        let mut policy = TtlEvictionPolicy::new(Duration::from_secs(60));
+        peers.iter().for_each(|peer| policy.on_insert(*peer));
+        policy.on_access(&peers[0]);
+        // Nothing has expired, so the least recently used worker is evicted.
+        assert_eq!(policy.select_victim(), Some(peers[1]));
+
+        let mut policy = TtlEvictionPolicy::new(Duration::ZERO);
+        peers.iter().for_each(|peer| policy.on_insert(*peer));
+        policy.on_access(&peers[0]);
+        // Everything has expired, so the oldest worker is evicted.
+        assert_eq!(policy.select_victim(), Some(peers[0]));
+    }
+
+    #[test]
+    fn test_stake_weighted_eviction_policy() {
+        let peers = peers(3);
+        let stakes = Arc::new(RwLock::new(HashMap::from([
+            (peers[0], 100),
+            (peers[1], 10),
+        ])));
+        let mut policy = StakeWeightedEvictionPolicy::new(stakes.clone());
+        peers.iter().for_each(|peer| policy.on_insert(*peer));
+        // Peer 2 is unstaked.
+        assert_eq!(policy.select_victim(), Some(peers[2]));
+
+        stakes.write().unwrap().insert(peers[2], 1000);
+        assert_eq!(policy.select_victim(), Some(peers[1]));
+    }
+}
@@ -0,0 +1,56 @@
+//! This module defines [`PeerCapabilities`] which describes what a peer
+//! supports, as detected by its worker on the established connection.
+
+use {
+    quinn::{crypto::rustls::HandshakeData, Connection},
+    serde_derive::Serialize,
+    solana_streamer::nonblocking::quic::ALPN_TPU_PROTOCOL_ID,
+};
+
+/// [`PeerCapabilities`] describes what the peer supports, so that the client
+/// can adapt its behavior per validator version. It is detected by the worker
+/// after the handshake and refreshed after each batch, see
+/// [`WorkerSnapshot::capabilities`](super::WorkerSnapshot::capabilities).
+///
+/// QUIC doesn't let the client read the stream limit granted by the peer,
+/// which Agave validators set according to the stake of the client, so the
+/// treatment by the peer is inferred from whether the limit has been reached.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PeerCapabilities {
+    /// The application protocol negotiated during the handshake, `None` if
+    /// the peer doesn't support ALPN.
+    pub alpn_protocol: Option<Vec<u8>>,
+    /// The maximal size of the datagrams accepted by the peer, `None` if it
+    /// doesn't support datagrams.
+    pub max_datagram_size: Option<usize>,
+    /// Whether sending has been blocked by the stream limit of the peer, which
+    /// is more likely for the connections treated as unstaked.
+    pub stream_limit_reached: bool,
+    /// The MTU currently used on the path to the peer, it grows as the MTU
+    /// discovery progresses.
+    pub mtu: u16,
+    /// The congestion window right after the handshake, in bytes.
+    pub initial_congestion_window: u64,
+}
+
+impl PeerCapabilities {
+    pub(crate) fn detect(connection: &Connection) -> Self {
+        let alpn_protocol = connection
+            .handshake_data()
+            .and_then(|data| data.downcast::<HandshakeData>().ok())
+            .and_then(|data| data.protocol);
+        let stats = connection.stats();
+        Self {
+            alpn_protocol,
+            max_datagram_size: connection.max_datagram_size(),
+            stream_limit_reached: stats.frame_tx.streams_blocked_uni > 0,
+            mtu: stats.path.current_mtu,
+            initial_congestion_window: stats.path.cwnd,
+        }
+    }
+
+    /// Returns whether the peer has negotiated the TPU application protocol.
+    pub fn supports_tpu_alpn(&self) -> bool {
+        self.alpn_protocol.as_deref() == Some(ALPN_TPU_PROTOCOL_ID)
+    }
+}
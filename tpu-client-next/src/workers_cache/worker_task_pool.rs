@@ -0,0 +1,188 @@
+//! This module defines [`WorkerTaskPool`] which allows to run connection
+//! workers on recycled tokio tasks instead of spawning a new task per worker.
+
+#[cfg(feature = "agave-unstable-api")]
+use qualifier_attr::qualifiers;
+use {
+    crate::task_names::spawn_named,
+    std::{
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    },
+    tokio::sync::{mpsc, Mutex},
+    tokio_util::sync::CancellationToken,
+};
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// [`WorkerTaskPool`] keeps up to `max_idle_tasks` tokio tasks which have
+/// finished running a worker and can be bound to the worker for another peer.
+/// It reduces the overhead of spawning and tearing down tasks for schedulers
+/// cycling through many leaders.
+///
+/// Idle tasks exit once the pool is dropped.
+pub struct WorkerTaskPool {
+    job_sender: mpsc::UnboundedSender<Job>,
+    idle_tasks: Arc<IdleTasks>,
+}
+
+/// State shared between the pool and its tasks.
+struct IdleTasks {
+    job_receiver: Mutex<mpsc::UnboundedReceiver<Job>>,
+    num_idle: AtomicUsize,
+    max_idle: usize,
+}
+
+/// Handle of the job run by [`WorkerTaskPool`], it plays the role of
+/// [`tokio::task::JoinHandle`] for pooled workers.
+#[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+pub(crate) struct PooledTaskHandle {
+    done: CancellationToken,
+    abort: CancellationToken,
+}
+
+impl WorkerTaskPool {
+    pub fn new(max_idle_tasks: usize) -> Self {
+        let (job_sender, job_receiver) = mpsc::unbounded_channel();
+        Self {
+            job_sender,
+            idle_tasks: Arc::new(IdleTasks {
+                job_receiver: Mutex::new(job_receiver),
+                num_idle: AtomicUsize::new(0),
+                max_idle: max_idle_tasks,
+            }),
+        }
+    }
+
+    /// Returns the number of tasks waiting for a job.
+    pub fn num_idle_tasks(&self) -> usize {
+        self.idle_tasks.num_idle.load(Ordering::Relaxed)
+    }
+
+    /// Runs `job` on an idle task if there is one, otherwise spawns a new
+    /// task which joins the pool after finishing the `job`.
+    #[cfg_attr(feature = "agave-unstable-api", qualifiers(pub))]
+    pub(crate) fn spawn<F>(&self, job: F) -> PooledTaskHandle
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let done = CancellationToken::new();
+        let abort = CancellationToken::new();
+        let job: Job = {
+            // Marks the job as done even if it panics.
+            let done_guard = done.clone().drop_guard();
+            let abort = abort.clone();
+            Box::pin(async move {
+                let _done_guard = done_guard;
+                abort.run_until_cancelled(job).await;
+            })
+        };
+
+        let has_idle_task = self
+            .idle_tasks
+            .num_idle
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |num_idle| {
+                num_idle.checked_sub(1)
+            })
+            .is_ok();
+        if has_idle_task {
+            // The receiver is owned by the pool, so sending never fails.
+            let _ = self.job_sender.send(job);
+        } else {
+            spawn_named(
+                || "tpu-worker-pool".to_string(),
+                Self::run_task(self.idle_tasks.clone(), job),
+            );
+        }
+        PooledTaskHandle { done, abort }
+    }
+
+    async fn run_task(idle_tasks: Arc<IdleTasks>, mut job: Job) {
+        loop {
+            job.await;
+            let joined_idle = idle_tasks
+                .num_idle
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |num_idle| {
+                    (num_idle < idle_tasks.max_idle).then_some(num_idle.saturating_add(1))
+                })
+                .is_ok();
+            if !joined_idle {
+                return;
+            }
+            let next_job = idle_tasks.job_receiver.lock().await.recv().await;
+            match next_job {
+                Some(next_job) => job = next_job,
+                // The pool has been dropped.
+                None => return,
+            }
+        }
+    }
+}
+
+impl PooledTaskHandle {
+    pub(crate) fn is_finished(&self) -> bool {
+        self.done.is_cancelled()
+    }
+
+    /// Stops the job at its next await point.
+    pub(crate) fn abort(&self) {
+        self.abort.cancel();
+    }
+
+    /// Waits for the job to finish.
+    pub(crate) async fn join(&self) {
+        self.done.cancelled().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::time::Duration,
+        tokio::{sync::oneshot, task, time::timeout},
+    };
+
+    const TEST_MAX_TIME: Duration = Duration::from_secs(5);
+
+    async fn run_job(pool: &WorkerTaskPool) -> task::Id {
+        let (id_sender, id_receiver) = oneshot::channel();
+        let handle = pool.spawn(async move {
+            id_sender.send(task::id()).unwrap();
+        });
+        timeout(TEST_MAX_TIME, handle.join()).await.unwrap();
+        assert!(handle.is_finished());
+        id_receiver.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_task_recycled() {
+        let pool = WorkerTaskPool::new(1);
+        let first_task = run_job(&pool).await;
+        // Wait until the task joins the pool.
+        timeout(TEST_MAX_TIME, async {
+            while pool.num_idle_tasks() == 0 {
+                task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let second_task = run_job(&pool).await;
+        assert_eq!(first_task, second_task);
+    }
+
+    #[tokio::test]
+    async fn test_abort() {
+        let pool = WorkerTaskPool::new(0);
+        let handle = pool.spawn(std::future::pending());
+        assert!(!handle.is_finished());
+        handle.abort();
+        timeout(TEST_MAX_TIME, handle.join()).await.unwrap();
+        assert!(handle.is_finished());
+    }
+}
@@ -1,10 +1,16 @@
 use {
+    async_trait::async_trait,
     crossbeam_channel::Receiver as CrossbeamReceiver,
     futures::future::BoxFuture,
+    rustls::{
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        DigitallySignedStruct, NamedGroup, SignatureScheme,
+    },
     solana_cli_config::ConfigInput,
     solana_commitment_config::CommitmentConfig,
     solana_keypair::Keypair,
-    solana_net_utils::sockets::unique_port_range_for_tests,
+    solana_net_utils::sockets::{bind_to_localhost_unique, unique_port_range_for_tests},
     solana_pubkey::Pubkey,
     solana_rpc_client::nonblocking::rpc_client::RpcClient,
     solana_signer::Signer,
@@ -16,20 +22,26 @@ use {
         quic::QuicServerParams,
         streamer::StakedNodes,
     },
+    solana_tls_utils::SkipServerVerification,
     solana_tpu_client_next::{
         connection_workers_scheduler::{
-            BindTarget, ConnectionWorkersSchedulerConfig, Fanout, StakeIdentity,
+            BindTarget, ConnectionWorkersSchedulerConfig, Fanout, LeaderProbingConfig,
+            StakeIdentity,
         },
-        leader_updater::create_leader_updater,
-        send_transaction_stats::SendTransactionStatsNonAtomic,
-        transaction_batch::TransactionBatch,
+        leader_updater::{create_leader_updater, LeaderUpdater},
+        negotiated_parameters::StreamLimits,
+        send_transaction_stats::{SendTransactionStatsNonAtomic, SendingIdentity},
+        transaction_batch::{send_transaction_batch_ref, TransactionBatch, TransactionBatchRef},
         ConnectionWorkersScheduler, ConnectionWorkersSchedulerError, SendTransactionStats,
     },
     std::{
         collections::HashMap,
         net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
         num::Saturating,
-        sync::{atomic::Ordering, Arc},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
         time::Duration,
     },
     tokio::{
@@ -115,6 +127,18 @@ async fn join_scheduler(
     scheduler_stats.read_and_reset()
 }
 
+/// Checks that the counters of `stats` are zeros, except for the `expected`
+/// ones.
+fn assert_counters(stats: &SendTransactionStatsNonAtomic, expected: &[(&str, u64)]) {
+    for (name, value) in stats.fields() {
+        let expected_value = expected
+            .iter()
+            .find(|(expected_name, _)| *expected_name == name)
+            .map_or(0, |(_, expected_value)| *expected_value);
+        assert_eq!(value, expected_value, "Unexpected {name} in {stats:?}");
+    }
+}
+
 // Specify the pessimistic time to finish generation and result checks.
 const TEST_MAX_TIME: Duration = Duration::from_millis(2500);
 
@@ -259,6 +283,32 @@ async fn test_basic_transactions_sending() {
     server_handle.await.unwrap();
 }
 
+#[tokio::test]
+async fn test_send_transaction_batch_ref() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    // The same buffer is sent twice without building any batch.
+    let buffer = [[7u8; 16]; 4];
+    let connection = make_client_endpoint(&server_address, None).await;
+    for _ in 0..2 {
+        send_transaction_batch_ref(&connection, TransactionBatchRef::new(&buffer))
+            .await
+            .unwrap();
+    }
+
+    let num_received = count_received_packets_for(receiver, 16, Duration::from_secs(1)).await;
+    assert_eq!(num_received, 8);
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
 async fn count_received_packets_for(
     receiver: CrossbeamReceiver<PacketBatch>,
     expected_tx_size: usize,
@@ -438,12 +488,12 @@ async fn test_staked_connection() {
     // Wait for the exchange to finish.
     tx_sender_shutdown.await;
     let stats = join_scheduler(scheduler_handle).await;
-    assert_eq!(
-        stats,
-        SendTransactionStatsNonAtomic {
-            successfully_sent: expected_num_txs as u64,
-            ..Default::default()
-        }
+    assert_counters(
+        &stats,
+        &[
+            ("successfully_sent", expected_num_txs as u64),
+            ("bytes_sent", (expected_num_txs * tx_size) as u64),
+        ],
     );
 
     // Exit server
@@ -485,12 +535,12 @@ async fn test_connection_throttling() {
     // Stop sending
     tx_sender_shutdown.await;
     let stats = join_scheduler(scheduler_handle).await;
-    assert_eq!(
-        stats,
-        SendTransactionStatsNonAtomic {
-            successfully_sent: expected_num_txs as u64,
-            ..Default::default()
-        }
+    assert_counters(
+        &stats,
+        &[
+            ("successfully_sent", expected_num_txs as u64),
+            ("bytes_sent", (expected_num_txs * tx_size) as u64),
+        ],
     );
 
     // Exit server
@@ -586,13 +636,13 @@ async fn test_rate_limiting() {
 
     // we get 2 transactions registered as sent (but not acked) because of how QUIC works
     // before ratelimiter kicks in.
-    assert!(
-        stats
-            == SendTransactionStatsNonAtomic {
-                successfully_sent: 2,
-                write_error_connection_lost: 2,
-                ..Default::default()
-            }
+    assert_counters(
+        &stats,
+        &[
+            ("successfully_sent", 2),
+            ("bytes_sent", 2 * tx_size as u64),
+            ("write_error_connection_lost", 2),
+        ],
     );
 
     // Stop the server.
@@ -668,6 +718,7 @@ async fn test_rate_limiting_establish_connection() {
     // All the rest of the error counters should be 0.
     stats.connection_error_timed_out = 0;
     stats.successfully_sent = 0;
+    stats.bytes_sent = 0;
     assert_eq!(stats, SendTransactionStatsNonAtomic::default());
 
     // Stop the server.
@@ -821,3 +872,861 @@ async fn test_proactive_connection_close_detection() {
     exit.store(true, Ordering::Relaxed);
     server_handle.await.unwrap();
 }
+
+/// Waits until `condition` holds for the `stats` of the scheduler, failing
+/// after `max_time`.
+async fn wait_for_stats(
+    stats: &SendTransactionStats,
+    max_time: Duration,
+    condition: impl Fn(&SendTransactionStats) -> bool,
+) {
+    let start = Instant::now();
+    while !condition(stats) {
+        assert!(
+            start.elapsed() < max_time,
+            "Timed out waiting for the stats: {:?}",
+            stats.snapshot().retries
+        );
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+// Check that the reconnection of a worker whose connection has been pruned is
+// accounted as a retry which succeeded at the first attempt.
+#[tokio::test]
+async fn test_reconnect_accounting() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(
+        None,
+        QuicServerParams {
+            max_connections_per_peer: 1,
+            max_unstaked_connections: 1,
+            ..QuicServerParams::default_for_tests()
+        },
+    );
+
+    let tx_size = 1;
+    let (tx_sender, tx_receiver) = channel(10);
+    let cancel = CancellationToken::new();
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![server_address],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        cancel.clone(),
+    );
+    let stats = scheduler.get_stats();
+    let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+    tx_sender
+        .send(TransactionBatch::new(vec![vec![1u8; tx_size]]))
+        .await
+        .unwrap();
+    let num_packets = count_received_packets_for(receiver, tx_size, Duration::from_secs(1)).await;
+    assert_eq!(num_packets, 1);
+    assert_eq!(stats.retries.reconnect_attempts.load(Ordering::Relaxed), 0);
+
+    // The connection of the worker is pruned to make room for this one, and
+    // the worker prunes it back when it reconnects after the backoff.
+    let _pruning_connection = make_client_endpoint(&server_address, None).await;
+    wait_for_stats(&stats, Duration::from_secs(5), |stats| {
+        stats
+            .retries
+            .reconnected_at_first_attempt
+            .load(Ordering::Relaxed)
+            > 0
+    })
+    .await;
+
+    cancel.cancel();
+    scheduler_handle
+        .await
+        .unwrap()
+        .expect("Scheduler should stop successfully.");
+    let retries = stats.snapshot().retries;
+    assert_eq!(retries.reconnect_attempts, 1, "{retries:?}");
+    assert_eq!(retries.reconnected_at_first_attempt, 1);
+    assert_eq!(retries.reconnected_after_retry, 0);
+    assert_eq!(retries.gave_up, 0);
+    // The connection was idle when it was pruned.
+    assert_eq!(retries.transactions_not_retried, 0);
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+// Check that a worker which fails to connect gives up after
+// `max_reconnect_attempts` reconnections, and that each of them is accounted.
+#[tokio::test]
+async fn test_max_reconnect_attempts_accounting() {
+    // Nothing answers on this socket, so every handshake times out.
+    let silent_socket = bind_to_localhost_unique().unwrap();
+    let peer = silent_socket.local_addr().unwrap();
+
+    let (tx_sender, tx_receiver) = channel(10);
+    let cancel = CancellationToken::new();
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![peer],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        cancel.clone(),
+    );
+    let stats = scheduler.get_stats();
+    let config = ConnectionWorkersSchedulerConfig {
+        max_reconnect_attempts: 1,
+        ..test_config(None)
+    };
+    let scheduler_handle = tokio::spawn(scheduler.run(config));
+
+    tx_sender
+        .send(TransactionBatch::new(vec![vec![1u8; 1]]))
+        .await
+        .unwrap();
+    // Two handshake timeouts and the backoff in between.
+    wait_for_stats(&stats, Duration::from_secs(10), |stats| {
+        stats.retries.gave_up.load(Ordering::Relaxed) > 0
+    })
+    .await;
+
+    cancel.cancel();
+    scheduler_handle
+        .await
+        .unwrap()
+        .expect("Scheduler should stop successfully.");
+    let snapshot = stats.snapshot();
+    let retries = &snapshot.retries;
+    assert_eq!(retries.reconnect_attempts, 1, "{retries:?}");
+    assert_eq!(retries.reconnected_at_first_attempt, 0);
+    assert_eq!(retries.reconnected_after_retry, 0);
+    assert_eq!(retries.gave_up, 1);
+    assert_eq!(retries.transactions_not_retried, 0);
+    assert_eq!(snapshot.counters.connection_error_timed_out, 2);
+    assert_eq!(snapshot.leaders[&peer].handshakes_failed, 2);
+}
+
+/// Returns the same list of leaders regardless of the requested lookahead, up
+/// to `lookahead_leaders` of them.
+struct FixedLeadersUpdater {
+    leaders: Vec<SocketAddr>,
+}
+
+#[async_trait]
+impl LeaderUpdater for FixedLeadersUpdater {
+    fn next_leaders(&mut self, lookahead_leaders: usize) -> Vec<SocketAddr> {
+        self.leaders
+            .iter()
+            .take(lookahead_leaders)
+            .copied()
+            .collect()
+    }
+
+    async fn stop(&mut self) {}
+}
+
+// Check that the bytes of the transactions sent are counted in total, per
+// leader and per identity.
+#[tokio::test]
+async fn test_bytes_sent_accounting() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let (tx_sender, tx_receiver) = channel(10);
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![server_address],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        CancellationToken::new(),
+    );
+    let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+    let tx_sizes = [10, 20, 30];
+    tx_sender
+        .send(TransactionBatch::new(
+            tx_sizes.iter().map(|size| vec![1u8; *size]).collect(),
+        ))
+        .await
+        .unwrap();
+    let mut num_packets = 0;
+    let start = Instant::now();
+    while num_packets < tx_sizes.len() && start.elapsed() < TEST_MAX_TIME {
+        match receiver.try_recv() {
+            Ok(packets) => num_packets += packets.len(),
+            Err(_) => sleep(Duration::from_millis(10)).await,
+        }
+    }
+    assert_eq!(num_packets, tx_sizes.len());
+
+    drop(tx_sender);
+    let stats = scheduler_handle
+        .await
+        .unwrap()
+        .expect("Scheduler should stop successfully.")
+        .snapshot();
+    let total_bytes = tx_sizes.iter().sum::<usize>() as u64;
+    assert_eq!(stats.counters.bytes_sent, total_bytes);
+    assert_eq!(stats.leaders[&server_address].bytes_sent, total_bytes);
+    assert_eq!(
+        stats.identities[&SendingIdentity::Unstaked].bytes_sent,
+        total_bytes
+    );
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+// Check that the first batch sent to a leader is a workers cache miss, which
+// pays for the connection, and the following ones are hits.
+#[tokio::test]
+async fn test_workers_cache_hit_accounting() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let tx_size = 1;
+    let (tx_sender, tx_receiver) = channel(10);
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![server_address],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        CancellationToken::new(),
+    );
+    let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+    let num_batches = 3;
+    for _ in 0..num_batches {
+        tx_sender
+            .send(TransactionBatch::new(vec![vec![1u8; tx_size]]))
+            .await
+            .unwrap();
+    }
+    let num_packets = count_received_packets_for(receiver, tx_size, Duration::from_secs(1)).await;
+    assert_eq!(num_packets, num_batches);
+
+    drop(tx_sender);
+    let stats = scheduler_handle
+        .await
+        .unwrap()
+        .expect("Scheduler should stop successfully.");
+    let leader_stats = stats.leaders.get(&server_address).unwrap().to_non_atomic();
+    assert_eq!(leader_stats.cache_misses, 1);
+    assert_eq!(leader_stats.cache_hits, num_batches as u64 - 1);
+    assert_eq!(leader_stats.cache_expired, 0);
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+// Check that the workers evicted to make room for other leaders, and the ones
+// inserted again after their eviction, are counted.
+#[tokio::test]
+async fn test_workers_cache_eviction_accounting() {
+    // The leaders never answer, the workers only need to be cached.
+    let silent_sockets: Vec<_> = (0..2)
+        .map(|_| bind_to_localhost_unique().unwrap())
+        .collect();
+    let leaders: Vec<_> = silent_sockets
+        .iter()
+        .map(|socket| socket.local_addr().unwrap())
+        .collect();
+
+    let (tx_sender, tx_receiver) = channel(10);
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: leaders.clone(),
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        CancellationToken::new(),
+    );
+    let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+    // Each batch targets both leaders while the cache holds a single worker,
+    // so every insertion evicts the other leader, and all the insertions but
+    // the first two reinsert an evicted one.
+    let num_batches = 3;
+    for _ in 0..num_batches {
+        tx_sender
+            .send(
+                TransactionBatch::new(vec![vec![1u8; 1]]).with_leaders_fanout(Fanout {
+                    send: 2,
+                    connect: 2,
+                }),
+            )
+            .await
+            .unwrap();
+    }
+    drop(tx_sender);
+    let stats = join_scheduler(scheduler_handle).await;
+    assert_eq!(stats.workers_evicted, 2 * num_batches - 1);
+    assert_eq!(stats.workers_reinserted, 2 * num_batches - 2);
+}
+
+// Check that a batch requesting a deeper fanout is sent to more leaders than
+// the scheduler is configured for, while other batches are not.
+#[tokio::test]
+async fn test_per_batch_leaders_fanout() {
+    let SpawnTestServerResult {
+        join_handle: server_handle1,
+        exit: exit1,
+        receiver: receiver1,
+        server_address: server_address1,
+        stats: _stats1,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+    let SpawnTestServerResult {
+        join_handle: server_handle2,
+        exit: exit2,
+        receiver: receiver2,
+        server_address: server_address2,
+        stats: _stats2,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let tx_size = 1;
+    let (tx_sender, tx_receiver) = channel(10);
+    let cancel = CancellationToken::new();
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![server_address1, server_address2],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        cancel.clone(),
+    );
+    let config = ConnectionWorkersSchedulerConfig {
+        num_connections: 2,
+        ..test_config(None)
+    };
+    let scheduler_handle = tokio::spawn(scheduler.run(config));
+
+    tx_sender
+        .send(TransactionBatch::new(vec![vec![1u8; tx_size]]))
+        .await
+        .unwrap();
+    tx_sender
+        .send(
+            TransactionBatch::new(vec![vec![2u8; tx_size]]).with_leaders_fanout(Fanout {
+                send: 2,
+                connect: 2,
+            }),
+        )
+        .await
+        .unwrap();
+
+    let (num_packets1, num_packets2) = tokio::join!(
+        count_received_packets_for(receiver1, tx_size, TEST_MAX_TIME),
+        count_received_packets_for(receiver2, tx_size, TEST_MAX_TIME),
+    );
+    assert_eq!(num_packets1, 2);
+    assert_eq!(num_packets2, 1);
+
+    drop(tx_sender);
+    let stats = join_scheduler(scheduler_handle).await;
+    assert_eq!(stats.successfully_sent, 3);
+
+    exit1.store(true, Ordering::Relaxed);
+    exit2.store(true, Ordering::Relaxed);
+    server_handle1.await.unwrap();
+    server_handle2.await.unwrap();
+}
+
+// Check that the upcoming leader is probed once while it stays upcoming and
+// before any worker connects to it.
+#[tokio::test]
+async fn test_leader_probing() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver: _receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let (_tx_sender, tx_receiver) = channel(10);
+    let cancel = CancellationToken::new();
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![server_address],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        cancel.clone(),
+    )
+    .with_leader_probing(LeaderProbingConfig {
+        interval: Duration::from_millis(50),
+        ..LeaderProbingConfig::default()
+    });
+    let stats = scheduler.get_stats();
+    let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+    let start = Instant::now();
+    while stats
+        .leaders
+        .get(&server_address)
+        .is_none_or(|leader_stats| leader_stats.probes.load(Ordering::Relaxed) == 0)
+    {
+        assert!(start.elapsed() < TEST_MAX_TIME, "Leader was not probed");
+        sleep(Duration::from_millis(10)).await;
+    }
+    // Let a few more ticks pass, the leader stays upcoming so it is not probed
+    // again.
+    sleep(Duration::from_millis(200)).await;
+
+    cancel.cancel();
+    scheduler_handle
+        .await
+        .unwrap()
+        .expect("Scheduler should stop successfully.");
+    let leader_stats = stats.leaders.get(&server_address).unwrap().to_non_atomic();
+    assert_eq!(leader_stats.probes, 1);
+    assert_eq!(leader_stats.probes_failed, 0);
+    assert!(leader_stats.mean_probe_rtt().is_some());
+    assert_eq!(leader_stats.connections_established, 0);
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+// Check that the handshakes use the crypto provider of the scheduler: the
+// server only accepts X25519, so a provider without it can't connect.
+#[tokio::test]
+async fn test_crypto_provider() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let mut secp256r1_provider = rustls::crypto::ring::default_provider();
+    secp256r1_provider
+        .kx_groups
+        .retain(|kx| kx.name() == NamedGroup::secp256r1);
+    let tx_size = 1;
+    for (provider, expected_num_packets) in [
+        (secp256r1_provider, 0),
+        (solana_tls_utils::crypto_provider(), 1),
+    ] {
+        let (tx_sender, tx_receiver) = channel(1);
+        let cancel = CancellationToken::new();
+        let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+        let scheduler = ConnectionWorkersScheduler::new(
+            Box::new(FixedLeadersUpdater {
+                leaders: vec![server_address],
+            }),
+            tx_receiver,
+            update_identity_receiver,
+            cancel.clone(),
+        )
+        .with_crypto_provider(Arc::new(provider));
+        let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+        tx_sender
+            .send(TransactionBatch::new(vec![vec![1u8; tx_size]]))
+            .await
+            .unwrap();
+        let num_packets =
+            count_received_packets_for(receiver.clone(), tx_size, Duration::from_secs(1)).await;
+        assert_eq!(num_packets, expected_num_packets);
+
+        drop(tx_sender);
+        let stats = join_scheduler(scheduler_handle).await;
+        assert_eq!(stats.successfully_sent, expected_num_packets as u64);
+    }
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+/// Verifier counting the certificates it is given and accepting them if
+/// `accept` is set, the TLS signatures are checked by `SkipServerVerification`.
+#[derive(Debug)]
+struct CountingVerifier {
+    accept: bool,
+    num_verified: AtomicUsize,
+    inner: Arc<SkipServerVerification>,
+}
+
+impl ServerCertVerifier for CountingVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        assert!(server_name.to_str().ends_with(".sol"));
+        self.num_verified.fetch_add(1, Ordering::Relaxed);
+        if self.accept {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "untrusted server certificate".to_string(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+// Check that the certificate of the server is checked by the verifier given to
+// the scheduler.
+#[tokio::test]
+async fn test_server_cert_verifier() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let tx_size = 1;
+    for (accept, expected_num_packets) in [(false, 0), (true, 1)] {
+        let verifier = Arc::new(CountingVerifier {
+            accept,
+            num_verified: AtomicUsize::new(0),
+            inner: SkipServerVerification::new(),
+        });
+        let (tx_sender, tx_receiver) = channel(1);
+        let cancel = CancellationToken::new();
+        let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+        let scheduler = ConnectionWorkersScheduler::new(
+            Box::new(FixedLeadersUpdater {
+                leaders: vec![server_address],
+            }),
+            tx_receiver,
+            update_identity_receiver,
+            cancel.clone(),
+        )
+        .with_server_cert_verifier(verifier.clone());
+        let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+        tx_sender
+            .send(TransactionBatch::new(vec![vec![1u8; tx_size]]))
+            .await
+            .unwrap();
+        let num_packets =
+            count_received_packets_for(receiver.clone(), tx_size, Duration::from_secs(1)).await;
+        assert_eq!(num_packets, expected_num_packets);
+        assert!(verifier.num_verified.load(Ordering::Relaxed) > 0);
+
+        drop(tx_sender);
+        let stats = join_scheduler(scheduler_handle).await;
+        assert_eq!(stats.successfully_sent, expected_num_packets as u64);
+    }
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+// Check that the scheduler and its workers run on the runtime given to the
+// scheduler rather than on the one of the test.
+#[tokio::test]
+async fn test_scheduler_runtime() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+    let tx_size = 1;
+    let (tx_sender, tx_receiver) = channel(1);
+    let cancel = CancellationToken::new();
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![server_address],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        cancel.clone(),
+    )
+    .with_runtime(runtime.handle().clone());
+    let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+    tx_sender
+        .send(TransactionBatch::new(vec![vec![1u8; tx_size]]))
+        .await
+        .unwrap();
+    let num_packets = count_received_packets_for(receiver, tx_size, TEST_MAX_TIME).await;
+    assert_eq!(num_packets, 1);
+    // The main loop, the worker and the driver of the endpoint at least.
+    assert!(runtime.metrics().num_alive_tasks() >= 3);
+
+    drop(tx_sender);
+    let stats = join_scheduler(scheduler_handle).await;
+    assert_eq!(stats.successfully_sent, 1);
+    runtime.shutdown_background();
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+// Check that the scheduler is reported ready once it has connected to the
+// leader, and not running after it has stopped.
+#[tokio::test]
+async fn test_health_probe() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let tx_size = 1;
+    let (tx_sender, tx_receiver) = channel(1);
+    let cancel = CancellationToken::new();
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![server_address],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        cancel.clone(),
+    );
+    let health_probe = scheduler.health_probe();
+    assert!(!health_probe.health().is_running);
+    let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+    tx_sender
+        .send(TransactionBatch::new(vec![vec![1u8; tx_size]]))
+        .await
+        .unwrap();
+    let num_packets = count_received_packets_for(receiver, tx_size, Duration::from_secs(1)).await;
+    assert_eq!(num_packets, 1);
+    let health = health_probe.health();
+    assert!(health.is_ready(), "{health:?}");
+    assert_eq!(health.num_active_connections, 1);
+
+    drop(tx_sender);
+    join_scheduler(scheduler_handle).await;
+    let health = health_probe.health();
+    assert!(!health.is_running);
+    assert!(!health.is_ready());
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+// Check that the worker detects what the streamer supports once connected.
+#[tokio::test]
+async fn test_peer_capabilities() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let tx_size = 1;
+    let (tx_sender, tx_receiver) = channel(1);
+    let cancel = CancellationToken::new();
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let (snapshot_sender, mut snapshot_receiver) = watch::channel(Vec::new());
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![server_address],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        cancel.clone(),
+    )
+    .with_health_check_interval(Duration::from_millis(100))
+    .with_workers_snapshot_sender(snapshot_sender);
+    let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+    tx_sender
+        .send(TransactionBatch::new(vec![vec![1u8; tx_size]]))
+        .await
+        .unwrap();
+    let num_packets = count_received_packets_for(receiver, tx_size, Duration::from_secs(1)).await;
+    assert_eq!(num_packets, 1);
+    snapshot_receiver.changed().await.unwrap();
+    let snapshot = snapshot_receiver.borrow_and_update().clone();
+    let capabilities = snapshot[0].capabilities.as_ref().unwrap();
+    assert!(capabilities.supports_tpu_alpn());
+    assert!(!capabilities.stream_limit_reached);
+
+    drop(tx_sender);
+    join_scheduler(scheduler_handle).await;
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+// Check that the negotiated parameters are published once the scheduler has
+// connected to the leader.
+#[tokio::test]
+async fn test_negotiated_parameters() {
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let tx_size = 1;
+    let (tx_sender, tx_receiver) = channel(1);
+    let cancel = CancellationToken::new();
+    let (_update_identity_sender, update_identity_receiver) = watch::channel(None);
+    let scheduler = ConnectionWorkersScheduler::new(
+        Box::new(FixedLeadersUpdater {
+            leaders: vec![server_address],
+        }),
+        tx_receiver,
+        update_identity_receiver,
+        cancel.clone(),
+    )
+    .with_health_check_interval(Duration::from_millis(100));
+    let mut parameters_receiver = scheduler.negotiated_parameters();
+    assert!(parameters_receiver.borrow().is_none());
+    let scheduler_handle = tokio::spawn(scheduler.run(test_config(None)));
+
+    tx_sender
+        .send(TransactionBatch::new(vec![vec![1u8; tx_size]]))
+        .await
+        .unwrap();
+    let num_packets = count_received_packets_for(receiver, tx_size, Duration::from_secs(1)).await;
+    assert_eq!(num_packets, 1);
+    let parameters = parameters_receiver
+        .wait_for(Option::is_some)
+        .await
+        .unwrap()
+        .clone()
+        .unwrap();
+    assert_eq!(parameters.identity, SendingIdentity::Unstaked);
+    assert_eq!(parameters.stream_limits, StreamLimits::default());
+    assert_eq!(parameters.peers.len(), 1);
+    let (peer, capabilities) = &parameters.peers[0];
+    assert_eq!(*peer, server_address);
+    assert!(capabilities.initial_congestion_window > 0);
+    assert!(parameters.min_mtu().unwrap() >= 1200);
+    assert_eq!(parameters.stream_limited_peers().count(), 0);
+
+    drop(tx_sender);
+    join_scheduler(scheduler_handle).await;
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
+
+// Check that the data sent through the blocking and nonblocking connections of
+// the connection cache reaches the server.
+#[cfg(feature = "connection-cache")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connection_cache() {
+    use {
+        solana_connection_cache::{
+            client_connection::ClientConnection as BlockingClientConnection,
+            connection_cache::NewConnectionConfig,
+            nonblocking::client_connection::ClientConnection as NonblockingClientConnection,
+        },
+        solana_tpu_client_next::connection_cache::{
+            TpuClientNextConfig, TpuClientNextConnectionCache, TpuClientNextConnectionManager,
+        },
+    };
+
+    let SpawnTestServerResult {
+        join_handle: server_handle,
+        exit,
+        receiver,
+        server_address,
+        stats: _stats,
+    } = setup_quic_server(None, QuicServerParams::default_for_tests());
+
+    let config = TpuClientNextConfig::new()
+        .unwrap()
+        .with_runtime(tokio::runtime::Handle::current())
+        .with_bind_address(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0));
+    let stats = config.stats().clone();
+    let connection_cache = TpuClientNextConnectionCache::new(
+        "test_connection_cache",
+        TpuClientNextConnectionManager::new_with_connection_config(config),
+        1,
+    )
+    .unwrap();
+
+    let tx_size = 1;
+    // The connections implement both traits, the blocking and nonblocking
+    // connections are the same.
+    let connection = connection_cache.get_connection(&server_address);
+    assert_eq!(
+        BlockingClientConnection::server_addr(&*connection),
+        &server_address
+    );
+    BlockingClientConnection::send_data(&*connection, &[1u8; 1]).unwrap();
+    connection
+        .send_data_batch_async(vec![vec![2u8; 1], vec![3u8; 1]])
+        .unwrap();
+    let connection = connection_cache.get_nonblocking_connection(&server_address);
+    NonblockingClientConnection::send_data(&*connection, &[4u8; 1])
+        .await
+        .unwrap();
+
+    let num_packets = count_received_packets_for(receiver, tx_size, Duration::from_secs(1)).await;
+    assert_eq!(num_packets, 4);
+    assert_eq!(stats.successfully_sent.load(Ordering::Relaxed), 4);
+
+    exit.store(true, Ordering::Relaxed);
+    server_handle.await.unwrap();
+}
@@ -4,6 +4,8 @@ use {
     bincode::serialize,
     futures_util::{future::join_all, stream::StreamExt},
     log::*,
+    rand::{thread_rng, Rng},
+    serde_derive::{Deserialize, Serialize},
     solana_clock::{Slot, DEFAULT_MS_PER_SLOT, NUM_CONSECUTIVE_LEADER_SLOTS},
     solana_commitment_config::CommitmentConfig,
     solana_connection_cache::{
@@ -28,15 +30,19 @@ use {
     solana_transaction_error::{TransportError, TransportResult},
     std::{
         collections::{HashMap, HashSet},
+        fs::File,
+        io::{BufReader, BufWriter},
         net::SocketAddr,
+        path::Path,
         str::FromStr,
         sync::{
-            atomic::{AtomicBool, Ordering},
-            Arc, RwLock,
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc, Mutex, RwLock,
         },
     },
     thiserror::Error,
     tokio::{
+        sync::watch,
         task::JoinHandle,
         time::{sleep, timeout, Duration, Instant},
     },
@@ -80,6 +86,148 @@ impl LeaderTpuCacheUpdateInfo {
             || self.maybe_epoch_schedule.is_some()
             || self.maybe_slot_leaders.is_some()
     }
+
+    /// Returns true if any of the RPC requests has been rejected by the RPC
+    /// node because of rate limiting.
+    fn is_rate_limited(&self) -> bool {
+        self.maybe_cluster_nodes
+            .as_ref()
+            .is_some_and(is_rate_limited_result)
+            || self
+                .maybe_epoch_schedule
+                .as_ref()
+                .is_some_and(is_rate_limited_result)
+            || self
+                .maybe_slot_leaders
+                .as_ref()
+                .is_some_and(is_rate_limited_result)
+    }
+}
+
+/// Default time after which a resolved leader TPU socket is considered stale
+/// and the cluster contact info is fetched again.
+pub const DEFAULT_LEADER_TPU_SOCKET_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Minimal interval between two consecutive cluster contact info fetches
+/// triggered by an explicit invalidation of a leader TPU socket.
+const MIN_CLUSTER_REFRESH_INTERVAL: Duration =
+    Duration::from_millis(NUM_CONSECUTIVE_LEADER_SLOTS * DEFAULT_MS_PER_SLOT);
+
+/// Default upper bound of the interval between RPC requests of the
+/// [`LeaderTpuService`] while the RPC node is rate limiting them.
+pub const DEFAULT_MAX_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Config params for [`LeaderTpuService`].
+#[derive(Clone, Debug)]
+pub struct LeaderTpuServiceConfig {
+    /// Time after which a resolved leader TPU socket is considered stale and
+    /// must be resolved again.
+    pub leader_tpu_socket_ttl: Duration,
+
+    /// TPU sockets to use for the given leaders instead of the ones advertised
+    /// in the cluster contact info. It allows to reach leaders that don't
+    /// advertise a TPU socket at all.
+    pub leader_tpu_socket_overrides: HashMap<Pubkey, SocketAddr>,
+
+    /// Whether the TPU forwards socket of a leader is used when it doesn't
+    /// advertise a TPU socket. The forwards port is meant for the transactions
+    /// forwarded by the other validators, so it is disabled by default.
+    pub tpu_forwards_fallback: bool,
+
+    /// Maximal interval between leader schedule refreshes when the RPC node
+    /// responds with `429 Too Many Requests`. The interval grows exponentially
+    /// with jitter up to this value and shrinks back once requests succeed.
+    pub max_rate_limit_backoff: Duration,
+}
+
+impl Default for LeaderTpuServiceConfig {
+    fn default() -> Self {
+        Self {
+            leader_tpu_socket_ttl: DEFAULT_LEADER_TPU_SOCKET_TTL,
+            leader_tpu_socket_overrides: HashMap::new(),
+            tpu_forwards_fallback: false,
+            max_rate_limit_backoff: DEFAULT_MAX_RATE_LIMIT_BACKOFF,
+        }
+    }
+}
+
+/// Snapshot of the leader schedule and of the leaders TPU sockets known to the
+/// [`LeaderTpuService`].
+///
+/// It can be saved to a file and later used to start a [`LeaderTpuService`]
+/// without fetching the schedule over RPC, which allows offline testing and
+/// fast cold starts when the RPC is temporarily unavailable.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderScheduleSnapshot {
+    /// Estimated current slot at the moment the snapshot was taken.
+    pub current_slot: Slot,
+    /// Slot of the first leader in `leaders`.
+    pub first_slot: Slot,
+    pub slots_in_epoch: Slot,
+    pub last_slot_in_epoch: Slot,
+    /// Leader for each slot starting from `first_slot`.
+    pub leaders: Vec<Pubkey>,
+    pub leader_tpu_sockets: HashMap<Pubkey, SocketAddr>,
+}
+
+impl LeaderScheduleSnapshot {
+    /// Writes the snapshot to the file at `path` using bincode.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(file, self).map_err(|err| {
+            TpuSenderError::Custom(format!("Failed to serialize leader schedule: {err}"))
+        })
+    }
+
+    /// Reads a snapshot previously written with [`LeaderScheduleSnapshot::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        bincode::deserialize_from(file).map_err(|err| {
+            TpuSenderError::Custom(format!("Failed to deserialize leader schedule: {err}"))
+        })
+    }
+}
+
+/// Last known good TPU sockets of the leaders, which don't depend on the
+/// leader schedule.
+///
+/// It can be saved to a file before the client stops, so that after restart
+/// the client can start connecting to the known leaders before the first
+/// leader schedule update completes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownPeers {
+    pub tpu_sockets: HashMap<Pubkey, SocketAddr>,
+}
+
+impl KnownPeers {
+    /// Writes the known peers to the file at `path` using bincode.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(file, self).map_err(|err| {
+            TpuSenderError::Custom(format!("Failed to serialize known peers: {err}"))
+        })
+    }
+
+    /// Reads the known peers previously written with [`KnownPeers::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        bincode::deserialize_from(file).map_err(|err| {
+            TpuSenderError::Custom(format!("Failed to deserialize known peers: {err}"))
+        })
+    }
+}
+
+/// TPU socket of an upcoming leader along with the span of consecutive slots
+/// it is scheduled to lead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LeaderTpuSocket {
+    pub leader: Pubkey,
+    pub socket: SocketAddr,
+    /// First slot of the span, it might be in the middle of the leader's
+    /// slots if the leader is the current one.
+    pub first_slot: Slot,
+    /// Last slot of the span, inclusive.
+    pub last_slot: Slot,
 }
 
 struct LeaderTpuCache {
@@ -87,6 +235,21 @@ struct LeaderTpuCache {
     first_slot: Slot,
     leaders: Vec<Pubkey>,
     leader_tpu_map: HashMap<Pubkey, SocketAddr>,
+    // Leaders whose TPU socket has been invalidated since the last cluster
+    // refresh.
+    invalidated_leaders: HashSet<Pubkey>,
+    // Whether the TPU sockets come from a snapshot and haven't been resolved
+    // since.
+    has_restored_tpu_sockets: bool,
+    leader_tpu_socket_ttl: Duration,
+    leader_tpu_socket_overrides: HashMap<Pubkey, SocketAddr>,
+    tpu_forwards_fallback: bool,
+    // Scheduled leaders found without known TPU socket since the last cluster
+    // refresh, each of them is counted once until the next refresh.
+    leaders_without_tpu_socket: Mutex<HashSet<Pubkey>>,
+    // Number of leaders added to `leaders_without_tpu_socket` which haven't
+    // been taken yet.
+    num_leaders_without_tpu_socket: AtomicU64,
     slots_in_epoch: Slot,
     last_slot_in_epoch: Slot,
 }
@@ -99,18 +262,84 @@ impl LeaderTpuCache {
         leaders: Vec<Pubkey>,
         cluster_nodes: Vec<RpcContactInfo>,
         protocol: Protocol,
+        config: LeaderTpuServiceConfig,
     ) -> Self {
-        let leader_tpu_map = Self::extract_cluster_tpu_sockets(protocol, cluster_nodes);
+        let LeaderTpuServiceConfig {
+            leader_tpu_socket_ttl,
+            leader_tpu_socket_overrides,
+            tpu_forwards_fallback,
+            max_rate_limit_backoff: _,
+        } = config;
+        let leader_tpu_map = Self::extract_cluster_tpu_sockets(
+            protocol,
+            cluster_nodes,
+            &leader_tpu_socket_overrides,
+            tpu_forwards_fallback,
+        );
         Self {
             protocol,
             first_slot,
             leaders,
             leader_tpu_map,
+            invalidated_leaders: HashSet::new(),
+            has_restored_tpu_sockets: false,
+            leader_tpu_socket_ttl,
+            leader_tpu_socket_overrides,
+            tpu_forwards_fallback,
+            leaders_without_tpu_socket: Mutex::default(),
+            num_leaders_without_tpu_socket: AtomicU64::new(0),
             slots_in_epoch,
             last_slot_in_epoch,
         }
     }
 
+    /// Creates the cache from a `snapshot`. The TPU sockets from the snapshot
+    /// are considered stale, so they are resolved again as soon as possible.
+    fn from_snapshot(
+        snapshot: LeaderScheduleSnapshot,
+        protocol: Protocol,
+        config: LeaderTpuServiceConfig,
+    ) -> Self {
+        let LeaderScheduleSnapshot {
+            current_slot: _,
+            first_slot,
+            slots_in_epoch,
+            last_slot_in_epoch,
+            leaders,
+            leader_tpu_sockets,
+        } = snapshot;
+        let mut cache = Self::new(
+            first_slot,
+            slots_in_epoch,
+            last_slot_in_epoch,
+            leaders,
+            vec![],
+            protocol,
+            config,
+        );
+        cache.leader_tpu_map.extend(leader_tpu_sockets);
+        cache.has_restored_tpu_sockets = true;
+        cache
+    }
+
+    fn snapshot(&self, current_slot: Slot) -> LeaderScheduleSnapshot {
+        LeaderScheduleSnapshot {
+            current_slot,
+            first_slot: self.first_slot,
+            slots_in_epoch: self.slots_in_epoch,
+            last_slot_in_epoch: self.last_slot_in_epoch,
+            leaders: self.leaders.clone(),
+            leader_tpu_sockets: self.leader_tpu_map.clone(),
+        }
+    }
+
+    /// Returns the TPU sockets which haven't been invalidated.
+    fn known_peers(&self) -> KnownPeers {
+        KnownPeers {
+            tpu_sockets: self.leader_tpu_map.clone(),
+        }
+    }
+
     // Last slot that has a cached leader pubkey
     pub fn last_slot(&self) -> Slot {
         self.first_slot + self.leaders.len().saturating_sub(1) as u64
@@ -144,6 +373,94 @@ impl LeaderTpuCache {
         unique_sockets
     }
 
+    // Get the TPU sockets for the current leader and the next `num_leaders - 1` leaders. Consecutive
+    // slots of the same leader are merged into a single span. The leaders without TPU socket are
+    // skipped and the following ones are collected instead, up to `num_leaders` spans.
+    fn get_leader_slot_spans(
+        &self,
+        estimated_current_slot: Slot,
+        num_leaders: usize,
+    ) -> Vec<LeaderTpuSocket> {
+        let (spans, leaders_without_tpu_socket) =
+            self.leader_slot_spans(estimated_current_slot, num_leaders);
+        for leader in &leaders_without_tpu_socket {
+            self.record_leader_without_tpu_socket(leader);
+        }
+        spans
+    }
+
+    // Same as `get_leader_slot_spans`, but returns the leaders without TPU socket instead of
+    // accounting them.
+    fn leader_slot_spans(
+        &self,
+        estimated_current_slot: Slot,
+        num_leaders: usize,
+    ) -> (Vec<LeaderTpuSocket>, Vec<Pubkey>) {
+        let current_slot = std::cmp::max(estimated_current_slot, self.first_slot);
+        // `num_leaders` may come from the users, e.g. with the fanout requested
+        // by a transaction batch, so it doesn't bound the allocation.
+        let mut spans = Vec::with_capacity(num_leaders.min(self.leaders.len()));
+        let mut leaders_without_tpu_socket = Vec::new();
+        // Span of the leader of the last visited slot, which is complete once
+        // another leader follows.
+        let mut last_span: Option<(Pubkey, Slot, Slot)> = None;
+        for slot in current_slot.. {
+            if spans.len() == num_leaders {
+                break;
+            }
+            let Some(leader) = self.get_slot_leader(slot) else {
+                if spans.len() + usize::from(last_span.is_some()) < num_leaders {
+                    // Overran the local leader schedule cache
+                    warn!(
+                        "Leader not known for slot {}; cache holds slots [{},{}]",
+                        slot,
+                        self.first_slot,
+                        self.last_slot()
+                    );
+                }
+                break;
+            };
+            if let Some((last_leader, _, last_slot)) = last_span.as_mut() {
+                if last_leader == leader {
+                    *last_slot = slot;
+                    continue;
+                }
+            }
+            if let Some(span) = last_span.replace((*leader, slot, slot)) {
+                self.push_slot_span(span, &mut spans, &mut leaders_without_tpu_socket);
+            }
+        }
+        if spans.len() < num_leaders {
+            if let Some(span) = last_span {
+                self.push_slot_span(span, &mut spans, &mut leaders_without_tpu_socket);
+            }
+        }
+        (spans, leaders_without_tpu_socket)
+    }
+
+    // Pushes the slot span of `leader` to `spans` if its TPU socket is known, otherwise to
+    // `leaders_without_tpu_socket`.
+    fn push_slot_span(
+        &self,
+        (leader, first_slot, last_slot): (Pubkey, Slot, Slot),
+        spans: &mut Vec<LeaderTpuSocket>,
+        leaders_without_tpu_socket: &mut Vec<Pubkey>,
+    ) {
+        match self.leader_tpu_map.get(&leader) {
+            Some(tpu_socket) => spans.push(LeaderTpuSocket {
+                leader,
+                socket: *tpu_socket,
+                first_slot,
+                last_slot,
+            }),
+            None => {
+                // The leader is probably delinquent
+                trace!("TPU not available for leader {leader}");
+                leaders_without_tpu_socket.push(leader);
+            }
+        }
+    }
+
     // Get the TPU sockets for the current leader and upcoming leaders according to fanout size.
     fn get_leader_sockets(
         &self,
@@ -164,6 +481,7 @@ impl LeaderTpuCache {
                 } else {
                     // The leader is probably delinquent
                     trace!("TPU not available for leader {leader}");
+                    self.record_leader_without_tpu_socket(leader);
                 }
             } else {
                 // Overran the local leader schedule cache
@@ -187,26 +505,93 @@ impl LeaderTpuCache {
         }
     }
 
+    /// Extracts the TPU socket of each node from the cluster contact info.
+    ///
+    /// If a node doesn't advertise a TPU socket for the given `protocol`, its
+    /// TPU forwards socket is used instead when `tpu_forwards_fallback` is
+    /// set. Sockets from `tpu_socket_overrides` take precedence over the
+    /// advertised ones.
     fn extract_cluster_tpu_sockets(
         protocol: Protocol,
         cluster_contact_info: Vec<RpcContactInfo>,
+        tpu_socket_overrides: &HashMap<Pubkey, SocketAddr>,
+        tpu_forwards_fallback: bool,
     ) -> HashMap<Pubkey, SocketAddr> {
-        cluster_contact_info
+        let mut leader_tpu_map: HashMap<_, _> = cluster_contact_info
             .into_iter()
             .filter_map(|contact_info| {
                 let pubkey = Pubkey::from_str(&contact_info.pubkey).ok()?;
-                let socket = match protocol {
-                    Protocol::QUIC => contact_info.tpu_quic.or_else(|| {
-                        let mut socket = contact_info.tpu?;
-                        let port = socket.port().checked_add(QUIC_PORT_OFFSET)?;
-                        socket.set_port(port);
-                        Some(socket)
-                    }),
-                    Protocol::UDP => contact_info.tpu,
-                }?;
+                let (tpu, tpu_forwards) = match protocol {
+                    Protocol::QUIC => (
+                        contact_info.tpu_quic.or_else(|| {
+                            let mut socket = contact_info.tpu?;
+                            let port = socket.port().checked_add(QUIC_PORT_OFFSET)?;
+                            socket.set_port(port);
+                            Some(socket)
+                        }),
+                        contact_info.tpu_forwards_quic,
+                    ),
+                    Protocol::UDP => (contact_info.tpu, contact_info.tpu_forwards),
+                };
+                let socket = tpu.or(tpu_forwards.filter(|_| tpu_forwards_fallback))?;
                 Some((pubkey, socket))
             })
-            .collect()
+            .collect();
+        leader_tpu_map.extend(tpu_socket_overrides);
+        leader_tpu_map
+    }
+
+    /// Accounts `leader` as scheduled without known TPU socket, unless it
+    /// has already been since the last cluster refresh.
+    fn record_leader_without_tpu_socket(&self, leader: &Pubkey) {
+        if self
+            .leaders_without_tpu_socket
+            .lock()
+            .unwrap()
+            .insert(*leader)
+        {
+            self.num_leaders_without_tpu_socket
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of distinct scheduled leaders found without known
+    /// TPU socket since the previous call. A leader is counted once per
+    /// cluster refresh, however many times it is looked up.
+    pub fn take_num_leaders_without_tpu_socket(&self) -> u64 {
+        self.num_leaders_without_tpu_socket
+            .swap(0, Ordering::Relaxed)
+    }
+
+    /// Removes the TPU sockets equal to `socket` from the cache, so that the
+    /// corresponding leaders are resolved again on the next cluster refresh.
+    ///
+    /// Returns `true` if any leader has been invalidated.
+    pub fn invalidate_tpu_socket(&mut self, socket: &SocketAddr) -> bool {
+        let invalidated_leaders = &mut self.invalidated_leaders;
+        let num_sockets = self.leader_tpu_map.len();
+        self.leader_tpu_map.retain(|leader, tpu_socket| {
+            if tpu_socket == socket {
+                invalidated_leaders.insert(*leader);
+                return false;
+            }
+            true
+        });
+        self.leader_tpu_map.len() != num_sockets
+    }
+
+    /// Checks if the cluster contact info must be fetched again, either
+    /// because the cached TPU sockets have outlived the TTL, as they are all
+    /// resolved at `last_cluster_refresh`, because they have been restored
+    /// from a snapshot, or because some of them have been explicitly
+    /// invalidated.
+    pub fn needs_cluster_refresh(&self, last_cluster_refresh: Instant) -> bool {
+        let since_last_refresh = last_cluster_refresh.elapsed();
+        if !self.invalidated_leaders.is_empty() && since_last_refresh > MIN_CLUSTER_REFRESH_INTERVAL
+        {
+            return true;
+        }
+        self.has_restored_tpu_sockets || since_last_refresh > self.leader_tpu_socket_ttl
     }
 
     pub fn fanout(slots_in_epoch: Slot) -> Slot {
@@ -219,8 +604,15 @@ impl LeaderTpuCache {
         if let Some(cluster_nodes) = cache_update_info.maybe_cluster_nodes {
             match cluster_nodes {
                 Ok(cluster_nodes) => {
-                    self.leader_tpu_map =
-                        Self::extract_cluster_tpu_sockets(self.protocol, cluster_nodes);
+                    self.leader_tpu_map = Self::extract_cluster_tpu_sockets(
+                        self.protocol,
+                        cluster_nodes,
+                        &self.leader_tpu_socket_overrides,
+                        self.tpu_forwards_fallback,
+                    );
+                    self.invalidated_leaders.clear();
+                    self.has_restored_tpu_sockets = false;
+                    self.leaders_without_tpu_socket.get_mut().unwrap().clear();
                     cluster_refreshed = true;
                 }
                 Err(err) => {
@@ -726,6 +1118,7 @@ impl<P, M, C> Drop for TpuClient<P, M, C> {
 pub struct LeaderTpuService {
     recent_slots: RecentLeaderSlots,
     leader_tpu_cache: Arc<RwLock<LeaderTpuCache>>,
+    slot_receiver: watch::Receiver<Slot>,
     t_leader_tpu_service: Option<JoinHandle<Result<()>>>,
 }
 
@@ -735,6 +1128,23 @@ impl LeaderTpuService {
         websocket_url: &str,
         protocol: Protocol,
         exit: Arc<AtomicBool>,
+    ) -> Result<Self> {
+        Self::new_with_config(
+            rpc_client,
+            websocket_url,
+            protocol,
+            exit,
+            LeaderTpuServiceConfig::default(),
+        )
+        .await
+    }
+
+    pub async fn new_with_config(
+        rpc_client: Arc<RpcClient>,
+        websocket_url: &str,
+        protocol: Protocol,
+        exit: Arc<AtomicBool>,
+        config: LeaderTpuServiceConfig,
     ) -> Result<Self> {
         let epoch_schedule = rpc_client.get_epoch_schedule().await?;
         let start_slot = rpc_client
@@ -802,6 +1212,7 @@ impl LeaderTpuService {
                  {tpu_leader_service_creation_timeout:?}."
             ))
         })??;
+        let max_rate_limit_backoff = config.max_rate_limit_backoff;
         let leader_tpu_cache = Arc::new(RwLock::new(LeaderTpuCache::new(
             start_slot,
             slots_in_epoch,
@@ -809,6 +1220,7 @@ impl LeaderTpuService {
             leaders,
             cluster_nodes,
             protocol,
+            config,
         )));
 
         let pubsub_client = if !websocket_url.is_empty() {
@@ -817,6 +1229,66 @@ impl LeaderTpuService {
             None
         };
 
+        Ok(Self::spawn(
+            rpc_client,
+            recent_slots,
+            leader_tpu_cache,
+            pubsub_client,
+            exit,
+            max_rate_limit_backoff,
+        ))
+    }
+
+    /// Creates the service from a previously taken leader schedule `snapshot`.
+    ///
+    /// Unlike [`LeaderTpuService::new_with_config`], it doesn't wait for the
+    /// RPC to provide the leader schedule and the cluster contact info. They are
+    /// refreshed in the background once the RPC is reachable. If the websocket
+    /// connection cannot be established, the estimated slot is not updated.
+    pub async fn new_from_snapshot(
+        rpc_client: Arc<RpcClient>,
+        websocket_url: &str,
+        protocol: Protocol,
+        exit: Arc<AtomicBool>,
+        config: LeaderTpuServiceConfig,
+        snapshot: LeaderScheduleSnapshot,
+    ) -> Self {
+        let recent_slots = RecentLeaderSlots::new(snapshot.current_slot);
+        let max_rate_limit_backoff = config.max_rate_limit_backoff;
+        let leader_tpu_cache = Arc::new(RwLock::new(LeaderTpuCache::from_snapshot(
+            snapshot, protocol, config,
+        )));
+
+        let pubsub_client = if !websocket_url.is_empty() {
+            PubsubClient::new(websocket_url)
+                .await
+                .inspect_err(|err| {
+                    warn!("Failed to connect to {websocket_url}, slots won't be tracked: {err}")
+                })
+                .ok()
+        } else {
+            None
+        };
+
+        Self::spawn(
+            rpc_client,
+            recent_slots,
+            leader_tpu_cache,
+            pubsub_client,
+            exit,
+            max_rate_limit_backoff,
+        )
+    }
+
+    fn spawn(
+        rpc_client: Arc<RpcClient>,
+        recent_slots: RecentLeaderSlots,
+        leader_tpu_cache: Arc<RwLock<LeaderTpuCache>>,
+        pubsub_client: Option<PubsubClient>,
+        exit: Arc<AtomicBool>,
+        max_rate_limit_backoff: Duration,
+    ) -> Self {
+        let (slot_sender, slot_receiver) = watch::channel(recent_slots.estimated_current_slot());
         let t_leader_tpu_service = Some({
             let recent_slots = recent_slots.clone();
             let leader_tpu_cache = leader_tpu_cache.clone();
@@ -825,15 +1297,18 @@ impl LeaderTpuService {
                 recent_slots,
                 leader_tpu_cache,
                 pubsub_client,
+                slot_sender,
                 exit,
+                max_rate_limit_backoff,
             ))
         });
 
-        Ok(LeaderTpuService {
+        LeaderTpuService {
             recent_slots,
             leader_tpu_cache,
+            slot_receiver,
             t_leader_tpu_service,
-        })
+        }
     }
 
     pub async fn join(&mut self) {
@@ -862,34 +1337,138 @@ impl LeaderTpuService {
             .get_leader_sockets(current_slot, fanout_slots)
     }
 
+    /// Returns the TPU sockets of the current leader and of the following
+    /// leaders, `num_leaders` in total. Consecutive slots of the same leader
+    /// are combined, so that a leader which is scheduled for several
+    /// consecutive slot groups is returned only once. The leaders without
+    /// known TPU socket are skipped and don't count towards `num_leaders`.
+    pub fn leader_tpu_slot_spans(&self, num_leaders: usize) -> Vec<LeaderTpuSocket> {
+        let current_slot = self.recent_slots.estimated_current_slot();
+        self.leader_tpu_cache
+            .read()
+            .unwrap()
+            .get_leader_slot_spans(current_slot, num_leaders)
+    }
+
+    /// Subscribes to the changes of the current leader and of the following
+    /// leaders, `num_leaders` in total, as returned by
+    /// [`LeaderTpuService::leader_tpu_slot_spans`].
+    ///
+    /// A new value is published only when the set of leaders or their TPU
+    /// sockets change, so applications can align their work with leader
+    /// transitions without polling. The leaders are checked whenever the
+    /// estimated slot advances and after every update of the leader cache,
+    /// e.g. a cluster refresh. The returned channel is closed once the
+    /// service has exited.
+    pub fn subscribe_leader_changes(
+        &self,
+        num_leaders: usize,
+    ) -> watch::Receiver<Vec<LeaderTpuSocket>> {
+        let mut slot_receiver = self.slot_receiver.clone();
+        let current_slot = *slot_receiver.borrow_and_update();
+        let (leaders, _) = self
+            .leader_tpu_cache
+            .read()
+            .unwrap()
+            .leader_slot_spans(current_slot, num_leaders);
+        let (leaders_sender, leaders_receiver) = watch::channel(leaders);
+        tokio::spawn(run_leader_change_notifier(
+            self.leader_tpu_cache.clone(),
+            slot_receiver,
+            leaders_sender,
+            num_leaders,
+        ));
+        leaders_receiver
+    }
+
+    /// Returns a snapshot of the leader schedule and the leaders TPU sockets
+    /// which can be used to create the service with
+    /// [`LeaderTpuService::new_from_snapshot`].
+    pub fn leader_schedule_snapshot(&self) -> LeaderScheduleSnapshot {
+        let current_slot = self.recent_slots.estimated_current_slot();
+        self.leader_tpu_cache.read().unwrap().snapshot(current_slot)
+    }
+
+    /// Returns the last known good TPU sockets of the leaders, which can be
+    /// persisted across restarts, see [`KnownPeers`].
+    pub fn known_peers(&self) -> KnownPeers {
+        self.leader_tpu_cache.read().unwrap().known_peers()
+    }
+
+    /// Returns the number of distinct leaders within the requested fanout
+    /// found without known TPU socket since the previous call. A leader is
+    /// counted once per cluster refresh.
+    pub fn take_num_leaders_without_tpu_socket(&self) -> u64 {
+        self.leader_tpu_cache
+            .read()
+            .unwrap()
+            .take_num_leaders_without_tpu_socket()
+    }
+
+    /// Invalidates the cached TPU `socket`, typically after repeated failures
+    /// to connect to it. The leaders using this socket are not returned until
+    /// their contact info is resolved again, which handles the case of a
+    /// validator changing its TPU address in the middle of an epoch.
+    pub fn invalidate_leader_tpu_socket(&self, socket: &SocketAddr) {
+        if self
+            .leader_tpu_cache
+            .write()
+            .unwrap()
+            .invalidate_tpu_socket(socket)
+        {
+            debug!("Invalidated leader TPU socket {socket}");
+        }
+    }
+
     async fn run(
         rpc_client: Arc<RpcClient>,
         recent_slots: RecentLeaderSlots,
         leader_tpu_cache: Arc<RwLock<LeaderTpuCache>>,
         pubsub_client: Option<PubsubClient>,
+        slot_sender: watch::Sender<Slot>,
         exit: Arc<AtomicBool>,
+        max_rate_limit_backoff: Duration,
     ) -> Result<()> {
         tokio::try_join!(
-            Self::run_slot_watcher(recent_slots.clone(), pubsub_client, exit.clone()),
-            Self::run_cache_refresher(rpc_client, recent_slots, leader_tpu_cache, exit),
+            Self::run_slot_watcher(
+                recent_slots.clone(),
+                pubsub_client,
+                slot_sender.clone(),
+                exit.clone()
+            ),
+            Self::run_cache_refresher(
+                rpc_client,
+                recent_slots,
+                leader_tpu_cache,
+                slot_sender,
+                exit,
+                max_rate_limit_backoff
+            ),
         )?;
 
         Ok(())
     }
 
+    /// Refreshes the leader cache until the service exits. It owns
+    /// `slot_sender` meanwhile, so that the subscribers of the leader changes
+    /// are notified of the cache updates, and of the estimated slot when the
+    /// service runs without a websocket connection.
     async fn run_cache_refresher(
         rpc_client: Arc<RpcClient>,
         recent_slots: RecentLeaderSlots,
         leader_tpu_cache: Arc<RwLock<LeaderTpuCache>>,
+        slot_sender: watch::Sender<Slot>,
         exit: Arc<AtomicBool>,
+        max_rate_limit_backoff: Duration,
     ) -> Result<()> {
         let mut last_cluster_refresh = Instant::now();
-        let mut sleep_ms = DEFAULT_MS_PER_SLOT;
+        let mut sleep_duration = Duration::from_millis(DEFAULT_MS_PER_SLOT);
+        let mut refresh_interval = RefreshInterval::new(max_rate_limit_backoff);
 
         while !exit.load(Ordering::Relaxed) {
             // Sleep a slot before checking if leader cache needs to be refreshed again
-            sleep(Duration::from_millis(sleep_ms)).await;
-            sleep_ms = DEFAULT_MS_PER_SLOT;
+            sleep(sleep_duration).await;
+            sleep_duration = refresh_interval.next_interval();
 
             let cache_update_info = maybe_fetch_cache_info(
                 &leader_tpu_cache,
@@ -900,15 +1479,25 @@ impl LeaderTpuService {
             .await;
 
             if cache_update_info.has_some() {
-                let mut leader_tpu_cache = leader_tpu_cache.write().unwrap();
-                let (has_error, cluster_refreshed) = leader_tpu_cache.update_all(cache_update_info);
-                if has_error {
-                    sleep_ms = 100;
+                let is_rate_limited = cache_update_info.is_rate_limited();
+                let (has_error, cluster_refreshed) = leader_tpu_cache
+                    .write()
+                    .unwrap()
+                    .update_all(cache_update_info);
+                if is_rate_limited {
+                    sleep_duration = refresh_interval.backoff();
+                    warn!("RPC node is rate limiting requests, retrying in {sleep_duration:?}");
+                } else if has_error {
+                    sleep_duration = Duration::from_millis(100);
                 }
                 if cluster_refreshed {
                     last_cluster_refresh = Instant::now();
                 }
+                // The schedule or the TPU sockets may have changed for the
+                // same slot, the notifiers only publish the actual changes.
+                slot_sender.send_modify(|_| ());
             }
+            publish_slot(&slot_sender, recent_slots.estimated_current_slot());
         }
 
         Ok(())
@@ -917,6 +1506,7 @@ impl LeaderTpuService {
     async fn run_slot_watcher(
         recent_slots: RecentLeaderSlots,
         pubsub_client: Option<PubsubClient>,
+        slot_sender: watch::Sender<Slot>,
         exit: Arc<AtomicBool>,
     ) -> Result<()> {
         let Some(pubsub_client) = pubsub_client else {
@@ -948,6 +1538,7 @@ impl LeaderTpuService {
                     _ => continue,
                 };
                 recent_slots.record_slot(current_slot);
+                publish_slot(&slot_sender, recent_slots.estimated_current_slot());
             }
         }
 
@@ -967,19 +1558,26 @@ async fn maybe_fetch_cache_info(
     rpc_client: &RpcClient,
     recent_slots: &RecentLeaderSlots,
 ) -> LeaderTpuCacheUpdateInfo {
-    // Refresh cluster TPU ports every 5min in case validators restart with new port configuration
-    // or new validators come online
-    let maybe_cluster_nodes = if last_cluster_refresh.elapsed() > Duration::from_secs(5 * 60) {
-        Some(rpc_client.get_cluster_nodes().await)
-    } else {
-        None
-    };
-
     // Grab information about the slot leaders currently in the cache.
     let estimated_current_slot = recent_slots.estimated_current_slot();
-    let (last_slot, last_slot_in_epoch, slots_in_epoch) = {
+    let (last_slot, last_slot_in_epoch, slots_in_epoch, needs_cluster_refresh) = {
         let leader_tpu_cache = leader_tpu_cache.read().unwrap();
-        leader_tpu_cache.slot_info()
+        let (last_slot, last_slot_in_epoch, slots_in_epoch) = leader_tpu_cache.slot_info();
+        (
+            last_slot,
+            last_slot_in_epoch,
+            slots_in_epoch,
+            leader_tpu_cache.needs_cluster_refresh(last_cluster_refresh),
+        )
+    };
+
+    // Refresh cluster TPU ports once the cached ones are stale or have been
+    // invalidated, in case validators restart with new port configuration or
+    // new validators come online
+    let maybe_cluster_nodes = if needs_cluster_refresh {
+        Some(rpc_client.get_cluster_nodes().await)
+    } else {
+        None
     };
 
     // If we're crossing into a new epoch, fetch the updated epoch schedule.
@@ -1014,6 +1612,108 @@ async fn maybe_fetch_cache_info(
     }
 }
 
+/// Interval between consecutive leader cache refreshes.
+///
+/// Normally, the cache is checked every slot. Once the RPC node starts rate
+/// limiting the requests, the interval is doubled after every rate limited
+/// response up to `max_interval`, and it is halved back after every
+/// successful refresh so that the polling rate recovers gradually.
+struct RefreshInterval {
+    interval: Duration,
+    max_interval: Duration,
+}
+
+impl RefreshInterval {
+    const MIN_INTERVAL: Duration = Duration::from_millis(DEFAULT_MS_PER_SLOT);
+
+    fn new(max_interval: Duration) -> Self {
+        Self {
+            interval: Self::MIN_INTERVAL,
+            max_interval: max_interval.max(Self::MIN_INTERVAL),
+        }
+    }
+
+    /// Returns the interval to wait before the next refresh, shrinking it if
+    /// it has been stretched by rate limiting before.
+    fn next_interval(&mut self) -> Duration {
+        let interval = self.interval;
+        self.interval = (self.interval / 2).max(Self::MIN_INTERVAL);
+        interval
+    }
+
+    /// Stretches the interval after a rate limited response and returns the
+    /// jittered time to wait before the next refresh, so that clients sharing
+    /// the RPC node don't retry all at once.
+    fn backoff(&mut self) -> Duration {
+        self.interval = self.interval.saturating_mul(2).min(self.max_interval);
+        thread_rng().gen_range(self.interval / 2..=self.interval)
+    }
+}
+
+/// Publishes `estimated_current_slot` to the leader change notifiers if it has
+/// changed.
+fn publish_slot(slot_sender: &watch::Sender<Slot>, estimated_current_slot: Slot) {
+    slot_sender.send_if_modified(|slot| {
+        let modified = *slot != estimated_current_slot;
+        *slot = estimated_current_slot;
+        modified
+    });
+}
+
+/// Publishes the upcoming leaders over `leaders_sender` every time they change
+/// because of a new estimated slot or an update of the leader cache. Stops
+/// once all the subscribers are gone or the [`LeaderTpuService`] has exited.
+async fn run_leader_change_notifier(
+    leader_tpu_cache: Arc<RwLock<LeaderTpuCache>>,
+    mut slot_receiver: watch::Receiver<Slot>,
+    leaders_sender: watch::Sender<Vec<LeaderTpuSocket>>,
+    num_leaders: usize,
+) {
+    loop {
+        tokio::select! {
+            result = slot_receiver.changed() => {
+                if result.is_err() {
+                    break;
+                }
+            }
+            () = leaders_sender.closed() => break,
+        }
+        let current_slot = *slot_receiver.borrow_and_update();
+        let (leaders, _) = leader_tpu_cache
+            .read()
+            .unwrap()
+            .leader_slot_spans(current_slot, num_leaders);
+        leaders_sender.send_if_modified(|current_leaders| {
+            // The first slot of the current leader's span advances with every
+            // slot, so only the leaders and their sockets are compared.
+            let changed = current_leaders.len() != leaders.len()
+                || current_leaders
+                    .iter()
+                    .zip(leaders.iter())
+                    .any(|(current, new)| {
+                        current.leader != new.leader || current.socket != new.socket
+                    });
+            if changed {
+                *current_leaders = leaders;
+            }
+            changed
+        });
+    }
+}
+
+fn is_rate_limited_result<T>(result: &ClientResult<T>) -> bool {
+    const HTTP_TOO_MANY_REQUESTS: u16 = 429;
+    match result {
+        Err(client_error) => match client_error.kind() {
+            ErrorKind::Reqwest(error) => error
+                .status()
+                .is_some_and(|status| status.as_u16() == HTTP_TOO_MANY_REQUESTS),
+            _ => false,
+        },
+        Ok(_) => false,
+    }
+}
+
 fn is_invalid_slot_range_error(client_error: &ClientError) -> bool {
     if let ErrorKind::RpcError(RpcError::RpcResponseError { code, message, .. }) =
         client_error.kind()
@@ -1023,3 +1723,394 @@ fn is_invalid_slot_range_error(client_error: &ClientError) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::net::Ipv4Addr};
+
+    fn contact_info(pubkey: &Pubkey, tpu_quic: SocketAddr) -> RpcContactInfo {
+        RpcContactInfo {
+            pubkey: pubkey.to_string(),
+            gossip: None,
+            tvu: None,
+            tpu: None,
+            tpu_quic: Some(tpu_quic),
+            tpu_forwards: None,
+            tpu_forwards_quic: None,
+            tpu_vote: None,
+            serve_repair: None,
+            rpc: None,
+            pubsub: None,
+            version: None,
+            feature_set: None,
+            shred_version: None,
+        }
+    }
+
+    #[test]
+    fn test_invalidate_tpu_socket() {
+        let leader = Pubkey::new_unique();
+        let socket = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8009);
+        let mut cache = LeaderTpuCache::new(
+            0,
+            32,
+            31,
+            vec![leader; NUM_CONSECUTIVE_LEADER_SLOTS as usize],
+            vec![contact_info(&leader, socket)],
+            Protocol::QUIC,
+            LeaderTpuServiceConfig::default(),
+        );
+        let last_cluster_refresh = Instant::now();
+        assert_eq!(cache.get_leader_sockets(0, 1), vec![socket]);
+        assert!(!cache.needs_cluster_refresh(last_cluster_refresh));
+
+        let unknown_socket = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8010);
+        assert!(!cache.invalidate_tpu_socket(&unknown_socket));
+        assert!(cache.invalidate_tpu_socket(&socket));
+        assert!(cache.get_leader_sockets(0, 1).is_empty());
+
+        // Invalidation doesn't trigger a refresh right after the previous one.
+        assert!(!cache.needs_cluster_refresh(last_cluster_refresh));
+        let last_cluster_refresh = Instant::now() - MIN_CLUSTER_REFRESH_INTERVAL * 2;
+        assert!(cache.needs_cluster_refresh(last_cluster_refresh));
+
+        let (has_error, cluster_refreshed) = cache.update_all(LeaderTpuCacheUpdateInfo {
+            maybe_cluster_nodes: Some(Ok(vec![contact_info(&leader, socket)])),
+            maybe_epoch_schedule: None,
+            maybe_slot_leaders: None,
+            first_slot: 0,
+        });
+        assert!(!has_error);
+        assert!(cluster_refreshed);
+        assert_eq!(cache.get_leader_sockets(0, 1), vec![socket]);
+        assert!(!cache.needs_cluster_refresh(Instant::now()));
+    }
+
+    #[test]
+    fn test_leader_tpu_socket_ttl() {
+        let leader = Pubkey::new_unique();
+        let socket = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8009);
+        let ttl = Duration::from_millis(10);
+        let cache = LeaderTpuCache::new(
+            0,
+            32,
+            31,
+            vec![leader],
+            vec![contact_info(&leader, socket)],
+            Protocol::QUIC,
+            LeaderTpuServiceConfig {
+                leader_tpu_socket_ttl: ttl,
+                ..LeaderTpuServiceConfig::default()
+            },
+        );
+        let last_cluster_refresh = Instant::now();
+        assert!(!cache.needs_cluster_refresh(last_cluster_refresh));
+        std::thread::sleep(ttl * 2);
+        assert!(cache.needs_cluster_refresh(last_cluster_refresh));
+        // Stale sockets are still used until they are resolved again.
+        assert_eq!(cache.get_leader_sockets(0, 1), vec![socket]);
+    }
+
+    #[test]
+    fn test_tpu_socket_fallback_resolution() {
+        let leader_with_forwards = Pubkey::new_unique();
+        let leader_with_override = Pubkey::new_unique();
+        let leader_without_socket = Pubkey::new_unique();
+        let forwards_socket = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8010);
+        let override_socket = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8020);
+
+        let mut with_forwards = contact_info(&leader_with_forwards, forwards_socket);
+        with_forwards.tpu_quic = None;
+        with_forwards.tpu_forwards_quic = Some(forwards_socket);
+        let mut without_socket = contact_info(&leader_without_socket, forwards_socket);
+        without_socket.tpu_quic = None;
+
+        let new_cache = |tpu_forwards_fallback| {
+            LeaderTpuCache::new(
+                0,
+                32,
+                31,
+                [
+                    leader_with_forwards,
+                    leader_with_override,
+                    leader_without_socket,
+                ]
+                .iter()
+                .flat_map(|leader| [*leader; NUM_CONSECUTIVE_LEADER_SLOTS as usize])
+                .collect(),
+                vec![with_forwards.clone(), without_socket.clone()],
+                Protocol::QUIC,
+                LeaderTpuServiceConfig {
+                    leader_tpu_socket_overrides: HashMap::from([(
+                        leader_with_override,
+                        override_socket,
+                    )]),
+                    tpu_forwards_fallback,
+                    ..LeaderTpuServiceConfig::default()
+                },
+            )
+        };
+
+        // The forwards socket is only used once opted in.
+        let cache = new_cache(false);
+        assert_eq!(
+            cache.get_leader_sockets(0, 3 * NUM_CONSECUTIVE_LEADER_SLOTS),
+            vec![override_socket]
+        );
+        assert_eq!(cache.take_num_leaders_without_tpu_socket(), 2);
+
+        let mut cache = new_cache(true);
+        assert_eq!(
+            cache.get_leader_sockets(0, 3 * NUM_CONSECUTIVE_LEADER_SLOTS),
+            vec![forwards_socket, override_socket]
+        );
+        assert_eq!(cache.take_num_leaders_without_tpu_socket(), 1);
+        assert_eq!(cache.take_num_leaders_without_tpu_socket(), 0);
+
+        // The leader without socket is counted once per cluster refresh.
+        cache.get_leader_sockets(0, 3 * NUM_CONSECUTIVE_LEADER_SLOTS);
+        assert_eq!(cache.take_num_leaders_without_tpu_socket(), 0);
+        let (has_error, cluster_refreshed) = cache.update_all(LeaderTpuCacheUpdateInfo {
+            maybe_cluster_nodes: Some(Ok(vec![with_forwards, without_socket])),
+            maybe_epoch_schedule: None,
+            maybe_slot_leaders: None,
+            first_slot: 0,
+        });
+        assert!(!has_error);
+        assert!(cluster_refreshed);
+        cache.get_leader_sockets(0, 3 * NUM_CONSECUTIVE_LEADER_SLOTS);
+        assert_eq!(cache.take_num_leaders_without_tpu_socket(), 1);
+    }
+
+    #[test]
+    fn test_leader_schedule_snapshot() {
+        let leaders: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let cluster_nodes = leaders
+            .iter()
+            .enumerate()
+            .map(|(i, leader)| {
+                contact_info(
+                    leader,
+                    SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i as u16),
+                )
+            })
+            .collect();
+        let cache = LeaderTpuCache::new(
+            10,
+            32,
+            31,
+            leaders.clone(),
+            cluster_nodes,
+            Protocol::QUIC,
+            LeaderTpuServiceConfig::default(),
+        );
+        let snapshot = cache.snapshot(11);
+        assert_eq!(snapshot.leaders, leaders);
+        assert_eq!(snapshot.leader_tpu_sockets.len(), 3);
+
+        let path = std::env::temp_dir().join(format!(
+            "leader_schedule_snapshot_{}.bin",
+            Pubkey::new_unique()
+        ));
+        snapshot.save(&path).unwrap();
+        let loaded = LeaderScheduleSnapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, snapshot);
+
+        let restored = LeaderTpuCache::from_snapshot(
+            loaded,
+            Protocol::QUIC,
+            LeaderTpuServiceConfig::default(),
+        );
+        assert_eq!(
+            restored.get_leader_sockets(10, 3),
+            cache.get_leader_sockets(10, 3)
+        );
+        assert_eq!(restored.snapshot(11), snapshot);
+        // Sockets restored from a snapshot are resolved again right away.
+        assert!(restored.needs_cluster_refresh(Instant::now()));
+    }
+
+    #[test]
+    fn test_known_peers() {
+        let leaders: Vec<_> = (0..2).map(|_| Pubkey::new_unique()).collect();
+        let sockets: Vec<_> = (0..2)
+            .map(|i| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i))
+            .collect();
+        let cluster_nodes = leaders
+            .iter()
+            .zip(&sockets)
+            .map(|(leader, socket)| contact_info(leader, *socket))
+            .collect();
+        let mut cache = LeaderTpuCache::new(
+            0,
+            32,
+            31,
+            leaders.clone(),
+            cluster_nodes,
+            Protocol::QUIC,
+            LeaderTpuServiceConfig::default(),
+        );
+        assert!(cache.invalidate_tpu_socket(&sockets[1]));
+        let known_peers = cache.known_peers();
+        assert_eq!(
+            known_peers.tpu_sockets,
+            HashMap::from([(leaders[0], sockets[0])])
+        );
+
+        let path = std::env::temp_dir().join(format!("known_peers_{}.bin", Pubkey::new_unique()));
+        known_peers.save(&path).unwrap();
+        let loaded = KnownPeers::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, known_peers);
+    }
+
+    #[test]
+    fn test_leader_slot_spans() {
+        let leaders: Vec<_> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let sockets: Vec<_> = (0..4)
+            .map(|i| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i))
+            .collect();
+        let group = NUM_CONSECUTIVE_LEADER_SLOTS as usize;
+        // Leader 0 leads two consecutive slot groups, then leaders 1, 0, 2.
+        // Leader 3 has no TPU socket.
+        let schedule: Vec<_> = [0, 0, 1, 0, 3, 2]
+            .into_iter()
+            .flat_map(|i| std::iter::repeat_n(leaders[i], group))
+            .collect();
+        let cluster_nodes = (0..3)
+            .map(|i| contact_info(&leaders[i], sockets[i]))
+            .collect();
+        let cache = LeaderTpuCache::new(
+            0,
+            32,
+            31,
+            schedule,
+            cluster_nodes,
+            Protocol::QUIC,
+            LeaderTpuServiceConfig::default(),
+        );
+        let span = |i: usize, first_slot: Slot, last_slot: Slot| LeaderTpuSocket {
+            leader: leaders[i],
+            socket: sockets[i],
+            first_slot,
+            last_slot,
+        };
+
+        assert!(cache.get_leader_slot_spans(0, 0).is_empty());
+        assert_eq!(cache.get_leader_slot_spans(1, 1), vec![span(0, 1, 7)]);
+        assert_eq!(
+            cache.get_leader_slot_spans(1, 3),
+            vec![span(0, 1, 7), span(1, 8, 11), span(0, 12, 15)]
+        );
+        assert_eq!(cache.take_num_leaders_without_tpu_socket(), 0);
+
+        // The span of the leader without TPU socket is skipped.
+        assert_eq!(
+            cache.get_leader_slot_spans(12, 3),
+            vec![span(0, 12, 15), span(2, 20, 23)]
+        );
+        assert_eq!(cache.take_num_leaders_without_tpu_socket(), 1);
+        cache.get_leader_slot_spans(12, 3);
+        assert_eq!(cache.take_num_leaders_without_tpu_socket(), 0);
+        // The span of the leader without TPU socket doesn't count towards the
+        // requested leaders.
+        assert_eq!(
+            cache.get_leader_slot_spans(8, 3),
+            vec![span(1, 8, 11), span(0, 12, 15), span(2, 20, 23)]
+        );
+
+        // Overrunning the cached schedule returns the known leaders only.
+        assert_eq!(cache.get_leader_slot_spans(21, 3), vec![span(2, 21, 23)]);
+    }
+
+    #[test]
+    fn test_refresh_interval_backoff() {
+        let min_interval = RefreshInterval::MIN_INTERVAL;
+        let max_interval = Duration::from_secs(2);
+        let mut refresh_interval = RefreshInterval::new(max_interval);
+        assert_eq!(refresh_interval.next_interval(), min_interval);
+
+        let mut expected_interval = min_interval;
+        for _ in 0..5 {
+            expected_interval = (expected_interval * 2).min(max_interval);
+            let backoff = refresh_interval.backoff();
+            assert!(backoff >= expected_interval / 2 && backoff <= expected_interval);
+        }
+        assert_eq!(expected_interval, max_interval);
+
+        // The interval shrinks gradually once requests succeed again.
+        assert_eq!(refresh_interval.next_interval(), max_interval);
+        assert_eq!(refresh_interval.next_interval(), max_interval / 2);
+        assert_eq!(refresh_interval.next_interval(), max_interval / 4);
+        assert_eq!(refresh_interval.next_interval(), min_interval);
+        assert_eq!(refresh_interval.next_interval(), min_interval);
+    }
+
+    #[tokio::test]
+    async fn test_leader_change_notifier() {
+        let leaders: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let sockets: Vec<_> = (0..3)
+            .map(|i| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8000 + i))
+            .collect();
+        let schedule: Vec<_> = leaders
+            .iter()
+            .flat_map(|leader| std::iter::repeat_n(*leader, NUM_CONSECUTIVE_LEADER_SLOTS as usize))
+            .collect();
+        let cluster_nodes = (0..3)
+            .map(|i| contact_info(&leaders[i], sockets[i]))
+            .collect();
+        let leader_tpu_cache = Arc::new(RwLock::new(LeaderTpuCache::new(
+            0,
+            32,
+            31,
+            schedule,
+            cluster_nodes,
+            Protocol::QUIC,
+            LeaderTpuServiceConfig::default(),
+        )));
+        let notified_leaders = |leaders: &[LeaderTpuSocket]| {
+            leaders
+                .iter()
+                .map(|leader| leader.socket)
+                .collect::<Vec<_>>()
+        };
+
+        let (slot_sender, slot_receiver) = watch::channel(0);
+        let (leaders_sender, mut leaders_receiver) =
+            watch::channel(leader_tpu_cache.read().unwrap().leader_slot_spans(0, 2).0);
+        let notifier = tokio::spawn(run_leader_change_notifier(
+            leader_tpu_cache.clone(),
+            slot_receiver,
+            leaders_sender,
+            2,
+        ));
+
+        // Slots of the same leader don't trigger a notification.
+        slot_sender.send(1).unwrap();
+        slot_sender.send(2).unwrap();
+        slot_sender.send(NUM_CONSECUTIVE_LEADER_SLOTS).unwrap();
+        leaders_receiver.changed().await.unwrap();
+        assert_eq!(
+            notified_leaders(&leaders_receiver.borrow_and_update()),
+            vec![sockets[1], sockets[2]]
+        );
+
+        // An update of the cache for the same slot is published too.
+        leader_tpu_cache
+            .write()
+            .unwrap()
+            .invalidate_tpu_socket(&sockets[2]);
+        slot_sender.send_modify(|_| ());
+        leaders_receiver.changed().await.unwrap();
+        assert_eq!(
+            notified_leaders(&leaders_receiver.borrow_and_update()),
+            vec![sockets[1]]
+        );
+
+        // The notifier stops once the service exits.
+        drop(slot_sender);
+        notifier.await.unwrap();
+        assert!(leaders_receiver.changed().await.is_err());
+    }
+}